@@ -4,5 +4,8 @@ pub enum Error {
     DerError(#[from] der::Error),
 
     #[error(transparent)]
-    SpkiError(#[from] spki::Error)
+    SpkiError(#[from] spki::Error),
+
+    #[error(transparent)]
+    Pkcs8Error(#[from] pkcs8::Error),
 }
@@ -10,6 +10,8 @@ pub struct KeyPair<T> {
 pub trait KeyPairTrait: Sized {
     fn generate() -> Result<Self>;
 
+    fn from_private_key_pem(pem: &str) -> Result<Self>;
+
     fn get_private_key_pem(&self) -> Result<String>;
 
     fn get_public_key_pem(&self) -> Result<String>;
@@ -1,5 +1,7 @@
 use p256::{ecdsa::SigningKey, elliptic_curve::ALGORITHM_OID, NistP256, SecretKey};
-use pkcs8::{der::EncodePem, AssociatedOid, EncodePublicKey, LineEnding, PrivateKeyInfo};
+use pkcs8::{
+    der::EncodePem, AssociatedOid, DecodePrivateKey, EncodePublicKey, LineEnding, PrivateKeyInfo,
+};
 use rand::rngs::OsRng;
 
 use crate::{KeyPair, KeyPairTrait, Result};
@@ -18,6 +20,14 @@ impl KeyPairTrait for KeyPair<P256> {
         })
     }
 
+    fn from_private_key_pem(pem: &str) -> Result<Self> {
+        Ok(KeyPair {
+            key_pair: P256 {
+                secret_key: SecretKey::from_pkcs8_pem(pem)?,
+            },
+        })
+    }
+
     fn get_private_key_pem(&self) -> Result<String> {
         let algorithm_identifier = pkcs8::AlgorithmIdentifierRef {
             oid: ALGORITHM_OID,
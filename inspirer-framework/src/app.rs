@@ -90,6 +90,14 @@ pub trait AppTrait: Sized + Clone + Send + Sync {
     /// Register application routes
     async fn routes(app: AppContext<Self>) -> Result<axum::Router<AppContext<Self>>>;
 
+    /// Run background tasks along with the server
+    ///
+    /// The future is spawned before the server starts to accept requests,
+    /// and is dropped when the server shuts down. Errors returned are logged.
+    async fn background(_app: AppContext<Self>) -> Result<()> {
+        Ok(())
+    }
+
     /// Register application cli commands
     ///
     /// ```rust
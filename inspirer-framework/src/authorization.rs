@@ -0,0 +1,108 @@
+//! Route level authorization
+//!
+//! Application resolves the principal of request by implementing [`PrincipalResolver`],
+//! and routes require a permission by the [`Require`] extractor, or only require the
+//! request is authenticated by the [`Authenticated`] extractor.
+//!
+//! ```rust,ignore
+//! use inspirer_framework::{authorization::Require, permission};
+//!
+//! permission!(ManageUsers, "users.manage");
+//!
+//! async fn delete_user(Require(principal, ..): Require<ManageUsers, App>) -> Resp<()> {
+//!     ok(())
+//! }
+//! ```
+
+use std::marker::PhantomData;
+
+use axum::{extract::FromRequestParts, http::request::Parts};
+
+use crate::{
+    app::{AppContext, AppTrait},
+    response::ErrorDetail,
+    Error, Result,
+};
+
+/// Authenticated principal of the request
+pub trait Principal: Send + Sync {
+    fn has_permission(&self, permission: &str) -> bool;
+}
+
+/// Resolve the principal from request
+#[async_trait::async_trait]
+pub trait PrincipalResolver: AppTrait {
+    type Principal: Principal;
+
+    /// Return [`Error::Unauthorized`] if the request is not authenticated
+    async fn resolve_principal(
+        parts: &mut Parts,
+        context: &AppContext<Self>,
+    ) -> Result<Self::Principal>;
+}
+
+/// Permission required by a route, see [`permission!`](crate::permission)
+pub trait Permission {
+    const NAME: &'static str;
+}
+
+/// Define a [`Permission`] type
+#[macro_export]
+macro_rules! permission {
+    ($(#[$meta:meta])* $vis:vis $name:ident, $permission:literal) => {
+        $(#[$meta])*
+        $vis struct $name;
+
+        impl $crate::authorization::Permission for $name {
+            const NAME: &'static str = $permission;
+        }
+    };
+}
+
+/// Extractor requires the principal has the permission `P`, rejects with `403 Forbidden` otherwise
+pub struct Require<P, T>(pub T::Principal, pub PhantomData<P>)
+where
+    P: Permission,
+    T: PrincipalResolver;
+
+#[async_trait::async_trait]
+impl<P, T> FromRequestParts<AppContext<T>> for Require<P, T>
+where
+    P: Permission,
+    T: PrincipalResolver + 'static,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppContext<T>) -> Result<Self> {
+        let principal = T::resolve_principal(parts, state).await?;
+
+        if !principal.has_permission(P::NAME) {
+            return Err(Error::CustomError(
+                axum::http::StatusCode::FORBIDDEN,
+                ErrorDetail::new(
+                    "forbidden".to_string(),
+                    format!("Missing permission: {}", P::NAME),
+                ),
+            ));
+        }
+
+        Ok(Require(principal, PhantomData))
+    }
+}
+
+/// Extractor requires the request is authenticated, no permission is checked
+pub struct Authenticated<T>(pub T::Principal)
+where
+    T: PrincipalResolver;
+
+#[async_trait::async_trait]
+impl<T> FromRequestParts<AppContext<T>> for Authenticated<T>
+where
+    T: PrincipalResolver + 'static,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppContext<T>) -> Result<Self> {
+        Ok(Authenticated(T::resolve_principal(parts, state).await?))
+    }
+}
@@ -4,7 +4,7 @@ use clap::{ArgMatches, Command, CommandFactory, FromArgMatches};
 
 use crate::preludes::{AppContext, AppTrait, Result};
 
-pub use dialoguer::Confirm;
+pub use dialoguer::{Confirm, Password};
 use dialoguer::theme::ColorfulTheme;
 
 #[async_trait::async_trait]
@@ -54,4 +54,11 @@ pub fn ask<S: Into<String>>(prompt: S) -> Result<bool> {
         .with_prompt(prompt)
         .default(true)
         .interact()?)
-}
\ No newline at end of file
+}
+
+pub fn ask_password<S: Into<String>>(prompt: S) -> Result<String> {
+    Ok(Password::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt)
+        .with_confirmation("Repeat password", "Error: the passwords don't match.")
+        .interact()?)
+}
@@ -0,0 +1,726 @@
+//! DPoP sender-constrained tokens
+//!
+//! Validate DPoP proofs defined in [RFC 9449](https://www.rfc-editor.org/rfc/rfc9449).
+//! Application provides the replay cache, nonces and the key bound to access tokens by
+//! implementing [`DPoPVerifier`], then:
+//!
+//! - the [`DPoP`] extractor validates the proof of the request, e.g. on the token endpoint
+//! - the [`DPoPToken`] extractor validates the DPoP-bound access token presented with
+//!   `Authorization: DPoP <token>` on resource servers
+//!
+//! ```rust,ignore
+//! use inspirer_framework::dpop::DPoPToken;
+//!
+//! async fn resource(DPoPToken(token, proof, ..): DPoPToken<App>) -> Resp<()> {
+//!     ok(())
+//! }
+//! ```
+
+use std::marker::PhantomData;
+
+use axum::{
+    extract::{FromRequestParts, OriginalUri},
+    http::{
+        header::{AUTHORIZATION, WWW_AUTHENTICATE},
+        request::Parts,
+        HeaderValue, StatusCode,
+    },
+    response::{IntoResponse, Response},
+};
+use base64::prelude::*;
+use jsonwebtoken::{decode, decode_header, jwk::Jwk, Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use crate::{
+    app::{AppContext, AppTrait},
+    response::{json_error_response, ErrorDetail},
+    Error, Result,
+};
+
+/// Header of the DPoP proof
+pub const DPOP_HEADER: &str = "dpop";
+
+/// Header of the nonce provided by the server
+pub const DPOP_NONCE_HEADER: &str = "dpop-nonce";
+
+/// Algorithms of DPoP proofs, proofs are always signed by asymmetric keys
+pub const DPOP_SIGNING_ALGS: &[&str] = &["RS256", "PS256", "ES256", "EdDSA"];
+
+const PROOF_TYPE: &str = "dpop+jwt";
+
+/// Replay cache, nonces and token binding of DPoP proofs
+#[async_trait::async_trait]
+pub trait DPoPVerifier: AppTrait {
+    /// Public url of the application, `htu` of proofs is checked against the request path
+    /// joined to it
+    fn endpoint(context: &AppContext<Self>) -> Result<Url>;
+
+    /// Max seconds between `iat` of proofs and now
+    fn proof_max_age(_context: &AppContext<Self>) -> u64 {
+        300
+    }
+
+    /// Record `jti` of the proof key until `expires_at`, returns `false` if it was recorded
+    async fn record_proof(
+        context: &AppContext<Self>,
+        jkt: &str,
+        jti: &str,
+        expires_at: i64,
+    ) -> Result<bool>;
+
+    /// Nonce the client must include in proofs, `None` if nonces are not required
+    async fn nonce(_context: &AppContext<Self>) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    /// Whether the nonce of the proof is acceptable, only the current nonce is accepted by default
+    async fn verify_nonce(context: &AppContext<Self>, nonce: &str) -> Result<bool> {
+        Ok(Self::nonce(context).await?.as_deref() == Some(nonce))
+    }
+
+    /// JWK thumbprint the access token is bound to (`cnf.jkt`), `None` if the token is not
+    /// DPoP-bound. Returns [`Error::Unauthorized`] if the token is invalid.
+    async fn bound_key(context: &AppContext<Self>, access_token: &str) -> Result<Option<String>>;
+}
+
+/// Validated DPoP proof
+#[derive(Debug, Clone)]
+pub struct DPoPProof {
+    /// SHA-256 JWK thumbprint of the proof key, see [RFC 7638](https://www.rfc-editor.org/rfc/rfc7638)
+    pub jkt: String,
+    pub jti: String,
+    pub iat: i64,
+}
+
+/// Rejection of DPoP validation, responds `401 Unauthorized` with the `DPoP` challenge
+#[derive(thiserror::Error, Debug)]
+pub enum DPoPRejection {
+    #[error("{0}")]
+    InvalidProof(String),
+
+    /// The proof has no valid nonce, the current nonce is returned to the client
+    #[error("DPoP nonce is required")]
+    UseNonce(String),
+
+    #[error("{0}")]
+    InvalidToken(String),
+
+    #[error(transparent)]
+    Error(#[from] Error),
+}
+
+impl DPoPRejection {
+    /// Error code of the rejection, see [RFC 9449 7.1](https://www.rfc-editor.org/rfc/rfc9449#section-7.1)
+    pub fn error(&self) -> &'static str {
+        match self {
+            DPoPRejection::InvalidProof(_) => "invalid_dpop_proof",
+            DPoPRejection::UseNonce(_) => "use_dpop_nonce",
+            DPoPRejection::InvalidToken(_) => "invalid_token",
+            DPoPRejection::Error(_) => "server_error",
+        }
+    }
+}
+
+impl IntoResponse for DPoPRejection {
+    fn into_response(self) -> Response {
+        if let DPoPRejection::Error(err) = self {
+            return err.into_response();
+        }
+
+        let challenge = format!(
+            "DPoP algs=\"{}\", error=\"{}\"",
+            DPOP_SIGNING_ALGS.join(" "),
+            self.error()
+        );
+        let nonce = match &self {
+            DPoPRejection::UseNonce(nonce) => Some(nonce.clone()),
+            _ => None,
+        };
+
+        let mut response = (
+            StatusCode::UNAUTHORIZED,
+            json_error_response(ErrorDetail::new(self.error().to_string(), self.to_string())),
+        )
+            .into_response();
+
+        let headers = response.headers_mut();
+        if let Ok(challenge) = HeaderValue::try_from(challenge) {
+            headers.insert(WWW_AUTHENTICATE, challenge);
+        }
+        if let Some(nonce) = nonce.and_then(|nonce| HeaderValue::try_from(nonce).ok()) {
+            headers.insert(DPOP_NONCE_HEADER, nonce);
+        }
+
+        response
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ProofClaims {
+    jti: String,
+    htm: String,
+    htu: Url,
+    iat: i64,
+    ath: Option<String>,
+    nonce: Option<String>,
+}
+
+/// SHA-256 JWK thumbprint in URL-safe base64, see [RFC 7638 3](https://www.rfc-editor.org/rfc/rfc7638#section-3)
+pub fn jwk_thumbprint(jwk: &Jwk) -> Option<String> {
+    let jwk = serde_json::to_value(jwk).ok()?;
+    let member = |name: &str| jwk.get(name).and_then(Value::as_str);
+
+    // 只包含必需成员，按字典序排列且不含空白
+    let canonical = match member("kty")? {
+        "EC" => format!(
+            r#"{{"crv":"{}","kty":"EC","x":"{}","y":"{}"}}"#,
+            member("crv")?,
+            member("x")?,
+            member("y")?
+        ),
+        "RSA" => format!(
+            r#"{{"e":"{}","kty":"RSA","n":"{}"}}"#,
+            member("e")?,
+            member("n")?
+        ),
+        "OKP" => format!(
+            r#"{{"crv":"{}","kty":"OKP","x":"{}"}}"#,
+            member("crv")?,
+            member("x")?
+        ),
+        _ => return None,
+    };
+
+    Some(BASE64_URL_SAFE_NO_PAD.encode(Sha256::digest(canonical)))
+}
+
+/// Validate the DPoP proof of the request, see [RFC 9449 4.3](https://www.rfc-editor.org/rfc/rfc9449#section-4.3).
+/// `access_token` is required to be hashed in the `ath` claim if given.
+///
+/// Returns `None` if the request has no DPoP proof.
+pub async fn verify_proof<T: DPoPVerifier>(
+    parts: &Parts,
+    context: &AppContext<T>,
+    access_token: Option<&str>,
+) -> std::result::Result<Option<DPoPProof>, DPoPRejection> {
+    let invalid = |reason: &str| DPoPRejection::InvalidProof(reason.to_string());
+
+    let mut proofs = parts.headers.get_all(DPOP_HEADER).iter();
+    let Some(proof) = proofs.next() else {
+        return Ok(None);
+    };
+    if proofs.next().is_some() {
+        return Err(invalid("Multiple DPoP proofs"));
+    }
+    let proof = proof.to_str().map_err(|_| invalid("Invalid DPoP proof"))?;
+
+    let header = decode_header(proof).map_err(|_| invalid("Invalid DPoP proof"))?;
+    if header.typ.as_deref() != Some(PROOF_TYPE) {
+        return Err(invalid("Invalid type of DPoP proof"));
+    }
+    if matches!(
+        header.alg,
+        Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+    ) {
+        return Err(invalid("DPoP proof must be signed by an asymmetric key"));
+    }
+    let jwk = header
+        .jwk
+        .as_ref()
+        .ok_or_else(|| invalid("Missing public key of DPoP proof"))?;
+    let jkt = jwk_thumbprint(jwk).ok_or_else(|| invalid("Unsupported public key of DPoP proof"))?;
+    let key =
+        DecodingKey::from_jwk(jwk).map_err(|_| invalid("Invalid public key of DPoP proof"))?;
+
+    let mut validation = Validation::new(header.alg);
+    validation.validate_exp = false;
+    validation.validate_aud = false;
+    validation.set_required_spec_claims::<&str>(&[]);
+    let claims = decode::<ProofClaims>(proof, &key, &validation)
+        .map_err(|_| invalid("Invalid DPoP proof"))?
+        .claims;
+
+    if claims.htm != parts.method.as_str() {
+        return Err(invalid("DPoP proof method mismatch"));
+    }
+
+    // 嵌套路由中 `parts.uri` 不包含前缀，需要使用原始 uri
+    let path = parts
+        .extensions
+        .get::<OriginalUri>()
+        .map(|uri| uri.path())
+        .unwrap_or(parts.uri.path());
+    let mut htu = claims.htu.clone();
+    htu.set_query(None);
+    htu.set_fragment(None);
+    if htu != T::endpoint(context)?.join(path).map_err(Error::from)? {
+        return Err(invalid("DPoP proof uri mismatch"));
+    }
+
+    let max_age = T::proof_max_age(context) as i64;
+    let now = unix_now();
+    if (now - claims.iat).abs() > max_age {
+        return Err(invalid("DPoP proof is expired or issued in the future"));
+    }
+
+    if let Some(nonce) = T::nonce(context).await? {
+        let accepted = match &claims.nonce {
+            Some(claimed) => T::verify_nonce(context, claimed).await?,
+            None => false,
+        };
+        if !accepted {
+            return Err(DPoPRejection::UseNonce(nonce));
+        }
+    }
+
+    if let Some(access_token) = access_token {
+        let ath = BASE64_URL_SAFE_NO_PAD.encode(Sha256::digest(access_token));
+        if claims.ath.as_deref() != Some(ath.as_str()) {
+            return Err(invalid("DPoP proof access token hash mismatch"));
+        }
+    }
+
+    if !T::record_proof(context, &jkt, &claims.jti, claims.iat + max_age).await? {
+        return Err(invalid("DPoP proof has been used"));
+    }
+
+    Ok(Some(DPoPProof {
+        jkt,
+        jti: claims.jti,
+        iat: claims.iat,
+    }))
+}
+
+fn unix_now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+/// Extractor validates the DPoP proof of the request, `None` if the request has no proof
+pub struct DPoP<T>(pub Option<DPoPProof>, pub PhantomData<T>)
+where
+    T: DPoPVerifier;
+
+#[async_trait::async_trait]
+impl<T> FromRequestParts<AppContext<T>> for DPoP<T>
+where
+    T: DPoPVerifier + 'static,
+{
+    type Rejection = DPoPRejection;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppContext<T>,
+    ) -> std::result::Result<Self, DPoPRejection> {
+        Ok(DPoP(verify_proof(parts, state, None).await?, PhantomData))
+    }
+}
+
+/// Extractor requires a DPoP-bound access token with the proof of its key, returns the token
+/// and the proof
+pub struct DPoPToken<T>(pub String, pub DPoPProof, pub PhantomData<T>)
+where
+    T: DPoPVerifier;
+
+#[async_trait::async_trait]
+impl<T> FromRequestParts<AppContext<T>> for DPoPToken<T>
+where
+    T: DPoPVerifier + 'static,
+{
+    type Rejection = DPoPRejection;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppContext<T>,
+    ) -> std::result::Result<Self, DPoPRejection> {
+        let token = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split_once(' '))
+            .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("DPoP"))
+            .map(|(_, token)| token.trim().to_string())
+            .ok_or_else(|| DPoPRejection::InvalidToken("Missing DPoP access token".into()))?;
+
+        let proof = verify_proof(parts, state, Some(&token))
+            .await?
+            .ok_or_else(|| DPoPRejection::InvalidProof("Missing DPoP proof".into()))?;
+
+        match T::bound_key(state, &token).await {
+            Ok(Some(jkt)) if jkt == proof.jkt => Ok(DPoPToken(token, proof, PhantomData)),
+            Ok(_) => Err(DPoPRejection::InvalidToken(
+                "The access token is not bound to the proof key".into(),
+            )),
+            Err(Error::Unauthorized(reason)) => Err(DPoPRejection::InvalidToken(reason)),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashSet,
+        sync::{Arc, Mutex},
+    };
+
+    use axum::http::{Method, Request, Uri};
+    use crypto_utils::{p256::P256, KeyPair, KeyPairTrait};
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        app::Booter,
+        config::{ConfigLoader, Environment},
+    };
+
+    #[derive(Clone, Default)]
+    struct TestApp {
+        nonce: Option<String>,
+        proofs: Arc<Mutex<HashSet<(String, String)>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl AppTrait for TestApp {
+        fn app_name() -> &'static str {
+            "test"
+        }
+
+        async fn init(_booter: Booter) -> Result<Self> {
+            Ok(TestApp::default())
+        }
+
+        async fn routes(_app: AppContext<Self>) -> Result<axum::Router<AppContext<Self>>> {
+            Ok(axum::Router::new())
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl DPoPVerifier for TestApp {
+        fn endpoint(_context: &AppContext<Self>) -> Result<Url> {
+            Ok(Url::parse("https://auth.example.com/")?)
+        }
+
+        async fn record_proof(
+            context: &AppContext<Self>,
+            jkt: &str,
+            jti: &str,
+            _expires_at: i64,
+        ) -> Result<bool> {
+            Ok(context
+                .proofs
+                .lock()
+                .unwrap()
+                .insert((jkt.to_string(), jti.to_string())))
+        }
+
+        async fn nonce(context: &AppContext<Self>) -> Result<Option<String>> {
+            Ok(context.nonce.clone())
+        }
+
+        async fn bound_key(
+            _context: &AppContext<Self>,
+            _access_token: &str,
+        ) -> Result<Option<String>> {
+            Ok(None)
+        }
+    }
+
+    fn context(nonce: Option<&str>) -> AppContext<TestApp> {
+        let folder = std::env::temp_dir().join("inspirer-framework-dpop-tests");
+        std::fs::create_dir_all(&folder).unwrap();
+        std::fs::write(folder.join("test.toml"), "").unwrap();
+        let config = ConfigLoader::default()
+            .load_folder(&Environment::Test, &folder)
+            .unwrap();
+
+        AppContext::new(
+            TestApp {
+                nonce: nonce.map(str::to_string),
+                ..Default::default()
+            },
+            config,
+            Environment::Test,
+        )
+    }
+
+    struct ProofKey(KeyPair<P256>);
+
+    impl ProofKey {
+        fn new() -> Self {
+            ProofKey(KeyPair::<P256>::generate().unwrap())
+        }
+
+        fn jwk(&self) -> Jwk {
+            serde_json::from_value(self.0.get_jwks()).unwrap()
+        }
+
+        fn sign(&self, claims: &Value) -> String {
+            let mut header = Header::new(Algorithm::ES256);
+            header.typ = Some(PROOF_TYPE.to_string());
+            header.jwk = Some(self.jwk());
+            let key =
+                EncodingKey::from_ec_pem(self.0.get_private_key_pem().unwrap().as_bytes()).unwrap();
+
+            encode(&header, claims, &key).unwrap()
+        }
+    }
+
+    fn claims() -> Value {
+        json!({
+            "jti": "proof-1",
+            "htm": "POST",
+            "htu": "https://auth.example.com/oauth/token",
+            "iat": unix_now(),
+        })
+    }
+
+    fn claims_with(name: &str, value: Value) -> Value {
+        let mut claims = claims();
+        claims[name] = value;
+        claims
+    }
+
+    fn parts(proof: &str) -> Parts {
+        Request::builder()
+            .method(Method::POST)
+            .uri("/oauth/token?grant_type=refresh_token")
+            .header(DPOP_HEADER, proof)
+            .body(())
+            .unwrap()
+            .into_parts()
+            .0
+    }
+
+    async fn verify(
+        context: &AppContext<TestApp>,
+        proof: &str,
+        access_token: Option<&str>,
+    ) -> std::result::Result<Option<DPoPProof>, DPoPRejection> {
+        verify_proof(&parts(proof), context, access_token).await
+    }
+
+    fn rejected_with(
+        result: std::result::Result<Option<DPoPProof>, DPoPRejection>,
+        expected: &str,
+    ) -> bool {
+        matches!(result, Err(DPoPRejection::InvalidProof(reason)) if reason == expected)
+    }
+
+    #[tokio::test]
+    async fn accepts_valid_proofs() {
+        let key = ProofKey::new();
+        let context = context(None);
+
+        let proof = verify(&context, &key.sign(&claims()), None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(Some(proof.jkt), jwk_thumbprint(&key.jwk()));
+        assert_eq!(proof.jti, "proof-1");
+
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/oauth/token")
+            .body(())
+            .unwrap();
+        assert!(verify_proof(&request.into_parts().0, &context, None)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn rejects_proofs_of_other_requests() {
+        let key = ProofKey::new();
+        let context = context(None);
+
+        assert!(rejected_with(
+            verify(&context, &key.sign(&claims_with("htm", json!("GET"))), None).await,
+            "DPoP proof method mismatch"
+        ));
+        assert!(rejected_with(
+            verify(
+                &context,
+                &key.sign(&claims_with(
+                    "htu",
+                    json!("https://auth.example.com/oauth/revoke")
+                )),
+                None
+            )
+            .await,
+            "DPoP proof uri mismatch"
+        ));
+        assert!(rejected_with(
+            verify(
+                &context,
+                &key.sign(&claims_with(
+                    "htu",
+                    json!("https://evil.example.com/oauth/token")
+                )),
+                None
+            )
+            .await,
+            "DPoP proof uri mismatch"
+        ));
+
+        // 嵌套路由中按原始 uri 比较
+        let mut parts = parts(&key.sign(&claims_with("jti", json!("nested"))));
+        parts.uri = Uri::from_static("/token");
+        parts
+            .extensions
+            .insert(OriginalUri(Uri::from_static("/oauth/token")));
+        assert!(verify_proof(&parts, &context, None).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn rejects_stale_and_future_proofs() {
+        let key = ProofKey::new();
+        let context = context(None);
+        let expected = "DPoP proof is expired or issued in the future";
+
+        assert!(rejected_with(
+            verify(
+                &context,
+                &key.sign(&claims_with("iat", json!(unix_now() - 301))),
+                None
+            )
+            .await,
+            expected
+        ));
+        assert!(rejected_with(
+            verify(
+                &context,
+                &key.sign(&claims_with("iat", json!(unix_now() + 301))),
+                None
+            )
+            .await,
+            expected
+        ));
+    }
+
+    #[tokio::test]
+    async fn rejects_replayed_proofs() {
+        let key = ProofKey::new();
+        let context = context(None);
+        let proof = key.sign(&claims());
+
+        assert!(verify(&context, &proof, None).await.is_ok());
+        assert!(rejected_with(
+            verify(&context, &proof, None).await,
+            "DPoP proof has been used"
+        ));
+        // jti 按证明密钥区分
+        assert!(verify(&context, &ProofKey::new().sign(&claims()), None)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn rejects_proofs_of_other_access_tokens() {
+        let key = ProofKey::new();
+        let context = context(None);
+        let ath = BASE64_URL_SAFE_NO_PAD.encode(Sha256::digest("token"));
+        let expected = "DPoP proof access token hash mismatch";
+
+        assert!(rejected_with(
+            verify(&context, &key.sign(&claims()), Some("token")).await,
+            expected
+        ));
+        assert!(rejected_with(
+            verify(
+                &context,
+                &key.sign(&claims_with("ath", json!(ath))),
+                Some("other")
+            )
+            .await,
+            expected
+        ));
+        assert!(verify(
+            &context,
+            &key.sign(&claims_with("ath", json!(ath))),
+            Some("token")
+        )
+        .await
+        .is_ok());
+    }
+
+    #[tokio::test]
+    async fn requires_the_current_nonce() {
+        let key = ProofKey::new();
+        let context = context(Some("n-1"));
+        let use_nonce =
+            |result| matches!(result, Err(DPoPRejection::UseNonce(nonce)) if nonce == "n-1");
+
+        assert!(use_nonce(
+            verify(&context, &key.sign(&claims()), None).await
+        ));
+        assert!(use_nonce(
+            verify(
+                &context,
+                &key.sign(&claims_with("nonce", json!("n-0"))),
+                None
+            )
+            .await
+        ));
+        assert!(verify(
+            &context,
+            &key.sign(&claims_with("nonce", json!("n-1"))),
+            None
+        )
+        .await
+        .is_ok());
+
+        let response = DPoPRejection::UseNonce("n-1".into()).into_response();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(response.headers()[DPOP_NONCE_HEADER], "n-1");
+        assert!(response.headers()[WWW_AUTHENTICATE]
+            .to_str()
+            .unwrap()
+            .contains(r#"error="use_dpop_nonce""#));
+    }
+
+    #[tokio::test]
+    async fn rejects_malformed_proofs() {
+        let key = ProofKey::new();
+        let context = context(None);
+
+        let mut header = Header::new(Algorithm::HS256);
+        header.typ = Some(PROOF_TYPE.to_string());
+        header.jwk = Some(key.jwk());
+        let proof = encode(&header, &claims(), &EncodingKey::from_secret(b"secret")).unwrap();
+        assert!(rejected_with(
+            verify(&context, &proof, None).await,
+            "DPoP proof must be signed by an asymmetric key"
+        ));
+
+        let proof = key.sign(&claims());
+        let (_, rest) = proof.split_once('.').unwrap();
+        let mut header = Header::new(Algorithm::ES256);
+        header.jwk = Some(key.jwk());
+        let untyped = format!(
+            "{}.{rest}",
+            BASE64_URL_SAFE_NO_PAD.encode(serde_json::to_vec(&header).unwrap())
+        );
+        assert!(rejected_with(
+            verify(&context, &untyped, None).await,
+            "Invalid type of DPoP proof"
+        ));
+
+        // 签名与头部中的公钥不匹配
+        let other = ProofKey::new().sign(&claims());
+        let (signed, _) = other.rsplit_once('.').unwrap();
+        let (_, signature) = proof.rsplit_once('.').unwrap();
+        let forged = format!("{signed}.{signature}");
+        assert!(rejected_with(
+            verify(&context, &forged, None).await,
+            "Invalid DPoP proof"
+        ));
+    }
+}
@@ -3,10 +3,12 @@
 //! The framework base on [`axum`], and provide more components to simplify development.
 
 pub mod app;
+pub mod authorization;
 pub mod cli;
 pub mod command;
 pub mod component;
 pub mod config;
+pub mod dpop;
 pub mod error;
 pub mod logger;
 pub mod response;
@@ -17,6 +17,16 @@ where
 {
     let server_config = context.config.get::<ServerConfig>(config_keys::SERVER)?;
     let listener = tokio::net::TcpListener::bind(server_config.listen).await?;
+
+    let background = tokio::spawn({
+        let context = context.clone();
+        async move {
+            if let Err(err) = T::background(context).await {
+                tracing::error!(error = %err, "background task failed");
+            }
+        }
+    });
+
     let routes = T::routes(context.clone())
         .await?
         .with_state(context)
@@ -26,6 +36,8 @@ where
         .with_graceful_shutdown(shutdown_signal())
         .await?;
 
+    background.abort();
+
     Ok(())
 }
 
@@ -1,14 +1,51 @@
+import { FormEvent, useEffect, useState } from 'react'
+import { branding, logoUri } from './branding'
+
+interface Provider {
+  uuid: string
+  name: string
+  display_name: string
+}
+
 function App() {
+  const [providers, setProviders] = useState<Provider[]>([])
+  const [error, setError] = useState<string | null>(null)
+
+  useEffect(() => {
+    fetch('/login/providers')
+      .then((res) => res.json())
+      .then((res) => res.success && setProviders(res.data))
+      .catch(() => setProviders([]))
+  }, [])
+
+  const login = (event: FormEvent<HTMLFormElement>) => {
+    event.preventDefault()
+    const form = new FormData(event.currentTarget)
+
+    fetch('/login', {
+      method: 'POST',
+      headers: { 'Content-Type': 'application/json' },
+      body: JSON.stringify({
+        credential: { type: 'email', payload: { email: form.get('email'), password: form.get('password') } },
+      }),
+    })
+      .then((res) => res.json())
+      .then((res) => (res.success ? window.location.assign(res.data.redirect) : setError(res.data?.error ?? 'Sign in failed')))
+      .catch(() => setError('Sign in failed'))
+  }
+
   return (
     <>
       <div className="flex min-h-full flex-col justify-center px-6 py-12 lg:px-8">
         <div className="sm:mx-auto sm:w-full sm:max-w-sm">
-          <img className="mx-auto h-10 w-auto" src="https://tailwindui.com/img/logos/mark.svg?color=indigo&shade=600" alt="Your Company" />
-          <h2 className="mt-10 text-center text-2xl font-bold leading-9 tracking-tight text-gray-900">Sign in to your account</h2>
+          <img className="mx-auto h-10 w-auto" src={logoUri} alt={branding.display_name ?? 'Logo'} />
+          <h2 className="mt-10 text-center text-2xl font-bold leading-9 tracking-tight text-gray-900">Sign in to {branding.display_name ?? 'your account'}</h2>
         </div>
 
         <div className="mt-10 sm:mx-auto sm:w-full sm:max-w-sm">
-          <form className="space-y-6" action="#" method="POST">
+          <form className="space-y-6" onSubmit={login}>
+            {error && <p className="text-center text-sm text-red-600">{error}</p>}
+
             <div>
               <label htmlFor="email" className="block text-sm font-medium leading-6 text-gray-900">Email address</label>
               <div className="mt-2">
@@ -33,6 +70,14 @@ function App() {
             </div>
           </form>
 
+          {providers.length > 0 && (
+            <div className="mt-6 space-y-3">
+              {providers.map((provider) => (
+                <a key={provider.uuid} href={`/login/federation/${provider.uuid}`} className="flex w-full justify-center rounded-md bg-white px-3 py-1.5 text-sm font-semibold leading-6 text-gray-900 shadow-sm ring-1 ring-inset ring-gray-300 hover:bg-gray-50">Sign in with {provider.display_name}</a>
+              ))}
+            </div>
+          )}
+
           <p className="mt-10 text-center text-sm text-gray-500">
             <span className="pr-1">Not a member?</span>
             <a href="#" className="font-semibold leading-6 text-indigo-600 hover:text-indigo-500">Click here signup</a>
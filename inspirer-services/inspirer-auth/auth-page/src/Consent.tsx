@@ -0,0 +1,67 @@
+import { useEffect, useState } from 'react'
+import { branding, logoUri } from './branding'
+
+interface ConsentDetails {
+  app_uuid: string
+  app_name: string
+  scopes: string[]
+  granted: string[]
+}
+
+function Consent() {
+  const [details, setDetails] = useState<ConsentDetails | null>(null)
+  const [error, setError] = useState<string | null>(null)
+
+  useEffect(() => {
+    fetch('/consent/details')
+      .then((res) => res.json())
+      .then((res) => (res.success ? setDetails(res.data) : setError(res.data?.error ?? 'Invalid request')))
+      .catch(() => setError('Invalid request'))
+  }, [])
+
+  const decide = (approve: boolean) => {
+    fetch('/consent', {
+      method: 'POST',
+      headers: { 'Content-Type': 'application/json' },
+      body: JSON.stringify({ approve }),
+    })
+      .then((res) => res.json())
+      .then((res) => (res.success ? window.location.assign(res.data.redirect) : setError(res.data?.error ?? 'Invalid request')))
+      .catch(() => setError('Invalid request'))
+  }
+
+  return (
+    <div className="flex min-h-full flex-col justify-center px-6 py-12 lg:px-8">
+      <div className="sm:mx-auto sm:w-full sm:max-w-sm">
+        <img className="mx-auto h-10 w-auto" src={logoUri} alt={branding.display_name ?? 'Logo'} />
+        <h2 className="mt-10 text-center text-2xl font-bold leading-9 tracking-tight text-gray-900">
+          {details ? `${details.app_name} wants to access your account` : 'Authorize'}
+        </h2>
+      </div>
+
+      <div className="mt-10 sm:mx-auto sm:w-full sm:max-w-sm">
+        {error && <p className="text-center text-sm text-red-600">{error}</p>}
+
+        {details && (
+          <>
+            <ul className="space-y-2 text-sm text-gray-900">
+              {details.scopes.map((scope) => (
+                <li key={scope} className="flex items-center justify-between rounded-md px-3 py-2 ring-1 ring-inset ring-gray-300">
+                  <span>{scope}</span>
+                  {!details.granted.includes(scope) && <span className="text-xs font-semibold text-indigo-600">New</span>}
+                </li>
+              ))}
+            </ul>
+
+            <div className="mt-6 flex gap-3">
+              <button onClick={() => decide(false)} className="flex w-full justify-center rounded-md bg-white px-3 py-1.5 text-sm font-semibold leading-6 text-gray-900 shadow-sm ring-1 ring-inset ring-gray-300 hover:bg-gray-50">Deny</button>
+              <button onClick={() => decide(true)} className="flex w-full justify-center rounded-md bg-indigo-600 px-3 py-1.5 text-sm font-semibold leading-6 text-white shadow-sm hover:bg-indigo-500 focus-visible:outline focus-visible:outline-2 focus-visible:outline-offset-2 focus-visible:outline-indigo-600">Allow</button>
+            </div>
+          </>
+        )}
+      </div>
+    </div>
+  )
+}
+
+export default Consent
@@ -0,0 +1,96 @@
+import { FormEvent, useEffect, useState } from 'react'
+import { branding, logoUri } from './branding'
+
+interface DeviceDetails {
+  user_code: string
+  app_uuid: string
+  app_name: string
+  scopes: string[]
+}
+
+function Device() {
+  const userCode = new URLSearchParams(window.location.search).get('user_code')
+  const [details, setDetails] = useState<DeviceDetails | null>(null)
+  const [message, setMessage] = useState<string | null>(null)
+
+  useEffect(() => {
+    if (!userCode) return
+
+    fetch(`/device/details?user_code=${encodeURIComponent(userCode)}`)
+      .then((res) => {
+        // 未登录时先校验 user code 并跳转到登录页
+        if (res.status === 401) {
+          window.location.assign(`/device/verify?user_code=${encodeURIComponent(userCode)}`)
+          return null
+        }
+        return res.json()
+      })
+      .then((res) => res && (res.success ? setDetails(res.data) : setMessage(res.data?.error ?? 'Invalid or expired code')))
+      .catch(() => setMessage('Invalid or expired code'))
+  }, [userCode])
+
+  const enter = (event: FormEvent<HTMLFormElement>) => {
+    event.preventDefault()
+    const code = new FormData(event.currentTarget).get('user_code') as string
+    window.location.assign(`/device/verify?user_code=${encodeURIComponent(code)}`)
+  }
+
+  const decide = (approve: boolean) => {
+    fetch('/device/decision', {
+      method: 'POST',
+      headers: { 'Content-Type': 'application/json' },
+      body: JSON.stringify({ user_code: userCode, approve }),
+    })
+      .then((res) => res.json())
+      .then((res) => {
+        setDetails(null)
+        setMessage(res.success ? (approve ? 'Device connected, you can return to your device.' : 'Access denied.') : res.data?.error ?? 'Invalid request')
+      })
+      .catch(() => setMessage('Invalid request'))
+  }
+
+  return (
+    <div className="flex min-h-full flex-col justify-center px-6 py-12 lg:px-8">
+      <div className="sm:mx-auto sm:w-full sm:max-w-sm">
+        <img className="mx-auto h-10 w-auto" src={logoUri} alt={branding.display_name ?? 'Logo'} />
+        <h2 className="mt-10 text-center text-2xl font-bold leading-9 tracking-tight text-gray-900">
+          {details ? `Connect ${details.app_name} on your device` : 'Connect a device'}
+        </h2>
+      </div>
+
+      <div className="mt-10 sm:mx-auto sm:w-full sm:max-w-sm">
+        {message && <p className="text-center text-sm text-gray-700">{message}</p>}
+
+        {!userCode && (
+          <form className="space-y-6" onSubmit={enter}>
+            <div>
+              <label htmlFor="user_code" className="block text-sm font-medium leading-6 text-gray-900">Enter the code shown on your device</label>
+              <div className="mt-2">
+                <input id="user_code" name="user_code" type="text" required autoComplete="off" className="block w-full rounded-md border-0 py-1.5 text-center uppercase tracking-widest text-gray-900 shadow-sm ring-1 ring-inset ring-gray-300 focus:ring-2 focus:ring-inset focus:ring-indigo-600 sm:text-sm sm:leading-6 p-2" />
+              </div>
+            </div>
+            <button type="submit" className="flex w-full justify-center rounded-md bg-indigo-600 px-3 py-1.5 text-sm font-semibold leading-6 text-white shadow-sm hover:bg-indigo-500">Continue</button>
+          </form>
+        )}
+
+        {details && (
+          <>
+            <p className="text-center text-lg font-semibold tracking-widest text-gray-900">{details.user_code}</p>
+            <ul className="mt-6 space-y-2 text-sm text-gray-900">
+              {details.scopes.map((scope) => (
+                <li key={scope} className="rounded-md px-3 py-2 ring-1 ring-inset ring-gray-300">{scope}</li>
+              ))}
+            </ul>
+
+            <div className="mt-6 flex gap-3">
+              <button onClick={() => decide(false)} className="flex w-full justify-center rounded-md bg-white px-3 py-1.5 text-sm font-semibold leading-6 text-gray-900 shadow-sm ring-1 ring-inset ring-gray-300 hover:bg-gray-50">Deny</button>
+              <button onClick={() => decide(true)} className="flex w-full justify-center rounded-md bg-indigo-600 px-3 py-1.5 text-sm font-semibold leading-6 text-white shadow-sm hover:bg-indigo-500">Allow</button>
+            </div>
+          </>
+        )}
+      </div>
+    </div>
+  )
+}
+
+export default Device
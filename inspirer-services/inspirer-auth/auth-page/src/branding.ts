@@ -0,0 +1,17 @@
+/** Branding of the domain and the app, injected into index.html by the server */
+export interface Branding {
+  display_name?: string | null
+  logo_uri?: string | null
+  primary_color?: string | null
+  background_color?: string | null
+}
+
+declare global {
+  interface Window {
+    __AUTH_BRANDING__?: Branding
+  }
+}
+
+export const branding: Branding = window.__AUTH_BRANDING__ ?? {}
+
+export const logoUri = branding.logo_uri ?? 'https://tailwindui.com/img/logos/mark.svg?color=indigo&shade=600'
@@ -1,10 +1,12 @@
 import React from 'react'
 import ReactDOM from 'react-dom/client'
 import App from './App.tsx'
+import Consent from './Consent.tsx'
+import Device from './Device.tsx'
 import './index.css'
 
 ReactDOM.createRoot(document.getElementById('root')!).render(
   <React.StrictMode>
-    <App />
+    {window.location.pathname === '/consent' ? <Consent /> : window.location.pathname === '/device' ? <Device /> : <App />}
   </React.StrictMode>,
 )
@@ -5,7 +5,15 @@ export default {
     "./src/**/*.{js,ts,jsx,tsx}",
   ],
   theme: {
-    extend: {},
+    extend: {
+      // 品牌色由服务端注入的 CSS 变量决定
+      colors: {
+        indigo: {
+          500: 'var(--brand-primary, #6366f1)',
+          600: 'var(--brand-primary, #4f46e5)',
+        },
+      },
+    },
   },
   plugins: [],
 }
\ No newline at end of file
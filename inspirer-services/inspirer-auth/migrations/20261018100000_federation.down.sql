@@ -0,0 +1,2 @@
+drop table if exists linked_identities;
+drop table if exists identity_providers;
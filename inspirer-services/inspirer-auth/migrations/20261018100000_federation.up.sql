@@ -0,0 +1,36 @@
+-- identity_providers
+create table
+    if not exists identity_providers (
+        id int unsigned not null auto_increment primary key,
+        uuid binary(16) not null,
+        domain_uuid binary(16) not null,
+        name varchar(100) not null,
+        display_name varchar(100) not null,
+        issuer_url varchar(255) not null,
+        client_id varchar(255) not null,
+        client_secret varchar(255) default null,
+        scopes varchar(255) not null default 'openid email profile',
+        claim_mapping json not null,
+        created_at timestamp not null,
+        updated_at timestamp not null
+    );
+
+create unique index unique_identity_provider_uuid on identity_providers (uuid);
+
+create unique index unique_identity_provider_name on identity_providers (domain_uuid, name);
+
+-- linked_identities
+create table
+    if not exists linked_identities (
+        id bigint unsigned not null auto_increment primary key,
+        user_uuid binary(16) not null,
+        provider_uuid binary(16) not null,
+        subject varchar(255) not null,
+        claims json not null,
+        created_at timestamp not null,
+        updated_at timestamp not null
+    );
+
+create index index_user on linked_identities (user_uuid);
+
+create unique index unique_provider_subject on linked_identities (provider_uuid, subject);
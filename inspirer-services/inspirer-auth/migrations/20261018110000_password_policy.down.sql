@@ -0,0 +1,3 @@
+drop table if exists password_histories;
+
+alter table domains drop column setting;
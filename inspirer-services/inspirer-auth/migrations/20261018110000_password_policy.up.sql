@@ -0,0 +1,16 @@
+alter table domains add column setting json;
+
+update domains set setting = '{}' where setting is null;
+
+alter table domains modify column setting json not null;
+
+-- password_histories
+create table
+    if not exists password_histories (
+        id bigint unsigned not null auto_increment primary key,
+        user_uuid binary(16) not null,
+        password varchar(120) not null,
+        created_at timestamp not null
+    );
+
+create index index_user on password_histories (user_uuid);
@@ -0,0 +1,85 @@
+-- roles
+create table
+    if not exists roles (
+        id int unsigned not null auto_increment primary key,
+        uuid binary(16) not null,
+        domain_uuid binary(16) not null,
+        name varchar(100) not null,
+        display_name varchar(100) not null,
+        created_at timestamp not null,
+        updated_at timestamp not null
+    );
+
+create unique index unique_role_uuid on roles (uuid);
+
+create unique index unique_role_name on roles (domain_uuid, name);
+
+-- permissions
+create table
+    if not exists permissions (
+        id int unsigned not null auto_increment primary key,
+        uuid binary(16) not null,
+        domain_uuid binary(16) not null,
+        name varchar(100) not null,
+        description varchar(255) not null default '',
+        created_at timestamp not null,
+        updated_at timestamp not null
+    );
+
+create unique index unique_permission_uuid on permissions (uuid);
+
+create unique index unique_permission_name on permissions (domain_uuid, name);
+
+-- groups
+create table
+    if not exists `groups` (
+        id int unsigned not null auto_increment primary key,
+        uuid binary(16) not null,
+        domain_uuid binary(16) not null,
+        name varchar(100) not null,
+        display_name varchar(100) not null,
+        created_at timestamp not null,
+        updated_at timestamp not null
+    );
+
+create unique index unique_group_uuid on `groups` (uuid);
+
+create unique index unique_group_name on `groups` (domain_uuid, name);
+
+-- role_permissions
+create table
+    if not exists role_permissions (
+        role_uuid binary(16) not null,
+        permission_uuid binary(16) not null,
+        created_at timestamp not null,
+        primary key (role_uuid, permission_uuid)
+    );
+
+-- user_roles
+create table
+    if not exists user_roles (
+        user_uuid binary(16) not null,
+        role_uuid binary(16) not null,
+        created_at timestamp not null,
+        primary key (user_uuid, role_uuid)
+    );
+
+-- group_members
+create table
+    if not exists group_members (
+        group_uuid binary(16) not null,
+        user_uuid binary(16) not null,
+        created_at timestamp not null,
+        primary key (group_uuid, user_uuid)
+    );
+
+create index index_user on group_members (user_uuid);
+
+-- group_roles
+create table
+    if not exists group_roles (
+        group_uuid binary(16) not null,
+        role_uuid binary(16) not null,
+        created_at timestamp not null,
+        primary key (group_uuid, role_uuid)
+    );
@@ -0,0 +1,25 @@
+-- audit_events
+create table
+    if not exists audit_events (
+        id bigint unsigned not null auto_increment primary key,
+        uuid binary(16) not null,
+        domain_uuid binary(16) default null,
+        action varchar(40) not null,
+        outcome varchar(20) not null,
+        actor_uuid binary(16) default null,
+        subject_uuid binary(16) default null,
+        ip varchar(64) default null,
+        user_agent varchar(255) default null,
+        detail json not null,
+        created_at timestamp not null
+    );
+
+create unique index unique_audit_event_uuid on audit_events (uuid);
+
+create index index_domain_created on audit_events (domain_uuid, created_at);
+
+create index index_actor on audit_events (actor_uuid);
+
+create index index_subject on audit_events (subject_uuid);
+
+create index index_created on audit_events (created_at);
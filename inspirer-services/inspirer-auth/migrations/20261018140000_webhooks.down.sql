@@ -0,0 +1,3 @@
+drop table if exists webhook_dead_letters;
+
+drop table if exists webhook_deliveries;
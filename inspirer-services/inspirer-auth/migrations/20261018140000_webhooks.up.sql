@@ -0,0 +1,38 @@
+-- webhook_deliveries
+create table
+    if not exists webhook_deliveries (
+        id bigint unsigned not null auto_increment primary key,
+        uuid binary(16) not null,
+        app_uuid binary(16) not null,
+        event varchar(40) not null,
+        url varchar(2048) not null,
+        payload json not null,
+        attempts int unsigned not null default 0,
+        next_attempt_at timestamp not null,
+        last_error text default null,
+        created_at timestamp not null,
+        updated_at timestamp not null
+    );
+
+create unique index unique_webhook_delivery_uuid on webhook_deliveries (uuid);
+
+create index index_next_attempt on webhook_deliveries (next_attempt_at);
+
+-- webhook_dead_letters
+create table
+    if not exists webhook_dead_letters (
+        id bigint unsigned not null auto_increment primary key,
+        uuid binary(16) not null,
+        app_uuid binary(16) not null,
+        event varchar(40) not null,
+        url varchar(2048) not null,
+        payload json not null,
+        attempts int unsigned not null,
+        last_error text default null,
+        created_at timestamp not null,
+        failed_at timestamp not null
+    );
+
+create unique index unique_webhook_dead_letter_uuid on webhook_dead_letters (uuid);
+
+create index index_app_failed on webhook_dead_letters (app_uuid, failed_at);
@@ -0,0 +1,18 @@
+-- verifications
+create table
+    if not exists verifications (
+        id bigint unsigned not null auto_increment primary key,
+        uuid binary(16) not null,
+        user_uuid binary(16) not null,
+        channel varchar(20) not null,
+        target varchar(255) not null,
+        code char(64) not null,
+        attempts int unsigned not null default 0,
+        expires_at timestamp not null,
+        verified_at timestamp null default null,
+        created_at timestamp not null
+    );
+
+create unique index unique_verification_uuid on verifications (uuid);
+
+create index index_user_channel on verifications (user_uuid, channel);
@@ -0,0 +1,2 @@
+-- Data migration only, the normalized profiles are still valid JSON and nothing needs to be reverted
+select 1;
@@ -0,0 +1,66 @@
+-- Normalize users.profile to the `UserProfile` representation, which is the standard claims JSON.
+-- Profiles written by `openidconnect::StandardClaims` only miss the required claims, profiles
+-- written by `UserProfile` before used RFC 3339 `updated_at` and structured `phone_number`.
+set time_zone = '+00:00';
+
+update users
+set
+    profile = json_object()
+where
+    profile is null
+    or json_type(profile) <> 'OBJECT';
+
+update users
+set
+    profile = json_set(profile, '$.sub', bin_to_uuid(uuid))
+where
+    json_extract(profile, '$.sub') is null;
+
+update users
+set
+    profile = json_set(profile, '$.name', username)
+where
+    json_extract(profile, '$.name') is null
+    and username is not null;
+
+update users
+set
+    profile = json_set(
+        profile,
+        '$.updated_at',
+        unix_timestamp(
+            str_to_date(
+                left(json_unquote(json_extract(profile, '$.updated_at')), 19),
+                '%Y-%m-%dT%H:%i:%s'
+            )
+        )
+    )
+where
+    json_type(json_extract(profile, '$.updated_at')) = 'STRING';
+
+update users
+set
+    profile = json_set(
+        profile,
+        '$.gender',
+        lower(json_unquote(json_extract(profile, '$.gender')))
+    )
+where
+    json_type(json_extract(profile, '$.gender')) = 'STRING';
+
+update users
+set
+    profile = if(
+        phone_number is null,
+        json_remove(profile, '$.phone_number'),
+        json_set(profile, '$.phone_number', phone_number)
+    )
+where
+    json_type(json_extract(profile, '$.phone_number')) = 'OBJECT';
+
+update users
+set
+    profile = json_set(profile, '$.email', email)
+where
+    email is not null
+    and json_extract(profile, '$.email') is null;
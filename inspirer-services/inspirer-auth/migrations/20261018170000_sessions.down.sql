@@ -0,0 +1,5 @@
+drop table if exists refresh_tokens;
+
+drop table if exists grants;
+
+drop table if exists user_sessions;
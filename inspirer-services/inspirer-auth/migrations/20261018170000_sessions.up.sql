@@ -0,0 +1,55 @@
+-- user_sessions
+create table
+    if not exists user_sessions (
+        id bigint unsigned not null auto_increment primary key,
+        uuid binary(16) not null,
+        user_uuid binary(16) not null,
+        domain_uuid binary(16) not null,
+        ip varchar(64) default null,
+        user_agent varchar(255) default null,
+        created_at timestamp not null,
+        last_seen_at timestamp not null,
+        revoked_at timestamp null default null
+    );
+
+create unique index unique_user_session_uuid on user_sessions (uuid);
+
+create index index_user on user_sessions (user_uuid);
+
+-- grants
+create table
+    if not exists grants (
+        id bigint unsigned not null auto_increment primary key,
+        uuid binary(16) not null,
+        session_uuid binary(16) not null,
+        user_uuid binary(16) not null,
+        app_uuid binary(16) not null,
+        scope varchar(1024) not null,
+        created_at timestamp not null,
+        last_used_at timestamp not null,
+        revoked_at timestamp null default null
+    );
+
+create unique index unique_grant_uuid on grants (uuid);
+
+create index index_session on grants (session_uuid);
+
+create index index_user on grants (user_uuid);
+
+-- refresh_tokens
+create table
+    if not exists refresh_tokens (
+        id bigint unsigned not null auto_increment primary key,
+        uuid binary(16) not null,
+        grant_uuid binary(16) not null,
+        token char(64) not null,
+        expires_at timestamp not null,
+        created_at timestamp not null,
+        revoked_at timestamp null default null
+    );
+
+create unique index unique_refresh_token_uuid on refresh_tokens (uuid);
+
+create unique index unique_refresh_token on refresh_tokens (token);
+
+create index index_grant on refresh_tokens (grant_uuid);
@@ -0,0 +1,3 @@
+drop table if exists authorization_codes;
+
+drop table if exists consents;
@@ -0,0 +1,31 @@
+-- consents
+create table
+    if not exists consents (
+        id bigint unsigned not null auto_increment primary key,
+        uuid binary(16) not null,
+        user_uuid binary(16) not null,
+        app_uuid binary(16) not null,
+        scope varchar(1024) not null,
+        created_at timestamp not null,
+        updated_at timestamp not null
+    );
+
+create unique index unique_consent_uuid on consents (uuid);
+
+create unique index unique_user_app on consents (user_uuid, app_uuid);
+
+-- authorization_codes
+create table
+    if not exists authorization_codes (
+        id bigint unsigned not null auto_increment primary key,
+        code char(64) not null,
+        grant_uuid binary(16) not null,
+        app_uuid binary(16) not null,
+        redirect_uri varchar(1024) not null,
+        nonce varchar(255) default null,
+        expires_at timestamp not null,
+        created_at timestamp not null,
+        consumed_at timestamp null default null
+    );
+
+create unique index unique_authorization_code on authorization_codes (code);
@@ -0,0 +1,3 @@
+drop table if exists client_registrations;
+
+drop table if exists initial_access_tokens;
@@ -0,0 +1,30 @@
+-- initial_access_tokens
+create table
+    if not exists initial_access_tokens (
+        id bigint unsigned not null auto_increment primary key,
+        uuid binary(16) not null,
+        domain_uuid binary(16) not null,
+        token char(64) not null,
+        description varchar(255) not null default '',
+        expires_at timestamp not null,
+        created_at timestamp not null
+    );
+
+create unique index unique_initial_access_token_uuid on initial_access_tokens (uuid);
+
+create unique index unique_initial_access_token on initial_access_tokens (token);
+
+-- client_registrations
+create table
+    if not exists client_registrations (
+        id bigint unsigned not null auto_increment primary key,
+        app_uuid binary(16) not null,
+        token char(64) not null,
+        metadata json not null,
+        created_at timestamp not null,
+        updated_at timestamp not null
+    );
+
+create unique index unique_registration_app on client_registrations (app_uuid);
+
+create unique index unique_registration_token on client_registrations (token);
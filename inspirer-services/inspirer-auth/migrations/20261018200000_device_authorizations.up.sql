@@ -0,0 +1,19 @@
+-- device_authorizations
+create table
+    if not exists device_authorizations (
+        id bigint unsigned not null auto_increment primary key,
+        device_code char(64) not null,
+        user_code varchar(16) not null,
+        app_uuid binary(16) not null,
+        scope varchar(1024) not null,
+        status varchar(16) not null,
+        grant_uuid binary(16) default null,
+        `interval` int unsigned not null,
+        last_polled_at timestamp null default null,
+        expires_at timestamp not null,
+        created_at timestamp not null
+    );
+
+create unique index unique_device_code on device_authorizations (device_code);
+
+create unique index unique_user_code on device_authorizations (user_code);
@@ -0,0 +1,11 @@
+-- client_assertions
+create table
+    if not exists client_assertions (
+        id bigint unsigned not null auto_increment primary key,
+        app_uuid binary(16) not null,
+        jti char(64) not null,
+        expires_at timestamp not null,
+        created_at timestamp not null
+    );
+
+create unique index unique_app_jti on client_assertions (app_uuid, jti);
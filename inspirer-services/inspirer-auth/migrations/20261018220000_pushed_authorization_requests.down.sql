@@ -0,0 +1 @@
+drop table if exists pushed_authorization_requests;
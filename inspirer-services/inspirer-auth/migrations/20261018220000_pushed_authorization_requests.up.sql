@@ -0,0 +1,12 @@
+-- pushed_authorization_requests
+create table
+    if not exists pushed_authorization_requests (
+        id bigint unsigned not null auto_increment primary key,
+        request_uri char(64) not null,
+        app_uuid binary(16) not null,
+        parameters json not null,
+        expires_at timestamp not null,
+        created_at timestamp not null
+    );
+
+create unique index unique_request_uri on pushed_authorization_requests (request_uri);
@@ -0,0 +1,3 @@
+alter table refresh_tokens drop column jkt;
+
+drop table if exists dpop_proofs;
@@ -0,0 +1,12 @@
+-- dpop_proofs
+create table
+    if not exists dpop_proofs (
+        id bigint unsigned not null auto_increment primary key,
+        jti char(64) not null,
+        expires_at timestamp not null,
+        created_at timestamp not null
+    );
+
+create unique index unique_dpop_jti on dpop_proofs (jti);
+
+alter table refresh_tokens add column jkt varchar(64) default null;
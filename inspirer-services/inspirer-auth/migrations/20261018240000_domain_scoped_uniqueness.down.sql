@@ -0,0 +1,15 @@
+drop index unique_app_name on apps;
+
+create unique index unique_app_name on apps (name);
+
+drop index unique_username on users;
+
+create unique index unique_username on users (username);
+
+drop index unique_phone on users;
+
+create unique index unique_phone on users (phone_number);
+
+drop index unique_email on users;
+
+create unique index unique_email on users (email);
@@ -0,0 +1,17 @@
+-- users
+drop index unique_email on users;
+
+create unique index unique_email on users (domain_uuid, email);
+
+drop index unique_phone on users;
+
+create unique index unique_phone on users (domain_uuid, phone_number);
+
+drop index unique_username on users;
+
+create unique index unique_username on users (domain_uuid, username);
+
+-- apps
+drop index unique_app_name on apps;
+
+create unique index unique_app_name on apps (domain_uuid, name);
@@ -0,0 +1 @@
+alter table user_sessions drop column amr;
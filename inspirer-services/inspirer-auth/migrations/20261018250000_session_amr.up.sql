@@ -0,0 +1 @@
+alter table user_sessions add column amr varchar(64) not null default 'pwd';
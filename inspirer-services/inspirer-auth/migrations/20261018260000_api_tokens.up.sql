@@ -0,0 +1,23 @@
+-- api_tokens
+create table
+    if not exists api_tokens (
+        id bigint unsigned not null auto_increment primary key,
+        uuid binary(16) not null,
+        domain_uuid binary(16) not null,
+        kind varchar(20) not null,
+        owner_uuid binary(16) not null,
+        name varchar(100) not null,
+        prefix varchar(16) not null,
+        token char(64) not null,
+        scope varchar(255) not null default '',
+        expires_at timestamp null default null,
+        last_used_at timestamp null default null,
+        created_at timestamp not null,
+        revoked_at timestamp null default null
+    );
+
+create unique index unique_api_token_uuid on api_tokens (uuid);
+
+create unique index unique_api_token on api_tokens (token);
+
+create index index_owner on api_tokens (kind, owner_uuid);
@@ -0,0 +1,7 @@
+drop index unique_group_external_id on `groups`;
+
+alter table `groups` drop column external_id;
+
+drop index unique_user_external_id on users;
+
+alter table users drop column external_id;
@@ -0,0 +1,9 @@
+-- users
+alter table users add column external_id varchar(255) null default null;
+
+create unique index unique_user_external_id on users (domain_uuid, external_id);
+
+-- groups
+alter table `groups` add column external_id varchar(255) null default null;
+
+create unique index unique_group_external_id on `groups` (domain_uuid, external_id);
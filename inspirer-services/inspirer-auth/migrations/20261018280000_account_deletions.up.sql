@@ -0,0 +1,19 @@
+-- account_deletions
+create table
+    if not exists account_deletions (
+        id bigint unsigned not null auto_increment primary key,
+        uuid binary(16) not null,
+        domain_uuid binary(16) not null,
+        user_uuid binary(16) not null,
+        reason varchar(255) null default null,
+        requested_at timestamp not null,
+        scheduled_at timestamp not null,
+        cancelled_at timestamp null default null,
+        completed_at timestamp null default null
+    );
+
+create unique index unique_account_deletion_uuid on account_deletions (uuid);
+
+create index index_user on account_deletions (user_uuid);
+
+create index index_scheduled_at on account_deletions (scheduled_at);
@@ -0,0 +1,7 @@
+drop index index_status on users;
+
+alter table users drop column status_changed_at;
+
+alter table users drop column status_reason;
+
+alter table users drop column status;
@@ -0,0 +1,8 @@
+-- users
+alter table users add column status varchar(30) not null default 'active';
+
+alter table users add column status_reason varchar(255) null default null;
+
+alter table users add column status_changed_at timestamp null default null;
+
+create index index_status on users (domain_uuid, status);
@@ -0,0 +1,2 @@
+-- grants
+alter table grants drop column claims;
@@ -0,0 +1,2 @@
+-- grants
+alter table grants add column claims json null default null;
@@ -0,0 +1,3 @@
+alter table account_deletions drop column last_error;
+
+alter table account_deletions drop column attempts;
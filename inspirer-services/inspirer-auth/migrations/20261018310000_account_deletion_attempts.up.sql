@@ -0,0 +1,4 @@
+-- account_deletions
+alter table account_deletions add column attempts int unsigned not null default 0;
+
+alter table account_deletions add column last_error text default null;
@@ -0,0 +1 @@
+alter table identity_providers drop column auto_link_verified_email;
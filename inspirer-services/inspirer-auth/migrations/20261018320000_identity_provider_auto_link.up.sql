@@ -0,0 +1,2 @@
+-- identity_providers
+alter table identity_providers add column auto_link_verified_email boolean not null default false;
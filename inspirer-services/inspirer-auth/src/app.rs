@@ -1,24 +1,46 @@
+use axum_extra::{
+    headers::{authorization::Bearer, Authorization},
+    TypedHeader,
+};
 use axum_login::tower_sessions::{
     cookie::time::Duration, Expiry, MemoryStore, SessionManagerLayer, SessionStore,
 };
-use inspirer_framework::{command::CommandRegister, preludes::*};
+use inspirer_framework::{
+    authorization::PrincipalResolver,
+    axum::{
+        extract::FromRequestParts,
+        http::{header::AUTHORIZATION, request::Parts},
+        middleware,
+    },
+    command::CommandRegister,
+    dpop::{DPoPRejection, DPoPToken, DPoPVerifier},
+    preludes::*,
+};
 use sea_orm::DbConn;
 use tower_sessions_redis_store::{
     fred::{clients::RedisPool, interfaces::ClientLike, types::RedisConfig},
     RedisStore,
 };
+use url::Url;
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
 use crate::{
+    auth::{domain::RequestDomain, rbac::Principal},
     command,
     config::{AppConfig, SessionDriverConfig},
     controller,
+    service::{
+        app::App as AppService, domain::Domain, dpop::DPoP, privacy::Privacy, rbac::Rbac,
+        session::Session, user::User, webhook::Webhook, ServiceInterface,
+    },
+    token::{AccessToken, SigningKey},
 };
 
 #[derive(Clone)]
 pub struct App {
     pub database: DbConn,
+    pub signing_key: SigningKey,
 }
 
 #[async_trait::async_trait]
@@ -28,8 +50,11 @@ impl AppTrait for App {
     }
 
     async fn init(booter: Booter) -> Result<Self> {
+        let config = booter.config().get::<AppConfig>("app")?;
+
         Ok(App {
             database: booter.component().await?,
+            signing_key: SigningKey::load(&config.signing_key.private_key)?,
         })
     }
 
@@ -39,7 +64,15 @@ impl AppTrait for App {
         let router = Router::new()
             .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::openapi()))
             .merge({
-                let router = controller::auth::routes(&app);
+                let router = controller::auth::routes(&app)?
+                    .merge(controller::federation::routes())
+                    .merge(controller::authorize::routes())
+                    .nest("/d/:domain", controller::authorize::routes())
+                    .merge(controller::device::routes())
+                    .layer(middleware::from_fn_with_state(
+                        app.clone(),
+                        controller::auth::track_session,
+                    ));
                 match &app_config.session.driver {
                     SessionDriverConfig::Memory => router.layer(build_session_manage_layer(
                         &app_config,
@@ -66,14 +99,193 @@ impl AppTrait for App {
                 }
             })
             .merge(controller::api::routes())
-            .merge(controller::oidc::routes());
+            .merge(controller::oidc::routes())
+            .nest("/d/:domain", controller::oidc::routes())
+            .merge(controller::oidc::app_routes())
+            .merge(controller::registration::routes())
+            .merge(controller::scim::routes())
+            .nest("/d/:domain", controller::scim::routes());
 
         Ok(router)
     }
 
+    async fn background(app: AppContext<Self>) -> Result<()> {
+        let webhook = app.service::<Webhook>();
+        let privacy = app.service::<Privacy>();
+        // 各 worker 独立运行，一个 worker 退出不会取消其他 worker
+        let (webhook, privacy) = tokio::join!(webhook.work(), privacy.work());
+        for (worker, result) in [("webhook", webhook), ("account deletion", privacy)] {
+            if let Err(err) = result {
+                tracing::error!(worker, error = %err, "background worker stopped");
+            }
+        }
+
+        Ok(())
+    }
+
     fn commands(register: &mut CommandRegister<Self>) {
         register.register::<command::init::InitData>("app:init");
         register.register::<command::list::List>("app:list");
+        register.register::<command::claims::MapClaim>("app:claim");
+        register.register::<command::idp::AddIdentityProvider>("idp:add");
+        register.register::<command::user::ChangePassword>("user:password");
+        register.register::<command::user::ChangeStatus>("user:status");
+        register.register::<command::user_transfer::ImportUsers>("user:import");
+        register.register::<command::user_transfer::ExportUsers>("user:export");
+        register.register::<command::rbac::AddRole>("role:add");
+        register.register::<command::rbac::AddPermission>("permission:add");
+        register.register::<command::rbac::AddGroup>("group:add");
+        register.register::<command::rbac::GrantPermission>("role:grant");
+        register.register::<command::rbac::AssignRole>("role:assign");
+        register.register::<command::rbac::JoinGroup>("group:join");
+        register.register::<command::audit::TailAudit>("audit:tail");
+        register.register::<command::audit::PruneAudit>("audit:prune");
+        register.register::<command::webhook::SubscribeWebhook>("webhook:subscribe");
+        register.register::<command::webhook::ReplayWebhook>("webhook:replay");
+        register.register::<command::registration::IssueInitialToken>("client:token");
+        register.register::<command::api_token::IssueApiToken>("token:issue");
+        register.register::<command::api_token::ListApiTokens>("token:list");
+        register.register::<command::api_token::RevokeApiToken>("token:revoke");
+    }
+}
+
+#[async_trait::async_trait]
+impl PrincipalResolver for App {
+    type Principal = Principal;
+
+    async fn resolve_principal(parts: &mut Parts, context: &AppContext<Self>) -> Result<Principal> {
+        let token = AccessToken::from_request_parts(parts, context).await?;
+
+        let user = context
+            .service::<User>()
+            .find_user_by_uuid(token.sub)
+            .await
+            .map_err(|_| Error::Unauthorized("User not exists".into()))?;
+        // 代理登录等没有会话的 token 不会随会话撤销失效，需检查账户状态
+        user.status.check()?;
+
+        Ok(Principal {
+            session_uuid: token.sid,
+            actor_uuid: token.act.map(|actor| actor.sub),
+            ..context.service::<Rbac>().principal(&user).await?
+        })
+    }
+}
+
+/// Access token of the `Authorization` header, a DPoP proof is required for the DPoP scheme.
+/// Tokens of revoked sessions are rejected.
+#[async_trait::async_trait]
+impl FromRequestParts<AppContext<App>> for AccessToken {
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, context: &AppContext<App>) -> Result<Self> {
+        let dpop = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.get(..5))
+            .is_some_and(|scheme| scheme.eq_ignore_ascii_case("DPoP "));
+
+        let token = if dpop {
+            let DPoPToken(token, ..) = DPoPToken::<App>::from_request_parts(parts, context)
+                .await
+                .map_err(|rejection| match rejection {
+                DPoPRejection::Error(err) => err,
+                rejection => Error::Unauthorized(rejection.to_string()),
+            })?;
+
+            AccessToken::verify(&token, &context.app.signing_key, None, None)
+                .map_err(|err| Error::Unauthorized(err.to_string()))?
+        } else {
+            let TypedHeader(Authorization(bearer)) =
+                TypedHeader::<Authorization<Bearer>>::from_request_parts(parts, context)
+                    .await
+                    .map_err(|_| Error::Unauthorized("Missing bearer token".into()))?;
+
+            let token = AccessToken::verify(bearer.token(), &context.app.signing_key, None, None)
+                .map_err(|err| Error::Unauthorized(err.to_string()))?;
+
+            // DPoP 绑定的 token 不能作为 bearer token 使用
+            if token.cnf.is_some() {
+                return Err(Error::Unauthorized(
+                    "DPoP-bound token requires a DPoP proof".into(),
+                ));
+            }
+
+            token
+        };
+
+        // token 只在签发它的域中有效，未识别域时以应用所属的域为准
+        let RequestDomain(domain) = RequestDomain::from_request_parts(parts, context).await?;
+        let domains = context.service::<Domain>();
+        let issuer = match domain {
+            Some(domain) => domains.issuer(&domain)?,
+            None => {
+                let app = context
+                    .service::<AppService>()
+                    .find_app_by_uuid(token.aud)
+                    .await
+                    .map_err(|_| Error::Unauthorized("Invalid access token".into()))?;
+                domains.issuer_of(app.domain_uuid).await?
+            }
+        };
+        if token.iss != issuer.as_str() {
+            return Err(Error::Unauthorized(
+                "The access token is not issued by the domain".into(),
+            ));
+        }
+
+        if let Some(sid) = token.sid {
+            context
+                .service::<Session>()
+                .find_active_session(sid)
+                .await
+                .map_err(|_| Error::Unauthorized("Session has been revoked".into()))?;
+        }
+
+        Ok(token)
+    }
+}
+
+#[async_trait::async_trait]
+impl DPoPVerifier for App {
+    fn endpoint(context: &AppContext<Self>) -> Result<Url> {
+        Ok(context.config.get::<AppConfig>("app")?.app_endpoint)
+    }
+
+    fn proof_max_age(context: &AppContext<Self>) -> u64 {
+        context
+            .config
+            .get::<AppConfig>("app")
+            .map(|config| config.dpop.proof_max_age)
+            .unwrap_or(300)
+    }
+
+    async fn record_proof(
+        context: &AppContext<Self>,
+        jkt: &str,
+        jti: &str,
+        expires_at: i64,
+    ) -> Result<bool> {
+        context
+            .service::<DPoP>()
+            .record_proof(jkt, jti, expires_at)
+            .await
+    }
+
+    async fn nonce(context: &AppContext<Self>) -> Result<Option<String>> {
+        context.service::<DPoP>().nonce()
+    }
+
+    async fn verify_nonce(context: &AppContext<Self>, nonce: &str) -> Result<bool> {
+        context.service::<DPoP>().verify_nonce(nonce)
+    }
+
+    async fn bound_key(context: &AppContext<Self>, access_token: &str) -> Result<Option<String>> {
+        let token = AccessToken::verify(access_token, &context.app.signing_key, None, None)
+            .map_err(|err| Error::Unauthorized(err.to_string()))?;
+
+        Ok(token.cnf.map(|cnf| cnf.jkt))
     }
 }
 
@@ -104,7 +316,7 @@ where
     paths(crate::controller::api::login),
     components(schemas(
         crate::controller::api::LoginRequest,
-        crate::controller::api::LoginCredential,
+        crate::auth::user::UserCredential,
         crate::controller::api::LoginResponse
     ))
 )]
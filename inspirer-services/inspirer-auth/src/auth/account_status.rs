@@ -0,0 +1,70 @@
+//! Account status lifecycle
+//!
+//! 只有 `active` 的账号可以登录、刷新 token 和访问 userinfo 端点，其他状态的账号会被拒绝并返回对应的错误。
+//! 账号被停用或锁定时会撤销其所有会话和授权。每次状态变更都需要记录原因，并写入审计日志。
+
+use std::fmt;
+
+use clap::ValueEnum;
+use inspirer_framework::{http::StatusCode, permission, response::ErrorDetail, Error};
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+permission!(
+    /// Change the status of users of the domain
+    pub ManageAccountStatus,
+    "auth.user.status"
+);
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize, ValueEnum,
+)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::N(30))")]
+#[serde(rename_all = "snake_case")]
+#[value(rename_all = "snake_case")]
+pub enum AccountStatus {
+    #[sea_orm(string_value = "active")]
+    Active,
+    /// Suspended by an admin or the provisioning system
+    #[sea_orm(string_value = "disabled")]
+    Disabled,
+    /// Locked for security reasons, e.g. the account is compromised
+    #[sea_orm(string_value = "locked")]
+    Locked,
+    /// Waiting for the identity of the user to be checked, e.g. accounts created by an admin
+    /// before onboarding. Verifying the email or phone number does not change the status,
+    /// only an admin or the provisioning system moves the account out of it.
+    #[sea_orm(string_value = "pending_verification")]
+    PendingVerification,
+}
+
+impl AccountStatus {
+    /// Whether sessions and grants of the account are revoked when it enters the status
+    pub fn revokes_sessions(&self) -> bool {
+        matches!(self, AccountStatus::Disabled | AccountStatus::Locked)
+    }
+
+    /// Check the account can be used, returns the error describing the status otherwise
+    pub fn check(&self) -> Result<(), Error> {
+        let (error, description) = match self {
+            AccountStatus::Active => return Ok(()),
+            AccountStatus::Disabled => ("account_disabled", "The account is disabled"),
+            AccountStatus::Locked => ("account_locked", "The account is locked"),
+            AccountStatus::PendingVerification => (
+                "account_pending_verification",
+                "The account is pending verification",
+            ),
+        };
+
+        Err(Error::CustomError(
+            StatusCode::FORBIDDEN,
+            ErrorDetail::new(error, description),
+        ))
+    }
+}
+
+impl fmt::Display for AccountStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_value())
+    }
+}
@@ -0,0 +1,57 @@
+//! Personal access tokens and API keys
+//!
+//! 用户可以创建带名称、scope 和有效期的个人访问令牌（personal access token），
+//! 应用可以拥有 API key，domain 可以拥有用于 SCIM 用户同步的令牌。它们都是长期有效的不透明令牌，
+//! 只保存哈希值和用于辨识的前缀，明文只在创建时返回一次。资源服务通过 introspection 端点验证
+//! 个人访问令牌和 API key。
+
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Length of the prefix kept in plaintext to identify the token
+pub const TOKEN_PREFIX_LENGTH: usize = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::N(20))")]
+#[serde(rename_all = "snake_case")]
+pub enum ApiTokenKind {
+    /// Owned by a user, acts on behalf of the user
+    #[sea_orm(string_value = "personal_access_token")]
+    PersonalAccessToken,
+    /// Owned by an app
+    #[sea_orm(string_value = "api_key")]
+    ApiKey,
+    /// Owned by a domain, authenticates SCIM provisioning requests of the domain
+    #[sea_orm(string_value = "scim_token")]
+    ScimToken,
+}
+
+impl ApiTokenKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiTokenKind::PersonalAccessToken => "personal_access_token",
+            ApiTokenKind::ApiKey => "api_key",
+            ApiTokenKind::ScimToken => "scim_token",
+        }
+    }
+
+    /// Marker at the beginning of the plaintext token, so that leaked tokens are easy to spot
+    pub fn marker(&self) -> &'static str {
+        match self {
+            ApiTokenKind::PersonalAccessToken => "pat_",
+            ApiTokenKind::ApiKey => "key_",
+            ApiTokenKind::ScimToken => "scim_",
+        }
+    }
+
+    /// Kind of the plaintext token, `None` if it is not an opaque token of the kinds
+    pub fn of_token(token: &str) -> Option<Self> {
+        [
+            ApiTokenKind::PersonalAccessToken,
+            ApiTokenKind::ApiKey,
+            ApiTokenKind::ScimToken,
+        ]
+        .into_iter()
+        .find(|kind| token.starts_with(kind.marker()))
+    }
+}
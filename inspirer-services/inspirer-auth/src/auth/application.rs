@@ -6,6 +6,11 @@ use serde::{Deserialize, Serialize};
 use tabled::Tabled;
 
 use self::app_setting::{BaseSetting, OIDCSetting};
+use super::{
+    claims::{default_mappings, ClaimMapping},
+    exchange::TokenExchangePolicy,
+    webhook::WebhookSubscription,
+};
 
 #[derive(
     Debug, Clone, Serialize, Deserialize, Default, FromJsonQueryResult, PartialEq, Eq, Tabled,
@@ -15,23 +20,82 @@ pub struct AppSetting {
     pub base_setting: BaseSetting,
     #[tabled(inline)]
     pub oidc_setting: OIDCSetting,
+    /// 用户生命周期事件的 webhook 订阅
+    #[serde(default)]
+    #[tabled(skip)]
+    pub webhooks: Vec<WebhookSubscription>,
+    /// 允许该应用通过 token exchange 换取的目标服务
+    #[serde(default)]
+    #[tabled(skip)]
+    pub token_exchange: Vec<TokenExchangePolicy>,
+    /// 输出到 token 和 userinfo 的 claims，为空时使用默认映射
+    #[serde(default)]
+    #[tabled(skip)]
+    pub claims: Vec<ClaimMapping>,
+}
+
+impl AppSetting {
+    /// Claim mappings of the app, the default mappings are used if none is configured
+    pub fn claim_mappings(&self) -> Vec<ClaimMapping> {
+        match self.claims.is_empty() {
+            true => default_mappings(&self.oidc_setting.roles_claim),
+            false => self.claims.clone(),
+        }
+    }
 }
 
 pub mod app_setting {
     use std::str::FromStr;
 
+    use jsonwebtoken::jwk::JwkSet;
     use sea_orm::FromJsonQueryResult;
     use serde::{Deserialize, Serialize};
     use tabled::Tabled;
     use url::Url;
 
+    use crate::auth::client::TokenEndpointAuthMethod;
+
     #[derive(Debug, Clone, Serialize, Deserialize, FromJsonQueryResult, PartialEq, Eq, Tabled)]
+    #[serde(default)]
     pub struct OIDCSetting {
         pub access_token_expire_in: u64,
         pub id_token_expire_in: u64,
         pub refresh_token_expire_in: u64,
         /// 授权码过期时间
         pub authorize_code_expire_in: u64,
+        /// 设备授权（device code）过期时间
+        pub device_code_expire_in: u64,
+        /// 推送授权请求（PAR）的 `request_uri` 过期时间
+        pub pushed_request_expire_in: u64,
+        /// 在 access token 和 id token 中输出用户角色的 claim 名称，为空时不输出。
+        /// 仅用于默认的 claim 映射
+        pub roles_claim: String,
+        /// 已注册的回调地址，授权请求的 `redirect_uri` 必须与其中之一完全一致
+        #[tabled(skip)]
+        pub redirect_uris: Vec<Url>,
+        /// 第一方应用，授权时跳过用户确认（consent）步骤
+        pub first_party: bool,
+        /// Token、introspection 和 revocation 端点的客户端认证方式
+        pub token_endpoint_auth_method: TokenEndpointAuthMethod,
+        /// `private_key_jwt` 验证断言使用的公钥，与 `jwks_uri` 二选一
+        #[tabled(skip)]
+        pub jwks: Option<JwkSet>,
+        #[tabled(skip)]
+        pub jwks_uri: Option<Url>,
+        /// 授权请求必须先推送到 PAR 端点，不接受直接传递的授权参数
+        pub require_pushed_authorization_requests: bool,
+        /// 签发的 token 必须通过 DPoP 绑定到客户端的密钥
+        pub dpop_bound_access_tokens: bool,
+        /// 允许客户端使用的授权类型，为空时不限制
+        #[tabled(skip)]
+        pub grant_types: Vec<String>,
+    }
+
+    impl OIDCSetting {
+        /// Whether the client may use the grant type at the token endpoint
+        pub fn allows_grant_type(&self, grant_type: &str) -> bool {
+            self.grant_types.is_empty() || self.grant_types.iter().any(|g| g == grant_type)
+        }
     }
 
     impl Default for OIDCSetting {
@@ -41,6 +105,17 @@ pub mod app_setting {
                 id_token_expire_in: 604800,
                 refresh_token_expire_in: 1209600,
                 authorize_code_expire_in: 600,
+                device_code_expire_in: 600,
+                pushed_request_expire_in: 90,
+                roles_claim: "roles".into(),
+                redirect_uris: vec![],
+                first_party: false,
+                token_endpoint_auth_method: TokenEndpointAuthMethod::default(),
+                jwks: None,
+                jwks_uri: None,
+                require_pushed_authorization_requests: false,
+                dpop_bound_access_tokens: false,
+                grant_types: vec![],
             }
         }
     }
@@ -0,0 +1,139 @@
+//! Audit log of authentication and administrative events
+//!
+//! 审计事件只允许追加，除了按保留期清理和删除账号时的匿名化之外不允许修改或删除。
+
+use std::fmt;
+
+use axum_extra::headers::{HeaderMapExt, UserAgent};
+use inspirer_framework::{
+    axum::{extract::FromRequestParts, http::request::Parts},
+    permission,
+};
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+permission!(
+    /// Read audit events of the domain
+    pub ReadAudit,
+    "auth.audit.read"
+);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::N(40))")]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    #[sea_orm(string_value = "login")]
+    Login,
+    #[sea_orm(string_value = "federated_login")]
+    FederatedLogin,
+    #[sea_orm(string_value = "password_change")]
+    PasswordChange,
+    #[sea_orm(string_value = "profile_update")]
+    ProfileUpdate,
+    #[sea_orm(string_value = "session_revoke")]
+    SessionRevoke,
+    #[sea_orm(string_value = "rbac_change")]
+    RbacChange,
+    #[sea_orm(string_value = "app_change")]
+    AppChange,
+    #[sea_orm(string_value = "api_token_change")]
+    ApiTokenChange,
+    #[sea_orm(string_value = "impersonation")]
+    Impersonation,
+    #[sea_orm(string_value = "user_import")]
+    UserImport,
+    #[sea_orm(string_value = "scim_change")]
+    ScimChange,
+    #[sea_orm(string_value = "account_deletion")]
+    AccountDeletion,
+    #[sea_orm(string_value = "account_status_change")]
+    AccountStatusChange,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::N(20))")]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOutcome {
+    #[sea_orm(string_value = "success")]
+    Success,
+    #[sea_orm(string_value = "failure")]
+    Failure,
+}
+
+impl fmt::Display for AuditAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_value())
+    }
+}
+
+impl fmt::Display for AuditOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_value())
+    }
+}
+
+/// Client information of the request
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+#[async_trait::async_trait]
+impl<S> FromRequestParts<S> for ClientInfo
+where
+    S: Send + Sync,
+{
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let header = |name: &str| {
+            parts
+                .headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+        };
+
+        let ip = header("x-forwarded-for")
+            .and_then(|value| value.split(',').next())
+            .or_else(|| header("x-real-ip"))
+            .map(|value| value.trim().to_string());
+
+        Ok(ClientInfo {
+            ip,
+            user_agent: parts
+                .headers
+                .typed_get::<UserAgent>()
+                .map(|agent| agent.to_string()),
+        })
+    }
+}
+
+/// Audit event to be recorded
+#[derive(Debug, Clone)]
+pub struct AuditEvent {
+    pub domain_uuid: Option<Uuid>,
+    pub action: AuditAction,
+    pub outcome: AuditOutcome,
+    /// The user who performed the action, `None` for anonymous requests and cli commands
+    pub actor_uuid: Option<Uuid>,
+    /// The user affected by the action
+    pub subject_uuid: Option<Uuid>,
+    pub client: ClientInfo,
+    pub detail: Value,
+}
+
+impl AuditEvent {
+    pub fn new(action: AuditAction, outcome: AuditOutcome) -> Self {
+        AuditEvent {
+            domain_uuid: None,
+            action,
+            outcome,
+            actor_uuid: None,
+            subject_uuid: None,
+            client: ClientInfo::default(),
+            detail: Value::Object(Default::default()),
+        }
+    }
+}
@@ -0,0 +1,53 @@
+//! User authentication
+//!
+//! 所有登录方式（会话登录、API 登录、联合登录）都经过同一个认证流程：由凭据对应的认证器验证用户，
+//! 记录审计事件并创建会话。认证结果携带 `amr`（Authentication Method Reference），
+//! 记录在会话中并输出到 ID Token。
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::entity::{user_sessions, users};
+
+/// Authentication method reference, see [RFC 8176](https://www.rfc-editor.org/rfc/rfc8176)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AuthenticationMethod {
+    /// Password-based authentication
+    #[serde(rename = "pwd")]
+    Password,
+    /// Authenticated by an upstream identity provider, not registered in RFC 8176
+    #[serde(rename = "fed")]
+    Federated,
+}
+
+impl AuthenticationMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuthenticationMethod::Password => "pwd",
+            AuthenticationMethod::Federated => "fed",
+        }
+    }
+}
+
+impl fmt::Display for AuthenticationMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Join the methods into the space-separated form stored in sessions
+pub fn join_methods(amr: &[AuthenticationMethod]) -> String {
+    amr.iter()
+        .map(AuthenticationMethod::as_str)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The authenticated user with the session created for the authentication
+#[derive(Debug)]
+pub struct AuthenticatedPrincipal {
+    pub user: users::Model,
+    pub session: user_sessions::Model,
+    pub amr: Vec<AuthenticationMethod>,
+}
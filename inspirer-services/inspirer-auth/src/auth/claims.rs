@@ -0,0 +1,516 @@
+//! Claim mapping of apps
+//!
+//! 应用可以配置输出到 ID token、access token 和 userinfo 的 claims，claim 的值可以来自用户档案字段、
+//! 用户角色、静态值或者 tera 模板。未配置时使用默认映射，标准 claims 按
+//! [OpenID Connect Core 5.4](https://openid.net/specs/openid-connect-core-1_0.html#ScopeClaims)
+//! 由 scope 决定是否输出。
+//!
+//! 客户端可以通过 `claims` 请求参数请求 scope 之外的 claims，见
+//! [OpenID Connect Core 5.5](https://openid.net/specs/openid-connect-core-1_0.html#ClaimsParameter)，
+//! 只有应用映射中存在的 claims 会被输出。
+
+use std::collections::BTreeSet;
+
+use sea_orm::FromJsonQueryResult;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{json, Map, Value};
+use tera::{Context, Tera};
+
+use crate::entity::{apps, users};
+
+/// Standard claims of the scopes, see
+/// [OpenID Connect Core 5.4](https://openid.net/specs/openid-connect-core-1_0.html#ScopeClaims)
+pub const SCOPE_CLAIMS: &[(&str, &[&str])] = &[
+    (
+        "profile",
+        &[
+            "name",
+            "family_name",
+            "given_name",
+            "middle_name",
+            "nickname",
+            "preferred_username",
+            "profile",
+            "picture",
+            "website",
+            "gender",
+            "birthdate",
+            "zoneinfo",
+            "locale",
+            "updated_at",
+        ],
+    ),
+    ("email", &["email", "email_verified"]),
+    ("phone", &["phone_number", "phone_number_verified"]),
+    ("address", &["address"]),
+];
+
+/// Claims set by the issuer which can not be mapped
+pub const RESERVED_CLAIMS: &[&str] = &[
+    "iss",
+    "sub",
+    "aud",
+    "exp",
+    "iat",
+    "nbf",
+    "jti",
+    "nonce",
+    "auth_time",
+    "acr",
+    "amr",
+    "azp",
+    "sid",
+    "act",
+    "cnf",
+    "scope",
+    "client_id",
+];
+
+/// Where the claim is emitted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+#[value(rename_all = "snake_case")]
+pub enum ClaimTarget {
+    IdToken,
+    AccessToken,
+    Userinfo,
+}
+
+impl ClaimTarget {
+    pub const ALL: &'static [ClaimTarget] = &[
+        ClaimTarget::IdToken,
+        ClaimTarget::AccessToken,
+        ClaimTarget::Userinfo,
+    ];
+}
+
+/// Where the value of the claim comes from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "source", rename_all = "snake_case")]
+pub enum ClaimSource {
+    /// Claim of the user profile, e.g. `email` or `name#ja-Kana-JP`
+    Profile {
+        field: String,
+    },
+    /// Names of the roles of the user
+    Roles,
+    Static {
+        value: Value,
+    },
+    /// Tera template rendered with `user`, `profile`, `roles` and `app`, the result is a string
+    Template {
+        template: String,
+    },
+}
+
+/// Claim emitted by the app
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClaimMapping {
+    /// Name of the claim in the tokens
+    pub name: String,
+    #[serde(flatten)]
+    pub source: ClaimSource,
+    /// Emitted into the ID token and the userinfo response by default
+    #[serde(default = "default_targets")]
+    pub targets: Vec<ClaimTarget>,
+    /// Scope required to emit the claim, the claim is always emitted if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+}
+
+fn default_targets() -> Vec<ClaimTarget> {
+    vec![ClaimTarget::IdToken, ClaimTarget::Userinfo]
+}
+
+impl ClaimMapping {
+    /// Validate the mapping, returns the violation
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.is_empty() || self.name.chars().any(char::is_whitespace) {
+            return Err(format!("Invalid claim name {:?}", self.name));
+        }
+        if RESERVED_CLAIMS.contains(&self.name.as_str()) {
+            return Err(format!("Claim {} is reserved", self.name));
+        }
+        if self.targets.is_empty() {
+            return Err(format!("Claim {} has no target", self.name));
+        }
+
+        match &self.source {
+            ClaimSource::Profile { field } if field.is_empty() || field == "sub" => {
+                Err(format!("Claim {} maps an invalid profile field", self.name))
+            }
+            ClaimSource::Template { template } => Tera::default()
+                .add_raw_template(&self.name, template)
+                .map_err(|err| format!("Invalid template of claim {}: {err}", self.name)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether the claim is emitted into the target with the granted scopes, claims requested
+    /// by the `claims` parameter are emitted regardless of the scope
+    pub fn emitted(
+        &self,
+        target: ClaimTarget,
+        scopes: &BTreeSet<String>,
+        requested: Option<&ClaimsRequest>,
+    ) -> bool {
+        self.targets.contains(&target)
+            && (self
+                .scope
+                .as_ref()
+                .is_none_or(|scope| scopes.contains(scope))
+                || requested.is_some_and(|requested| requested.contains(target, &self.name)))
+    }
+}
+
+/// Mappings used by apps without claim mappings: the standard claims of the granted scopes
+/// and the roles in `roles_claim` if it is not empty
+pub fn default_mappings(roles_claim: &str) -> Vec<ClaimMapping> {
+    let mut mappings = SCOPE_CLAIMS
+        .iter()
+        .flat_map(|(scope, claims)| {
+            claims.iter().map(|claim| ClaimMapping {
+                name: claim.to_string(),
+                source: ClaimSource::Profile {
+                    field: claim.to_string(),
+                },
+                targets: default_targets(),
+                scope: Some(scope.to_string()),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    if !roles_claim.is_empty() {
+        mappings.push(ClaimMapping {
+            name: roles_claim.to_string(),
+            source: ClaimSource::Roles,
+            targets: ClaimTarget::ALL.to_vec(),
+            scope: None,
+        });
+    }
+
+    mappings
+}
+
+/// Data the claims of the user are resolved from
+pub struct ClaimSubject {
+    profile: Map<String, Value>,
+    roles: Vec<String>,
+    context: Context,
+}
+
+impl ClaimSubject {
+    pub fn new(user: &users::Model, roles: Vec<String>, app: &apps::Model) -> tera::Result<Self> {
+        let mut profile = match serde_json::to_value(&user.profile)? {
+            Value::Object(profile) => profile,
+            _ => Map::new(),
+        };
+        profile.remove("sub");
+
+        let context = Context::from_value(json!({
+            "user": {
+                "uuid": user.uuid,
+                "domain_uuid": user.domain_uuid,
+                "username": user.username,
+                "email": user.email,
+                "phone_number": user.phone_number,
+                "external_id": user.external_id,
+            },
+            "profile": profile,
+            "roles": roles,
+            "app": {
+                "uuid": app.uuid,
+                "name": app.name,
+                "display_name": app.display_name,
+            },
+        }))?;
+
+        Ok(ClaimSubject {
+            profile,
+            roles,
+            context,
+        })
+    }
+
+    /// Value of the claim, `None` if the profile does not have the field
+    pub fn resolve(&self, mapping: &ClaimMapping) -> tera::Result<Option<Value>> {
+        Ok(match &mapping.source {
+            ClaimSource::Profile { field } => self.profile.get(field).cloned(),
+            ClaimSource::Roles => Some(json!(self.roles)),
+            ClaimSource::Static { value } => Some(value.clone()),
+            ClaimSource::Template { template } => Some(Value::String(Tera::one_off(
+                template,
+                &self.context,
+                false,
+            )?)),
+        })
+    }
+}
+
+/// The `claims` request parameter, only the names of the requested claims are used
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, FromJsonQueryResult)]
+pub struct ClaimsRequest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub userinfo: Option<Map<String, Value>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id_token: Option<Map<String, Value>>,
+}
+
+impl ClaimsRequest {
+    pub fn contains(&self, target: ClaimTarget, name: &str) -> bool {
+        let claims = match target {
+            ClaimTarget::IdToken => &self.id_token,
+            ClaimTarget::Userinfo => &self.userinfo,
+            ClaimTarget::AccessToken => return false,
+        };
+
+        claims
+            .as_ref()
+            .is_some_and(|claims| claims.contains_key(name))
+    }
+}
+
+/// The `claims` parameter is a JSON string in the query or form parameters, and an object in
+/// the request object
+pub fn deserialize_claims_request<'de, D>(
+    deserializer: D,
+) -> Result<Option<ClaimsRequest>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<Value>::deserialize(deserializer)? {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(claims)) => serde_json::from_str(&claims)
+            .map(Some)
+            .map_err(serde::de::Error::custom),
+        Some(claims) => serde_json::from_value(claims)
+            .map(Some)
+            .map_err(serde::de::Error::custom),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::auth::account_status::AccountStatus;
+
+    fn user() -> users::Model {
+        users::Model {
+            id: 1,
+            uuid: Uuid::new_v4(),
+            domain_uuid: Uuid::new_v4(),
+            email: Some("alice@example.com".into()),
+            username: Some("alice".into()),
+            phone_number: None,
+            external_id: Some("E-42".into()),
+            status: AccountStatus::Active,
+            status_reason: None,
+            status_changed_at: None,
+            password: String::new(),
+            profile: serde_json::from_value(json!({
+                "sub": "1",
+                "name": "Alice",
+                "email": "alice@example.com",
+                "name#ja-Kana-JP": "アリス",
+            }))
+            .unwrap(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn app() -> apps::Model {
+        apps::Model {
+            id: 1,
+            uuid: Uuid::new_v4(),
+            domain_uuid: Uuid::new_v4(),
+            name: "wiki".into(),
+            display_name: "Wiki".into(),
+            secret: vec![],
+            profile: json!({}),
+            setting: Default::default(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn mapping(name: &str, source: ClaimSource) -> ClaimMapping {
+        ClaimMapping {
+            name: name.into(),
+            source,
+            targets: default_targets(),
+            scope: None,
+        }
+    }
+
+    fn template(template: &str) -> ClaimSource {
+        ClaimSource::Template {
+            template: template.into(),
+        }
+    }
+
+    #[test]
+    fn resolves_claims() {
+        let subject = ClaimSubject::new(&user(), vec!["admin".into()], &app()).unwrap();
+        let profile = |field: &str| ClaimSource::Profile {
+            field: field.into(),
+        };
+
+        assert_eq!(
+            subject.resolve(&mapping("n", profile("name"))).unwrap(),
+            Some(json!("Alice"))
+        );
+        assert_eq!(
+            subject
+                .resolve(&mapping("n", profile("name#ja-Kana-JP")))
+                .unwrap(),
+            Some(json!("アリス"))
+        );
+        // 档案中没有的字段不输出
+        assert_eq!(
+            subject.resolve(&mapping("n", profile("nickname"))).unwrap(),
+            None
+        );
+        assert_eq!(
+            subject.resolve(&mapping("n", profile("sub"))).unwrap(),
+            None
+        );
+        assert_eq!(
+            subject.resolve(&mapping("r", ClaimSource::Roles)).unwrap(),
+            Some(json!(["admin"]))
+        );
+        assert_eq!(
+            subject
+                .resolve(&mapping("s", ClaimSource::Static { value: json!(7) }))
+                .unwrap(),
+            Some(json!(7))
+        );
+    }
+
+    #[test]
+    fn renders_templates() {
+        let subject =
+            ClaimSubject::new(&user(), vec!["admin".into(), "dev".into()], &app()).unwrap();
+
+        assert_eq!(
+            subject
+                .resolve(&mapping(
+                    "t",
+                    template("{{ user.username }}@{{ app.name }}:{{ roles | join(sep=\",\") }}")
+                ))
+                .unwrap(),
+            Some(json!("alice@wiki:admin,dev"))
+        );
+        assert_eq!(
+            subject
+                .resolve(&mapping("t", template("{{ profile.name | upper }}")))
+                .unwrap(),
+            Some(json!("ALICE"))
+        );
+        // 模板不转义 HTML
+        assert_eq!(
+            subject
+                .resolve(&mapping("t", template("<{{ user.external_id }}>")))
+                .unwrap(),
+            Some(json!("<E-42>"))
+        );
+        // 引用缺失的值时渲染失败，由调用方跳过该 claim
+        assert!(subject
+            .resolve(&mapping("t", template("{{ profile.nickname }}")))
+            .is_err());
+        assert_eq!(
+            subject
+                .resolve(&mapping(
+                    "t",
+                    template("{{ profile.nickname | default(value=\"-\") }}")
+                ))
+                .unwrap(),
+            Some(json!("-"))
+        );
+    }
+
+    #[test]
+    fn validates_mappings() {
+        assert!(mapping("department", template("{{ user.uuid }}"))
+            .validate()
+            .is_ok());
+        assert!(mapping("sub", ClaimSource::Roles).validate().is_err());
+        assert!(mapping("a b", ClaimSource::Roles).validate().is_err());
+        assert!(mapping("t", template("{{ user.uuid")).validate().is_err());
+        assert!(mapping(
+            "s",
+            ClaimSource::Profile {
+                field: "sub".into()
+            }
+        )
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    fn emits_requested_claims_outside_the_scope() {
+        let email = default_mappings("roles")
+            .into_iter()
+            .find(|mapping| mapping.name == "email")
+            .unwrap();
+        let scopes = BTreeSet::from(["openid".to_string()]);
+        let requested: ClaimsRequest = serde_json::from_value(json!({
+            "id_token": { "email": { "essential": true } },
+            "userinfo": { "name": null },
+        }))
+        .unwrap();
+
+        assert!(!email.emitted(ClaimTarget::IdToken, &scopes, None));
+        assert!(email.emitted(ClaimTarget::IdToken, &scopes, Some(&requested)));
+        assert!(!email.emitted(ClaimTarget::Userinfo, &scopes, Some(&requested)));
+        assert!(email.emitted(
+            ClaimTarget::Userinfo,
+            &BTreeSet::from(["email".to_string()]),
+            None
+        ));
+        // 请求的 claims 仍受映射的输出位置限制
+        assert!(!email.emitted(ClaimTarget::AccessToken, &scopes, Some(&requested)));
+
+        let roles = default_mappings("roles").pop().unwrap();
+        assert!(roles.emitted(ClaimTarget::AccessToken, &scopes, None));
+        assert!(default_mappings("")
+            .iter()
+            .all(|mapping| mapping.source != ClaimSource::Roles));
+    }
+
+    #[test]
+    fn deserializes_claims_requests() {
+        #[derive(Deserialize)]
+        struct Request {
+            #[serde(default, deserialize_with = "deserialize_claims_request")]
+            claims: Option<ClaimsRequest>,
+        }
+        let parse = |value: Value| {
+            serde_json::from_value::<Request>(value)
+                .map(|request| request.claims)
+                .ok()
+        };
+
+        let expected = ClaimsRequest {
+            userinfo: Some(Map::from_iter([(
+                "email".to_string(),
+                json!({ "essential": true }),
+            )])),
+            id_token: None,
+        };
+        assert_eq!(
+            parse(json!({ "claims": r#"{"userinfo":{"email":{"essential":true}}}"# })),
+            Some(Some(expected.clone()))
+        );
+        assert_eq!(
+            parse(json!({ "claims": { "userinfo": { "email": { "essential": true } } } })),
+            Some(Some(expected))
+        );
+        assert_eq!(parse(json!({})), Some(None));
+        assert_eq!(parse(json!({ "claims": null })), Some(None));
+        assert_eq!(parse(json!({ "claims": "{" })), None);
+    }
+}
@@ -0,0 +1,78 @@
+//! Client authentication
+//!
+//! 除 client secret 外，客户端还可以使用基于 [RFC 7523](https://www.rfc-editor.org/rfc/rfc7523)
+//! 的 JWT 断言认证：`client_secret_jwt` 使用 client secret 以 HMAC 签名，`private_key_jwt`
+//! 使用客户端私钥签名，服务端通过应用登记的 JWK Set 或 JWKS URI 验证。
+//! 每个断言的 `jti` 在过期前只能使用一次。
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Assertion type of `client_assertion_type`, see [RFC 7523 2.2](https://www.rfc-editor.org/rfc/rfc7523#section-2.2)
+pub const CLIENT_ASSERTION_TYPE: &str = "urn:ietf:params:oauth:client-assertion-type:jwt-bearer";
+
+/// Client authentication method of the token endpoint, see
+/// [OpenID Connect Core 9](https://openid.net/specs/openid-connect-core-1_0.html#ClientAuthentication)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenEndpointAuthMethod {
+    #[default]
+    ClientSecretBasic,
+    ClientSecretPost,
+    ClientSecretJwt,
+    PrivateKeyJwt,
+}
+
+impl TokenEndpointAuthMethod {
+    pub const ALL: &'static [TokenEndpointAuthMethod] = &[
+        TokenEndpointAuthMethod::ClientSecretBasic,
+        TokenEndpointAuthMethod::ClientSecretPost,
+        TokenEndpointAuthMethod::ClientSecretJwt,
+        TokenEndpointAuthMethod::PrivateKeyJwt,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenEndpointAuthMethod::ClientSecretBasic => "client_secret_basic",
+            TokenEndpointAuthMethod::ClientSecretPost => "client_secret_post",
+            TokenEndpointAuthMethod::ClientSecretJwt => "client_secret_jwt",
+            TokenEndpointAuthMethod::PrivateKeyJwt => "private_key_jwt",
+        }
+    }
+
+    /// `client_secret_basic` 和 `client_secret_post` 互相兼容，均直接提交 client secret
+    pub fn accepts_secret(&self) -> bool {
+        matches!(
+            self,
+            TokenEndpointAuthMethod::ClientSecretBasic | TokenEndpointAuthMethod::ClientSecretPost
+        )
+    }
+}
+
+impl fmt::Display for TokenEndpointAuthMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Client credentials submitted in the request body
+#[derive(Debug, Default, Deserialize)]
+pub struct ClientCredentials {
+    pub client_id: Option<String>,
+    /// `client_secret_post`
+    pub client_secret: Option<String>,
+    pub client_assertion_type: Option<String>,
+    /// `client_secret_jwt` or `private_key_jwt`
+    pub client_assertion: Option<String>,
+}
+
+/// Claims of the client assertion, see [RFC 7523 3](https://www.rfc-editor.org/rfc/rfc7523#section-3)
+///
+/// `iss`, `sub`, `aud` and `exp` are validated when decoding.
+#[derive(Debug, Deserialize)]
+pub struct ClientAssertion {
+    pub sub: String,
+    pub jti: String,
+    pub exp: i64,
+}
@@ -0,0 +1,39 @@
+//! User consent of the scopes requested by apps
+//!
+//! 用户对每个应用同意过的 scope 集合会被保存，之后应用请求的 scope 不超出该集合时不再询问用户。
+//! 第一方应用（`OIDCSetting::first_party`）总是跳过确认步骤。
+
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Key of the pending authentication request stored in the browser session
+pub const AUTHORIZATION_REQUEST_KEY: &str = "authorization_request";
+
+/// Parse the space-delimited scope parameter into a set
+pub fn scope_set(scope: &str) -> BTreeSet<String> {
+    scope.split_whitespace().map(str::to_string).collect()
+}
+
+/// Join the scope set into the space-delimited form
+pub fn join_scopes(scopes: &BTreeSet<String>) -> String {
+    scopes.iter().cloned().collect::<Vec<_>>().join(" ")
+}
+
+/// Information shown on the consent screen of the auth page
+#[derive(Debug, Serialize)]
+pub struct ConsentDetails {
+    pub app_uuid: Uuid,
+    pub app_name: String,
+    /// Scopes requested by the app
+    pub scopes: BTreeSet<String>,
+    /// Scopes approved before, these are not asked again unless `prompt=consent`
+    pub granted: BTreeSet<String>,
+}
+
+/// Decision of the user on the consent screen
+#[derive(Debug, Deserialize)]
+pub struct ConsentDecision {
+    pub approve: bool,
+}
@@ -0,0 +1,84 @@
+//! Device authorization grant
+//!
+//! 基于 [RFC 8628](https://www.rfc-editor.org/rfc/rfc8628) 实现，设备获取 device code 和 user code 后，
+//! 用户在授权页面输入 user code 并确认授权，设备轮询 token 端点换取 token。
+
+use rand::{rngs::OsRng, Rng};
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// Grant type of the token request, see [RFC 8628 3.4](https://www.rfc-editor.org/rfc/rfc8628#section-3.4)
+pub const DEVICE_CODE_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+/// Minimum seconds between polling requests, increased by 5 seconds on each `slow_down`
+pub const POLLING_INTERVAL: u32 = 5;
+
+/// Key of the user code being verified in the browser session
+pub const DEVICE_USER_CODE_KEY: &str = "device_user_code";
+
+/// Characters of user codes, vowels and confusable characters are excluded
+const USER_CODE_CHARSET: &[u8] = b"BCDFGHJKLMNPQRSTVWXZ";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::N(16))")]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceAuthorizationStatus {
+    #[sea_orm(string_value = "pending")]
+    Pending,
+    #[sea_orm(string_value = "approved")]
+    Approved,
+    #[sea_orm(string_value = "denied")]
+    Denied,
+    /// Tokens have been issued to the device
+    #[sea_orm(string_value = "consumed")]
+    Consumed,
+}
+
+/// Generate a user code of 8 characters, stored without the separator
+pub fn generate_user_code() -> String {
+    (0..8)
+        .map(|_| USER_CODE_CHARSET[OsRng.gen_range(0..USER_CODE_CHARSET.len())] as char)
+        .collect()
+}
+
+/// Normalize the user code entered by the user, separators and case are ignored
+pub fn normalize_user_code(code: &str) -> String {
+    code.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+/// Format the user code as `XXXX-XXXX` to be read easily
+pub fn display_user_code(code: &str) -> String {
+    let (head, tail) = code.split_at(code.len() / 2);
+    format!("{head}-{tail}")
+}
+
+/// Device authorization response, see [RFC 8628 3.2](https://www.rfc-editor.org/rfc/rfc8628#section-3.2)
+#[derive(Debug, Serialize)]
+pub struct DeviceAuthorizationResponse {
+    pub device_code: String,
+    pub user_code: String,
+    pub verification_uri: Url,
+    pub verification_uri_complete: Url,
+    pub expires_in: u64,
+    pub interval: u32,
+}
+
+/// Information shown on the device verification page of the auth page
+#[derive(Debug, Serialize)]
+pub struct DeviceDetails {
+    pub user_code: String,
+    pub app_uuid: Uuid,
+    pub app_name: String,
+    pub scopes: Vec<String>,
+}
+
+/// Decision of the user on the device verification page
+#[derive(Debug, Deserialize)]
+pub struct DeviceDecision {
+    pub user_code: String,
+    pub approve: bool,
+}
@@ -0,0 +1,223 @@
+//! Auth service domain
+//!
+//! 域之间相互隔离：用户名、邮箱、手机号和应用名称只在域内唯一，用户只能登录同一域内的应用。
+//! 每个域有独立的 issuer 和 discovery 文档，请求所属的域由 `/d/{domain}` 路径前缀或
+//! 请求的 host（与域的 `endpoint` 匹配）决定。
+
+use inspirer_framework::{
+    app::AppContext,
+    axum::extract::{FromRequestParts, RawPathParams},
+    http::{header::HOST, request::Parts},
+    Error,
+};
+use sea_orm::FromJsonQueryResult;
+use serde::{Deserialize, Serialize};
+use url::Url;
+use uuid::Uuid;
+
+use crate::{
+    app::App,
+    entity::domains,
+    service::{domain::Domain, ServiceInterface},
+};
+
+use self::domain_setting::PasswordPolicy;
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, FromJsonQueryResult, PartialEq, Eq)]
+pub struct DomainSetting {
+    #[serde(default)]
+    pub password_policy: PasswordPolicy,
+    /// 域的独立访问地址，host 与之相同的请求属于该域；未设置时通过 `/d/{domain}` 路径访问
+    #[serde(default)]
+    pub endpoint: Option<Url>,
+}
+
+/// Domain of the request, resolved from the `/d/{domain}` path prefix or the host
+///
+/// `None` if the request is not routed to a specific domain, resources of any domain are
+/// accessible in such requests, e.g. clients authenticate at `/oidc/token` with their own domain.
+pub struct RequestDomain(pub Option<domains::Model>);
+
+impl RequestDomain {
+    /// Whether resources of the domain are accessible in the request
+    pub fn allows(&self, domain_uuid: Uuid) -> bool {
+        self.0
+            .as_ref()
+            .is_none_or(|domain| domain.uuid == domain_uuid)
+    }
+}
+
+#[async_trait::async_trait]
+impl FromRequestParts<AppContext<App>> for RequestDomain {
+    type Rejection = Error;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        context: &AppContext<App>,
+    ) -> Result<Self, Self::Rejection> {
+        let service = context.service::<Domain>();
+
+        let name = RawPathParams::from_request_parts(parts, context)
+            .await
+            .ok()
+            .and_then(|params| {
+                params
+                    .iter()
+                    .find(|(key, _)| *key == "domain")
+                    .map(|(_, value)| value.to_string())
+            });
+        if let Some(name) = name {
+            return Ok(RequestDomain(Some(
+                service.find_domain_by_name(&name).await?,
+            )));
+        }
+
+        let host = parts
+            .headers
+            .get(HOST)
+            .and_then(|value| value.to_str().ok())
+            .or_else(|| parts.uri.host());
+        match host {
+            Some(host) => Ok(RequestDomain(service.find_domain_by_host(host).await?)),
+            None => Ok(RequestDomain(None)),
+        }
+    }
+}
+
+pub mod domain_setting {
+    use std::{
+        collections::{HashMap, HashSet},
+        fmt, fs,
+        path::{Path, PathBuf},
+        sync::{Arc, RwLock},
+    };
+
+    use once_cell::sync::Lazy;
+    use sea_orm::FromJsonQueryResult;
+    use serde::{Deserialize, Serialize};
+
+    /// Common password lists, cached by file path
+    static COMMON_PASSWORDS: Lazy<RwLock<HashMap<PathBuf, Arc<HashSet<String>>>>> =
+        Lazy::new(Default::default);
+
+    #[derive(Debug, Clone, Serialize, Deserialize, FromJsonQueryResult, PartialEq, Eq)]
+    #[serde(default)]
+    pub struct PasswordPolicy {
+        pub min_length: usize,
+        pub max_length: usize,
+        pub require_lowercase: bool,
+        pub require_uppercase: bool,
+        pub require_digit: bool,
+        pub require_symbol: bool,
+        /// 常见/已泄露密码列表文件，每行一个密码，比较时忽略大小写
+        pub common_password_list: Option<PathBuf>,
+        /// 禁止重复使用最近 N 次使用过的密码，为 0 时不限制
+        pub history_size: u64,
+    }
+
+    impl Default for PasswordPolicy {
+        fn default() -> Self {
+            PasswordPolicy {
+                min_length: 8,
+                max_length: 128,
+                require_lowercase: false,
+                require_uppercase: false,
+                require_digit: false,
+                require_symbol: false,
+                common_password_list: None,
+                history_size: 0,
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum PolicyViolation {
+        TooShort(usize),
+        TooLong(usize),
+        MissingLowercase,
+        MissingUppercase,
+        MissingDigit,
+        MissingSymbol,
+        CommonPassword,
+        RecentlyUsed(u64),
+    }
+
+    impl fmt::Display for PolicyViolation {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Self::TooShort(min) => write!(f, "password must be at least {min} characters"),
+                Self::TooLong(max) => write!(f, "password must be at most {max} characters"),
+                Self::MissingLowercase => write!(f, "password must contain a lowercase letter"),
+                Self::MissingUppercase => write!(f, "password must contain an uppercase letter"),
+                Self::MissingDigit => write!(f, "password must contain a digit"),
+                Self::MissingSymbol => write!(f, "password must contain a symbol"),
+                Self::CommonPassword => write!(f, "password is too common"),
+                Self::RecentlyUsed(size) => {
+                    write!(f, "password must differ from the last {size} passwords")
+                }
+            }
+        }
+    }
+
+    impl PasswordPolicy {
+        /// Check the password against the policy, except the reuse history
+        pub fn check(&self, password: &str) -> std::io::Result<Vec<PolicyViolation>> {
+            let mut violations = vec![];
+            let length = password.chars().count();
+
+            if length < self.min_length {
+                violations.push(PolicyViolation::TooShort(self.min_length));
+            }
+
+            if length > self.max_length {
+                violations.push(PolicyViolation::TooLong(self.max_length));
+            }
+
+            if self.require_lowercase && !password.chars().any(char::is_lowercase) {
+                violations.push(PolicyViolation::MissingLowercase);
+            }
+
+            if self.require_uppercase && !password.chars().any(char::is_uppercase) {
+                violations.push(PolicyViolation::MissingUppercase);
+            }
+
+            if self.require_digit && !password.chars().any(|c| c.is_ascii_digit()) {
+                violations.push(PolicyViolation::MissingDigit);
+            }
+
+            if self.require_symbol && password.chars().all(char::is_alphanumeric) {
+                violations.push(PolicyViolation::MissingSymbol);
+            }
+
+            if let Some(path) = &self.common_password_list {
+                if common_passwords(path)?.contains(&password.to_lowercase()) {
+                    violations.push(PolicyViolation::CommonPassword);
+                }
+            }
+
+            Ok(violations)
+        }
+    }
+
+    fn common_passwords(path: &Path) -> std::io::Result<Arc<HashSet<String>>> {
+        if let Some(list) = COMMON_PASSWORDS.read().unwrap().get(path) {
+            return Ok(list.clone());
+        }
+
+        let list = Arc::new(
+            fs::read_to_string(path)?
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(str::to_lowercase)
+                .collect::<HashSet<_>>(),
+        );
+
+        COMMON_PASSWORDS
+            .write()
+            .unwrap()
+            .insert(path.to_path_buf(), list.clone());
+
+        Ok(list)
+    }
+}
@@ -0,0 +1,37 @@
+//! Token exchange for delegation between services
+//!
+//! 基于 [RFC 8693](https://www.rfc-editor.org/rfc/rfc8693) 实现。服务 A 代表用户调用服务 B 时，
+//! 使用用户签发给 A 的 access token 换取以 B 为 audience 的 token，新 token 的 scope 不超过原 token，
+//! 并通过 `act` claim 记录代为操作的服务。应用可交换的 audience 和 scope 由 `AppSetting` 中的策略控制。
+
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+use tabled::Tabled;
+use uuid::Uuid;
+
+pub const TOKEN_EXCHANGE_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:token-exchange";
+
+pub const ACCESS_TOKEN_TYPE: &str = "urn:ietf:params:oauth:token-type:access_token";
+
+/// Policy of token exchange of the app
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Tabled)]
+pub struct TokenExchangePolicy {
+    /// App uuid of the target service
+    pub audience: Uuid,
+    /// 允许交换的 scope 上限，为空时不额外限制（仍不能超过原 token 的 scope）
+    #[serde(default)]
+    #[tabled(skip)]
+    pub scopes: Vec<String>,
+}
+
+impl TokenExchangePolicy {
+    /// Scopes allowed to be exchanged from the subject scopes
+    pub fn allowed_scopes(&self, subject_scopes: &BTreeSet<String>) -> BTreeSet<String> {
+        subject_scopes
+            .iter()
+            .filter(|scope| self.scopes.is_empty() || self.scopes.contains(scope))
+            .cloned()
+            .collect()
+    }
+}
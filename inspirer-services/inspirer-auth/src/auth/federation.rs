@@ -0,0 +1,137 @@
+//! Federated login with upstream OpenID Connect providers
+//!
+//! 每个 domain 可以配置若干上游 OpenID Connect 身份提供方，用户通过上游认证后，
+//! 会根据 [ClaimMapping] 将上游的 claims 映射为本地用户数据，并通过 `linked_identities` 表与本地用户关联。
+//!
+//! 尚未关联的上游身份按 [link_target] 关联：已登录时关联到当前用户；未登录时只有身份提供方开启
+//! `auto_link_verified_email` 且上游验证过邮箱，才会关联到同一邮箱的用户，否则用户需要先登录再关联。
+
+use inspirer_framework::{http::StatusCode, response::ErrorDetail, Error};
+use sea_orm::FromJsonQueryResult;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::entity::{identity_providers, users};
+
+/// Mapping of upstream claim names to local user fields
+///
+/// 每个字段的值为上游 claims 中对应的字段名称，设置为空字符串时表示不映射该字段。
+#[derive(Debug, Clone, Serialize, Deserialize, FromJsonQueryResult, PartialEq, Eq)]
+#[serde(default)]
+pub struct ClaimMapping {
+    pub username: String,
+    pub email: String,
+    pub email_verified: String,
+    pub phone_number: String,
+    pub name: String,
+    pub picture: String,
+}
+
+impl Default for ClaimMapping {
+    fn default() -> Self {
+        ClaimMapping {
+            username: "preferred_username".into(),
+            email: "email".into(),
+            email_verified: "email_verified".into(),
+            phone_number: "phone_number".into(),
+            name: "name".into(),
+            picture: "picture".into(),
+        }
+    }
+}
+
+impl ClaimMapping {
+    /// Map upstream claims to an [ExternalIdentity]
+    pub fn map(&self, subject: String, claims: Value) -> ExternalIdentity {
+        let string = |name: &str| {
+            if name.is_empty() {
+                return None;
+            }
+
+            claims
+                .get(name)
+                .and_then(Value::as_str)
+                .filter(|value| !value.is_empty())
+                .map(ToString::to_string)
+        };
+
+        ExternalIdentity {
+            username: string(&self.username),
+            email: string(&self.email),
+            email_verified: claims
+                .get(&self.email_verified)
+                .and_then(Value::as_bool)
+                .unwrap_or(false),
+            phone_number: string(&self.phone_number),
+            name: string(&self.name),
+            picture: string(&self.picture),
+            subject,
+            claims,
+        }
+    }
+}
+
+/// Identity of an end-user authenticated by an upstream provider
+#[derive(Debug, Clone)]
+pub struct ExternalIdentity {
+    /// Subject identifier at the upstream issuer
+    pub subject: String,
+    pub username: Option<String>,
+    pub email: Option<String>,
+    pub email_verified: bool,
+    pub phone_number: Option<String>,
+    pub name: Option<String>,
+    pub picture: Option<String>,
+    /// Raw claims merged from the ID token and the userinfo response
+    pub claims: Value,
+}
+
+/// State of an in-flight federated login, kept in the auth session
+/// between the redirect to upstream and the callback
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FederationState {
+    pub provider: Uuid,
+    pub csrf_token: String,
+    pub nonce: String,
+    pub pkce_verifier: String,
+}
+
+/// Local user an external identity not linked yet is linked to
+#[derive(Debug, PartialEq)]
+pub enum LinkTarget {
+    /// The signed in user or the user of the verified email
+    User(Box<users::Model>),
+    /// No user has the email, a new user is created
+    Create,
+}
+
+/// Decide the user the external identity is linked to, `current` is the user signed in the
+/// domain and `existing` the user with the email of the identity.
+///
+/// Returns a conflict if another user has the email and the identity can not be linked to it
+/// automatically.
+pub fn link_target(
+    provider: &identity_providers::Model,
+    identity: &ExternalIdentity,
+    current: Option<users::Model>,
+    existing: Option<users::Model>,
+) -> Result<LinkTarget, Error> {
+    if let Some(current) = current {
+        return Ok(LinkTarget::User(Box::new(current)));
+    }
+
+    match existing {
+        Some(user) if provider.auto_link_verified_email && identity.email_verified => {
+            Ok(LinkTarget::User(Box::new(user)))
+        }
+        Some(_) => Err(Error::CustomError(
+            StatusCode::CONFLICT,
+            ErrorDetail::new(
+                "account_exists",
+                "The email is already registered, sign in to link the account",
+            ),
+        )),
+        None => Ok(LinkTarget::Create),
+    }
+}
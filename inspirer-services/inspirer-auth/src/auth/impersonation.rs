@@ -0,0 +1,14 @@
+//! Admin impersonation
+//!
+//! 具有 `auth.user.impersonate` 权限的管理员可以为同一 domain 内的用户签发 access token，
+//! 用于排查问题时查看用户所见的内容。此类 token 通过 `act` claim 标识操作的管理员，
+//! 不关联会话且有效期较短，每次签发（包括被拒绝的请求）都会记录审计事件，用户可以查看自己账户上的模拟登录记录。
+//! 管理员只能模拟权限不超过自己的用户，模拟得到的 token 不能再次发起模拟。
+
+use inspirer_framework::permission;
+
+permission!(
+    /// Issue impersonation tokens for users of the domain
+    pub Impersonate,
+    "auth.user.impersonate"
+);
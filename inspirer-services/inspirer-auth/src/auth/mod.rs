@@ -1,5 +1,26 @@
 //! Authn and authz core module, defined related components and models
 
+pub mod account_status;
+pub mod api_token;
 pub mod application;
+pub mod audit;
+pub mod authentication;
+pub mod claims;
+pub mod client;
+pub mod consent;
+pub mod device;
+pub mod domain;
+pub mod exchange;
+pub mod federation;
+pub mod impersonation;
+pub mod oauth;
 pub mod ocid;
+pub mod par;
+pub mod rbac;
+pub mod registration;
+pub mod scim;
+pub mod session;
 pub mod user;
+pub mod user_transfer;
+pub mod verification;
+pub mod webhook;
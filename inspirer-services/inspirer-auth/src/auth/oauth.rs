@@ -0,0 +1,129 @@
+//! OAuth 2.0 protocol helpers
+//!
+//! OAuth 端点（token、introspection、revocation 等）需要按
+//! [RFC 6749 5.2](https://www.rfc-editor.org/rfc/rfc6749#section-5.2) 返回错误，
+//! 而不是框架默认的响应结构。
+
+use inspirer_framework::{
+    axum::{
+        response::{IntoResponse, Response},
+        Json,
+    },
+    dpop::{DPoPRejection, DPOP_NONCE_HEADER},
+    http::{header::WWW_AUTHENTICATE, HeaderValue, StatusCode},
+    Error,
+};
+use serde::Serialize;
+
+/// Error response of OAuth endpoints
+#[derive(Debug, Serialize)]
+pub struct OAuthError {
+    #[serde(skip)]
+    pub status: StatusCode,
+    pub error: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_description: Option<String>,
+    /// Nonce returned in the `DPoP-Nonce` header
+    #[serde(skip)]
+    pub dpop_nonce: Option<String>,
+}
+
+pub type OAuthResult<T> = std::result::Result<T, OAuthError>;
+
+impl OAuthError {
+    pub fn new(error: &'static str, description: impl Into<String>) -> Self {
+        OAuthError {
+            status: StatusCode::BAD_REQUEST,
+            error,
+            error_description: Some(description.into()),
+            dpop_nonce: None,
+        }
+    }
+
+    pub fn invalid_request(description: impl Into<String>) -> Self {
+        Self::new("invalid_request", description)
+    }
+
+    pub fn invalid_client(description: impl Into<String>) -> Self {
+        OAuthError {
+            status: StatusCode::UNAUTHORIZED,
+            ..Self::new("invalid_client", description)
+        }
+    }
+
+    /// The bearer token of the request is missing, invalid or expired,
+    /// see [RFC 6750 3.1](https://www.rfc-editor.org/rfc/rfc6750#section-3.1)
+    pub fn invalid_token(description: impl Into<String>) -> Self {
+        OAuthError {
+            status: StatusCode::UNAUTHORIZED,
+            ..Self::new("invalid_token", description)
+        }
+    }
+
+    pub fn invalid_grant(description: impl Into<String>) -> Self {
+        Self::new("invalid_grant", description)
+    }
+
+    /// The client is not allowed to use the grant type
+    pub fn unauthorized_client(description: impl Into<String>) -> Self {
+        Self::new("unauthorized_client", description)
+    }
+
+    pub fn unsupported_grant_type(grant_type: &str) -> Self {
+        Self::new(
+            "unsupported_grant_type",
+            format!("Unsupported grant type {grant_type}"),
+        )
+    }
+}
+
+impl From<Error> for OAuthError {
+    fn from(err: Error) -> Self {
+        tracing::error!(error = %err, "oauth endpoint error");
+
+        OAuthError {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            error: "server_error",
+            error_description: None,
+            dpop_nonce: None,
+        }
+    }
+}
+
+/// Errors of DPoP proofs on the token endpoint, see
+/// [RFC 9449 5](https://www.rfc-editor.org/rfc/rfc9449#section-5)
+impl From<DPoPRejection> for OAuthError {
+    fn from(rejection: DPoPRejection) -> Self {
+        match rejection {
+            DPoPRejection::UseNonce(nonce) => OAuthError {
+                dpop_nonce: Some(nonce),
+                ..Self::new("use_dpop_nonce", "DPoP nonce is required")
+            },
+            DPoPRejection::Error(err) => err.into(),
+            rejection => Self::new("invalid_dpop_proof", rejection.to_string()),
+        }
+    }
+}
+
+impl IntoResponse for OAuthError {
+    fn into_response(self) -> Response {
+        let mut response = (self.status, Json(&self)).into_response();
+        if self.status == StatusCode::UNAUTHORIZED {
+            let challenge = match self.error {
+                "invalid_token" => "Bearer error=\"invalid_token\"",
+                _ => "Basic",
+            };
+            response
+                .headers_mut()
+                .insert(WWW_AUTHENTICATE, HeaderValue::from_static(challenge));
+        }
+        if let Some(nonce) = self
+            .dpop_nonce
+            .and_then(|nonce| HeaderValue::try_from(nonce).ok())
+        {
+            response.headers_mut().insert(DPOP_NONCE_HEADER, nonce);
+        }
+
+        response
+    }
+}
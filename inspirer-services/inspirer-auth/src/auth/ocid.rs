@@ -13,6 +13,8 @@ use serde::{Deserialize, Serialize};
 use url::Url;
 use utoipa::ToSchema;
 
+use super::claims::{deserialize_claims_request, ClaimsRequest};
+
 /// Authentication Request
 ///
 /// 相关结构标准的定义可查阅
@@ -86,4 +88,15 @@ pub struct AuthenticationRequest {
     ///
     /// The defined values: [openidconnect::core::CoreAuthPrompt]
     pub prompt: Option<CoreAuthPrompt>,
+
+    /// OPTIONAL. Requests that specific Claims be returned from the UserInfo Endpoint
+    /// and/or in the ID Token, see
+    /// [Section 5.5](https://openid.net/specs/openid-connect-core-1_0.html#ClaimsParameter).
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_claims_request"
+    )]
+    #[schema(value_type = Option<Object>)]
+    pub claims: Option<ClaimsRequest>,
 }
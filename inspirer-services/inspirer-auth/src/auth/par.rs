@@ -0,0 +1,25 @@
+//! Pushed authorization requests and request objects
+//!
+//! 基于 [RFC 9126](https://www.rfc-editor.org/rfc/rfc9126) 实现，客户端先将授权参数推送到
+//! `/oidc/par`，再使用返回的 `request_uri` 发起授权请求，授权参数不再经过浏览器。
+//! 授权参数也可以通过 `request` 参数以签名 JWT 传递（[RFC 9101](https://www.rfc-editor.org/rfc/rfc9101)），
+//! 签名使用应用登记的公钥验证，此时只使用请求对象中的参数。
+
+use serde::Serialize;
+
+/// Prefix of the request uri returned by the pushed authorization request endpoint
+pub const REQUEST_URI_PREFIX: &str = "urn:ietf:params:oauth:request_uri:";
+
+/// Max seconds between the issued time or now and the expiration of request objects, see
+/// [FAPI 2.0 Message Signing 5.3.1](https://openid.net/specs/fapi-2_0-message-signing.html)
+pub const REQUEST_OBJECT_MAX_LIFETIME: i64 = 3600;
+
+/// Algorithms of request objects, only signatures of the app's registered keys are accepted
+pub const REQUEST_OBJECT_SIGNING_ALGS: &[&str] = &["RS256", "PS256", "ES256", "EdDSA"];
+
+/// Pushed authorization response, see [RFC 9126 2.2](https://www.rfc-editor.org/rfc/rfc9126#section-2.2)
+#[derive(Debug, Serialize)]
+pub struct PushedAuthorizationResponse {
+    pub request_uri: String,
+    pub expires_in: u64,
+}
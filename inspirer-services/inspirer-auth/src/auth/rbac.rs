@@ -0,0 +1,35 @@
+//! Role-based access control
+//!
+//! 角色、权限和用户组均属于某个 domain，用户的有效角色为直接分配的角色与所属用户组的角色的并集，
+//! 有效权限为有效角色所拥有权限的并集。
+
+use std::collections::HashSet;
+
+use inspirer_framework::{authorization, permission};
+use uuid::Uuid;
+
+permission!(
+    /// Manage roles, permissions and groups of the domain
+    pub ManageRbac,
+    "auth.rbac.manage"
+);
+
+/// Authenticated user with effective roles and permissions
+#[derive(Debug, Clone)]
+pub struct Principal {
+    pub user_uuid: Uuid,
+    pub domain_uuid: Uuid,
+    /// Session of the access token, `None` for tokens issued without session
+    pub session_uuid: Option<Uuid>,
+    /// Subject of the `act` claim of the access token, i.e. the admin impersonating the user
+    /// or the service acting on behalf of the user
+    pub actor_uuid: Option<Uuid>,
+    pub roles: Vec<String>,
+    pub permissions: HashSet<String>,
+}
+
+impl authorization::Principal for Principal {
+    fn has_permission(&self, permission: &str) -> bool {
+        self.permissions.contains(permission)
+    }
+}
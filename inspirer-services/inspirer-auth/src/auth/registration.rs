@@ -0,0 +1,165 @@
+//! Dynamic client registration
+//!
+//! 基于 [RFC 7591](https://www.rfc-editor.org/rfc/rfc7591) 和
+//! [RFC 7592](https://www.rfc-editor.org/rfc/rfc7592) 实现，注册接口需要管理员签发的
+//! initial access token，注册成功后返回的 registration access token 用于读取、更新和删除客户端。
+
+use chrono::{DateTime, Utc};
+use inspirer_framework::permission;
+use jsonwebtoken::jwk::JwkSet;
+use sea_orm::FromJsonQueryResult;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use super::{
+    client::TokenEndpointAuthMethod, device::DEVICE_CODE_GRANT_TYPE,
+    exchange::TOKEN_EXCHANGE_GRANT_TYPE, oauth::OAuthError,
+};
+
+permission!(
+    /// Manage apps and issue initial access tokens of dynamic client registration
+    pub ManageApps,
+    "auth.apps.manage"
+);
+
+pub const SUPPORTED_GRANT_TYPES: &[&str] = &[
+    "authorization_code",
+    "refresh_token",
+    DEVICE_CODE_GRANT_TYPE,
+    TOKEN_EXCHANGE_GRANT_TYPE,
+];
+pub const SUPPORTED_RESPONSE_TYPES: &[&str] = &["code"];
+
+/// Client metadata, see [RFC 7591 2](https://www.rfc-editor.org/rfc/rfc7591#section-2)
+///
+/// Unknown metadata is ignored.
+#[derive(Debug, Clone, Serialize, Deserialize, FromJsonQueryResult, PartialEq, Eq)]
+pub struct ClientMetadata {
+    pub redirect_uris: Vec<Url>,
+    #[serde(default)]
+    pub token_endpoint_auth_method: TokenEndpointAuthMethod,
+    #[serde(default = "default_grant_types")]
+    pub grant_types: Vec<String>,
+    #[serde(default = "default_response_types")]
+    pub response_types: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_uri: Option<Url>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logo_uri: Option<Url>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub contacts: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tos_uri: Option<Url>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy_uri: Option<Url>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jwks: Option<JwkSet>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jwks_uri: Option<Url>,
+    /// See [RFC 9126 6](https://www.rfc-editor.org/rfc/rfc9126#section-6)
+    #[serde(default)]
+    pub require_pushed_authorization_requests: bool,
+    /// See [RFC 9449 5.2](https://www.rfc-editor.org/rfc/rfc9449#section-5.2)
+    #[serde(default)]
+    pub dpop_bound_access_tokens: bool,
+}
+
+fn default_grant_types() -> Vec<String> {
+    vec!["authorization_code".into()]
+}
+
+fn default_response_types() -> Vec<String> {
+    vec!["code".into()]
+}
+
+impl ClientMetadata {
+    /// Validate the metadata, returns the error defined in
+    /// [RFC 7591 3.2.2](https://www.rfc-editor.org/rfc/rfc7591#section-3.2.2)
+    pub fn validate(&self) -> Result<(), OAuthError> {
+        if self.redirect_uris.is_empty() {
+            return Err(OAuthError::new(
+                "invalid_redirect_uri",
+                "At least one redirect uri is required",
+            ));
+        }
+
+        if let Some(uri) = self
+            .redirect_uris
+            .iter()
+            .find(|uri| uri.fragment().is_some())
+        {
+            return Err(OAuthError::new(
+                "invalid_redirect_uri",
+                format!("Redirect uri {uri} must not contain a fragment"),
+            ));
+        }
+
+        let unsupported = |values: &[String], supported: &[&str]| {
+            values
+                .iter()
+                .find(|value| !supported.contains(&value.as_str()))
+                .cloned()
+        };
+
+        if let Some(value) = unsupported(&self.grant_types, SUPPORTED_GRANT_TYPES) {
+            return Err(OAuthError::new(
+                "invalid_client_metadata",
+                format!("Unsupported grant type {value}"),
+            ));
+        }
+
+        if let Some(value) = unsupported(&self.response_types, SUPPORTED_RESPONSE_TYPES) {
+            return Err(OAuthError::new(
+                "invalid_client_metadata",
+                format!("Unsupported response type {value}"),
+            ));
+        }
+
+        if self.jwks.is_some() && self.jwks_uri.is_some() {
+            return Err(OAuthError::new(
+                "invalid_client_metadata",
+                "jwks and jwks_uri must not both be present",
+            ));
+        }
+
+        if matches!(&self.jwks_uri, Some(uri) if uri.scheme() != "https") {
+            return Err(OAuthError::new(
+                "invalid_client_metadata",
+                "jwks_uri must use the https scheme",
+            ));
+        }
+
+        if self.token_endpoint_auth_method == TokenEndpointAuthMethod::PrivateKeyJwt
+            && self.jwks.is_none()
+            && self.jwks_uri.is_none()
+        {
+            return Err(OAuthError::new(
+                "invalid_client_metadata",
+                "jwks or jwks_uri is required by private_key_jwt",
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Client information response, see [RFC 7591 3.2.1](https://www.rfc-editor.org/rfc/rfc7591#section-3.2.1)
+#[derive(Debug, Serialize)]
+pub struct ClientInformation {
+    pub client_id: String,
+    pub client_secret: String,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub client_id_issued_at: DateTime<Utc>,
+    /// Always `0`, client secrets do not expire
+    pub client_secret_expires_at: u64,
+    /// Only returned on registration
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub registration_access_token: Option<String>,
+    pub registration_client_uri: Url,
+    #[serde(flatten)]
+    pub metadata: ClientMetadata,
+}
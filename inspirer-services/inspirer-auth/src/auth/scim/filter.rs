@@ -0,0 +1,556 @@
+//! SCIM filter, see [RFC 7644 3.4.2.2](https://www.rfc-editor.org/rfc/rfc7644#section-3.4.2.2)
+//!
+//! 过滤器在资源的 JSON 表示上求值，属性名和字符串比较均不区分大小写。
+
+use std::{cmp::Ordering, iter::Peekable, str::CharIndices};
+
+use sea_orm::Condition;
+use serde_json::{Map, Value};
+
+/// Attribute path, e.g. `userName` or `name.givenName`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttrPath {
+    pub attr: String,
+    pub sub_attr: Option<String>,
+}
+
+impl AttrPath {
+    /// Parse the path, the schema URN prefix of core attributes is stripped
+    pub fn parse(path: &str) -> Self {
+        let path = strip_schema(path);
+        match path.split_once('.') {
+            Some((attr, sub_attr)) => AttrPath {
+                attr: attr.to_string(),
+                sub_attr: Some(sub_attr.to_string()),
+            },
+            None => AttrPath {
+                attr: path.to_string(),
+                sub_attr: None,
+            },
+        }
+    }
+
+    /// Values of the attribute in the resource, multi-valued attributes are flattened and
+    /// the `value` sub-attribute is used for complex values if no sub-attribute is given
+    fn values<'a>(&self, resource: &'a Value) -> Vec<&'a Value> {
+        let Some(value) = get(resource, &self.attr) else {
+            return vec![];
+        };
+
+        let values = match value {
+            Value::Array(values) => values.iter().collect(),
+            value => vec![value],
+        };
+
+        values
+            .into_iter()
+            .filter_map(|value| match (&self.sub_attr, value) {
+                (Some(sub_attr), value) => get(value, sub_attr),
+                (None, Value::Object(_)) => get(value, "value"),
+                (None, value) => Some(value),
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Eq,
+    Ne,
+    Co,
+    Sw,
+    Ew,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+impl Operator {
+    fn parse(word: &str) -> Option<Self> {
+        Some(match word.to_ascii_lowercase().as_str() {
+            "eq" => Operator::Eq,
+            "ne" => Operator::Ne,
+            "co" => Operator::Co,
+            "sw" => Operator::Sw,
+            "ew" => Operator::Ew,
+            "gt" => Operator::Gt,
+            "ge" => Operator::Ge,
+            "lt" => Operator::Lt,
+            "le" => Operator::Le,
+            _ => return None,
+        })
+    }
+
+    fn compare(&self, actual: &Value, expected: &Value) -> bool {
+        match (actual, expected) {
+            (Value::String(actual), Value::String(expected)) => {
+                let actual = actual.to_lowercase();
+                let expected = expected.to_lowercase();
+                match self {
+                    Operator::Co => actual.contains(&expected),
+                    Operator::Sw => actual.starts_with(&expected),
+                    Operator::Ew => actual.ends_with(&expected),
+                    operator => operator.ordering(actual.cmp(&expected)),
+                }
+            }
+            (Value::Number(actual), Value::Number(expected)) => {
+                match actual.as_f64().partial_cmp(&expected.as_f64()) {
+                    Some(ordering) => self.ordering(ordering),
+                    None => false,
+                }
+            }
+            (Value::Bool(actual), Value::Bool(expected)) => {
+                self.ordering(actual.cmp(expected)) && matches!(self, Operator::Eq | Operator::Ne)
+            }
+            (actual, Value::Null) => match self {
+                Operator::Eq => actual.is_null(),
+                Operator::Ne => !actual.is_null(),
+                _ => false,
+            },
+            _ => *self == Operator::Ne,
+        }
+    }
+
+    fn ordering(&self, ordering: Ordering) -> bool {
+        match self {
+            Operator::Eq => ordering.is_eq(),
+            Operator::Ne => ordering.is_ne(),
+            Operator::Gt => ordering.is_gt(),
+            Operator::Ge => ordering.is_ge(),
+            Operator::Lt => ordering.is_lt(),
+            Operator::Le => ordering.is_le(),
+            Operator::Co | Operator::Sw | Operator::Ew => false,
+        }
+    }
+}
+
+/// Operator and value compared with an attribute, `None` for `pr`
+pub type Comparison<'a> = Option<(Operator, &'a Value)>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    Not(Box<Filter>),
+    Present(AttrPath),
+    Compare(AttrPath, Operator, Value),
+    /// Filter on values of a multi-valued complex attribute, e.g. `emails[type eq "work"]`
+    ValuePath(String, Box<Filter>),
+}
+
+impl Filter {
+    pub fn parse(filter: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            tokens: tokenize(filter)?.into_iter().peekable(),
+        };
+        let filter = parser.parse_or()?;
+        match parser.tokens.next() {
+            Some(token) => Err(format!("Unexpected {token:?}")),
+            None => Ok(filter),
+        }
+    }
+
+    pub fn matches(&self, resource: &Value) -> bool {
+        match self {
+            Filter::And(left, right) => left.matches(resource) && right.matches(resource),
+            Filter::Or(left, right) => left.matches(resource) || right.matches(resource),
+            Filter::Not(filter) => !filter.matches(resource),
+            Filter::Present(path) => path.values(resource).into_iter().any(|value| match value {
+                Value::Null => false,
+                Value::String(value) => !value.is_empty(),
+                Value::Array(values) => !values.is_empty(),
+                _ => true,
+            }),
+            Filter::Compare(path, operator, expected) => {
+                let values = path.values(resource);
+                match operator {
+                    // 属性不存在时视为不等于任何值
+                    Operator::Ne => values.iter().all(|value| operator.compare(value, expected)),
+                    operator => values.iter().any(|value| operator.compare(value, expected)),
+                }
+            }
+            Filter::ValuePath(attr, filter) => match get(resource, attr) {
+                Some(Value::Array(values)) => values.iter().any(|value| filter.matches(value)),
+                Some(value) => filter.matches(value),
+                None => false,
+            },
+        }
+    }
+
+    /// Condition narrowing the rows which may match the filter, the filter is still
+    /// evaluated on the resources. `condition` maps a comparison of an attribute to the
+    /// columns, `None` for `pr`; it returns `None` if the attribute is not stored in a column.
+    ///
+    /// 返回的条件是匹配结果的超集，返回 `None` 时无法在数据库中缩小范围。
+    pub fn narrow(
+        &self,
+        condition: &dyn Fn(&AttrPath, Comparison) -> Option<Condition>,
+    ) -> Option<Condition> {
+        match self {
+            Filter::And(left, right) => match (left.narrow(condition), right.narrow(condition)) {
+                (Some(left), Some(right)) => Some(Condition::all().add(left).add(right)),
+                (left, right) => left.or(right),
+            },
+            Filter::Or(left, right) => Some(
+                Condition::any()
+                    .add(left.narrow(condition)?)
+                    .add(right.narrow(condition)?),
+            ),
+            // 取反后的条件不再是超集
+            Filter::Not(_) => None,
+            Filter::Present(path) => condition(path, None),
+            Filter::Compare(path, operator, value) => condition(path, Some((*operator, value))),
+            // 值路径中的属性是复合属性的子属性，如 `emails[value eq "a@b.c"]` 即 `emails.value`
+            Filter::ValuePath(attr, filter) => filter.narrow(&|path, comparison| {
+                let path = AttrPath {
+                    attr: attr.clone(),
+                    sub_attr: Some(path.attr.clone()),
+                };
+                condition(&path, comparison)
+            }),
+        }
+    }
+}
+
+/// Strip the schema URN of core attributes, e.g.
+/// `urn:ietf:params:scim:schemas:core:2.0:User:userName`
+pub fn strip_schema(path: &str) -> &str {
+    match path.strip_prefix("urn:") {
+        Some(_) => path.rsplit_once(':').map_or(path, |(_, attr)| attr),
+        None => path,
+    }
+}
+
+/// Get the attribute of the object, attribute names are case-insensitive
+pub fn get<'a>(value: &'a Value, attr: &str) -> Option<&'a Value> {
+    value
+        .as_object()?
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(attr))
+        .map(|(_, value)| value)
+}
+
+/// Key of the attribute in the object, attribute names are case-insensitive
+pub fn key_of(object: &Map<String, Value>, attr: &str) -> Option<String> {
+    object
+        .keys()
+        .find(|key| key.eq_ignore_ascii_case(attr))
+        .cloned()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    OpenBracket,
+    CloseBracket,
+    Word(String),
+    Literal(Value),
+}
+
+fn tokenize(filter: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut chars: Peekable<CharIndices> = filter.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            '[' => tokens.push(Token::OpenBracket),
+            ']' => tokens.push(Token::CloseBracket),
+            '"' => {
+                let mut escaped = false;
+                let end = loop {
+                    match chars.next() {
+                        Some((_, '\\')) if !escaped => escaped = true,
+                        Some((end, '"')) if !escaped => break end,
+                        Some(_) => escaped = false,
+                        None => return Err("Unterminated string".into()),
+                    }
+                };
+                let literal = serde_json::from_str(&filter[start..=end])
+                    .map_err(|err| format!("Invalid string: {err}"))?;
+                tokens.push(Token::Literal(literal));
+            }
+            _ => {
+                let mut end = start + c.len_utf8();
+                while let Some(&(index, c)) = chars.peek() {
+                    if c.is_whitespace() || "()[]\"".contains(c) {
+                        break;
+                    }
+                    end = index + c.len_utf8();
+                    chars.next();
+                }
+                tokens.push(Token::Word(filter[start..end].to_string()));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser<I: Iterator<Item = Token>> {
+    tokens: Peekable<I>,
+}
+
+impl<I: Iterator<Item = Token>> Parser<I> {
+    fn keyword(&mut self, keyword: &str) -> bool {
+        match self.tokens.peek() {
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword) => {
+                self.tokens.next();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), String> {
+        match self.tokens.next() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => Err(format!("Expected {expected:?}, found {token:?}")),
+            None => Err(format!("Expected {expected:?}")),
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Filter, String> {
+        let mut filter = self.parse_and()?;
+        while self.keyword("or") {
+            filter = Filter::Or(Box::new(filter), Box::new(self.parse_and()?));
+        }
+
+        Ok(filter)
+    }
+
+    fn parse_and(&mut self) -> Result<Filter, String> {
+        let mut filter = self.parse_unary()?;
+        while self.keyword("and") {
+            filter = Filter::And(Box::new(filter), Box::new(self.parse_unary()?));
+        }
+
+        Ok(filter)
+    }
+
+    fn parse_unary(&mut self) -> Result<Filter, String> {
+        if self.keyword("not") {
+            self.expect(Token::Open)?;
+            let filter = self.parse_or()?;
+            self.expect(Token::Close)?;
+            return Ok(Filter::Not(Box::new(filter)));
+        }
+
+        let path = match self.tokens.next() {
+            Some(Token::Open) => {
+                let filter = self.parse_or()?;
+                self.expect(Token::Close)?;
+                return Ok(filter);
+            }
+            Some(Token::Word(path)) => path,
+            Some(token) => return Err(format!("Unexpected {token:?}")),
+            None => return Err("Unexpected end of filter".into()),
+        };
+
+        if self.tokens.peek() == Some(&Token::OpenBracket) {
+            self.tokens.next();
+            let filter = self.parse_or()?;
+            self.expect(Token::CloseBracket)?;
+            return Ok(Filter::ValuePath(
+                strip_schema(&path).to_string(),
+                Box::new(filter),
+            ));
+        }
+
+        let path = AttrPath::parse(&path);
+        let operator = match self.tokens.next() {
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case("pr") => {
+                return Ok(Filter::Present(path))
+            }
+            Some(Token::Word(word)) => {
+                Operator::parse(&word).ok_or_else(|| format!("Unknown operator {word}"))?
+            }
+            _ => return Err(format!("Expected operator after {}", path.attr)),
+        };
+
+        let value = match self.tokens.next() {
+            Some(Token::Literal(value)) => value,
+            Some(Token::Word(word)) => serde_json::from_str(&word.to_ascii_lowercase())
+                .map_err(|_| format!("Invalid value {word}"))?,
+            _ => return Err(format!("Expected value after {}", path.attr)),
+        };
+
+        Ok(Filter::Compare(path, operator, value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::{ColumnTrait, DbBackend, EntityTrait, QueryFilter, QueryTrait};
+    use serde_json::json;
+
+    use super::*;
+    use crate::entity::users;
+
+    fn compare(path: &str, operator: Operator, value: Value) -> Filter {
+        Filter::Compare(AttrPath::parse(path), operator, value)
+    }
+
+    #[test]
+    fn tokenizes_filters() {
+        assert_eq!(
+            tokenize(r#"emails[type eq "work\"s"] and (active eq true)"#).unwrap(),
+            vec![
+                Token::Word("emails".into()),
+                Token::OpenBracket,
+                Token::Word("type".into()),
+                Token::Word("eq".into()),
+                Token::Literal(json!("work\"s")),
+                Token::CloseBracket,
+                Token::Word("and".into()),
+                Token::Open,
+                Token::Word("active".into()),
+                Token::Word("eq".into()),
+                Token::Word("true".into()),
+                Token::Close,
+            ]
+        );
+        assert_eq!(
+            tokenize(r#"userName eq "アリス""#).unwrap()[2],
+            Token::Literal(json!("アリス"))
+        );
+        assert!(tokenize(r#"userName eq "bjensen"#).is_err());
+    }
+
+    #[test]
+    fn parses_and_before_or() {
+        assert_eq!(
+            Filter::parse(r#"title pr or userType eq "Intern" AND active eq false"#).unwrap(),
+            Filter::Or(
+                Box::new(Filter::Present(AttrPath::parse("title"))),
+                Box::new(Filter::And(
+                    Box::new(compare("userType", Operator::Eq, json!("Intern"))),
+                    Box::new(compare("active", Operator::Eq, json!(false))),
+                )),
+            )
+        );
+        assert_eq!(
+            Filter::parse(r#"(title pr or userType eq "Intern") and active eq false"#).unwrap(),
+            Filter::And(
+                Box::new(Filter::Or(
+                    Box::new(Filter::Present(AttrPath::parse("title"))),
+                    Box::new(compare("userType", Operator::Eq, json!("Intern"))),
+                )),
+                Box::new(compare("active", Operator::Eq, json!(false))),
+            )
+        );
+        assert!(Filter::parse(r#"userName eq "a" or"#).is_err());
+        assert!(Filter::parse(r#"userName xx "a""#).is_err());
+        assert!(Filter::parse(r#"(userName eq "a""#).is_err());
+    }
+
+    #[test]
+    fn parses_schema_prefixed_paths() {
+        assert_eq!(
+            Filter::parse(r#"urn:ietf:params:scim:schemas:core:2.0:User:name.givenName sw "B""#)
+                .unwrap(),
+            compare("name.givenName", Operator::Sw, json!("B"))
+        );
+    }
+
+    #[test]
+    fn evaluates_not() {
+        let resource = json!({ "userName": "bjensen", "active": true });
+
+        let filter = Filter::parse(r#"not (userName eq "BJensen")"#).unwrap();
+        assert_eq!(
+            filter,
+            Filter::Not(Box::new(compare(
+                "userName",
+                Operator::Eq,
+                json!("BJensen")
+            )))
+        );
+        assert!(!filter.matches(&resource));
+        assert!(Filter::parse(r#"not (userName sw "x") and active eq true"#)
+            .unwrap()
+            .matches(&resource));
+        assert!(Filter::parse("not userName pr").is_err());
+    }
+
+    #[test]
+    fn evaluates_value_paths() {
+        let resource = json!({
+            "emails": [
+                { "value": "bjensen@example.com", "type": "work" },
+                { "value": "babs@example.org", "type": "home" },
+            ],
+        });
+
+        let filter = Filter::parse(r#"emails[type eq "work" and value co "example.com"]"#).unwrap();
+        assert_eq!(
+            filter,
+            Filter::ValuePath(
+                "emails".into(),
+                Box::new(Filter::And(
+                    Box::new(compare("type", Operator::Eq, json!("work"))),
+                    Box::new(compare("value", Operator::Co, json!("example.com"))),
+                )),
+            )
+        );
+        assert!(filter.matches(&resource));
+        // 条件需要由同一个值满足
+        assert!(
+            !Filter::parse(r#"emails[type eq "home" and value co "example.com"]"#)
+                .unwrap()
+                .matches(&resource)
+        );
+        assert!(Filter::parse(r#"emails.value ew "example.org""#)
+            .unwrap()
+            .matches(&resource));
+        assert!(Filter::parse(r#"emails ne "babs@example.org""#)
+            .unwrap()
+            .matches(&json!({})));
+    }
+
+    fn narrowed(filter: &str) -> Option<String> {
+        let condition = Filter::parse(filter).unwrap().narrow(&|path, comparison| {
+            let column = match (path.attr.as_str(), path.sub_attr.as_deref()) {
+                ("userName", None) => users::Column::Username,
+                ("emails", Some("value")) => users::Column::Email,
+                _ => return None,
+            };
+            match comparison {
+                Some((Operator::Eq, Value::String(value))) => {
+                    Some(Condition::all().add(column.eq(value)))
+                }
+                Some(_) => None,
+                None => Some(Condition::all().add(column.is_not_null())),
+            }
+        })?;
+
+        Some(
+            users::Entity::find()
+                .filter(condition)
+                .build(DbBackend::MySql)
+                .to_string(),
+        )
+    }
+
+    #[test]
+    fn narrows_to_a_superset() {
+        assert!(narrowed(r#"userName eq "a" and title eq "b""#)
+            .unwrap()
+            .ends_with("WHERE `users`.`username` = 'a'"));
+        assert!(narrowed(r#"userName eq "a" or emails[value eq "b"]"#)
+            .unwrap()
+            .ends_with("WHERE `users`.`username` = 'a' OR `users`.`email` = 'b'"));
+        assert!(narrowed(r#"userName pr and emails[type eq "work"]"#)
+            .unwrap()
+            .ends_with("WHERE `users`.`username` IS NOT NULL"));
+
+        // 任一分支无法缩小时整个 or 都无法缩小
+        assert_eq!(narrowed(r#"userName eq "a" or title eq "b""#), None);
+        assert_eq!(narrowed(r#"not (userName eq "a")"#), None);
+        assert_eq!(narrowed(r#"userName co "a""#), None);
+    }
+}
@@ -0,0 +1,777 @@
+//! SCIM 2.0 provisioning
+//!
+//! 基于 [RFC 7643](https://www.rfc-editor.org/rfc/rfc7643) 和 [RFC 7644](https://www.rfc-editor.org/rfc/rfc7644)
+//! 实现 `Users` 和 `Groups` 资源，供 HR 等外部系统同步账号。请求使用 domain 的 SCIM token 认证，
+//! 只能访问该 domain 的用户和用户组。User 的属性映射到 `users` 表的列和 [UserProfile]，
+//! 未支持的属性（包括扩展 schema 的属性）会被忽略。
+
+pub mod filter;
+pub mod patch;
+
+use axum_extra::{
+    headers::{authorization::Bearer, Authorization},
+    TypedHeader,
+};
+use inspirer_framework::{
+    app::AppContext,
+    axum::{
+        body::Bytes,
+        extract::{FromRequest, FromRequestParts, Request},
+        response::{IntoResponse, Response},
+        Json,
+    },
+    http::{
+        header::{CONTENT_TYPE, WWW_AUTHENTICATE},
+        request::Parts,
+        HeaderValue, StatusCode,
+    },
+    Error,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use url::Url;
+use uuid::Uuid;
+
+use crate::{
+    app::App,
+    entity::{groups, users},
+    service::{api_token::ApiToken, ServiceInterface},
+};
+
+use self::filter::{get, Filter};
+use super::{
+    account_status::AccountStatus, api_token::ApiTokenKind, domain::RequestDomain,
+    user::UserProfile,
+};
+
+pub const USER_SCHEMA: &str = "urn:ietf:params:scim:schemas:core:2.0:User";
+pub const GROUP_SCHEMA: &str = "urn:ietf:params:scim:schemas:core:2.0:Group";
+pub const LIST_RESPONSE_SCHEMA: &str = "urn:ietf:params:scim:api:messages:2.0:ListResponse";
+pub const PATCH_OP_SCHEMA: &str = "urn:ietf:params:scim:api:messages:2.0:PatchOp";
+pub const ERROR_SCHEMA: &str = "urn:ietf:params:scim:api:messages:2.0:Error";
+pub const SERVICE_PROVIDER_CONFIG_SCHEMA: &str =
+    "urn:ietf:params:scim:schemas:core:2.0:ServiceProviderConfig";
+pub const RESOURCE_TYPE_SCHEMA: &str = "urn:ietf:params:scim:schemas:core:2.0:ResourceType";
+pub const SCHEMA_SCHEMA: &str = "urn:ietf:params:scim:schemas:core:2.0:Schema";
+
+pub const SCIM_CONTENT_TYPE: &str = "application/scim+json";
+
+/// Detail error types, see [RFC 7644 3.12](https://www.rfc-editor.org/rfc/rfc7644#section-3.12)
+pub const SCIM_TYPES: &[&str] = &[
+    "invalidFilter",
+    "tooMany",
+    "uniqueness",
+    "mutability",
+    "invalidSyntax",
+    "invalidPath",
+    "noTarget",
+    "invalidValue",
+    "invalidVers",
+    "sensitive",
+];
+
+/// Max number of resources returned in one page
+pub const MAX_RESULTS: u64 = 1000;
+
+/// Max number of resources a filter is evaluated on after narrowing the query, filters
+/// matching more candidates are rejected with `tooMany`
+pub const MAX_FILTER_CANDIDATES: u64 = 10_000;
+
+/// Error response of SCIM endpoints, see [RFC 7644 3.12](https://www.rfc-editor.org/rfc/rfc7644#section-3.12)
+#[derive(Debug)]
+pub struct ScimError {
+    pub status: StatusCode,
+    pub scim_type: Option<&'static str>,
+    pub detail: String,
+}
+
+pub type ScimResult<T> = std::result::Result<T, ScimError>;
+
+impl ScimError {
+    pub fn new(
+        status: StatusCode,
+        scim_type: Option<&'static str>,
+        detail: impl Into<String>,
+    ) -> Self {
+        ScimError {
+            status,
+            scim_type,
+            detail: detail.into(),
+        }
+    }
+
+    pub fn bad_request(scim_type: &'static str, detail: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, Some(scim_type), detail)
+    }
+
+    pub fn not_found(detail: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, None, detail)
+    }
+
+    /// The attribute value must be unique but is already in use
+    pub fn uniqueness(detail: impl Into<String>) -> Self {
+        Self::new(StatusCode::CONFLICT, Some("uniqueness"), detail)
+    }
+
+    pub fn unauthorized(detail: impl Into<String>) -> Self {
+        Self::new(StatusCode::UNAUTHORIZED, None, detail)
+    }
+}
+
+impl From<Error> for ScimError {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::NotFound => Self::not_found("Resource not found"),
+            Error::BadRequest(reason) => Self::bad_request("invalidValue", reason),
+            Error::Unauthorized(reason) => Self::unauthorized(reason),
+            // 服务层以 error 字段携带 scimType
+            Error::CustomError(status, detail) if status.is_client_error() => {
+                let scim_type = SCIM_TYPES
+                    .iter()
+                    .find(|scim_type| detail.error.as_deref() == Some(**scim_type))
+                    .copied();
+                let detail = detail.description.or(detail.error).unwrap_or_default();
+                Self::new(status, scim_type, detail)
+            }
+            err => {
+                tracing::error!(error = %err, "scim endpoint error");
+                Self::new(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    None,
+                    "Internal server error",
+                )
+            }
+        }
+    }
+}
+
+impl IntoResponse for ScimError {
+    fn into_response(self) -> Response {
+        let body = json!({
+            "schemas": [ERROR_SCHEMA],
+            "status": self.status.as_u16().to_string(),
+            "scimType": self.scim_type,
+            "detail": self.detail,
+        });
+
+        let mut response = (self.status, ScimJson(strip_nulls(body))).into_response();
+        if self.status == StatusCode::UNAUTHORIZED {
+            response
+                .headers_mut()
+                .insert(WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        }
+
+        response
+    }
+}
+
+/// JSON request or response with the SCIM media type, request bodies are accepted regardless of
+/// the content type since clients send either `application/scim+json` or `application/json`
+pub struct ScimJson<T>(pub T);
+
+#[async_trait::async_trait]
+impl<T, S> FromRequest<S> for ScimJson<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ScimError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let body = Bytes::from_request(req, state)
+            .await
+            .map_err(|err| ScimError::bad_request("invalidSyntax", err.body_text()))?;
+
+        serde_json::from_slice(&body)
+            .map(ScimJson)
+            .map_err(|err| ScimError::bad_request("invalidSyntax", err.to_string()))
+    }
+}
+
+impl<T: Serialize> IntoResponse for ScimJson<T> {
+    fn into_response(self) -> Response {
+        let mut response = Json(self.0).into_response();
+        response
+            .headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_static(SCIM_CONTENT_TYPE));
+
+        response
+    }
+}
+
+/// Query of list endpoints, see [RFC 7644 3.4.2](https://www.rfc-editor.org/rfc/rfc7644#section-3.4.2)
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListQuery {
+    pub filter: Option<String>,
+    /// 1-based index of the first resource
+    pub start_index: Option<u64>,
+    pub count: Option<u64>,
+    /// Comma-separated attributes to return
+    pub attributes: Option<String>,
+    /// Comma-separated attributes not to return
+    pub excluded_attributes: Option<String>,
+}
+
+impl ListQuery {
+    pub fn filter(&self) -> ScimResult<Option<Filter>> {
+        self.filter
+            .as_deref()
+            .map(|filter| {
+                Filter::parse(filter).map_err(|err| ScimError::bad_request("invalidFilter", err))
+            })
+            .transpose()
+    }
+
+    /// Start index and count of the page
+    pub fn page(&self) -> (u64, u64) {
+        (
+            self.start_index.unwrap_or(1).max(1),
+            self.count.unwrap_or(100).min(MAX_RESULTS),
+        )
+    }
+
+    /// Whether the attribute is excluded from the resources
+    pub fn excludes(&self, attr: &str) -> bool {
+        let listed = |attributes: &Option<String>| {
+            attributes.as_deref().is_some_and(|attributes| {
+                attributes
+                    .split(',')
+                    .any(|listed| filter::strip_schema(listed.trim()).eq_ignore_ascii_case(attr))
+            })
+        };
+
+        listed(&self.excluded_attributes)
+            || (self.attributes.is_some() && !listed(&self.attributes))
+    }
+
+    /// Remove the attributes not requested, `schemas`, `id` and `meta` are always returned
+    pub fn project(&self, resource: Value) -> Value {
+        match resource {
+            Value::Object(resource) => Value::Object(
+                resource
+                    .into_iter()
+                    .filter(|(attr, _)| {
+                        matches!(attr.as_str(), "schemas" | "id" | "meta") || !self.excludes(attr)
+                    })
+                    .collect(),
+            ),
+            resource => resource,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListResponse {
+    pub schemas: [&'static str; 1],
+    pub total_results: u64,
+    pub start_index: u64,
+    pub items_per_page: u64,
+    #[serde(rename = "Resources")]
+    pub resources: Vec<Value>,
+}
+
+impl ListResponse {
+    pub fn new(total_results: u64, start_index: u64, resources: Vec<Value>) -> Self {
+        ListResponse {
+            schemas: [LIST_RESPONSE_SCHEMA],
+            total_results,
+            start_index,
+            items_per_page: resources.len() as u64,
+            resources,
+        }
+    }
+}
+
+/// SCIM client authenticated by the SCIM token of the domain
+#[derive(Debug, Clone, Copy)]
+pub struct ScimClient {
+    pub domain_uuid: Uuid,
+    pub token_uuid: Uuid,
+}
+
+#[async_trait::async_trait]
+impl FromRequestParts<AppContext<App>> for ScimClient {
+    type Rejection = ScimError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        context: &AppContext<App>,
+    ) -> Result<Self, Self::Rejection> {
+        let TypedHeader(Authorization(bearer)) =
+            TypedHeader::<Authorization<Bearer>>::from_request_parts(parts, context)
+                .await
+                .map_err(|_| ScimError::unauthorized("Missing bearer token"))?;
+
+        let token = context
+            .service::<ApiToken>()
+            .verify(bearer.token())
+            .await?
+            .filter(|token| token.kind == ApiTokenKind::ScimToken)
+            .ok_or_else(|| ScimError::unauthorized("Invalid SCIM token"))?;
+
+        if !RequestDomain::from_request_parts(parts, context)
+            .await?
+            .allows(token.owner_uuid)
+        {
+            return Err(ScimError::unauthorized(
+                "The SCIM token does not belong to the domain",
+            ));
+        }
+
+        Ok(ScimClient {
+            domain_uuid: token.owner_uuid,
+            token_uuid: token.uuid,
+        })
+    }
+}
+
+/// Attributes of the user resource mapped to the user
+#[derive(Debug)]
+pub struct UserAttributes {
+    pub user_name: String,
+    pub external_id: Option<String>,
+    pub email: Option<String>,
+    pub phone_number: Option<String>,
+    pub active: bool,
+    pub password: Option<String>,
+    /// Profile claims mapped from the attributes, claims without values are `null`
+    pub claims: Map<String, Value>,
+}
+
+impl UserAttributes {
+    pub fn from_resource(resource: &Value) -> ScimResult<Self> {
+        let user_name = string(resource, "userName")
+            .ok_or_else(|| ScimError::bad_request("invalidValue", "userName is required"))?;
+
+        let name = get(resource, "name");
+        let sub_attr = |attr: &str| name.and_then(|name| string(name, attr));
+
+        let address = primary(resource, "addresses").map(|address| {
+            let mut claim = Map::new();
+            for (attr, claim_name) in [
+                ("formatted", "formatted"),
+                ("streetAddress", "street_address"),
+                ("locality", "locality"),
+                ("region", "region"),
+                ("postalCode", "postal_code"),
+                ("country", "country"),
+            ] {
+                if let Some(value) = string(address, attr) {
+                    claim.insert(claim_name.into(), value.into());
+                }
+            }
+            Value::Object(claim)
+        });
+
+        let email = primary_value(resource, "emails");
+        let phone_number = primary_value(resource, "phoneNumbers");
+
+        let claims = Map::from_iter([
+            (
+                "name".into(),
+                sub_attr("formatted")
+                    .or_else(|| string(resource, "displayName"))
+                    .unwrap_or_default()
+                    .into(),
+            ),
+            ("given_name".into(), sub_attr("givenName").into()),
+            ("family_name".into(), sub_attr("familyName").into()),
+            ("middle_name".into(), sub_attr("middleName").into()),
+            ("nickname".into(), string(resource, "nickName").into()),
+            ("profile".into(), string(resource, "profileUrl").into()),
+            ("locale".into(), string(resource, "locale").into()),
+            ("zoneinfo".into(), string(resource, "timezone").into()),
+            ("picture".into(), primary_value(resource, "photos").into()),
+            ("address".into(), address.unwrap_or(Value::Null)),
+            ("email".into(), email.clone().into()),
+            ("phone_number".into(), phone_number.clone().into()),
+        ]);
+
+        let active = match get(resource, "active") {
+            None | Some(Value::Null) => true,
+            Some(Value::Bool(active)) => *active,
+            // 部分客户端以字符串形式发送布尔值
+            Some(Value::String(active)) if active.eq_ignore_ascii_case("true") => true,
+            Some(Value::String(active)) if active.eq_ignore_ascii_case("false") => false,
+            Some(_) => {
+                return Err(ScimError::bad_request(
+                    "invalidValue",
+                    "active must be a boolean",
+                ))
+            }
+        };
+
+        Ok(UserAttributes {
+            user_name,
+            external_id: string(resource, "externalId"),
+            email,
+            phone_number,
+            active,
+            password: string(resource, "password"),
+            claims,
+        })
+    }
+}
+
+/// Attributes of the group resource
+#[derive(Debug)]
+pub struct GroupAttributes {
+    pub display_name: String,
+    pub external_id: Option<String>,
+    pub members: Vec<Uuid>,
+}
+
+impl GroupAttributes {
+    pub fn from_resource(resource: &Value) -> ScimResult<Self> {
+        let display_name = string(resource, "displayName")
+            .ok_or_else(|| ScimError::bad_request("invalidValue", "displayName is required"))?;
+        if display_name.chars().count() > 100 {
+            return Err(ScimError::bad_request(
+                "invalidValue",
+                "displayName must be at most 100 characters",
+            ));
+        }
+
+        let members = match get(resource, "members") {
+            Some(Value::Array(members)) => members
+                .iter()
+                .map(|member| {
+                    string(member, "value")
+                        .and_then(|value| Uuid::parse_str(&value).ok())
+                        .ok_or_else(|| {
+                            ScimError::bad_request("invalidValue", "Invalid member value")
+                        })
+                })
+                .collect::<ScimResult<Vec<_>>>()?,
+            None | Some(Value::Null) => vec![],
+            Some(_) => {
+                return Err(ScimError::bad_request(
+                    "invalidValue",
+                    "members must be multi-valued",
+                ))
+            }
+        };
+
+        Ok(GroupAttributes {
+            display_name,
+            external_id: string(resource, "externalId"),
+            members,
+        })
+    }
+}
+
+/// Representation of the user, `base` is the base url of the SCIM endpoints
+pub fn user_resource(user: &users::Model, groups: &[groups::Model], base: &Url) -> Value {
+    let profile: &UserProfile = &user.profile;
+    let display_name = (!profile.name.is_empty()).then_some(&profile.name);
+
+    let address = profile.address.as_ref().map(|address| {
+        json!([{
+            "formatted": address.formatted,
+            "streetAddress": address.street_address,
+            "locality": address.locality,
+            "region": address.region,
+            "postalCode": address.postal_code,
+            "country": address.country,
+            "primary": true,
+        }])
+    });
+
+    strip_nulls(json!({
+        "schemas": [USER_SCHEMA],
+        "id": user.uuid,
+        "externalId": user.external_id,
+        "userName": user.username.as_ref().or(user.email.as_ref()),
+        "name": {
+            "formatted": display_name,
+            "givenName": profile.given_name,
+            "familyName": profile.family_name,
+            "middleName": profile.middle_name,
+        },
+        "displayName": display_name,
+        "nickName": profile.nickname,
+        "profileUrl": profile.profile,
+        "locale": profile.locale,
+        "timezone": profile.zoneinfo,
+        "active": user.status == AccountStatus::Active,
+        "emails": user.email.as_ref().map(|email| json!([{
+            "value": email,
+            "type": "work",
+            "primary": true,
+        }])),
+        "phoneNumbers": user.phone_number.as_ref().map(|phone_number| json!([{
+            "value": phone_number,
+            "type": "work",
+            "primary": true,
+        }])),
+        "photos": profile.picture.as_ref().map(|picture| json!([{
+            "value": picture,
+            "type": "photo",
+            "primary": true,
+        }])),
+        "addresses": address,
+        "groups": groups.iter().map(|group| json!({
+            "value": group.uuid,
+            "display": group.display_name,
+            "$ref": location(base, "Groups", group.uuid),
+        })).collect::<Vec<_>>(),
+        "meta": {
+            "resourceType": "User",
+            "created": user.created_at.to_rfc3339(),
+            "lastModified": user.updated_at.to_rfc3339(),
+            "location": location(base, "Users", user.uuid),
+        },
+    }))
+}
+
+/// Representation of the group, members are omitted if `None`
+pub fn group_resource(
+    group: &groups::Model,
+    members: Option<&[users::Model]>,
+    base: &Url,
+) -> Value {
+    let members = members.map(|members| {
+        members
+            .iter()
+            .map(|user| {
+                json!({
+                    "value": user.uuid,
+                    "display": user.username.as_ref().or(user.email.as_ref()),
+                    "$ref": location(base, "Users", user.uuid),
+                })
+            })
+            .collect::<Vec<_>>()
+    });
+
+    strip_nulls(json!({
+        "schemas": [GROUP_SCHEMA],
+        "id": group.uuid,
+        "externalId": group.external_id,
+        "displayName": group.display_name,
+        "members": members,
+        "meta": {
+            "resourceType": "Group",
+            "created": group.created_at.to_rfc3339(),
+            "lastModified": group.updated_at.to_rfc3339(),
+            "location": location(base, "Groups", group.uuid),
+        },
+    }))
+}
+
+/// Service provider configuration, see [RFC 7643 5](https://www.rfc-editor.org/rfc/rfc7643#section-5)
+pub fn service_provider_config(base: &Url) -> Value {
+    json!({
+        "schemas": [SERVICE_PROVIDER_CONFIG_SCHEMA],
+        "patch": { "supported": true },
+        "bulk": { "supported": false, "maxOperations": 0, "maxPayloadSize": 0 },
+        "filter": { "supported": true, "maxResults": MAX_RESULTS },
+        "changePassword": { "supported": true },
+        "sort": { "supported": false },
+        "etag": { "supported": false },
+        "authenticationSchemes": [{
+            "type": "oauthbearertoken",
+            "name": "SCIM token",
+            "description": "Bearer token issued to the domain for SCIM provisioning",
+            "primary": true,
+        }],
+        "meta": {
+            "resourceType": "ServiceProviderConfig",
+            "location": base.join("ServiceProviderConfig").ok(),
+        },
+    })
+}
+
+/// Resource types, see [RFC 7643 6](https://www.rfc-editor.org/rfc/rfc7643#section-6)
+pub fn resource_types(base: &Url) -> Vec<Value> {
+    [
+        ("User", "Users", USER_SCHEMA),
+        ("Group", "Groups", GROUP_SCHEMA),
+    ]
+    .into_iter()
+    .map(|(name, endpoint, schema)| {
+        json!({
+            "schemas": [RESOURCE_TYPE_SCHEMA],
+            "id": name,
+            "name": name,
+            "endpoint": format!("/{endpoint}"),
+            "schema": schema,
+            "meta": {
+                "resourceType": "ResourceType",
+                "location": base.join(&format!("ResourceTypes/{name}")).ok(),
+            },
+        })
+    })
+    .collect()
+}
+
+/// Definitions of the supported attributes, see [RFC 7643 7](https://www.rfc-editor.org/rfc/rfc7643#section-7)
+pub fn schemas(base: &Url) -> Vec<Value> {
+    let multi_valued = |name: &str, sub_attributes: Vec<Value>| {
+        json!({
+            "name": name,
+            "type": "complex",
+            "multiValued": true,
+            "required": false,
+            "mutability": "readWrite",
+            "returned": "default",
+            "subAttributes": sub_attributes,
+        })
+    };
+    let typed_values = |name: &str| {
+        multi_valued(
+            name,
+            vec![
+                attribute("value", "string", "readWrite"),
+                attribute("type", "string", "readWrite"),
+                attribute("primary", "boolean", "readWrite"),
+            ],
+        )
+    };
+
+    let user = json!({
+        "id": USER_SCHEMA,
+        "name": "User",
+        "description": "User Account",
+        "attributes": [
+            refine(attribute("userName", "string", "readWrite"), json!({
+                "uniqueness": "server",
+                "required": true})),
+            attribute("externalId", "string", "readWrite"),
+            refine(attribute("name", "complex", "readWrite"), json!({
+                "subAttributes": [
+                    attribute("formatted", "string", "readWrite"),
+                    attribute("givenName", "string", "readWrite"),
+                    attribute("familyName", "string", "readWrite"),
+                    attribute("middleName", "string", "readWrite"),
+                ]})),
+            attribute("displayName", "string", "readWrite"),
+            attribute("nickName", "string", "readWrite"),
+            attribute("profileUrl", "reference", "readWrite"),
+            attribute("locale", "string", "readWrite"),
+            attribute("timezone", "string", "readWrite"),
+            attribute("active", "boolean", "readWrite"),
+            refine(attribute("password", "string", "writeOnly"), json!({ "returned": "never"})),
+            typed_values("emails"),
+            typed_values("phoneNumbers"),
+            typed_values("photos"),
+            multi_valued("addresses", vec![
+                attribute("formatted", "string", "readWrite"),
+                attribute("streetAddress", "string", "readWrite"),
+                attribute("locality", "string", "readWrite"),
+                attribute("region", "string", "readWrite"),
+                attribute("postalCode", "string", "readWrite"),
+                attribute("country", "string", "readWrite"),
+                attribute("primary", "boolean", "readWrite"),
+            ]),
+            refine(attribute("groups", "complex", "readOnly"), json!({
+                "multiValued": true,
+                "subAttributes": [
+                    attribute("value", "string", "readOnly"),
+                    attribute("$ref", "reference", "readOnly"),
+                    attribute("display", "string", "readOnly"),
+                ]})),
+        ],
+    });
+
+    let group = json!({
+        "id": GROUP_SCHEMA,
+        "name": "Group",
+        "description": "Group",
+        "attributes": [
+            refine(attribute("displayName", "string", "readWrite"), json!({ "required": true})),
+            attribute("externalId", "string", "readWrite"),
+            refine(attribute("members", "complex", "readWrite"), json!({
+                "multiValued": true,
+                "subAttributes": [
+                    attribute("value", "string", "immutable"),
+                    attribute("$ref", "reference", "immutable"),
+                    attribute("display", "string", "readOnly"),
+                ]})),
+        ],
+    });
+
+    [user, group]
+        .into_iter()
+        .map(|mut schema| {
+            let id = schema["id"].as_str().unwrap_or_default().to_string();
+            schema["schemas"] = json!([SCHEMA_SCHEMA]);
+            schema["meta"] = json!({
+                "resourceType": "Schema",
+                "location": base.join(&format!("Schemas/{id}")).ok(),
+            });
+            schema
+        })
+        .collect()
+}
+
+fn attribute(name: &str, type_: &str, mutability: &str) -> Value {
+    Value::Object(Map::from_iter([
+        ("name".into(), name.into()),
+        ("type".into(), type_.into()),
+        ("multiValued".into(), false.into()),
+        ("required".into(), false.into()),
+        ("caseExact".into(), false.into()),
+        ("mutability".into(), mutability.into()),
+        ("returned".into(), "default".into()),
+        ("uniqueness".into(), "none".into()),
+    ]))
+}
+
+/// Override characteristics of the attribute
+fn refine(mut attribute: Value, characteristics: Value) -> Value {
+    if let (Value::Object(attribute), Value::Object(characteristics)) =
+        (&mut attribute, characteristics)
+    {
+        attribute.extend(characteristics);
+    }
+
+    attribute
+}
+
+fn location(base: &Url, endpoint: &str, uuid: Uuid) -> Option<Url> {
+    base.join(&format!("{endpoint}/{uuid}")).ok()
+}
+
+/// String value of the attribute, empty strings are treated as absent
+fn string(resource: &Value, attr: &str) -> Option<String> {
+    get(resource, attr)
+        .and_then(Value::as_str)
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+}
+
+/// The primary value of the multi-valued attribute, or the first one if none is primary
+fn primary<'a>(resource: &'a Value, attr: &str) -> Option<&'a Value> {
+    let values = get(resource, attr)?.as_array()?;
+
+    values
+        .iter()
+        .find(|value| get(value, "primary").and_then(Value::as_bool) == Some(true))
+        .or_else(|| values.first())
+}
+
+fn primary_value(resource: &Value, attr: &str) -> Option<String> {
+    primary(resource, attr).and_then(|value| string(value, "value"))
+}
+
+/// Remove `null` attributes and empty complex attributes recursively
+fn strip_nulls(value: Value) -> Value {
+    match value {
+        Value::Object(object) => Value::Object(
+            object
+                .into_iter()
+                .map(|(attr, value)| (attr, strip_nulls(value)))
+                .filter(|(_, value)| match value {
+                    Value::Null => false,
+                    Value::Object(object) => !object.is_empty(),
+                    _ => true,
+                })
+                .collect(),
+        ),
+        Value::Array(values) => Value::Array(values.into_iter().map(strip_nulls).collect()),
+        value => value,
+    }
+}
@@ -0,0 +1,428 @@
+//! SCIM PATCH operations, see [RFC 7644 3.5.2](https://www.rfc-editor.org/rfc/rfc7644#section-3.5.2)
+//!
+//! 操作作用于资源的 JSON 表示，修改后的资源再按替换（PUT）的方式保存。
+
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use super::{
+    filter::{key_of, strip_schema, Filter, Operator},
+    ScimError, ScimResult, PATCH_OP_SCHEMA,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct PatchRequest {
+    pub schemas: Vec<String>,
+    #[serde(rename = "Operations")]
+    pub operations: Vec<PatchOperation>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PatchOperation {
+    /// `add`, `remove` or `replace`, some clients capitalize it
+    pub op: String,
+    pub path: Option<String>,
+    pub value: Option<Value>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Add,
+    Remove,
+    Replace,
+}
+
+/// Target of the operation, e.g. `members[value eq "2819c223"]` or `name.givenName`
+#[derive(Debug)]
+struct PatchPath {
+    attr: String,
+    filter: Option<Filter>,
+    sub_attr: Option<String>,
+}
+
+impl PatchPath {
+    fn parse(path: &str) -> ScimResult<Self> {
+        let invalid = |reason: String| ScimError::bad_request("invalidPath", reason);
+        let path = strip_schema(path.trim());
+
+        let Some((attr, rest)) = path.split_once('[') else {
+            return Ok(match path.split_once('.') {
+                Some((attr, sub_attr)) => PatchPath {
+                    attr: attr.to_string(),
+                    filter: None,
+                    sub_attr: Some(sub_attr.to_string()),
+                },
+                None => PatchPath {
+                    attr: path.to_string(),
+                    filter: None,
+                    sub_attr: None,
+                },
+            });
+        };
+
+        let (filter, sub_attr) = rest
+            .rsplit_once(']')
+            .ok_or_else(|| invalid(format!("Unterminated filter in {path}")))?;
+        let sub_attr = match sub_attr {
+            "" => None,
+            sub_attr => Some(
+                sub_attr
+                    .strip_prefix('.')
+                    .ok_or_else(|| invalid(format!("Invalid path {path}")))?
+                    .to_string(),
+            ),
+        };
+
+        Ok(PatchPath {
+            attr: attr.to_string(),
+            filter: Some(Filter::parse(filter).map_err(invalid)?),
+            sub_attr,
+        })
+    }
+}
+
+impl PatchRequest {
+    /// Apply the operations to the resource in order
+    pub fn apply(self, resource: &mut Value) -> ScimResult<()> {
+        if !self.schemas.iter().any(|schema| schema == PATCH_OP_SCHEMA) {
+            return Err(ScimError::bad_request(
+                "invalidSyntax",
+                format!("Schema {PATCH_OP_SCHEMA} is required"),
+            ));
+        }
+
+        let Value::Object(resource) = resource else {
+            return Err(ScimError::bad_request("invalidSyntax", "Invalid resource"));
+        };
+
+        for operation in self.operations {
+            let op = match operation.op.to_ascii_lowercase().as_str() {
+                "add" => Op::Add,
+                "remove" => Op::Remove,
+                "replace" => Op::Replace,
+                op => {
+                    return Err(ScimError::bad_request(
+                        "invalidSyntax",
+                        format!("Unknown operation {op}"),
+                    ))
+                }
+            };
+
+            match (operation.path, operation.value) {
+                (Some(path), value) => apply(resource, op, &PatchPath::parse(&path)?, value)?,
+                // 没有 path 时 value 中的每个属性分别作为一个操作
+                (None, Some(Value::Object(values))) if op != Op::Remove => {
+                    for (path, value) in values {
+                        apply(resource, op, &PatchPath::parse(&path)?, Some(value))?;
+                    }
+                }
+                (None, _) => {
+                    return Err(ScimError::bad_request(
+                        "noTarget",
+                        "The path is required by the operation",
+                    ))
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn apply(
+    resource: &mut Map<String, Value>,
+    op: Op,
+    path: &PatchPath,
+    value: Option<Value>,
+) -> ScimResult<()> {
+    let value = match (op, value) {
+        (Op::Remove, _) => Value::Null,
+        (_, Some(value)) => value,
+        (_, None) => {
+            return Err(ScimError::bad_request(
+                "invalidValue",
+                "The value is required by the operation",
+            ))
+        }
+    };
+    let key = key_of(resource, &path.attr).unwrap_or_else(|| path.attr.clone());
+
+    match (&path.filter, &path.sub_attr) {
+        (None, None) => match op {
+            Op::Remove => {
+                resource.remove(&key);
+            }
+            Op::Add => match (resource.get_mut(&key), value) {
+                (Some(Value::Array(values)), Value::Array(added)) => {
+                    for value in added {
+                        if !values.contains(&value) {
+                            values.push(value);
+                        }
+                    }
+                }
+                (Some(Value::Object(object)), Value::Object(added)) => merge(object, added),
+                (_, value) => {
+                    resource.insert(key, value);
+                }
+            },
+            Op::Replace => {
+                resource.insert(key, value);
+            }
+        },
+        (None, Some(sub_attr)) => {
+            let object = resource
+                .entry(key)
+                .or_insert_with(|| Value::Object(Map::new()));
+            if !object.is_object() {
+                *object = Value::Object(Map::new());
+            }
+            if let Value::Object(object) = object {
+                let key = key_of(object, sub_attr).unwrap_or_else(|| sub_attr.clone());
+                match op {
+                    Op::Remove => {
+                        object.remove(&key);
+                    }
+                    _ => {
+                        object.insert(key, value);
+                    }
+                }
+            }
+        }
+        (Some(filter), sub_attr) => {
+            let values = resource.entry(key).or_insert_with(|| Value::Array(vec![]));
+            let Value::Array(values) = values else {
+                return Err(ScimError::bad_request(
+                    "invalidPath",
+                    format!("{} is not multi-valued", path.attr),
+                ));
+            };
+
+            let mut matched = false;
+            let mut index = 0;
+            while index < values.len() {
+                if !filter.matches(&values[index]) {
+                    index += 1;
+                    continue;
+                }
+                matched = true;
+
+                match (op, sub_attr, &mut values[index]) {
+                    (Op::Remove, None, _) => {
+                        values.remove(index);
+                        continue;
+                    }
+                    (Op::Remove, Some(sub_attr), Value::Object(object)) => {
+                        if let Some(key) = key_of(object, sub_attr) {
+                            object.remove(&key);
+                        }
+                    }
+                    (_, Some(sub_attr), Value::Object(object)) => {
+                        let key = key_of(object, sub_attr).unwrap_or_else(|| sub_attr.clone());
+                        object.insert(key, value.clone());
+                    }
+                    (_, None, Value::Object(object)) => {
+                        if let Value::Object(replaced) = value.clone() {
+                            merge(object, replaced);
+                        }
+                    }
+                    (_, _, target) => *target = value.clone(),
+                }
+                index += 1;
+            }
+
+            // 过滤条件为 `<attr> eq <value>` 且没有匹配的值时按条件新增一个值，
+            // 例如 `emails[type eq "work"].value`
+            if !matched && op != Op::Remove {
+                let Filter::Compare(attr, Operator::Eq, expected) = filter else {
+                    return Err(ScimError::bad_request(
+                        "noTarget",
+                        format!("No value of {} matches the filter", path.attr),
+                    ));
+                };
+
+                let mut added = Map::from_iter([(attr.attr.clone(), expected.clone())]);
+                match (sub_attr, value) {
+                    (Some(sub_attr), value) => {
+                        added.insert(sub_attr.clone(), value);
+                    }
+                    (None, Value::Object(value)) => merge(&mut added, value),
+                    (None, _) => {}
+                }
+                values.push(Value::Object(added));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn merge(object: &mut Map<String, Value>, values: Map<String, Value>) {
+    for (attr, value) in values {
+        let key = key_of(object, &attr).unwrap_or(attr);
+        object.insert(key, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn patch(resource: &mut Value, operations: Value) -> ScimResult<()> {
+        serde_json::from_value::<PatchRequest>(json!({
+            "schemas": [PATCH_OP_SCHEMA],
+            "Operations": operations,
+        }))
+        .unwrap()
+        .apply(resource)
+    }
+
+    fn user() -> Value {
+        json!({
+            "userName": "bjensen",
+            "name": { "givenName": "Barbara" },
+            "emails": [
+                { "value": "bjensen@example.com", "type": "work" },
+                { "value": "babs@example.org", "type": "home" },
+            ],
+        })
+    }
+
+    #[test]
+    fn adds_values() {
+        let mut resource = user();
+        patch(
+            &mut resource,
+            json!([
+                { "op": "add", "path": "title", "value": "Tour Guide" },
+                { "op": "Add", "path": "name", "value": { "familyName": "Jensen" } },
+                {
+                    "op": "add",
+                    "path": "emails",
+                    "value": [
+                        { "value": "babs@example.org", "type": "home" },
+                        { "value": "b@example.net", "type": "other" },
+                    ],
+                },
+                { "op": "add", "value": { "nickName": "Babs" } },
+            ]),
+        )
+        .unwrap();
+
+        assert_eq!(resource["title"], json!("Tour Guide"));
+        assert_eq!(
+            resource["name"],
+            json!({ "givenName": "Barbara", "familyName": "Jensen" })
+        );
+        assert_eq!(resource["emails"].as_array().unwrap().len(), 3);
+        assert_eq!(resource["nickName"], json!("Babs"));
+    }
+
+    #[test]
+    fn replaces_values() {
+        let mut resource = user();
+        patch(
+            &mut resource,
+            json!([
+                { "op": "replace", "path": "USERNAME", "value": "babs" },
+                { "op": "replace", "path": "name.givenName", "value": "Babs" },
+                { "op": "replace", "path": "emails[type eq \"work\"].value", "value": "b@example.com" },
+            ]),
+        )
+        .unwrap();
+
+        // 属性名不区分大小写，保留原有的键
+        assert_eq!(resource["userName"], json!("babs"));
+        assert_eq!(resource.get("USERNAME"), None);
+        assert_eq!(resource["name"]["givenName"], json!("Babs"));
+        assert_eq!(
+            resource["emails"][0],
+            json!({ "value": "b@example.com", "type": "work" })
+        );
+        assert_eq!(resource["emails"][1]["value"], json!("babs@example.org"));
+    }
+
+    #[test]
+    fn removes_values() {
+        let mut resource = user();
+        patch(
+            &mut resource,
+            json!([
+                { "op": "remove", "path": "name.givenName" },
+                { "op": "remove", "path": "emails[type eq \"home\"]" },
+                { "op": "remove", "path": "userName" },
+            ]),
+        )
+        .unwrap();
+
+        assert_eq!(resource["name"], json!({}));
+        assert_eq!(
+            resource["emails"],
+            json!([{ "value": "bjensen@example.com", "type": "work" }])
+        );
+        assert_eq!(resource.get("userName"), None);
+
+        let error = patch(&mut user(), json!([{ "op": "remove" }])).unwrap_err();
+        assert_eq!(error.scim_type, Some("noTarget"));
+    }
+
+    #[test]
+    fn adds_value_matching_the_equality_filter() {
+        let mut resource = user();
+        patch(
+            &mut resource,
+            json!([
+                { "op": "replace", "path": "phoneNumbers[type eq \"work\"].value", "value": "+15555550100" },
+            ]),
+        )
+        .unwrap();
+
+        assert_eq!(
+            resource["phoneNumbers"],
+            json!([{ "type": "work", "value": "+15555550100" }])
+        );
+
+        let error = patch(
+            &mut user(),
+            json!([
+                { "op": "replace", "path": "emails[type sw \"x\"].value", "value": "x@example.com" },
+            ]),
+        )
+        .unwrap_err();
+        assert_eq!(error.scim_type, Some("noTarget"));
+    }
+
+    #[test]
+    fn rejects_invalid_requests() {
+        let error = serde_json::from_value::<PatchRequest>(json!({
+            "schemas": [],
+            "Operations": [],
+        }))
+        .unwrap()
+        .apply(&mut user())
+        .unwrap_err();
+        assert_eq!(error.scim_type, Some("invalidSyntax"));
+
+        let error = patch(
+            &mut user(),
+            json!([{ "op": "move", "path": "userName", "value": "x" }]),
+        )
+        .unwrap_err();
+        assert_eq!(error.scim_type, Some("invalidSyntax"));
+
+        let error = patch(
+            &mut user(),
+            json!([{ "op": "add", "path": "emails[type eq \"work\"", "value": "x" }]),
+        )
+        .unwrap_err();
+        assert_eq!(error.scim_type, Some("invalidPath"));
+
+        let error = patch(
+            &mut user(),
+            json!([{ "op": "add", "path": "userName[value eq \"x\"]", "value": "x" }]),
+        )
+        .unwrap_err();
+        assert_eq!(error.scim_type, Some("invalidPath"));
+    }
+}
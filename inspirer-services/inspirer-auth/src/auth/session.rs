@@ -0,0 +1,31 @@
+//! Server-side sessions and the app grants tied to them
+//!
+//! 每次登录都会创建一个会话，应用在会话内获得授权（grant），refresh token 归属于授权。
+//! 撤销会话会同时撤销其下所有授权和 refresh token。
+
+use inspirer_framework::permission;
+use serde::Serialize;
+
+use crate::entity::{grants, user_sessions};
+
+permission!(
+    /// Read and revoke sessions of users in the domain
+    pub ManageSessions,
+    "auth.sessions.manage"
+);
+
+/// Key of the session uuid stored in the browser session
+pub const SESSION_UUID_KEY: &str = "session_uuid";
+
+/// Min seconds between updates of the last seen time of a session
+pub const SESSION_TOUCH_INTERVAL: i64 = 60;
+
+/// Session with the grants of apps
+#[derive(Debug, Serialize)]
+pub struct SessionInfo {
+    #[serde(flatten)]
+    pub session: user_sessions::Model,
+    /// Whether the session is the one of current request
+    pub current: bool,
+    pub grants: Vec<grants::Model>,
+}
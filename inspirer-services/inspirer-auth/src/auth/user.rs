@@ -1,15 +1,21 @@
 //! Auth service user
 //!
 
-use chrono::{DateTime, Utc};
+use std::{collections::BTreeMap, fmt};
+
+use chrono::{DateTime, NaiveDate, Utc};
 use chrono_tz::Tz;
+use once_cell::sync::Lazy;
 pub use openidconnect::StandardClaims;
 use openidconnect::{core::CoreGenderClaim, GenderClaim};
-use phonenumber::PhoneNumber;
+use phonenumber::{Mode, PhoneNumber};
+use regex::Regex;
 use sea_orm::FromJsonQueryResult;
 use serde::{Deserialize, Serialize};
 use serde_enum_str::{Deserialize_enum_str, Serialize_enum_str};
+use serde_json::Value;
 use url::Url;
+use utoipa::ToSchema;
 
 pub type StandardUserProfile = StandardClaims<CoreGenderClaim>;
 
@@ -24,26 +30,31 @@ pub struct UserProfile {
     /// End-User's full name in displayable form including all name parts,
     /// possibly including titles and suffixes,
     /// ordered according to the End-User's locale and preferences.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub name: String,
 
     /// Given name(s) or first name(s) of the End-User.
     /// Note that in some cultures, people can have multiple given names;
     /// all can be present, with the names being separated by space characters.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub given_name: Option<String>,
 
     /// Surname(s) or last name(s) of the End-User. Note that in some cultures,
     /// people can have multiple family names or no family name; all can be present,
     /// with the names being separated by space characters.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub family_name: Option<String>,
 
     /// Middle name(s) of the End-User. Note that in some cultures,
     /// people can have multiple middle names; all can be present,
     /// with the names being separated by space characters.
     /// Also note that in some cultures, middle names are not used.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub middle_name: Option<String>,
 
     /// Casual name of the End-User that may or may not be the same as the `given_name`.
     /// For instance, a `nickname` value of `Mike` might be returned alongside a `given_name` value of `Michael`.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub nickname: Option<String>,
 
     /// Shorthand name by which the End-User wishes to be referred to at the RP,
@@ -51,9 +62,11 @@ pub struct UserProfile {
     /// including special characters such as `@`, `/`, or whitespace.
     /// The RP MUST NOT rely upon this value being unique,
     /// as discussed in [Section 5.7](https://openid.net/specs/openid-connect-core-1_0.html#ClaimStability).
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub preferred_username: Option<String>,
 
     /// URL of the End-User's profile page. The contents of this Web page SHOULD be about the End-User.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub profile: Option<Url>,
 
     /// URL of the End-User's profile picture.
@@ -61,16 +74,19 @@ pub struct UserProfile {
     /// rather than to a Web page containing an image.
     /// Note that this URL SHOULD specifically reference a profile photo of the End-User suitable for
     /// displaying when describing the End-User, rather than an arbitrary photo taken by the End-User.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub picture: Option<Url>,
 
     /// URL of the End-User's Web page or blog. This Web page SHOULD contain information published
     /// by the End-User or an organization that the End-User is affiliated with.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub website: Option<Url>,
 
     /// End-User's preferred e-mail address. Its value MUST conform
     /// to the [RFC 5322](https://openid.net/specs/openid-connect-core-1_0.html#RFC5322) [RFC5322] addr-spec syntax.
     /// The RP MUST NOT rely upon this value being unique, as discussed in
     /// [Section 5.7](https://openid.net/specs/openid-connect-core-1_0.html#ClaimStability).
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub email: Option<String>,
 
     /// True if the End-User's e-mail address has been verified; otherwise false.
@@ -79,10 +95,12 @@ pub struct UserProfile {
     /// by the End-User at the time the verification was performed.
     /// The means by which an e-mail address is verified is context specific,
     /// and dependent upon the trust framework or contractual agreements within which the parties are operating.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub email_verified: Option<bool>,
 
     /// End-User's gender. Values defined by this specification are `female` and `male`.
     /// Other values MAY be used when neither of the defined values are applicable.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub gender: Option<Gender>,
 
     /// End-User's birthday, represented as an
@@ -92,12 +110,14 @@ pub struct UserProfile {
     /// Note that depending on the underlying platform's date related function,
     /// providing just year can result in varying month and day,
     /// so the implementers need to take this factor into account to correctly process the dates.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub birthdate: Option<String>,
 
     /// String from IANA Time Zone Database
     /// [IANA.time‑zones](https://openid.net/specs/openid-connect-core-1_0.html#IANA.time-zones)
     /// representing the End-User's time zone.
     /// For example, `Europe/Paris` or `America/Los_Angeles`.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub zoneinfo: Option<Tz>,
 
     /// End-User's locale, represented as a [BCP47](https://openid.net/specs/openid-connect-core-1_0.html#RFC5646) [RFC5646] language tag.
@@ -107,6 +127,7 @@ pub struct UserProfile {
     /// For example, `en-US` or `fr-CA`. As a compatibility note,
     /// some implementations have used an underscore as the separator rather than a dash,
     /// for example, `en_US`; Relying Parties MAY choose to accept this locale syntax as well.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub locale: Option<String>,
 
     /// End-User's preferred telephone number. [E.164](https://openid.net/specs/openid-connect-core-1_0.html#E.164) [E.164]
@@ -115,6 +136,7 @@ pub struct UserProfile {
     /// If the phone number contains an extension,
     /// it is RECOMMENDED that the extension be represented using the [RFC 3966](https://openid.net/specs/openid-connect-core-1_0.html#RFC3966) [RFC3966] extension syntax,
     /// for example, `+1 (604) 555-1234;ext=5678`.
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "e164")]
     pub phone_number: Option<PhoneNumber>,
 
     /// True if the End-User's phone number has been verified;
@@ -125,23 +147,203 @@ pub struct UserProfile {
     /// The means by which a phone number is verified is context specific,
     /// and dependent upon the trust framework or contractual agreements within which the parties are operating.
     /// When true, the `phone_number` Claim MUST be in E.164 format and any extensions MUST be represented in RFC 3966 format.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub phone_number_verified: Option<bool>,
 
     /// End-User's preferred postal address.
     /// The value of the address member is a [JSON](https://openid.net/specs/openid-connect-core-1_0.html#RFC8259) [RFC8259]
     /// structure containing some
     /// or all of the members defined in [Section 5.1.1](https://openid.net/specs/openid-connect-core-1_0.html#AddressClaim).
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub address: Option<AddressClaim>,
 
     /// Time the End-User's information was last updated.
     /// Its value is a JSON number representing the number of seconds from 1970-01-01T00:00:00Z
     /// as measured in UTC until the date/time.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "chrono::serde::ts_seconds_option"
+    )]
     pub updated_at: Option<DateTime<Utc>>,
+
+    /// Localized claims in `claim#language-tag` form, e.g. `name#ja-Kana-JP`, and other claims
+    /// not defined above. Values are kept as is, so that a claim of any JSON type does not
+    /// break the whole profile
+    #[serde(flatten)]
+    pub localized: BTreeMap<String, Value>,
+}
+
+impl UserProfile {
+    /// Create an empty profile with only the required claims
+    pub fn new<S: Into<String>, N: Into<String>>(sub: S, name: N) -> Self {
+        UserProfile {
+            sub: sub.into(),
+            name: name.into(),
+            given_name: None,
+            family_name: None,
+            middle_name: None,
+            nickname: None,
+            preferred_username: None,
+            profile: None,
+            picture: None,
+            website: None,
+            email: None,
+            email_verified: None,
+            gender: None,
+            birthdate: None,
+            zoneinfo: None,
+            locale: None,
+            phone_number: None,
+            phone_number_verified: None,
+            address: None,
+            updated_at: None,
+            localized: BTreeMap::new(),
+        }
+    }
+}
+
+impl UserProfile {
+    /// Claims the user can change by self-service
+    pub const EDITABLE_CLAIMS: &'static [&'static str] = &[
+        "name",
+        "given_name",
+        "family_name",
+        "middle_name",
+        "nickname",
+        "preferred_username",
+        "profile",
+        "picture",
+        "website",
+        "email",
+        "gender",
+        "birthdate",
+        "zoneinfo",
+        "locale",
+        "phone_number",
+        "address",
+    ];
+
+    /// Validate claims which can not be checked by the types, returns the violations
+    pub fn validate(&self) -> Vec<String> {
+        let mut violations = vec![];
+
+        for (claim, url) in [
+            ("profile", &self.profile),
+            ("picture", &self.picture),
+            ("website", &self.website),
+        ] {
+            if let Some(url) = url {
+                if !matches!(url.scheme(), "http" | "https") {
+                    violations.push(format!("{claim} must be a http(s) url"));
+                }
+            }
+        }
+
+        if let Some(email) = &self.email {
+            if !is_email(email) {
+                violations.push("email is invalid".into());
+            }
+        }
+
+        if let Some(birthdate) = &self.birthdate {
+            let valid = NaiveDate::parse_from_str(birthdate, "%Y-%m-%d").is_ok()
+                || (birthdate.len() == 4 && birthdate.chars().all(|c| c.is_ascii_digit()));
+            if !valid {
+                violations.push("birthdate must be in YYYY-MM-DD or YYYY format".into());
+            }
+        }
+
+        if let Some(locale) = &self.locale {
+            if !is_bcp47_locale(locale) {
+                violations.push("locale must be a BCP47 language tag".into());
+            }
+        }
+
+        if let Some(phone_number) = &self.phone_number {
+            if !phonenumber::is_valid(phone_number) {
+                violations.push("phone_number must be a valid E.164 number".into());
+            }
+        }
+
+        violations
+    }
+
+    /// Phone number in E.164 format
+    pub fn phone_number_e164(&self) -> Option<String> {
+        self.phone_number
+            .as_ref()
+            .map(|number| number.format().mode(Mode::E164).to_string())
+    }
+}
+
+/// 与 [StandardClaims] 的 JSON 表示一致，因此通过 JSON 互相转换不会丢失信息
+impl<GC: GenderClaim> TryFrom<StandardClaims<GC>> for UserProfile {
+    type Error = serde_json::Error;
+
+    fn try_from(claims: StandardClaims<GC>) -> Result<Self, Self::Error> {
+        serde_json::from_value(serde_json::to_value(claims)?)
+    }
+}
+
+impl<GC: GenderClaim> TryFrom<UserProfile> for StandardClaims<GC> {
+    type Error = serde_json::Error;
+
+    fn try_from(profile: UserProfile) -> Result<Self, Self::Error> {
+        serde_json::from_value(serde_json::to_value(profile)?)
+    }
+}
+
+/// 简单校验邮箱格式，只要求 `local@domain` 形式，真实性由邮箱验证保证
+pub fn is_email(email: &str) -> bool {
+    static EMAIL: Lazy<Regex> = Lazy::new(|| Regex::new(r"^[^@\s]+@[^@\s]+\.[^@\s]+$").unwrap());
+
+    email.len() <= 255 && EMAIL.is_match(email)
+}
+
+/// 校验 BCP47 语言标签（language[-script][-region][-variant]），兼容 `en_US` 的写法
+pub fn is_bcp47_locale(locale: &str) -> bool {
+    static BCP47: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(
+            r"^(?i)[a-z]{2,3}(-[a-z]{4})?(-([a-z]{2}|[0-9]{3}))?(-([a-z0-9]{5,8}|[0-9][a-z0-9]{3}))*$",
+        )
+        .unwrap()
+    });
+
+    BCP47.is_match(&locale.replace('_', "-"))
+}
+
+/// Serialize phone number as E.164 string
+mod e164 {
+    use phonenumber::{Mode, PhoneNumber};
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(number: &Option<PhoneNumber>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match number {
+            Some(number) => {
+                serializer.serialize_some(&number.format().mode(Mode::E164).to_string())
+            }
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<PhoneNumber>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Option::<String>::deserialize(deserializer)?
+            .map(|number| phonenumber::parse(None, number).map_err(D::Error::custom))
+            .transpose()
+    }
 }
 
 #[derive(
     Debug, Clone, Deserialize_enum_str, Serialize_enum_str, PartialEq, Eq, FromJsonQueryResult,
 )]
+#[serde(rename_all = "lowercase")]
 pub enum Gender {
     Male,
     Female,
@@ -188,7 +390,9 @@ pub struct AddressClaim {
 }
 
 /// User credential use for login
-#[derive(Debug, Deserialize, Serialize)]
+///
+/// `Debug` 输出中不包含密码，避免密码出现在日志中。
+#[derive(Deserialize, Serialize, ToSchema)]
 #[serde(tag = "type", content = "payload", rename_all = "snake_case")]
 pub enum UserCredential {
     /// 使用用户名作为登录凭据
@@ -206,3 +410,65 @@ pub enum UserCredential {
         password: String,
     },
 }
+
+impl UserCredential {
+    /// Username or email of the credential
+    pub fn identifier(&self) -> &str {
+        match self {
+            UserCredential::Username { username, .. } => username,
+            UserCredential::Email { email, .. } => email,
+        }
+    }
+
+    pub fn password(&self) -> &str {
+        match self {
+            UserCredential::Username { password, .. } | UserCredential::Email { password, .. } => {
+                password
+            }
+        }
+    }
+}
+
+impl fmt::Debug for UserCredential {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UserCredential::Username { username, .. } => f
+                .debug_struct("Username")
+                .field("username", username)
+                .field("password", &"[redacted]")
+                .finish(),
+            UserCredential::Email { email, .. } => f
+                .debug_struct("Email")
+                .field("email", email)
+                .field("password", &"[redacted]")
+                .finish(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn keeps_extra_claims_of_any_type() {
+        let profile: UserProfile = serde_json::from_value(json!({
+            "sub": "1",
+            "name": "Alice",
+            "name#ja-Kana-JP": "アリス",
+            "employee_number": 42,
+            "groups": ["staff"],
+        }))
+        .unwrap();
+
+        assert_eq!(profile.name, "Alice");
+        assert_eq!(profile.localized["name#ja-Kana-JP"], json!("アリス"));
+        assert_eq!(profile.localized["employee_number"], json!(42));
+        assert_eq!(
+            serde_json::to_value(&profile).unwrap()["groups"],
+            json!(["staff"])
+        );
+    }
+}
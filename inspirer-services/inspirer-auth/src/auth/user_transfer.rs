@@ -0,0 +1,202 @@
+//! Bulk import and export of users
+//!
+//! 用于从旧系统迁移用户。每条记录是一组列，列名通过映射对应到用户字段（`uuid`、`username`、`email`、
+//! `phone_number`、`password`）或 [UserProfile] 的 claim，未映射的列按同名字段处理，无法识别的列被忽略。
+//! `password` 为已经哈希过的密码，支持 PHC 格式的 argon2、scrypt 以及 bcrypt，
+//! 登录时按原算法验证，并在登录成功后以当前参数重新哈希。
+
+use std::collections::HashMap;
+
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+use crate::{entity::users, password::password_hash_supported};
+
+use super::user::UserProfile;
+
+/// Fields of users besides the profile claims
+pub const USER_FIELDS: &[&str] = &["uuid", "username", "email", "phone_number", "password"];
+
+/// Profile claims which are not editable by users but can be imported and exported
+const EXTRA_CLAIMS: &[&str] = &["email_verified", "phone_number_verified", "updated_at"];
+
+/// Claims of non-string types, their values are JSON encoded in CSV
+pub const JSON_CLAIMS: &[&str] = &[
+    "email_verified",
+    "phone_number_verified",
+    "address",
+    "updated_at",
+];
+
+/// Mapping from source columns to user fields or profile claims
+#[derive(Debug, Default, Clone)]
+pub struct ColumnMapping(HashMap<String, String>);
+
+impl ColumnMapping {
+    /// Parse the mapping from `column=field` entries
+    pub fn parse<S: AsRef<str>>(entries: &[S]) -> Result<Self, String> {
+        let mut mapping = HashMap::new();
+        for entry in entries {
+            let entry = entry.as_ref();
+            let (column, field) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid mapping {entry}, expected column=field"))?;
+            if !is_known_field(field) {
+                return Err(format!("Unknown field {field}"));
+            }
+            mapping.insert(column.to_string(), field.to_string());
+        }
+
+        Ok(ColumnMapping(mapping))
+    }
+
+    /// The field the column is mapped to, `None` if the column is ignored
+    pub fn field<'a>(&'a self, column: &'a str) -> Option<&'a str> {
+        match self.0.get(column) {
+            Some(field) => Some(field),
+            None => is_known_field(column).then_some(column),
+        }
+    }
+
+    /// Map the record into the user to be imported, returns the violations if the record
+    /// is invalid
+    pub fn map(&self, record: Map<String, Value>) -> Result<ImportedUser, Vec<String>> {
+        let mut violations = vec![];
+        let mut fields = HashMap::new();
+        let mut claims = Map::new();
+
+        for (column, value) in record {
+            let Some(field) = self.field(&column) else {
+                continue;
+            };
+
+            let value = match value {
+                Value::Null => continue,
+                Value::String(value) if value.trim().is_empty() => continue,
+                Value::String(value) if JSON_CLAIMS.contains(&field) => {
+                    match serde_json::from_str(&value) {
+                        Ok(value) => value,
+                        Err(_) => {
+                            violations.push(format!("{field} is not a valid JSON value"));
+                            continue;
+                        }
+                    }
+                }
+                value => value,
+            };
+
+            if USER_FIELDS.contains(&field) {
+                match value {
+                    Value::String(value) => {
+                        fields.insert(field.to_string(), value.trim().to_string());
+                    }
+                    _ => violations.push(format!("{field} must be a string")),
+                }
+            } else {
+                claims.insert(field.to_string(), value);
+            }
+        }
+
+        let uuid = match fields.remove("uuid") {
+            Some(uuid) => Uuid::parse_str(&uuid).unwrap_or_else(|_| {
+                violations.push("uuid is invalid".into());
+                Uuid::nil()
+            }),
+            None => Uuid::new_v4(),
+        };
+
+        let username = fields.remove("username");
+        let email = fields.remove("email");
+        if username.is_none() && email.is_none() {
+            violations.push("username or email is required".into());
+        }
+
+        let password = fields.remove("password").unwrap_or_default();
+        if !password.is_empty() && !password_hash_supported(&password) {
+            violations.push("password is not a supported argon2, scrypt or bcrypt hash".into());
+        }
+
+        claims.insert("sub".into(), uuid.to_string().into());
+        if let Some(email) = &email {
+            claims.insert("email".into(), email.clone().into());
+        }
+        if let Some(phone_number) = fields.remove("phone_number") {
+            claims.insert("phone_number".into(), phone_number.into());
+        }
+        if let Some(username) = &username {
+            claims
+                .entry("preferred_username")
+                .or_insert_with(|| username.clone().into());
+        }
+
+        let profile = match serde_json::from_value::<UserProfile>(Value::Object(claims)) {
+            Ok(profile) => {
+                violations.extend(profile.validate());
+                Some(profile)
+            }
+            Err(err) => {
+                violations.push(format!("profile is invalid: {err}"));
+                None
+            }
+        };
+
+        match profile {
+            Some(profile) if violations.is_empty() => Ok(ImportedUser {
+                uuid,
+                username,
+                email,
+                password,
+                profile,
+            }),
+            _ => Err(violations),
+        }
+    }
+}
+
+/// User mapped from a record, the profile has been validated
+#[derive(Debug)]
+pub struct ImportedUser {
+    pub uuid: Uuid,
+    pub username: Option<String>,
+    pub email: Option<String>,
+    /// Password hash, empty if the user has no password
+    pub password: String,
+    pub profile: UserProfile,
+}
+
+/// Profile claims exported as CSV columns besides [USER_FIELDS], localized claims are only
+/// exported in JSONL
+pub fn exported_claims() -> Vec<&'static str> {
+    UserProfile::EDITABLE_CLAIMS
+        .iter()
+        .chain(EXTRA_CLAIMS)
+        .filter(|claim| !USER_FIELDS.contains(claim))
+        .copied()
+        .collect()
+}
+
+/// Record of the user to be exported, the password hash is only included if required
+pub fn export_record(user: &users::Model, with_password: bool) -> Map<String, Value> {
+    let mut record = match serde_json::to_value(&user.profile) {
+        Ok(Value::Object(profile)) => profile,
+        _ => Map::new(),
+    };
+    record.remove("sub");
+    record.insert("uuid".into(), user.uuid.to_string().into());
+    record.insert("username".into(), user.username.clone().into());
+    record.insert("email".into(), user.email.clone().into());
+    record.insert("phone_number".into(), user.phone_number.clone().into());
+    if with_password {
+        record.insert("password".into(), user.password.clone().into());
+    }
+
+    record
+}
+
+fn is_known_field(field: &str) -> bool {
+    let claim = field.split('#').next().unwrap_or_default();
+
+    USER_FIELDS.contains(&field)
+        || UserProfile::EDITABLE_CLAIMS.contains(&claim)
+        || EXTRA_CLAIMS.contains(&field)
+}
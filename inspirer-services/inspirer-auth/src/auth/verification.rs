@@ -0,0 +1,23 @@
+//! Verification of user email and phone number
+//!
+//! 验证码只保存 SHA-256 摘要，由配置的发送服务（邮件、短信网关）投递给用户。
+
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::helper::sha256_hex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::N(20))")]
+#[serde(rename_all = "snake_case")]
+pub enum VerificationChannel {
+    #[sea_orm(string_value = "email")]
+    Email,
+    #[sea_orm(string_value = "phone")]
+    Phone,
+}
+
+/// Digest of the verification code
+pub fn code_digest(code: &str) -> String {
+    sha256_hex(code.as_bytes())
+}
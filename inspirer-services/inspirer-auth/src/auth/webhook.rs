@@ -0,0 +1,93 @@
+//! Webhook of user lifecycle events
+//!
+//! 事件投递的请求体使用应用密钥进行 HMAC-SHA256 签名，签名放在 `X-Inspirer-Signature` 头中，
+//! 格式为 `t=<timestamp>,v1=<hex>`，签名内容为 `<timestamp>.<body>`。
+
+use std::fmt;
+
+use hmac::{Hmac, Mac};
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use tabled::Tabled;
+use url::Url;
+
+pub const EVENT_HEADER: &str = "X-Inspirer-Event";
+pub const DELIVERY_HEADER: &str = "X-Inspirer-Delivery";
+pub const SIGNATURE_HEADER: &str = "X-Inspirer-Signature";
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, EnumIter, DeriveActiveEnum, Serialize, Deserialize,
+)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::N(40))")]
+pub enum UserEvent {
+    #[sea_orm(string_value = "user.created")]
+    #[serde(rename = "user.created")]
+    Created,
+    #[sea_orm(string_value = "user.updated")]
+    #[serde(rename = "user.updated")]
+    Updated,
+    #[sea_orm(string_value = "user.verified")]
+    #[serde(rename = "user.verified")]
+    Verified,
+    #[sea_orm(string_value = "user.disabled")]
+    #[serde(rename = "user.disabled")]
+    Disabled,
+    #[sea_orm(string_value = "user.deleted")]
+    #[serde(rename = "user.deleted")]
+    Deleted,
+}
+
+impl fmt::Display for UserEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_value())
+    }
+}
+
+/// Webhook subscription of the app
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Tabled)]
+pub struct WebhookSubscription {
+    pub url: Url,
+    /// 订阅的事件，为空时订阅所有事件
+    #[serde(default)]
+    #[tabled(skip)]
+    pub events: Vec<UserEvent>,
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+impl WebhookSubscription {
+    pub fn subscribed(&self, event: UserEvent) -> bool {
+        self.enabled && (self.events.is_empty() || self.events.contains(&event))
+    }
+}
+
+/// Sign the payload with the app secret, returns the value of signature header
+pub fn sign(secret: &[u8], timestamp: i64, payload: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC can take key of any size");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(payload);
+
+    format!(
+        "t={timestamp},v1={}",
+        hex::encode(mac.finalize().into_bytes())
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signature_is_hmac_sha256_of_timestamp_and_payload() {
+        assert_eq!(
+            sign(b"whsec_test", 1700000000, br#"{"event":"user.created"}"#),
+            "t=1700000000,v1=be54c9b0b1bfcb889662e9b74778f194903a82691c8323f7bf085ca53892ee78"
+        );
+    }
+}
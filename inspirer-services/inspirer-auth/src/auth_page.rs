@@ -0,0 +1,304 @@
+//! Auth page bundle
+//!
+//! 登录、授权确认和设备验证页面由 `auth-page` 构建，构建产物 `auth-page/dist` 在编译时嵌入二进制文件。
+//! 配置了 `default_auth_page` 时使用该目录中的页面替代嵌入的页面。
+//!
+//! 页面的 `index.html` 在返回前注入所属域和应用的品牌信息（名称、logo 和颜色），
+//! 读取自 `domains.profile` 和 `apps.profile`，应用的配置优先于域的配置。
+
+use std::{
+    borrow::Cow,
+    env::current_dir,
+    path::{Component, Path, PathBuf},
+};
+
+use inspirer_framework::preludes::*;
+use rust_embed::RustEmbed;
+use serde::Serialize;
+use serde_json::Value;
+use url::Url;
+
+use crate::{
+    config::AppConfig,
+    entity::{apps, domains},
+};
+
+const INDEX: &str = "index.html";
+
+/// The built auth page, missing if the page was not built before the server
+#[derive(RustEmbed)]
+#[folder = "auth-page/dist"]
+#[allow_missing = true]
+struct EmbeddedAuthPage;
+
+/// Where the files of the auth page are read from
+#[derive(Debug, Clone)]
+pub enum AuthPageBundle {
+    Embedded,
+    Directory(PathBuf),
+}
+
+/// File of the auth page with the content type guessed from the path
+pub struct Asset {
+    pub content: Cow<'static, [u8]>,
+    pub content_type: String,
+}
+
+impl AuthPageBundle {
+    /// The override directory of the config or the embedded bundle, returns an error if the
+    /// bundle does not contain `index.html`
+    pub fn from_config(config: &AppConfig) -> Result<Self> {
+        let bundle = match &config.default_auth_page {
+            Some(path) => AuthPageBundle::Directory(current_dir()?.join(path)),
+            None => AuthPageBundle::Embedded,
+        };
+
+        let missing = match &bundle {
+            AuthPageBundle::Directory(path) => !path.join(INDEX).is_file(),
+            AuthPageBundle::Embedded => EmbeddedAuthPage::get(INDEX).is_none(),
+        };
+        if missing {
+            return Err(Error::string(&match &bundle {
+                AuthPageBundle::Directory(path) => format!(
+                    "Auth page not found: {} does not contain {INDEX}",
+                    path.display()
+                ),
+                AuthPageBundle::Embedded => "Auth page not found: build auth-page before building \
+                     the server, or set default_auth_page to the directory of the built page"
+                    .to_string(),
+            }));
+        }
+
+        Ok(bundle)
+    }
+
+    /// File of the bundle, `None` if the file does not exist or the path leaves the bundle
+    pub async fn asset(&self, path: &str) -> Result<Option<Asset>> {
+        let path = Path::new(path.trim_start_matches('/'));
+        if path
+            .components()
+            .any(|component| !matches!(component, Component::Normal(_)))
+        {
+            return Ok(None);
+        }
+
+        let content = match self {
+            AuthPageBundle::Embedded => path
+                .to_str()
+                .and_then(EmbeddedAuthPage::get)
+                .map(|file| file.data),
+            AuthPageBundle::Directory(directory) => {
+                match tokio::fs::read(directory.join(path)).await {
+                    Ok(content) => Some(Cow::Owned(content)),
+                    Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+                    Err(err) => return Err(err.into()),
+                }
+            }
+        };
+
+        Ok(content.map(|content| Asset {
+            content,
+            content_type: mime_guess::from_path(path)
+                .first_or_octet_stream()
+                .to_string(),
+        }))
+    }
+
+    /// `index.html` with the branding injected
+    pub async fn index(&self, branding: &Branding) -> Result<String> {
+        let index = self
+            .asset(INDEX)
+            .await?
+            .ok_or_else(|| Error::string(&format!("Auth page {INDEX} is missing")))?;
+        let index = String::from_utf8(index.content.into_owned())
+            .map_err(|_| Error::string(&format!("Auth page {INDEX} is not valid UTF-8")))?;
+
+        Ok(branding.inject(&index)?)
+    }
+}
+
+/// Branding of the auth page
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Branding {
+    pub display_name: Option<String>,
+    pub logo_uri: Option<Url>,
+    pub primary_color: Option<String>,
+    pub background_color: Option<String>,
+}
+
+impl Branding {
+    /// Branding of the domain overridden by the app
+    pub fn new(domain: Option<&domains::Model>, app: Option<&apps::Model>) -> Self {
+        let domain = domain
+            .map(|domain| Branding::from_profile(&domain.display_name, &domain.profile))
+            .unwrap_or_default();
+        let app = app
+            .map(|app| Branding::from_profile(&app.display_name, &app.profile))
+            .unwrap_or_default();
+
+        Branding {
+            display_name: app.display_name.or(domain.display_name),
+            logo_uri: app.logo_uri.or(domain.logo_uri),
+            primary_color: app.primary_color.or(domain.primary_color),
+            background_color: app.background_color.or(domain.background_color),
+        }
+    }
+
+    /// Invalid values of the profile are ignored, the profile may not even be an object
+    fn from_profile(display_name: &str, profile: &Value) -> Self {
+        let string = |key: &str| {
+            profile
+                .get(key)
+                .and_then(Value::as_str)
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+        };
+
+        Branding {
+            display_name: string("display_name")
+                .or(Some(display_name.trim()).filter(|name| !name.is_empty()))
+                .map(str::to_string),
+            logo_uri: string("logo_uri")
+                .and_then(|uri| Url::parse(uri).ok())
+                .filter(|uri| matches!(uri.scheme(), "http" | "https")),
+            primary_color: string("primary_color")
+                .filter(|color| is_color(color))
+                .map(str::to_string),
+            background_color: string("background_color")
+                .filter(|color| is_color(color))
+                .map(str::to_string),
+        }
+    }
+
+    /// Replace the title and expose the branding as `window.__AUTH_BRANDING__` and CSS variables
+    fn inject(&self, index: &str) -> serde_json::Result<String> {
+        let mut index = index.to_string();
+
+        if let Some(display_name) = &self.display_name {
+            if let (Some(start), Some(end)) = (index.find("<title>"), index.find("</title>")) {
+                if start < end {
+                    index.replace_range(start + "<title>".len()..end, &escape_html(display_name));
+                }
+            }
+        }
+
+        // `<` 转义后 JSON 不会提前结束 script 标签
+        let mut head = format!(
+            "<script>window.__AUTH_BRANDING__ = {};</script>",
+            serde_json::to_string(self)?.replace('<', "\\u003c")
+        );
+        let variables = [
+            ("--brand-primary", &self.primary_color),
+            ("--brand-background", &self.background_color),
+        ]
+        .into_iter()
+        .filter_map(|(name, color)| color.as_ref().map(|color| format!("{name}: {color};")))
+        .collect::<Vec<_>>();
+        if !variables.is_empty() {
+            head.push_str(&format!(
+                "<style>:root {{ {} }}</style>",
+                variables.join(" ")
+            ));
+        }
+
+        match index.find("</head>") {
+            Some(position) => index.insert_str(position, &head),
+            None => index.insert_str(0, &head),
+        }
+
+        Ok(index)
+    }
+}
+
+/// Only hex colours are accepted, so that the value can not break out of the style
+fn is_color(value: &str) -> bool {
+    value.strip_prefix('#').is_some_and(|hex| {
+        matches!(hex.len(), 3 | 4 | 6 | 8) && hex.chars().all(|c| c.is_ascii_hexdigit())
+    })
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use serde_json::json;
+    use uuid::Uuid;
+
+    use super::*;
+
+    const INDEX_HTML: &str = "<html><head><title>Sign in</title></head><body></body></html>";
+
+    #[test]
+    fn only_hex_colors_are_accepted() {
+        for color in ["#fff", "#ffff", "#1a2b3c", "#1a2b3c4d"] {
+            assert!(is_color(color), "{color}");
+        }
+        for color in [
+            "red;}</style>",
+            "#fff;}</style>",
+            "red",
+            "#ggg",
+            "#12345",
+            "fff",
+        ] {
+            assert!(!is_color(color), "{color}");
+        }
+
+        let branding = Branding::from_profile(
+            "Wiki",
+            &json!({ "primary_color": "red;}</style><script>alert(1)</script>" }),
+        );
+        assert_eq!(branding.primary_color, None);
+    }
+
+    #[test]
+    fn injected_branding_is_escaped() {
+        let branding = Branding::from_profile(
+            "</title><script>alert(1)</script>",
+            &json!({ "logo_uri": "https://cdn.example.com/logo.png?v=</script><script>alert(1)" }),
+        );
+        let index = branding.inject(INDEX_HTML).unwrap();
+
+        assert!(
+            index.contains("<title>&lt;/title&gt;&lt;script&gt;alert(1)&lt;/script&gt;</title>")
+        );
+        assert!(!index.contains("<script>alert(1)"));
+        // 只有注入的 script 标签
+        assert_eq!(index.matches("</script>").count(), 1);
+
+        let branding =
+            Branding::from_profile("Wiki", &json!({ "logo_uri": "javascript:alert(1)" }));
+        assert_eq!(branding.logo_uri, None);
+    }
+
+    #[tokio::test]
+    async fn directory_bundle_stays_in_the_directory() {
+        let root = std::env::temp_dir().join(format!("inspirer-auth-page-{}", Uuid::new_v4()));
+        let directory = root.join("dist");
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join(INDEX), INDEX_HTML).unwrap();
+        fs::write(root.join("secret.txt"), "secret").unwrap();
+
+        let bundle = AuthPageBundle::Directory(directory);
+        assert!(bundle.asset(INDEX).await.unwrap().is_some());
+        for path in [
+            "../secret.txt",
+            "/../secret.txt",
+            "assets/../../secret.txt",
+            "./index.html",
+        ] {
+            assert!(bundle.asset(path).await.unwrap().is_none(), "{path}");
+        }
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,164 @@
+use clap::{Args, Parser};
+use inspirer_framework::preludes::*;
+use tabled::Table;
+use uuid::Uuid;
+
+use crate::{
+    app::App,
+    auth::{api_token::ApiTokenKind, audit::ClientInfo},
+    service::{
+        api_token::{ApiToken, NewApiToken},
+        app::App as AppService,
+        domain::Domain,
+        user::User,
+        ServiceInterface,
+    },
+};
+
+/// Owner of the tokens: a user owns personal access tokens, an app owns API keys and a domain
+/// owns SCIM tokens
+#[derive(Debug, Args)]
+#[group(required = true, multiple = false)]
+pub struct TokenOwner {
+    /// User UUID, for personal access tokens
+    #[arg(long)]
+    user: Option<Uuid>,
+
+    /// App UUID, for API keys
+    #[arg(long)]
+    app: Option<Uuid>,
+
+    /// Domain UUID, for SCIM provisioning tokens
+    #[arg(long)]
+    scim_domain: Option<Uuid>,
+}
+
+impl TokenOwner {
+    /// Kind of the tokens, the domain and the uuid of the owner
+    async fn resolve(&self, context: &AppContext<App>) -> Result<(ApiTokenKind, Uuid, Uuid)> {
+        match (self.user, self.app, self.scim_domain) {
+            (Some(uuid), ..) => {
+                let user = context.service::<User>().find_user_by_uuid(uuid).await?;
+                Ok((
+                    ApiTokenKind::PersonalAccessToken,
+                    user.domain_uuid,
+                    user.uuid,
+                ))
+            }
+            (_, Some(uuid), _) => {
+                let app = context
+                    .service::<AppService>()
+                    .find_app_by_uuid(uuid)
+                    .await?;
+                Ok((ApiTokenKind::ApiKey, app.domain_uuid, app.uuid))
+            }
+            (.., Some(uuid)) => {
+                let domain = context
+                    .service::<Domain>()
+                    .find_domain_by_uuid(uuid)
+                    .await?;
+                Ok((ApiTokenKind::ScimToken, domain.uuid, domain.uuid))
+            }
+            (None, None, None) => Err(Error::string(
+                "One of --user, --app or --scim-domain is required",
+            )),
+        }
+    }
+}
+
+/// Issue a personal access token of the user, an API key of the app or a SCIM token of the domain
+#[derive(Debug, Parser)]
+pub struct IssueApiToken {
+    #[command(flatten)]
+    owner: TokenOwner,
+
+    /// Name of the token
+    #[arg(long)]
+    name: String,
+
+    /// Space-delimited scopes
+    #[arg(long, default_value = "")]
+    scope: String,
+
+    /// Lifetime in seconds, the token never expires if not set
+    #[arg(long)]
+    expires_in: Option<u64>,
+}
+
+#[async_trait::async_trait]
+impl AppCommand<App> for IssueApiToken {
+    async fn execute(&self, context: AppContext<App>) -> Result<()> {
+        let (kind, domain_uuid, owner_uuid) = self.owner.resolve(&context).await?;
+
+        let (model, token) = context
+            .service::<ApiToken>()
+            .issue(
+                kind,
+                domain_uuid,
+                owner_uuid,
+                NewApiToken {
+                    name: self.name.clone(),
+                    scope: self.scope.clone(),
+                    expires_in: self.expires_in,
+                },
+                None,
+                ClientInfo::default(),
+            )
+            .await?;
+
+        println!("Token UUID = {}", model.uuid);
+        println!("Token (shown only once): {token}");
+
+        Ok(())
+    }
+}
+
+/// List personal access tokens of the user, API keys of the app or SCIM tokens of the domain
+#[derive(Debug, Parser)]
+pub struct ListApiTokens {
+    #[command(flatten)]
+    owner: TokenOwner,
+}
+
+#[async_trait::async_trait]
+impl AppCommand<App> for ListApiTokens {
+    async fn execute(&self, context: AppContext<App>) -> Result<()> {
+        let (kind, _, owner_uuid) = self.owner.resolve(&context).await?;
+
+        let tokens = context
+            .service::<ApiToken>()
+            .tokens(kind, owner_uuid)
+            .await?;
+
+        println!("{}", Table::new(&tokens));
+
+        Ok(())
+    }
+}
+
+/// Revoke a personal access token of the user, an API key of the app or a SCIM token of the domain
+#[derive(Debug, Parser)]
+pub struct RevokeApiToken {
+    #[command(flatten)]
+    owner: TokenOwner,
+
+    /// Token UUID
+    #[arg(value_name = "TOKEN")]
+    token: Uuid,
+}
+
+#[async_trait::async_trait]
+impl AppCommand<App> for RevokeApiToken {
+    async fn execute(&self, context: AppContext<App>) -> Result<()> {
+        let (kind, _, owner_uuid) = self.owner.resolve(&context).await?;
+
+        context
+            .service::<ApiToken>()
+            .revoke(kind, owner_uuid, self.token, None, ClientInfo::default())
+            .await?;
+
+        println!("Token revoked.");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,90 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use clap::Parser;
+use inspirer_framework::preludes::*;
+use tabled::Table;
+use uuid::Uuid;
+
+use crate::{
+    app::App,
+    config::AppConfig,
+    service::{
+        audit::{Audit, AuditFilter},
+        ServiceInterface,
+    },
+};
+
+/// Print the latest audit events
+#[derive(Debug, Parser)]
+pub struct TailAudit {
+    /// Only print events of the domain
+    #[arg(long)]
+    domain: Option<Uuid>,
+
+    /// Number of events to print
+    #[arg(long, short = 'n', default_value_t = 20)]
+    limit: u64,
+
+    /// Keep polling for new events
+    #[arg(long, short = 'f')]
+    follow: bool,
+}
+
+#[async_trait::async_trait]
+impl AppCommand<App> for TailAudit {
+    async fn execute(&self, context: AppContext<App>) -> Result<()> {
+        let service = context.service::<Audit>();
+        let mut after = None;
+
+        loop {
+            let mut events = service
+                .events(AuditFilter {
+                    domain_uuid: self.domain,
+                    after,
+                    limit: self.limit,
+                    ..Default::default()
+                })
+                .await?;
+
+            if !events.is_empty() {
+                events.reverse();
+                after = events.last().map(|event| event.id);
+                println!("{}", Table::new(&events));
+            }
+
+            if !self.follow {
+                break;
+            }
+
+            tokio::time::sleep(Duration::from_secs(2)).await;
+        }
+
+        Ok(())
+    }
+}
+
+/// Delete audit events older than the retention period
+#[derive(Debug, Parser)]
+pub struct PruneAudit {
+    /// Retention days, defaults to `audit.retention_days` of the config
+    #[arg(long)]
+    days: Option<u32>,
+}
+
+#[async_trait::async_trait]
+impl AppCommand<App> for PruneAudit {
+    async fn execute(&self, context: AppContext<App>) -> Result<()> {
+        let config = context.config.get::<AppConfig>("app")?;
+        let days = self.days.unwrap_or(config.audit.retention_days);
+
+        let deleted = context
+            .service::<Audit>()
+            .prune(Utc::now() - chrono::Duration::days(days as i64))
+            .await?;
+
+        println!("{deleted} audit events deleted.");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,121 @@
+use chrono::Utc;
+use clap::Parser;
+use inspirer_framework::preludes::*;
+use sea_orm::{ActiveModelTrait, IntoActiveModel, Set};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::{
+    app::App,
+    auth::claims::{ClaimMapping, ClaimSource, ClaimTarget},
+    service::{app::App as AppService, ServiceInterface},
+};
+
+/// Map a claim of the app, the mapping of the same name is replaced.
+///
+/// The default mappings are copied into the app on the first change.
+#[derive(Debug, Parser)]
+#[command(group = clap::ArgGroup::new("source").args(["profile", "roles", "value", "template", "remove"]).required(true))]
+pub struct MapClaim {
+    /// App UUID
+    #[arg(long)]
+    app: Uuid,
+
+    /// Name of the claim in the tokens
+    #[arg(long)]
+    name: String,
+
+    /// Claim of the user profile, e.g. `email`
+    #[arg(long)]
+    profile: Option<String>,
+
+    /// Names of the roles of the user
+    #[arg(long)]
+    roles: bool,
+
+    /// Static JSON value, e.g. `"tenant-a"` or `[1, 2]`
+    #[arg(long)]
+    value: Option<String>,
+
+    /// Tera template rendered with `user`, `profile`, `roles` and `app`,
+    /// e.g. `{{ user.username }}@{{ app.name }}`
+    #[arg(long)]
+    template: Option<String>,
+
+    /// Comma-separated targets, defaults to `id_token,userinfo`
+    #[arg(long, value_enum, value_delimiter = ',')]
+    targets: Vec<ClaimTarget>,
+
+    /// Scope required to emit the claim, the claim is always emitted if not set
+    #[arg(long)]
+    scope: Option<String>,
+
+    /// Remove the mapping of the claim instead
+    #[arg(long)]
+    remove: bool,
+}
+
+impl MapClaim {
+    fn source(&self) -> Result<ClaimSource> {
+        if let Some(field) = &self.profile {
+            return Ok(ClaimSource::Profile {
+                field: field.clone(),
+            });
+        }
+        if let Some(value) = &self.value {
+            return Ok(ClaimSource::Static {
+                value: serde_json::from_str::<Value>(value)
+                    .map_err(|err| Error::string(&format!("Invalid JSON value: {err}")))?,
+            });
+        }
+        if let Some(template) = &self.template {
+            return Ok(ClaimSource::Template {
+                template: template.clone(),
+            });
+        }
+
+        Ok(ClaimSource::Roles)
+    }
+}
+
+#[async_trait::async_trait]
+impl AppCommand<App> for MapClaim {
+    async fn execute(&self, context: AppContext<App>) -> Result<()> {
+        let app = context
+            .service::<AppService>()
+            .find_app_by_uuid(self.app)
+            .await?;
+
+        let mut setting = app.setting.clone();
+        let mut mappings = setting.claim_mappings();
+        mappings.retain(|mapping| mapping.name != self.name);
+
+        if !self.remove {
+            let mapping = ClaimMapping {
+                name: self.name.clone(),
+                source: self.source()?,
+                targets: match self.targets.is_empty() {
+                    true => vec![ClaimTarget::IdToken, ClaimTarget::Userinfo],
+                    false => self.targets.clone(),
+                },
+                scope: self.scope.clone(),
+            };
+            mapping.validate().map_err(|err| Error::string(&err))?;
+            mappings.push(mapping);
+        }
+
+        if mappings.is_empty() {
+            return Err(Error::string("The app must emit at least one claim"));
+        }
+        setting.claims = mappings;
+
+        let mut app = app.into_active_model();
+        app.setting = Set(setting);
+        app.updated_at = Set(Utc::now());
+        app.update(&context.database).await?;
+
+        println!("Done!");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,79 @@
+use chrono::Utc;
+use clap::Parser;
+use inspirer_framework::preludes::*;
+use sea_orm::{EntityTrait, Set};
+use uuid::Uuid;
+
+use crate::{app::App, auth::federation::ClaimMapping, entity::identity_providers};
+
+/// Add an upstream OpenID Connect provider to the domain
+#[derive(Debug, Parser)]
+pub struct AddIdentityProvider {
+    /// Domain UUID
+    #[arg(long)]
+    domain: Uuid,
+
+    /// Unique name of the provider in the domain
+    #[arg(long)]
+    name: String,
+
+    /// Name shown on the login page, e.g. "Sign in with <display name>"
+    #[arg(long)]
+    display_name: Option<String>,
+
+    /// Issuer url, the discovery document is loaded from it
+    #[arg(long)]
+    issuer: String,
+
+    #[arg(long)]
+    client_id: String,
+
+    #[arg(long)]
+    client_secret: Option<String>,
+
+    /// Space-delimited scopes requested from the provider
+    #[arg(long, default_value = "openid email profile")]
+    scopes: String,
+
+    /// Claim mapping in JSON, fields not set use the standard claim names
+    #[arg(long)]
+    claim_mapping: Option<String>,
+
+    /// Link upstream identities to existing users by the email verified by the provider,
+    /// only enable it if the provider is trusted to verify emails
+    #[arg(long)]
+    auto_link_verified_email: bool,
+}
+
+#[async_trait::async_trait]
+impl AppCommand<App> for AddIdentityProvider {
+    async fn execute(&self, context: AppContext<App>) -> Result<()> {
+        let claim_mapping = match &self.claim_mapping {
+            Some(mapping) => serde_json::from_str::<ClaimMapping>(mapping)?,
+            None => ClaimMapping::default(),
+        };
+
+        let provider_uuid = Uuid::new_v4();
+        identity_providers::Entity::insert(identity_providers::ActiveModel {
+            uuid: Set(provider_uuid),
+            domain_uuid: Set(self.domain),
+            name: Set(self.name.clone()),
+            display_name: Set(self.display_name.clone().unwrap_or(self.name.clone())),
+            issuer_url: Set(self.issuer.clone()),
+            client_id: Set(self.client_id.clone()),
+            client_secret: Set(self.client_secret.clone()),
+            scopes: Set(self.scopes.clone()),
+            claim_mapping: Set(claim_mapping),
+            auto_link_verified_email: Set(self.auto_link_verified_email),
+            created_at: Set(Utc::now()),
+            updated_at: Set(Utc::now()),
+            ..Default::default()
+        })
+        .exec(&context.database)
+        .await?;
+
+        println!("Identity Provider UUID = {}", provider_uuid);
+
+        Ok(())
+    }
+}
@@ -50,11 +50,16 @@ impl AppCommand<App> for InitData {
         let app_uuid = service.init_app(domain_uuid).await?;
 
         // init users
-        let user_uuid = service.init_user(domain_uuid).await?;
+        let (user_uuid, password) = service.init_user(domain_uuid).await?;
+
+        // init rbac
+        let role_uuid = service.init_rbac(domain_uuid, user_uuid).await?;
 
         println!("Default Domain UUID = {}", domain_uuid);
         println!("Default App UUID = {}", app_uuid);
         println!("Default User UUID = {}", user_uuid);
+        println!("Default User Password = {}", password);
+        println!("Admin Role UUID = {}", role_uuid);
         println!("Done!");
 
         Ok(())
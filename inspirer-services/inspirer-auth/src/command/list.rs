@@ -5,7 +5,7 @@ use tabled::Table;
 
 use crate::{
     app::App,
-    entity::{apps, domains, users},
+    entity::{apps, domains, groups, identity_providers, permissions, roles, users},
 };
 
 #[derive(Parser)]
@@ -18,6 +18,10 @@ pub struct List {
 pub enum ListData {
     Application,
     Domain,
+    Group,
+    IdentityProvider,
+    Permission,
+    Role,
     User,
 }
 
@@ -33,6 +37,24 @@ impl AppCommand<App> for List {
                 let domains = domains::Entity::find().all(&context.database).await?;
                 Table::new(&domains).to_string()
             }
+            ListData::IdentityProvider => {
+                let providers = identity_providers::Entity::find()
+                    .all(&context.database)
+                    .await?;
+                Table::new(&providers).to_string()
+            }
+            ListData::Group => {
+                let groups = groups::Entity::find().all(&context.database).await?;
+                Table::new(&groups).to_string()
+            }
+            ListData::Permission => {
+                let permissions = permissions::Entity::find().all(&context.database).await?;
+                Table::new(&permissions).to_string()
+            }
+            ListData::Role => {
+                let roles = roles::Entity::find().all(&context.database).await?;
+                Table::new(&roles).to_string()
+            }
             ListData::User => {
                 let users = users::Entity::find().all(&context.database).await?;
                 Table::new(&users).to_string()
@@ -1,2 +1,11 @@
+pub mod api_token;
+pub mod audit;
+pub mod claims;
+pub mod idp;
 pub mod init;
 pub mod list;
+pub mod rbac;
+pub mod registration;
+pub mod user;
+pub mod user_transfer;
+pub mod webhook;
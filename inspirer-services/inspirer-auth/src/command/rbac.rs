@@ -0,0 +1,331 @@
+use clap::Parser;
+use inspirer_framework::preludes::*;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::{
+    app::App,
+    auth::audit::{AuditAction, AuditEvent, AuditOutcome},
+    service::{audit::Audit, rbac::Rbac, ServiceInterface},
+};
+
+async fn audit(
+    context: &AppContext<App>,
+    domain_uuid: Uuid,
+    subject_uuid: Option<Uuid>,
+    mut detail: Value,
+) -> Result<()> {
+    detail["source"] = json!("cli");
+
+    context
+        .service::<Audit>()
+        .record(AuditEvent {
+            domain_uuid: Some(domain_uuid),
+            subject_uuid,
+            detail,
+            ..AuditEvent::new(AuditAction::RbacChange, AuditOutcome::Success)
+        })
+        .await
+}
+
+/// Add a role to the domain
+#[derive(Debug, Parser)]
+pub struct AddRole {
+    /// Domain UUID
+    #[arg(long)]
+    domain: Uuid,
+
+    #[arg(long)]
+    name: String,
+
+    #[arg(long)]
+    display_name: Option<String>,
+}
+
+#[async_trait::async_trait]
+impl AppCommand<App> for AddRole {
+    async fn execute(&self, context: AppContext<App>) -> Result<()> {
+        let role = context
+            .service::<Rbac>()
+            .create_role(
+                self.domain,
+                self.name.clone(),
+                self.display_name.clone().unwrap_or(self.name.clone()),
+            )
+            .await?;
+
+        audit(
+            &context,
+            self.domain,
+            None,
+            json!({ "operation": "create_role", "role": role.uuid }),
+        )
+        .await?;
+
+        println!("Role UUID = {}", role.uuid);
+
+        Ok(())
+    }
+}
+
+/// Add a permission to the domain
+#[derive(Debug, Parser)]
+pub struct AddPermission {
+    /// Domain UUID
+    #[arg(long)]
+    domain: Uuid,
+
+    #[arg(long)]
+    name: String,
+
+    #[arg(long)]
+    description: Option<String>,
+}
+
+#[async_trait::async_trait]
+impl AppCommand<App> for AddPermission {
+    async fn execute(&self, context: AppContext<App>) -> Result<()> {
+        let permission = context
+            .service::<Rbac>()
+            .create_permission(
+                self.domain,
+                self.name.clone(),
+                self.description.clone().unwrap_or_default(),
+            )
+            .await?;
+
+        audit(
+            &context,
+            self.domain,
+            None,
+            json!({ "operation": "create_permission", "permission": permission.uuid }),
+        )
+        .await?;
+
+        println!("Permission UUID = {}", permission.uuid);
+
+        Ok(())
+    }
+}
+
+/// Add a group to the domain
+#[derive(Debug, Parser)]
+pub struct AddGroup {
+    /// Domain UUID
+    #[arg(long)]
+    domain: Uuid,
+
+    #[arg(long)]
+    name: String,
+
+    #[arg(long)]
+    display_name: Option<String>,
+}
+
+#[async_trait::async_trait]
+impl AppCommand<App> for AddGroup {
+    async fn execute(&self, context: AppContext<App>) -> Result<()> {
+        let group = context
+            .service::<Rbac>()
+            .create_group(
+                self.domain,
+                self.name.clone(),
+                self.display_name.clone().unwrap_or(self.name.clone()),
+            )
+            .await?;
+
+        audit(
+            &context,
+            self.domain,
+            None,
+            json!({ "operation": "create_group", "group": group.uuid }),
+        )
+        .await?;
+
+        println!("Group UUID = {}", group.uuid);
+
+        Ok(())
+    }
+}
+
+/// Grant a permission to the role
+#[derive(Debug, Parser)]
+pub struct GrantPermission {
+    /// Domain UUID
+    #[arg(long)]
+    domain: Uuid,
+
+    /// Role UUID
+    #[arg(long)]
+    role: Uuid,
+
+    /// Permission UUID
+    #[arg(long)]
+    permission: Uuid,
+
+    /// Revoke the permission instead
+    #[arg(long)]
+    revoke: bool,
+}
+
+#[async_trait::async_trait]
+impl AppCommand<App> for GrantPermission {
+    async fn execute(&self, context: AppContext<App>) -> Result<()> {
+        let service = context.service::<Rbac>();
+
+        if self.revoke {
+            service
+                .revoke_permission(self.domain, self.role, self.permission)
+                .await?;
+            println!("Permission revoked.");
+        } else {
+            service
+                .grant_permission(self.domain, self.role, self.permission)
+                .await?;
+            println!("Permission granted.");
+        }
+
+        let operation = if self.revoke {
+            "revoke_permission"
+        } else {
+            "grant_permission"
+        };
+        audit(
+            &context,
+            self.domain,
+            None,
+            json!({ "operation": operation, "role": self.role, "permission": self.permission }),
+        )
+        .await?;
+
+        Ok(())
+    }
+}
+
+/// Assign a role to the user or group
+#[derive(Debug, Parser)]
+#[command(group = clap::ArgGroup::new("target").required(true))]
+pub struct AssignRole {
+    /// Domain UUID
+    #[arg(long)]
+    domain: Uuid,
+
+    /// Role UUID
+    #[arg(long)]
+    role: Uuid,
+
+    /// User UUID
+    #[arg(long, group = "target")]
+    user: Option<Uuid>,
+
+    /// Group UUID
+    #[arg(long, group = "target")]
+    group: Option<Uuid>,
+
+    /// Unassign the role instead
+    #[arg(long)]
+    unassign: bool,
+}
+
+#[async_trait::async_trait]
+impl AppCommand<App> for AssignRole {
+    async fn execute(&self, context: AppContext<App>) -> Result<()> {
+        let service = context.service::<Rbac>();
+
+        match (self.user, self.group, self.unassign) {
+            (Some(user), _, false) => {
+                service
+                    .assign_user_role(self.domain, user, self.role)
+                    .await?
+            }
+            (Some(user), _, true) => {
+                service
+                    .unassign_user_role(self.domain, user, self.role)
+                    .await?
+            }
+            (None, Some(group), false) => {
+                service
+                    .assign_group_role(self.domain, group, self.role)
+                    .await?
+            }
+            (None, Some(group), true) => {
+                service
+                    .unassign_group_role(self.domain, group, self.role)
+                    .await?
+            }
+            (None, None, _) => return Err(Error::string("Missing user or group")),
+        }
+
+        let (operation, target) = match (self.user, self.unassign) {
+            (Some(_), false) => ("assign_user_role", json!(null)),
+            (Some(_), true) => ("unassign_user_role", json!(null)),
+            (None, false) => ("assign_group_role", json!(self.group)),
+            (None, true) => ("unassign_group_role", json!(self.group)),
+        };
+        audit(
+            &context,
+            self.domain,
+            self.user,
+            json!({ "operation": operation, "role": self.role, "group": target }),
+        )
+        .await?;
+
+        println!("Done!");
+
+        Ok(())
+    }
+}
+
+/// Add the user to the group
+#[derive(Debug, Parser)]
+pub struct JoinGroup {
+    /// Domain UUID
+    #[arg(long)]
+    domain: Uuid,
+
+    /// Group UUID
+    #[arg(long)]
+    group: Uuid,
+
+    /// User UUID
+    #[arg(long)]
+    user: Uuid,
+
+    /// Remove the user from the group instead
+    #[arg(long)]
+    leave: bool,
+}
+
+#[async_trait::async_trait]
+impl AppCommand<App> for JoinGroup {
+    async fn execute(&self, context: AppContext<App>) -> Result<()> {
+        let service = context.service::<Rbac>();
+
+        if self.leave {
+            service
+                .remove_group_member(self.domain, self.group, self.user)
+                .await?;
+        } else {
+            service
+                .add_group_member(self.domain, self.group, self.user)
+                .await?;
+        }
+
+        let operation = if self.leave {
+            "remove_group_member"
+        } else {
+            "add_group_member"
+        };
+        audit(
+            &context,
+            self.domain,
+            Some(self.user),
+            json!({ "operation": operation, "group": self.group }),
+        )
+        .await?;
+
+        println!("Done!");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,39 @@
+use clap::Parser;
+use inspirer_framework::preludes::*;
+use uuid::Uuid;
+
+use crate::{
+    app::App,
+    service::{registration::Registration, ServiceInterface},
+};
+
+/// Issue an initial access token for dynamic client registration
+#[derive(Debug, Parser)]
+pub struct IssueInitialToken {
+    /// Domain UUID the registered clients belong to
+    #[arg(long)]
+    domain: Uuid,
+
+    /// Description of the token
+    #[arg(long, default_value = "")]
+    description: String,
+
+    /// Lifetime in seconds, use the configured default if not set
+    #[arg(long)]
+    expires_in: Option<u64>,
+}
+
+#[async_trait::async_trait]
+impl AppCommand<App> for IssueInitialToken {
+    async fn execute(&self, context: AppContext<App>) -> Result<()> {
+        let (model, token) = context
+            .service::<Registration>()
+            .issue_initial_token(self.domain, self.description.clone(), self.expires_in)
+            .await?;
+
+        println!("Initial access token (shown only once): {token}");
+        println!("Expires at: {}", model.expires_at);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,105 @@
+use clap::Parser;
+use inspirer_framework::{command::ask_password, preludes::*};
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use uuid::Uuid;
+
+use crate::{
+    app::App,
+    auth::{account_status::AccountStatus, audit::ClientInfo},
+    entity::users,
+    service::{domain::Domain, user::User, ServiceInterface},
+};
+
+/// Find the user by UUID, or by username in the domain which defaults to the default domain
+async fn find_user(
+    context: &AppContext<App>,
+    user: &str,
+    domain: Option<Uuid>,
+) -> Result<users::Model> {
+    match Uuid::parse_str(user) {
+        Ok(uuid) => users::Entity::find().filter(users::Column::Uuid.eq(uuid)),
+        Err(_) => {
+            let domains = context.service::<Domain>();
+            let domain = match domain {
+                Some(uuid) => domains.find_domain_by_uuid(uuid).await?,
+                None => domains.default_domain().await?,
+            };
+
+            users::Entity::find()
+                .filter(users::Column::DomainUuid.eq(domain.uuid))
+                .filter(users::Column::Username.eq(user))
+        }
+    }
+    .one(&context.database)
+    .await?
+    .ok_or(Error::NotFound)
+}
+
+/// Change password of the user, the password is checked against the domain's password policy
+#[derive(Debug, Parser)]
+pub struct ChangePassword {
+    /// User UUID or username
+    #[arg(value_name = "USER")]
+    user: String,
+
+    /// Domain of the user looked up by username, defaults to the default domain
+    #[arg(long)]
+    domain: Option<Uuid>,
+}
+
+#[async_trait::async_trait]
+impl AppCommand<App> for ChangePassword {
+    async fn execute(&self, context: AppContext<App>) -> Result<()> {
+        let user = find_user(&context, &self.user, self.domain).await?;
+
+        let password = ask_password("New password")?;
+
+        context
+            .service::<User>()
+            .change_password(user, &password, None)
+            .await?;
+
+        println!("Password changed.");
+
+        Ok(())
+    }
+}
+
+/// Change status of the user, sessions of the user are revoked if the account is disabled or locked
+#[derive(Debug, Parser)]
+pub struct ChangeStatus {
+    /// User UUID or username
+    #[arg(value_name = "USER")]
+    user: String,
+
+    #[arg(long, value_enum)]
+    status: AccountStatus,
+
+    /// Reason of the change, recorded in the audit log
+    #[arg(long)]
+    reason: String,
+
+    /// Domain of the user looked up by username, defaults to the default domain
+    #[arg(long)]
+    domain: Option<Uuid>,
+}
+
+#[async_trait::async_trait]
+impl AppCommand<App> for ChangeStatus {
+    async fn execute(&self, context: AppContext<App>) -> Result<()> {
+        let user = find_user(&context, &self.user, self.domain).await?;
+        let previous = user.status;
+
+        let user = context
+            .service::<User>()
+            .change_status(user, self.status, &self.reason, None, ClientInfo::default())
+            .await?;
+
+        println!(
+            "Status of {} changed from {previous} to {}.",
+            user.uuid, user.status
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,394 @@
+use std::{
+    collections::{BTreeSet, HashSet},
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use clap::{Parser, ValueEnum};
+use indicatif::{ProgressBar, ProgressStyle};
+use inspirer_framework::preludes::*;
+use serde_json::{json, Map, Value};
+use uuid::Uuid;
+
+use crate::{
+    app::App,
+    auth::{
+        audit::{AuditAction, AuditEvent, AuditOutcome},
+        user_transfer::{export_record, exported_claims, ColumnMapping, ImportedUser, USER_FIELDS},
+    },
+    entity::domains,
+    service::{audit::Audit, domain::Domain, user::User, ServiceInterface},
+};
+
+const EXPORT_PAGE_SIZE: u64 = 500;
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TransferFormat {
+    Csv,
+    Jsonl,
+}
+
+impl TransferFormat {
+    /// Detect the format from the extension of the path
+    fn detect(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "csv" => Some(TransferFormat::Csv),
+            "jsonl" | "ndjson" => Some(TransferFormat::Jsonl),
+            _ => None,
+        }
+    }
+}
+
+async fn find_domain(context: &AppContext<App>, domain: Option<Uuid>) -> Result<domains::Model> {
+    let domains = context.service::<Domain>();
+    match domain {
+        Some(uuid) => domains.find_domain_by_uuid(uuid).await,
+        None => domains.default_domain().await,
+    }
+}
+
+fn progress_bar(len: u64, template: &str) -> Result<ProgressBar> {
+    let style = ProgressStyle::with_template(template).map_err(Error::wrap)?;
+
+    Ok(ProgressBar::new(len).with_style(style))
+}
+
+/// Record read from the file with its line number and the bytes read so far
+struct SourceRecord {
+    line: u64,
+    position: u64,
+    fields: std::result::Result<Map<String, Value>, String>,
+}
+
+fn read_records(
+    path: &Path,
+    format: TransferFormat,
+) -> Result<Box<dyn Iterator<Item = SourceRecord> + Send>> {
+    match format {
+        TransferFormat::Csv => {
+            let mut reader = csv::Reader::from_path(path).map_err(Error::wrap)?;
+            let headers = reader.headers().map_err(Error::wrap)?.clone();
+
+            Ok(Box::new(reader.into_records().map(move |record| {
+                match record {
+                    Ok(record) => SourceRecord {
+                        line: record.position().map_or(0, |position| position.line()),
+                        position: record.position().map_or(0, |position| position.byte()),
+                        fields: Ok(headers
+                            .iter()
+                            .zip(record.iter())
+                            .map(|(column, value)| (column.to_string(), value.into()))
+                            .collect()),
+                    },
+                    Err(err) => SourceRecord {
+                        line: err.position().map_or(0, |position| position.line()),
+                        position: err.position().map_or(0, |position| position.byte()),
+                        fields: Err(err.to_string()),
+                    },
+                }
+            })))
+        }
+        TransferFormat::Jsonl => {
+            let reader = BufReader::new(File::open(path)?);
+            let mut position = 0;
+
+            Ok(Box::new(reader.lines().enumerate().filter_map(
+                move |(index, line)| {
+                    let fields = line.map_err(|err| err.to_string()).and_then(|line| {
+                        position += line.len() as u64 + 1;
+                        if line.trim().is_empty() {
+                            return Ok(None);
+                        }
+
+                        match serde_json::from_str(&line) {
+                            Ok(Value::Object(fields)) => Ok(Some(fields)),
+                            Ok(_) => Err("record must be a JSON object".into()),
+                            Err(err) => Err(err.to_string()),
+                        }
+                    });
+
+                    let fields = fields.transpose()?;
+                    Some(SourceRecord {
+                        line: index as u64 + 1,
+                        position,
+                        fields,
+                    })
+                },
+            )))
+        }
+    }
+}
+
+/// Import users from a CSV or JSONL file, invalid records are skipped and reported
+///
+/// Columns are mapped to user fields (`uuid`, `username`, `email`, `phone_number`, `password`)
+/// or profile claims, columns with the same name as a field are mapped implicitly. Passwords
+/// must be argon2, scrypt or bcrypt hashes, they are rehashed on the user's next login.
+#[derive(Debug, Parser)]
+pub struct ImportUsers {
+    /// Path of the CSV or JSONL file
+    #[arg(value_name = "FILE")]
+    file: PathBuf,
+
+    /// Format of the file, detected from the extension if not set
+    #[arg(long, value_enum)]
+    format: Option<TransferFormat>,
+
+    /// Domain the users are imported into, defaults to the default domain
+    #[arg(long)]
+    domain: Option<Uuid>,
+
+    /// Map a column to a user field or profile claim, e.g. `--map mail=email`
+    #[arg(long = "map", value_name = "COLUMN=FIELD")]
+    mapping: Vec<String>,
+
+    /// Validate the records and print the report without importing
+    #[arg(long)]
+    dry_run: bool,
+}
+
+/// Report of the import
+#[derive(Debug, Default)]
+struct ImportReport {
+    total: u64,
+    imported: u64,
+    failures: Vec<(u64, Vec<String>)>,
+    ignored_columns: BTreeSet<String>,
+    /// Identifiers seen in the file, to find duplicates which are not in the database yet
+    seen: HashSet<String>,
+}
+
+impl ImportReport {
+    /// Check the identifiers of the user are not duplicated in the file
+    fn check_duplicates(&mut self, user: &ImportedUser) -> Vec<String> {
+        let phone_number = user.profile.phone_number_e164();
+        let identifiers = [
+            ("uuid", Some(user.uuid.to_string())),
+            ("username", user.username.clone()),
+            ("email", user.email.clone()),
+            ("phone_number", phone_number),
+        ];
+
+        let mut duplicates = vec![];
+        for (field, value) in identifiers {
+            let Some(value) = value else {
+                continue;
+            };
+            if !self.seen.insert(format!("{field}:{value}")) {
+                duplicates.push(format!("{field} {value} is duplicated in the file"));
+            }
+        }
+
+        duplicates
+    }
+
+    fn print(&self, dry_run: bool) {
+        println!("Records: {}", self.total);
+        if dry_run {
+            println!("Valid: {}", self.total - self.failures.len() as u64);
+        } else {
+            println!("Imported: {}", self.imported);
+        }
+        println!("Invalid: {}", self.failures.len());
+
+        if !self.ignored_columns.is_empty() {
+            let columns = self.ignored_columns.iter().cloned().collect::<Vec<_>>();
+            println!("Ignored columns: {}", columns.join(", "));
+        }
+
+        for (line, violations) in &self.failures {
+            println!("  line {line}: {}", violations.join("; "));
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl AppCommand<App> for ImportUsers {
+    async fn execute(&self, context: AppContext<App>) -> Result<()> {
+        let format = self
+            .format
+            .or_else(|| TransferFormat::detect(&self.file))
+            .ok_or(Error::string("Unknown format, use --format csv or jsonl"))?;
+        let mapping = ColumnMapping::parse(&self.mapping).map_err(|err| Error::string(&err))?;
+        let domain = find_domain(&context, self.domain).await?;
+        let service = context.service::<User>();
+
+        let progress = progress_bar(
+            self.file.metadata()?.len(),
+            "{bar:40} {bytes}/{total_bytes} {msg}",
+        )?;
+        let mut report = ImportReport::default();
+
+        for record in read_records(&self.file, format)? {
+            report.total += 1;
+            progress.set_position(record.position);
+
+            let fields = match record.fields {
+                Ok(fields) => fields,
+                Err(err) => {
+                    report.failures.push((record.line, vec![err]));
+                    continue;
+                }
+            };
+
+            report.ignored_columns.extend(
+                fields
+                    .keys()
+                    .filter(|column| mapping.field(column).is_none())
+                    .cloned(),
+            );
+
+            let user = match mapping.map(fields) {
+                Ok(user) => user,
+                Err(violations) => {
+                    report.failures.push((record.line, violations));
+                    continue;
+                }
+            };
+
+            let duplicates = report.check_duplicates(&user);
+            if !duplicates.is_empty() {
+                report.failures.push((record.line, duplicates));
+                continue;
+            }
+
+            let conflicts = service.import_user(domain.uuid, user, self.dry_run).await?;
+            if !conflicts.is_empty() {
+                report.failures.push((record.line, conflicts));
+                continue;
+            }
+
+            if !self.dry_run {
+                report.imported += 1;
+            }
+            progress.set_message(format!("{} invalid", report.failures.len()));
+        }
+
+        progress.finish_and_clear();
+
+        if !self.dry_run {
+            context
+                .service::<Audit>()
+                .record(AuditEvent {
+                    domain_uuid: Some(domain.uuid),
+                    detail: json!({
+                        "file": self.file,
+                        "records": report.total,
+                        "imported": report.imported,
+                        "invalid": report.failures.len(),
+                    }),
+                    ..AuditEvent::new(AuditAction::UserImport, AuditOutcome::Success)
+                })
+                .await?;
+        }
+
+        report.print(self.dry_run);
+
+        Ok(())
+    }
+}
+
+/// Export users of the domain as CSV or JSONL
+#[derive(Debug, Parser)]
+pub struct ExportUsers {
+    /// Path of the output file, users are written to stdout if not set
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+
+    /// Format of the output, detected from the extension of the output file, defaults to JSONL
+    #[arg(long, value_enum)]
+    format: Option<TransferFormat>,
+
+    /// Domain of the users, defaults to the default domain
+    #[arg(long)]
+    domain: Option<Uuid>,
+
+    /// Include password hashes
+    #[arg(long)]
+    with_password: bool,
+}
+
+enum ExportOutput {
+    Csv(Box<csv::Writer<Box<dyn Write + Send>>>),
+    Jsonl(Box<dyn Write + Send>),
+}
+
+/// Values of CSV cells, non-string values are JSON encoded
+fn csv_value(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(value)) => value.clone(),
+        Some(value) => value.to_string(),
+    }
+}
+
+#[async_trait::async_trait]
+impl AppCommand<App> for ExportUsers {
+    async fn execute(&self, context: AppContext<App>) -> Result<()> {
+        let format = self
+            .format
+            .or_else(|| self.output.as_deref().and_then(TransferFormat::detect))
+            .unwrap_or(TransferFormat::Jsonl);
+        let domain = find_domain(&context, self.domain).await?;
+        let service = context.service::<User>();
+
+        let writer: Box<dyn Write + Send> = match &self.output {
+            Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+            None => Box::new(io::stdout()),
+        };
+
+        let claims = exported_claims();
+        let columns = USER_FIELDS
+            .iter()
+            .filter(|field| self.with_password || **field != "password")
+            .chain(claims.iter())
+            .copied()
+            .collect::<Vec<_>>();
+        let mut output = match format {
+            TransferFormat::Csv => {
+                let mut writer = csv::Writer::from_writer(writer);
+                writer.write_record(&columns).map_err(Error::wrap)?;
+                ExportOutput::Csv(Box::new(writer))
+            }
+            TransferFormat::Jsonl => ExportOutput::Jsonl(writer),
+        };
+
+        let progress = progress_bar(
+            service.count_users(domain.uuid).await?,
+            "{bar:40} {pos}/{len} users",
+        )?;
+        let mut after = 0;
+
+        loop {
+            let users = service.users(domain.uuid, after, EXPORT_PAGE_SIZE).await?;
+            let Some(last) = users.last() else {
+                break;
+            };
+            after = last.id;
+
+            for user in &users {
+                let record = export_record(user, self.with_password);
+                match &mut output {
+                    ExportOutput::Csv(writer) => writer
+                        .write_record(columns.iter().map(|column| csv_value(record.get(*column))))
+                        .map_err(Error::wrap)?,
+                    ExportOutput::Jsonl(writer) => {
+                        serde_json::to_writer(&mut *writer, &record)?;
+                        writer.write_all(b"\n")?;
+                    }
+                }
+            }
+
+            progress.inc(users.len() as u64);
+        }
+
+        match &mut output {
+            ExportOutput::Csv(writer) => writer.flush()?,
+            ExportOutput::Jsonl(writer) => writer.flush()?,
+        }
+        progress.finish_and_clear();
+
+        Ok(())
+    }
+}
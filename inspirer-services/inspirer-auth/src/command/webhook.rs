@@ -0,0 +1,108 @@
+use chrono::Utc;
+use clap::Parser;
+use inspirer_framework::preludes::*;
+use sea_orm::{ActiveEnum, ActiveModelTrait, IntoActiveModel, Set};
+use tabled::Table;
+use url::Url;
+use uuid::Uuid;
+
+use crate::{
+    app::App,
+    auth::webhook::{UserEvent, WebhookSubscription},
+    service::{app::App as AppService, webhook::Webhook, ServiceInterface},
+};
+
+/// Subscribe user lifecycle events of the app
+#[derive(Debug, Parser)]
+pub struct SubscribeWebhook {
+    /// App UUID
+    #[arg(long)]
+    app: Uuid,
+
+    /// Url receives the events
+    #[arg(long)]
+    url: Url,
+
+    /// Comma-separated events, e.g. `user.created,user.deleted`, subscribe all events if not set
+    #[arg(long, value_delimiter = ',')]
+    events: Vec<String>,
+
+    /// Remove the subscription of the url instead
+    #[arg(long)]
+    remove: bool,
+}
+
+#[async_trait::async_trait]
+impl AppCommand<App> for SubscribeWebhook {
+    async fn execute(&self, context: AppContext<App>) -> Result<()> {
+        let events = self
+            .events
+            .iter()
+            .map(|event| {
+                UserEvent::try_from_value(event)
+                    .map_err(|_| Error::string(&format!("Unknown event {event}")))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let app = context
+            .service::<AppService>()
+            .find_app_by_uuid(self.app)
+            .await?;
+
+        let mut setting = app.setting.clone();
+        setting
+            .webhooks
+            .retain(|subscription| subscription.url != self.url);
+
+        if !self.remove {
+            setting.webhooks.push(WebhookSubscription {
+                url: self.url.clone(),
+                events,
+                enabled: true,
+            });
+        }
+
+        let mut app = app.into_active_model();
+        app.setting = Set(setting);
+        app.updated_at = Set(Utc::now());
+        app.update(&context.database).await?;
+
+        println!("Done!");
+
+        Ok(())
+    }
+}
+
+/// Replay failed webhook deliveries in the dead-letter table
+#[derive(Debug, Parser)]
+#[command(group = clap::ArgGroup::new("target").required(true))]
+pub struct ReplayWebhook {
+    /// Delivery UUID
+    #[arg(long, group = "target")]
+    delivery: Option<Uuid>,
+
+    /// Replay all dead letters
+    #[arg(long, group = "target")]
+    all: bool,
+
+    /// Only list the dead letters
+    #[arg(long, group = "target")]
+    list: bool,
+}
+
+#[async_trait::async_trait]
+impl AppCommand<App> for ReplayWebhook {
+    async fn execute(&self, context: AppContext<App>) -> Result<()> {
+        let service = context.service::<Webhook>();
+
+        if self.list {
+            println!("{}", Table::new(service.dead_letters(100).await?));
+            return Ok(());
+        }
+
+        let count = service.replay(self.delivery).await?;
+        println!("{count} deliveries queued for replay.");
+
+        Ok(())
+    }
+}
@@ -4,6 +4,8 @@ use axum_login::tower_sessions::Expiry;
 use serde::{Deserialize, Serialize};
 use url::Url;
 
+use crate::password::PasswordHashConfig;
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct AppConfig {
     /// This is a default app name use for auth service
@@ -12,11 +14,206 @@ pub struct AppConfig {
     /// The url is endpoint of the first app (service)
     pub app_endpoint: Url,
 
-    /// The path is the directory of the auth page
-    pub default_auth_page: PathBuf,
+    /// Directory of the built auth page overriding the page embedded in the binary
+    #[serde(default)]
+    pub default_auth_page: Option<PathBuf>,
 
     /// Auth session config
     pub session: SessionConfig,
+
+    /// Key signing the tokens of all domains
+    pub signing_key: SigningKeyConfig,
+
+    /// Argon2 parameters for new password hashes
+    #[serde(default)]
+    pub password_hash: PasswordHashConfig,
+
+    /// Audit log config
+    #[serde(default)]
+    pub audit: AuditConfig,
+
+    /// Webhook delivery config
+    #[serde(default)]
+    pub webhook: WebhookConfig,
+
+    /// Email and phone verification config
+    #[serde(default)]
+    pub verification: VerificationConfig,
+
+    /// Dynamic client registration config
+    #[serde(default)]
+    pub registration: RegistrationConfig,
+
+    /// DPoP proof config
+    #[serde(default)]
+    pub dpop: DPoPConfig,
+
+    /// Admin impersonation config
+    #[serde(default)]
+    pub impersonation: ImpersonationConfig,
+
+    /// Self-service account deletion config
+    #[serde(default)]
+    pub account_deletion: AccountDeletionConfig,
+
+    /// Personal access token and API key config
+    #[serde(default)]
+    pub api_token: ApiTokenConfig,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SigningKeyConfig {
+    /// PKCS#8 PEM file of the P-256 private key, e.g. generated by
+    /// `openssl genpkey -algorithm EC -pkeyopt ec_paramgen_curve:P-256`
+    pub private_key: PathBuf,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct AccountDeletionConfig {
+    /// Seconds between the request and the deletion, the user can cancel the request meanwhile
+    pub grace_period: u64,
+    /// Interval of polling due deletions in seconds
+    pub poll_interval: u64,
+}
+
+impl Default for AccountDeletionConfig {
+    fn default() -> Self {
+        AccountDeletionConfig {
+            grace_period: 30 * 24 * 3600,
+            poll_interval: 3600,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ApiTokenConfig {
+    /// Max lifetime of tokens in seconds, tokens without a lifetime never expire
+    pub max_lifetime: u64,
+    /// Scopes tokens may be issued with
+    pub scopes: Vec<String>,
+}
+
+impl Default for ApiTokenConfig {
+    fn default() -> Self {
+        ApiTokenConfig {
+            max_lifetime: 365 * 24 * 3600,
+            scopes: ["openid", "profile", "email", "phone", "address"]
+                .map(str::to_string)
+                .to_vec(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ImpersonationConfig {
+    /// Max lifetime of impersonation tokens in seconds
+    pub max_lifetime: u64,
+}
+
+impl Default for ImpersonationConfig {
+    fn default() -> Self {
+        ImpersonationConfig { max_lifetime: 900 }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct DPoPConfig {
+    /// Max seconds between the issued time of proofs and now
+    pub proof_max_age: u64,
+    /// Secret to derive nonces, clients must include the nonce in proofs if set
+    pub nonce_secret: Option<String>,
+    /// Lifetime of each nonce in seconds, the previous nonce is still accepted
+    pub nonce_lifetime: u64,
+}
+
+impl Default for DPoPConfig {
+    fn default() -> Self {
+        DPoPConfig {
+            proof_max_age: 300,
+            nonce_secret: None,
+            nonce_lifetime: 300,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct RegistrationConfig {
+    /// Default lifetime of initial access tokens in seconds
+    pub initial_token_expire_in: u64,
+}
+
+impl Default for RegistrationConfig {
+    fn default() -> Self {
+        RegistrationConfig {
+            initial_token_expire_in: 86400,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct VerificationConfig {
+    /// Lifetime of the verification code in seconds
+    pub expires_in: u64,
+    /// Max failed attempts of a verification code
+    pub max_attempts: u32,
+    /// Endpoint of the sender service (mail or sms gateway) delivers verification codes,
+    /// verification codes are not delivered if not set
+    pub sender: Option<Url>,
+}
+
+impl Default for VerificationConfig {
+    fn default() -> Self {
+        VerificationConfig {
+            expires_in: 900,
+            max_attempts: 5,
+            sender: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct WebhookConfig {
+    /// Deliveries still failed after the attempts are moved to the dead-letter table
+    pub max_attempts: u32,
+    /// Timeout of each delivery request in seconds
+    pub timeout: u64,
+    /// Interval of polling due deliveries in seconds
+    pub poll_interval: u64,
+    /// Max number of deliveries sent in one poll
+    pub batch_size: u64,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        WebhookConfig {
+            max_attempts: 8,
+            timeout: 10,
+            poll_interval: 5,
+            batch_size: 50,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct AuditConfig {
+    /// Days to keep audit events, older events are deleted by `audit:prune`
+    pub retention_days: u32,
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        AuditConfig {
+            retention_days: 180,
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -0,0 +1,93 @@
+use chrono::{DateTime, Utc};
+use inspirer_framework::{
+    authorization::Require,
+    extract::{Path, State},
+    preludes::*,
+    routing::get,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    app::App,
+    auth::{
+        account_status::{AccountStatus, ManageAccountStatus},
+        audit::ClientInfo,
+        rbac::Principal,
+    },
+    entity::users,
+    service::{user::User, ServiceInterface},
+};
+
+/// Status of the account, the history of changes is kept in the audit log
+#[derive(Debug, Serialize)]
+pub struct AccountStatusInfo {
+    pub status: AccountStatus,
+    pub reason: Option<String>,
+    pub changed_at: Option<DateTime<Utc>>,
+}
+
+impl From<users::Model> for AccountStatusInfo {
+    fn from(user: users::Model) -> Self {
+        AccountStatusInfo {
+            status: user.status,
+            reason: user.status_reason,
+            changed_at: user.status_changed_at,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChangeStatusRequest {
+    pub status: AccountStatus,
+    pub reason: String,
+}
+
+/// Users of other domains are invisible to the principal
+async fn find_user(
+    app: &AppContext<App>,
+    principal: &Principal,
+    user_uuid: Uuid,
+) -> Result<users::Model> {
+    let user = app.service::<User>().find_user_by_uuid(user_uuid).await?;
+    if user.domain_uuid != principal.domain_uuid {
+        return Err(Error::NotFound);
+    }
+
+    Ok(user)
+}
+
+pub async fn get_status(
+    Require(principal, _): Require<ManageAccountStatus, App>,
+    State(app): State<AppContext<App>>,
+    Path((user_uuid,)): Path<(Uuid,)>,
+) -> Resp<AccountStatusInfo> {
+    ok(find_user(&app, &principal, user_uuid).await?.into())
+}
+
+/// Disabling or locking the account revokes all sessions of the user
+pub async fn change_status(
+    Require(principal, _): Require<ManageAccountStatus, App>,
+    State(app): State<AppContext<App>>,
+    client_info: ClientInfo,
+    Path((user_uuid,)): Path<(Uuid,)>,
+    Json(req): Json<ChangeStatusRequest>,
+) -> Resp<AccountStatusInfo> {
+    let user = find_user(&app, &principal, user_uuid).await?;
+
+    ok(app
+        .service::<User>()
+        .change_status(
+            user,
+            req.status,
+            &req.reason,
+            Some(principal.user_uuid),
+            client_info,
+        )
+        .await?
+        .into())
+}
+
+pub fn routes() -> Router<App> {
+    Router::new().route("/users/:user/status", get(get_status).put(change_status))
+}
@@ -0,0 +1,205 @@
+use inspirer_framework::{
+    authorization::{Authenticated, Require},
+    extract::{Path, State},
+    preludes::*,
+    response::ErrorDetail,
+    routing::{delete, get},
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    app::App,
+    auth::{api_token::ApiTokenKind, audit::ClientInfo, rbac::Principal, registration::ManageApps},
+    entity::{api_tokens, apps},
+    service::{
+        api_token::{ApiToken, NewApiToken},
+        app::App as AppService,
+        ServiceInterface,
+    },
+};
+
+#[derive(Debug, Deserialize)]
+pub struct IssueApiTokenRequest {
+    name: String,
+    /// Space-delimited scopes
+    #[serde(default)]
+    scope: String,
+    /// Lifetime in seconds, the token never expires if not set
+    expires_in: Option<u64>,
+}
+
+impl From<IssueApiTokenRequest> for NewApiToken {
+    fn from(req: IssueApiTokenRequest) -> Self {
+        NewApiToken {
+            name: req.name,
+            scope: req.scope,
+            expires_in: req.expires_in,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct IssuedApiToken {
+    #[serde(flatten)]
+    model: api_tokens::Model,
+    /// The token is only shown once
+    token: String,
+}
+
+/// Personal access tokens of current user
+pub async fn list_personal_tokens(
+    Authenticated(principal): Authenticated<App>,
+    State(app): State<AppContext<App>>,
+) -> Resp<Vec<api_tokens::Model>> {
+    ok(app
+        .service::<ApiToken>()
+        .tokens(ApiTokenKind::PersonalAccessToken, principal.user_uuid)
+        .await?)
+}
+
+pub async fn issue_personal_token(
+    Authenticated(principal): Authenticated<App>,
+    State(app): State<AppContext<App>>,
+    client_info: ClientInfo,
+    Json(req): Json<IssueApiTokenRequest>,
+) -> Resp<IssuedApiToken> {
+    // 模拟登录或代理得到的 token 不能签发长期有效的个人访问令牌
+    if principal.actor_uuid.is_some() {
+        return Err(Error::CustomError(
+            StatusCode::FORBIDDEN,
+            ErrorDetail::new(
+                "forbidden".to_string(),
+                "Delegated tokens cannot issue personal access tokens".to_string(),
+            ),
+        ));
+    }
+
+    let (model, token) = app
+        .service::<ApiToken>()
+        .issue(
+            ApiTokenKind::PersonalAccessToken,
+            principal.domain_uuid,
+            principal.user_uuid,
+            req.into(),
+            Some(principal.user_uuid),
+            client_info,
+        )
+        .await?;
+
+    ok(IssuedApiToken { model, token })
+}
+
+pub async fn revoke_personal_token(
+    Authenticated(principal): Authenticated<App>,
+    State(app): State<AppContext<App>>,
+    client_info: ClientInfo,
+    Path((token_uuid,)): Path<(Uuid,)>,
+) -> Resp<()> {
+    app.service::<ApiToken>()
+        .revoke(
+            ApiTokenKind::PersonalAccessToken,
+            principal.user_uuid,
+            token_uuid,
+            Some(principal.user_uuid),
+            client_info,
+        )
+        .await?;
+
+    ok(())
+}
+
+/// Apps of other domains are invisible to the principal
+async fn find_app(
+    app: &AppContext<App>,
+    principal: &Principal,
+    app_uuid: Uuid,
+) -> Result<apps::Model> {
+    let client = app
+        .service::<AppService>()
+        .find_app_by_uuid(app_uuid)
+        .await?;
+    if client.domain_uuid != principal.domain_uuid {
+        return Err(Error::NotFound);
+    }
+
+    Ok(client)
+}
+
+pub async fn list_api_keys(
+    Require(principal, _): Require<ManageApps, App>,
+    State(app): State<AppContext<App>>,
+    Path((app_uuid,)): Path<(Uuid,)>,
+) -> Resp<Vec<api_tokens::Model>> {
+    let client = find_app(&app, &principal, app_uuid).await?;
+
+    ok(app
+        .service::<ApiToken>()
+        .tokens(ApiTokenKind::ApiKey, client.uuid)
+        .await?)
+}
+
+pub async fn issue_api_key(
+    Require(principal, _): Require<ManageApps, App>,
+    State(app): State<AppContext<App>>,
+    client_info: ClientInfo,
+    Path((app_uuid,)): Path<(Uuid,)>,
+    Json(req): Json<IssueApiTokenRequest>,
+) -> Resp<IssuedApiToken> {
+    let client = find_app(&app, &principal, app_uuid).await?;
+
+    let (model, token) = app
+        .service::<ApiToken>()
+        .issue(
+            ApiTokenKind::ApiKey,
+            client.domain_uuid,
+            client.uuid,
+            req.into(),
+            Some(principal.user_uuid),
+            client_info,
+        )
+        .await?;
+
+    ok(IssuedApiToken { model, token })
+}
+
+pub async fn revoke_api_key(
+    Require(principal, _): Require<ManageApps, App>,
+    State(app): State<AppContext<App>>,
+    client_info: ClientInfo,
+    Path((app_uuid, key_uuid)): Path<(Uuid, Uuid)>,
+) -> Resp<()> {
+    let client = find_app(&app, &principal, app_uuid).await?;
+
+    app.service::<ApiToken>()
+        .revoke(
+            ApiTokenKind::ApiKey,
+            client.uuid,
+            key_uuid,
+            Some(principal.user_uuid),
+            client_info,
+        )
+        .await?;
+
+    ok(())
+}
+
+/// Routes of personal access tokens, nested in `/api/me`
+pub fn personal_routes() -> Router<App> {
+    Router::new()
+        .route(
+            "/tokens",
+            get(list_personal_tokens).post(issue_personal_token),
+        )
+        .route("/tokens/:token", delete(revoke_personal_token))
+}
+
+/// Routes of API keys, nested in `/api/admin`
+pub fn routes() -> Router<App> {
+    Router::new()
+        .route(
+            "/apps/:app/api-keys",
+            get(list_api_keys).post(issue_api_key),
+        )
+        .route("/apps/:app/api-keys/:key", delete(revoke_api_key))
+}
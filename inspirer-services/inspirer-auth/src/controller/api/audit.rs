@@ -0,0 +1,52 @@
+use inspirer_framework::{
+    authorization::Require,
+    extract::{Query, State},
+    preludes::*,
+    routing::get,
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    app::App,
+    auth::audit::{AuditAction, ReadAudit},
+    entity::audit_events,
+    service::{
+        audit::{Audit, AuditFilter},
+        ServiceInterface,
+    },
+};
+
+#[derive(Debug, Deserialize)]
+pub struct AuditEventsQuery {
+    action: Option<AuditAction>,
+    actor: Option<Uuid>,
+    subject: Option<Uuid>,
+    /// Cursor of pagination, the smallest `id` of previous page
+    before: Option<u64>,
+    limit: Option<u64>,
+}
+
+/// Query audit events of the principal's domain, newest first
+pub async fn list_events(
+    Require(principal, _): Require<ReadAudit, App>,
+    State(app): State<AppContext<App>>,
+    Query(query): Query<AuditEventsQuery>,
+) -> Resp<Vec<audit_events::Model>> {
+    ok(app
+        .service::<Audit>()
+        .events(AuditFilter {
+            domain_uuid: Some(principal.domain_uuid),
+            action: query.action,
+            actor_uuid: query.actor,
+            subject_uuid: query.subject,
+            before: query.before,
+            after: None,
+            limit: query.limit.unwrap_or(50).min(500),
+        })
+        .await?)
+}
+
+pub fn routes() -> Router<App> {
+    Router::new().route("/audit-events", get(list_events))
+}
@@ -0,0 +1,102 @@
+use inspirer_framework::{
+    authorization::{Authenticated, Require},
+    extract::{Path, Query, State},
+    preludes::*,
+    routing::{get, post},
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    app::App,
+    auth::{audit::ClientInfo, impersonation::Impersonate},
+    entity::audit_events,
+    service::{
+        impersonation::{Impersonation, ImpersonationRequest},
+        ServiceInterface,
+    },
+};
+
+#[derive(Debug, Deserialize)]
+pub struct ImpersonateRequest {
+    /// The app the token is issued to
+    app: Uuid,
+    /// Why the user is impersonated, shown to the user
+    reason: String,
+    /// Space-delimited scopes
+    scope: Option<String>,
+    /// Lifetime in seconds, limited by the configured max lifetime
+    expires_in: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImpersonateResponse {
+    access_token: String,
+    token_type: &'static str,
+    expires_in: u64,
+    scope: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImpersonationEventsQuery {
+    /// Cursor of pagination, the smallest `id` of previous page
+    before: Option<u64>,
+    limit: Option<u64>,
+}
+
+/// Issue an access token of the user, the token identifies the admin in the `act` claim
+pub async fn impersonate(
+    Require(principal, _): Require<Impersonate, App>,
+    State(app): State<AppContext<App>>,
+    client_info: ClientInfo,
+    Path((user_uuid,)): Path<(Uuid,)>,
+    Json(req): Json<ImpersonateRequest>,
+) -> Resp<ImpersonateResponse> {
+    let token = app
+        .service::<Impersonation>()
+        .impersonate(
+            &principal,
+            ImpersonationRequest {
+                user_uuid,
+                app_uuid: req.app,
+                reason: req.reason,
+                scope: req.scope,
+                expires_in: req.expires_in,
+            },
+            client_info,
+        )
+        .await?;
+
+    ok(ImpersonateResponse {
+        access_token: token.access_token,
+        token_type: token.token_type,
+        expires_in: token.expires_in,
+        scope: token.scope,
+    })
+}
+
+/// Impersonation events on the account of current user, newest first
+pub async fn my_impersonations(
+    Authenticated(principal): Authenticated<App>,
+    State(app): State<AppContext<App>>,
+    Query(query): Query<ImpersonationEventsQuery>,
+) -> Resp<Vec<audit_events::Model>> {
+    ok(app
+        .service::<Impersonation>()
+        .events(
+            principal.user_uuid,
+            query.before,
+            query.limit.unwrap_or(50).min(500),
+        )
+        .await?)
+}
+
+/// Routes of the account's impersonation events, nested in `/api/me`
+pub fn personal_routes() -> Router<App> {
+    Router::new().route("/impersonations", get(my_impersonations))
+}
+
+/// Routes of impersonation, nested in `/api/admin`
+pub fn routes() -> Router<App> {
+    Router::new().route("/users/:user/impersonate", post(impersonate))
+}
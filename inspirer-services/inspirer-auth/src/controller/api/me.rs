@@ -0,0 +1,288 @@
+use inspirer_framework::{
+    authorization::Authenticated,
+    extract::{Path, State},
+    http::header::CONTENT_DISPOSITION,
+    preludes::*,
+    response::ErrorDetail,
+    routing::{delete, get, post},
+};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+use crate::{
+    app::App,
+    auth::{
+        audit::ClientInfo, session::SessionInfo, user::UserProfile,
+        verification::VerificationChannel,
+    },
+    entity::{account_deletions, consents},
+    service::{
+        consent::Consent, privacy::Privacy, session::Session, user::User,
+        verification::Verification, ServiceInterface,
+    },
+};
+
+#[derive(Debug, Default, Deserialize)]
+pub struct DeletionRequest {
+    /// Why the account is deleted, optional
+    reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VerificationRequest {
+    channel: VerificationChannel,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ConfirmVerificationRequest {
+    channel: VerificationChannel,
+    code: String,
+}
+
+/// Profile of current user
+pub async fn profile(
+    Authenticated(principal): Authenticated<App>,
+    State(app): State<AppContext<App>>,
+) -> Resp<UserProfile> {
+    let user = app
+        .service::<User>()
+        .find_user_by_uuid(principal.user_uuid)
+        .await?;
+
+    ok(user.profile)
+}
+
+/// Update profile of current user, the body is a partial profile, claims set to `null` are removed
+pub async fn update_profile(
+    Authenticated(principal): Authenticated<App>,
+    State(app): State<AppContext<App>>,
+    client_info: ClientInfo,
+    Json(changes): Json<Map<String, Value>>,
+) -> Resp<UserProfile> {
+    forbid_delegated(principal.actor_uuid)?;
+
+    let service = app.service::<User>();
+    let user = service.find_user_by_uuid(principal.user_uuid).await?;
+    let user = service.update_profile(user, changes, client_info).await?;
+
+    ok(user.profile)
+}
+
+/// Resend the verification code of email or phone number
+pub async fn send_verification(
+    Authenticated(principal): Authenticated<App>,
+    State(app): State<AppContext<App>>,
+    Json(req): Json<VerificationRequest>,
+) -> Resp<()> {
+    let user = app
+        .service::<User>()
+        .find_user_by_uuid(principal.user_uuid)
+        .await?;
+
+    app.service::<Verification>()
+        .start(&user, req.channel)
+        .await?;
+
+    ok(())
+}
+
+pub async fn confirm_verification(
+    Authenticated(principal): Authenticated<App>,
+    State(app): State<AppContext<App>>,
+    Json(req): Json<ConfirmVerificationRequest>,
+) -> Resp<UserProfile> {
+    forbid_delegated(principal.actor_uuid)?;
+
+    let user = app
+        .service::<User>()
+        .find_user_by_uuid(principal.user_uuid)
+        .await?;
+    let user = app
+        .service::<Verification>()
+        .confirm(user, req.channel, &req.code)
+        .await?;
+
+    ok(user.profile)
+}
+
+/// Active sessions of current user with the apps granted in them
+pub async fn sessions(
+    Authenticated(principal): Authenticated<App>,
+    State(app): State<AppContext<App>>,
+) -> Resp<Vec<SessionInfo>> {
+    ok(app
+        .service::<Session>()
+        .sessions(principal.user_uuid, principal.session_uuid)
+        .await?)
+}
+
+/// Sign out of all sessions, returns the number of revoked sessions
+pub async fn revoke_sessions(
+    Authenticated(principal): Authenticated<App>,
+    State(app): State<AppContext<App>>,
+    client_info: ClientInfo,
+) -> Resp<u64> {
+    forbid_delegated(principal.actor_uuid)?;
+
+    ok(app
+        .service::<Session>()
+        .revoke(
+            principal.user_uuid,
+            None,
+            Some(principal.user_uuid),
+            client_info,
+        )
+        .await?)
+}
+
+pub async fn revoke_session(
+    Authenticated(principal): Authenticated<App>,
+    State(app): State<AppContext<App>>,
+    Path((session_uuid,)): Path<(Uuid,)>,
+    client_info: ClientInfo,
+) -> Resp<()> {
+    forbid_delegated(principal.actor_uuid)?;
+
+    app.service::<Session>()
+        .revoke(
+            principal.user_uuid,
+            Some(session_uuid),
+            Some(principal.user_uuid),
+            client_info,
+        )
+        .await?;
+
+    ok(())
+}
+
+/// Revoke access of an app, the session is kept
+pub async fn revoke_grant(
+    Authenticated(principal): Authenticated<App>,
+    State(app): State<AppContext<App>>,
+    Path((grant_uuid,)): Path<(Uuid,)>,
+) -> Resp<()> {
+    forbid_delegated(principal.actor_uuid)?;
+
+    app.service::<Session>()
+        .revoke_grant(principal.user_uuid, grant_uuid)
+        .await?;
+
+    ok(())
+}
+
+/// Apps the current user has consented to, with the approved scopes
+pub async fn consents(
+    Authenticated(principal): Authenticated<App>,
+    State(app): State<AppContext<App>>,
+) -> Resp<Vec<consents::Model>> {
+    ok(app
+        .service::<Consent>()
+        .consents(principal.user_uuid)
+        .await?)
+}
+
+/// Withdraw the consent of an app, the app will ask for consent again on next authorization
+pub async fn withdraw_consent(
+    Authenticated(principal): Authenticated<App>,
+    State(app): State<AppContext<App>>,
+    Path((app_uuid,)): Path<(Uuid,)>,
+) -> Resp<()> {
+    forbid_delegated(principal.actor_uuid)?;
+
+    app.service::<Consent>()
+        .withdraw(principal.user_uuid, app_uuid)
+        .await?;
+
+    ok(())
+}
+
+/// 模拟登录或代理得到的 token 只能读取账号，不能修改档案、撤销会话和授权、导出数据或删除账号，
+/// 否则审计日志会把操作记在用户名下
+fn forbid_delegated(actor_uuid: Option<Uuid>) -> Result<()> {
+    match actor_uuid {
+        Some(_) => Err(Error::CustomError(
+            StatusCode::FORBIDDEN,
+            ErrorDetail::new(
+                "forbidden".to_string(),
+                "Delegated tokens cannot change the account".to_string(),
+            ),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Download all data held about current user as a JSON archive
+pub async fn export(
+    Authenticated(principal): Authenticated<App>,
+    State(app): State<AppContext<App>>,
+) -> Result<impl IntoResponse> {
+    forbid_delegated(principal.actor_uuid)?;
+
+    let data = app.service::<Privacy>().export(principal.user_uuid).await?;
+    let disposition = format!(
+        "attachment; filename=\"personal-data-{}.json\"",
+        principal.user_uuid
+    );
+
+    Ok(([(CONTENT_DISPOSITION, disposition)], Json(data)))
+}
+
+/// The pending deletion request of current user, `null` if the deletion is not requested
+pub async fn deletion(
+    Authenticated(principal): Authenticated<App>,
+    State(app): State<AppContext<App>>,
+) -> Resp<Option<account_deletions::Model>> {
+    ok(app
+        .service::<Privacy>()
+        .pending_deletion(principal.user_uuid)
+        .await?)
+}
+
+/// Request deletion of current user's account, the account is deleted after the grace period
+pub async fn request_deletion(
+    Authenticated(principal): Authenticated<App>,
+    State(app): State<AppContext<App>>,
+    client_info: ClientInfo,
+    req: Option<Json<DeletionRequest>>,
+) -> Resp<account_deletions::Model> {
+    forbid_delegated(principal.actor_uuid)?;
+
+    let Json(req) = req.unwrap_or_default();
+    ok(app
+        .service::<Privacy>()
+        .request_deletion(principal.user_uuid, req.reason, client_info)
+        .await?)
+}
+
+/// Cancel the pending deletion request during the grace period
+pub async fn cancel_deletion(
+    Authenticated(principal): Authenticated<App>,
+    State(app): State<AppContext<App>>,
+    client_info: ClientInfo,
+) -> Resp<()> {
+    forbid_delegated(principal.actor_uuid)?;
+
+    app.service::<Privacy>()
+        .cancel_deletion(principal.user_uuid, client_info)
+        .await?;
+
+    ok(())
+}
+
+pub fn routes() -> Router<App> {
+    Router::new()
+        .route("/", get(profile).patch(update_profile))
+        .route("/verification", post(send_verification))
+        .route("/verification/confirm", post(confirm_verification))
+        .route("/sessions", get(sessions).delete(revoke_sessions))
+        .route("/sessions/:session", delete(revoke_session))
+        .route("/grants/:grant", delete(revoke_grant))
+        .route("/consents", get(consents))
+        .route("/consents/:app", delete(withdraw_consent))
+        .route("/export", get(export))
+        .route(
+            "/deletion",
+            get(deletion).post(request_deletion).delete(cancel_deletion),
+        )
+}
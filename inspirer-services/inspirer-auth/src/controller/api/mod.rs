@@ -1,39 +1,34 @@
-use std::time::Duration;
-
 use axum_extra::TypedHeader;
-use chrono::Utc;
-use inspirer_framework::{extract::State, preludes::*, response::ErrorDetail, routing::post};
-use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use inspirer_framework::{extract::State, preludes::*, routing::post};
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
 use crate::{
-    app::App, entity::users, header::AppId, password::password_verify, token::AccessToken,
+    app::App,
+    auth::{audit::ClientInfo, user::UserCredential},
+    header::AppId,
+    service::{
+        app::App as AppService,
+        authentication::{Authentication, PasswordAuthenticator},
+        session::Session,
+        token::Token,
+        ServiceInterface,
+    },
 };
 
+pub mod account_status;
+pub mod api_token;
+pub mod audit;
+pub mod impersonation;
+pub mod me;
+pub mod rbac;
+pub mod registration;
+pub mod session;
+
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct LoginRequest {
     /// 登录凭据
-    credential: LoginCredential,
-}
-
-#[derive(Debug, Deserialize, ToSchema)]
-#[serde(tag = "type", content = "payload", rename_all = "snake_case")]
-pub enum LoginCredential {
-    /// 使用用户名作为登录凭据
-    Username {
-        /// 用户名
-        username: String,
-        /// 密码
-        password: String,
-    },
-    /// 使用邮箱作为登录凭据
-    Email {
-        /// 邮箱
-        email: String,
-        /// 密码
-        password: String,
-    },
+    credential: UserCredential,
 }
 
 #[derive(Debug, Serialize, ToSchema)]
@@ -42,6 +37,12 @@ pub struct LoginResponse {
     token_type: &'static str,
     /// Access Token
     access_token: String,
+    /// ID Token
+    id_token: String,
+    /// Access Token 有效期（秒）
+    expires_in: u64,
+    /// Refresh Token，可在 `/oidc/token` 换取新的 Token
+    refresh_token: String,
 }
 
 /// 登录接口
@@ -59,52 +60,61 @@ pub struct LoginResponse {
 pub async fn login(
     TypedHeader(app_id): TypedHeader<AppId>,
     State(app): State<AppContext<App>>,
+    client_info: ClientInfo,
     Json(req): Json<LoginRequest>,
 ) -> Resp<LoginResponse> {
     tracing::debug!("Received login request, appId = {}", app_id.0);
-    let mut finder = users::Entity::find();
-
-    let password = match req.credential {
-        LoginCredential::Username { username, password } => {
-            tracing::debug!(
-                username = username,
-                password = password,
-                "login use username credential"
-            );
-            finder = finder.filter(users::Column::Username.eq(username));
-            password
-        }
-        LoginCredential::Email { email, password } => {
-            finder = finder.filter(users::Column::Email.eq(email));
-            password
-        }
-    };
+    let client = app
+        .service::<AppService>()
+        .find_app_by_uuid(app_id.0)
+        .await?;
 
-    let user = finder.one(&app.database).await?.ok_or(Error::CustomError(
-        StatusCode::NOT_FOUND,
-        ErrorDetail::with_reason("User not exists"),
-    ))?;
+    let principal = app
+        .service::<Authentication>()
+        .authenticate(&client, PasswordAuthenticator(req.credential), &client_info)
+        .await?;
 
-    if password_verify(password, user.password).is_err() {
-        return Err(Error::Unauthorized(
-            "User not exists or password error".into(),
-        ));
-    }
+    let grant = app
+        .service::<Session>()
+        .grant(
+            &principal.session,
+            client.uuid,
+            "openid profile email phone".into(),
+            None,
+        )
+        .await?;
 
-    let claims = AccessToken {
-        aud: app_id.0,
-        sub: user.uuid,
-        scope: "openid profile email phone".into(),
-        iat: Utc::now().timestamp() as usize,
-        exp: (Utc::now() + Duration::from_secs(3600)).timestamp() as usize,
-    };
+    let issued = app
+        .service::<Token>()
+        .issue(&client, &principal.user, &grant, None, None)
+        .await?;
 
     ok(LoginResponse {
         token_type: "Bearer",
-        access_token: claims.token(),
+        access_token: issued.access_token,
+        id_token: issued.id_token,
+        expires_in: issued.expires_in,
+        refresh_token: issued.refresh_token,
     })
 }
 
 pub fn routes() -> Router<App> {
-    Router::new().route("/api/login", post(login))
+    Router::new()
+        .route("/api/login", post(login))
+        .nest(
+            "/api/admin",
+            rbac::routes()
+                .merge(audit::routes())
+                .merge(session::routes())
+                .merge(registration::routes())
+                .merge(api_token::routes())
+                .merge(impersonation::routes())
+                .merge(account_status::routes()),
+        )
+        .nest(
+            "/api/me",
+            me::routes()
+                .merge(api_token::personal_routes())
+                .merge(impersonation::personal_routes()),
+        )
 }
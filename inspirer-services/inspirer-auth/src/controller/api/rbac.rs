@@ -0,0 +1,447 @@
+use inspirer_framework::{
+    authorization::Require,
+    extract::{Path, State},
+    preludes::*,
+    routing::{delete, get, put},
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::{
+    app::App,
+    auth::{
+        audit::{AuditAction, AuditEvent, AuditOutcome, ClientInfo},
+        rbac::{ManageRbac, Principal},
+    },
+    entity::{groups, permissions, roles},
+    service::{audit::Audit, rbac::Rbac, ServiceInterface},
+};
+
+type RequireManageRbac = Require<ManageRbac, App>;
+
+async fn audit(
+    app: &AppContext<App>,
+    principal: &Principal,
+    client: ClientInfo,
+    subject_uuid: Option<Uuid>,
+    detail: Value,
+) -> Result<()> {
+    app.service::<Audit>()
+        .record(AuditEvent {
+            domain_uuid: Some(principal.domain_uuid),
+            actor_uuid: Some(principal.user_uuid),
+            subject_uuid,
+            client,
+            detail,
+            ..AuditEvent::new(AuditAction::RbacChange, AuditOutcome::Success)
+        })
+        .await
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateRoleRequest {
+    name: String,
+    display_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreatePermissionRequest {
+    name: String,
+    description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateGroupRequest {
+    name: String,
+    display_name: Option<String>,
+}
+
+pub async fn list_roles(
+    Require(principal, _): RequireManageRbac,
+    State(app): State<AppContext<App>>,
+) -> Resp<Vec<roles::Model>> {
+    ok(app.service::<Rbac>().roles(principal.domain_uuid).await?)
+}
+
+pub async fn create_role(
+    Require(principal, _): RequireManageRbac,
+    State(app): State<AppContext<App>>,
+    client_info: ClientInfo,
+    Json(req): Json<CreateRoleRequest>,
+) -> Resp<roles::Model> {
+    let display_name = req.display_name.unwrap_or(req.name.clone());
+
+    let role = app
+        .service::<Rbac>()
+        .create_role(principal.domain_uuid, req.name, display_name)
+        .await?;
+
+    audit(
+        &app,
+        &principal,
+        client_info,
+        None,
+        json!({ "operation": "create_role", "role": role.uuid }),
+    )
+    .await?;
+
+    ok(role)
+}
+
+pub async fn delete_role(
+    Require(principal, _): RequireManageRbac,
+    State(app): State<AppContext<App>>,
+    client_info: ClientInfo,
+    Path((role,)): Path<(Uuid,)>,
+) -> Resp<()> {
+    app.service::<Rbac>()
+        .delete_role(principal.domain_uuid, role)
+        .await?;
+
+    audit(
+        &app,
+        &principal,
+        client_info,
+        None,
+        json!({ "operation": "delete_role", "role": role }),
+    )
+    .await?;
+
+    ok(())
+}
+
+pub async fn list_permissions(
+    Require(principal, _): RequireManageRbac,
+    State(app): State<AppContext<App>>,
+) -> Resp<Vec<permissions::Model>> {
+    ok(app
+        .service::<Rbac>()
+        .permissions(principal.domain_uuid)
+        .await?)
+}
+
+pub async fn create_permission(
+    Require(principal, _): RequireManageRbac,
+    State(app): State<AppContext<App>>,
+    client_info: ClientInfo,
+    Json(req): Json<CreatePermissionRequest>,
+) -> Resp<permissions::Model> {
+    let permission = app
+        .service::<Rbac>()
+        .create_permission(
+            principal.domain_uuid,
+            req.name,
+            req.description.unwrap_or_default(),
+        )
+        .await?;
+
+    audit(
+        &app,
+        &principal,
+        client_info,
+        None,
+        json!({ "operation": "create_permission", "permission": permission.uuid }),
+    )
+    .await?;
+
+    ok(permission)
+}
+
+pub async fn delete_permission(
+    Require(principal, _): RequireManageRbac,
+    State(app): State<AppContext<App>>,
+    client_info: ClientInfo,
+    Path((permission,)): Path<(Uuid,)>,
+) -> Resp<()> {
+    app.service::<Rbac>()
+        .delete_permission(principal.domain_uuid, permission)
+        .await?;
+
+    audit(
+        &app,
+        &principal,
+        client_info,
+        None,
+        json!({ "operation": "delete_permission", "permission": permission }),
+    )
+    .await?;
+
+    ok(())
+}
+
+pub async fn list_groups(
+    Require(principal, _): RequireManageRbac,
+    State(app): State<AppContext<App>>,
+) -> Resp<Vec<groups::Model>> {
+    ok(app.service::<Rbac>().groups(principal.domain_uuid).await?)
+}
+
+pub async fn create_group(
+    Require(principal, _): RequireManageRbac,
+    State(app): State<AppContext<App>>,
+    client_info: ClientInfo,
+    Json(req): Json<CreateGroupRequest>,
+) -> Resp<groups::Model> {
+    let display_name = req.display_name.unwrap_or(req.name.clone());
+
+    let group = app
+        .service::<Rbac>()
+        .create_group(principal.domain_uuid, req.name, display_name)
+        .await?;
+
+    audit(
+        &app,
+        &principal,
+        client_info,
+        None,
+        json!({ "operation": "create_group", "group": group.uuid }),
+    )
+    .await?;
+
+    ok(group)
+}
+
+pub async fn delete_group(
+    Require(principal, _): RequireManageRbac,
+    State(app): State<AppContext<App>>,
+    client_info: ClientInfo,
+    Path((group,)): Path<(Uuid,)>,
+) -> Resp<()> {
+    app.service::<Rbac>()
+        .delete_group(principal.domain_uuid, group)
+        .await?;
+
+    audit(
+        &app,
+        &principal,
+        client_info,
+        None,
+        json!({ "operation": "delete_group", "group": group }),
+    )
+    .await?;
+
+    ok(())
+}
+
+pub async fn grant_permission(
+    Require(principal, _): RequireManageRbac,
+    State(app): State<AppContext<App>>,
+    client_info: ClientInfo,
+    Path((role, permission)): Path<(Uuid, Uuid)>,
+) -> Resp<()> {
+    app.service::<Rbac>()
+        .grant_permission(principal.domain_uuid, role, permission)
+        .await?;
+
+    audit(
+        &app,
+        &principal,
+        client_info,
+        None,
+        json!({ "operation": "grant_permission", "role": role, "permission": permission }),
+    )
+    .await?;
+
+    ok(())
+}
+
+pub async fn revoke_permission(
+    Require(principal, _): RequireManageRbac,
+    State(app): State<AppContext<App>>,
+    client_info: ClientInfo,
+    Path((role, permission)): Path<(Uuid, Uuid)>,
+) -> Resp<()> {
+    app.service::<Rbac>()
+        .revoke_permission(principal.domain_uuid, role, permission)
+        .await?;
+
+    audit(
+        &app,
+        &principal,
+        client_info,
+        None,
+        json!({ "operation": "revoke_permission", "role": role, "permission": permission }),
+    )
+    .await?;
+
+    ok(())
+}
+
+pub async fn add_group_member(
+    Require(principal, _): RequireManageRbac,
+    State(app): State<AppContext<App>>,
+    client_info: ClientInfo,
+    Path((group, user)): Path<(Uuid, Uuid)>,
+) -> Resp<()> {
+    app.service::<Rbac>()
+        .add_group_member(principal.domain_uuid, group, user)
+        .await?;
+
+    audit(
+        &app,
+        &principal,
+        client_info,
+        Some(user),
+        json!({ "operation": "add_group_member", "group": group }),
+    )
+    .await?;
+
+    ok(())
+}
+
+pub async fn remove_group_member(
+    Require(principal, _): RequireManageRbac,
+    State(app): State<AppContext<App>>,
+    client_info: ClientInfo,
+    Path((group, user)): Path<(Uuid, Uuid)>,
+) -> Resp<()> {
+    app.service::<Rbac>()
+        .remove_group_member(principal.domain_uuid, group, user)
+        .await?;
+
+    audit(
+        &app,
+        &principal,
+        client_info,
+        Some(user),
+        json!({ "operation": "remove_group_member", "group": group }),
+    )
+    .await?;
+
+    ok(())
+}
+
+pub async fn assign_group_role(
+    Require(principal, _): RequireManageRbac,
+    State(app): State<AppContext<App>>,
+    client_info: ClientInfo,
+    Path((group, role)): Path<(Uuid, Uuid)>,
+) -> Resp<()> {
+    app.service::<Rbac>()
+        .assign_group_role(principal.domain_uuid, group, role)
+        .await?;
+
+    audit(
+        &app,
+        &principal,
+        client_info,
+        None,
+        json!({ "operation": "assign_group_role", "group": group, "role": role }),
+    )
+    .await?;
+
+    ok(())
+}
+
+pub async fn unassign_group_role(
+    Require(principal, _): RequireManageRbac,
+    State(app): State<AppContext<App>>,
+    client_info: ClientInfo,
+    Path((group, role)): Path<(Uuid, Uuid)>,
+) -> Resp<()> {
+    app.service::<Rbac>()
+        .unassign_group_role(principal.domain_uuid, group, role)
+        .await?;
+
+    audit(
+        &app,
+        &principal,
+        client_info,
+        None,
+        json!({ "operation": "unassign_group_role", "group": group, "role": role }),
+    )
+    .await?;
+
+    ok(())
+}
+
+pub async fn list_user_roles(
+    Require(principal, _): RequireManageRbac,
+    State(app): State<AppContext<App>>,
+    Path((user,)): Path<(Uuid,)>,
+) -> Resp<Vec<roles::Model>> {
+    let roles = app
+        .service::<Rbac>()
+        .user_roles(user)
+        .await?
+        .into_iter()
+        .filter(|role| role.domain_uuid == principal.domain_uuid)
+        .collect();
+
+    ok(roles)
+}
+
+pub async fn assign_user_role(
+    Require(principal, _): RequireManageRbac,
+    State(app): State<AppContext<App>>,
+    client_info: ClientInfo,
+    Path((user, role)): Path<(Uuid, Uuid)>,
+) -> Resp<()> {
+    app.service::<Rbac>()
+        .assign_user_role(principal.domain_uuid, user, role)
+        .await?;
+
+    audit(
+        &app,
+        &principal,
+        client_info,
+        Some(user),
+        json!({ "operation": "assign_user_role", "role": role }),
+    )
+    .await?;
+
+    ok(())
+}
+
+pub async fn unassign_user_role(
+    Require(principal, _): RequireManageRbac,
+    State(app): State<AppContext<App>>,
+    client_info: ClientInfo,
+    Path((user, role)): Path<(Uuid, Uuid)>,
+) -> Resp<()> {
+    app.service::<Rbac>()
+        .unassign_user_role(principal.domain_uuid, user, role)
+        .await?;
+
+    audit(
+        &app,
+        &principal,
+        client_info,
+        Some(user),
+        json!({ "operation": "unassign_user_role", "role": role }),
+    )
+    .await?;
+
+    ok(())
+}
+
+pub fn routes() -> Router<App> {
+    Router::new()
+        .route("/roles", get(list_roles).post(create_role))
+        .route("/roles/:role", delete(delete_role))
+        .route(
+            "/roles/:role/permissions/:permission",
+            put(grant_permission).delete(revoke_permission),
+        )
+        .route(
+            "/permissions",
+            get(list_permissions).post(create_permission),
+        )
+        .route("/permissions/:permission", delete(delete_permission))
+        .route("/groups", get(list_groups).post(create_group))
+        .route("/groups/:group", delete(delete_group))
+        .route(
+            "/groups/:group/members/:user",
+            put(add_group_member).delete(remove_group_member),
+        )
+        .route(
+            "/groups/:group/roles/:role",
+            put(assign_group_role).delete(unassign_group_role),
+        )
+        .route("/users/:user/roles", get(list_user_roles))
+        .route(
+            "/users/:user/roles/:role",
+            put(assign_user_role).delete(unassign_user_role),
+        )
+}
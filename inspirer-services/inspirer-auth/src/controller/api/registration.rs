@@ -0,0 +1,105 @@
+use inspirer_framework::{
+    authorization::Require,
+    extract::{Path, State},
+    preludes::*,
+    routing::{delete, get},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    app::App,
+    auth::{
+        audit::{AuditAction, AuditEvent, AuditOutcome, ClientInfo},
+        registration::ManageApps,
+    },
+    entity::initial_access_tokens,
+    service::{audit::Audit, registration::Registration, ServiceInterface},
+};
+
+#[derive(Debug, Deserialize)]
+pub struct IssueInitialTokenRequest {
+    #[serde(default)]
+    description: String,
+    /// Lifetime in seconds, the configured default is used if not set
+    expires_in: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IssuedInitialToken {
+    #[serde(flatten)]
+    model: initial_access_tokens::Model,
+    /// The token is only shown once
+    token: String,
+}
+
+pub async fn list_initial_tokens(
+    Require(principal, _): Require<ManageApps, App>,
+    State(app): State<AppContext<App>>,
+) -> Resp<Vec<initial_access_tokens::Model>> {
+    ok(app
+        .service::<Registration>()
+        .initial_tokens(principal.domain_uuid)
+        .await?)
+}
+
+/// Issue an initial access token for dynamic client registration of the principal's domain
+pub async fn issue_initial_token(
+    Require(principal, _): Require<ManageApps, App>,
+    State(app): State<AppContext<App>>,
+    client_info: ClientInfo,
+    Json(req): Json<IssueInitialTokenRequest>,
+) -> Resp<IssuedInitialToken> {
+    let (model, token) = app
+        .service::<Registration>()
+        .issue_initial_token(principal.domain_uuid, req.description, req.expires_in)
+        .await?;
+
+    app.service::<Audit>()
+        .record(AuditEvent {
+            domain_uuid: Some(principal.domain_uuid),
+            actor_uuid: Some(principal.user_uuid),
+            client: client_info,
+            detail: json!({ "operation": "issue_initial_access_token", "token": model.uuid }),
+            ..AuditEvent::new(AuditAction::AppChange, AuditOutcome::Success)
+        })
+        .await?;
+
+    ok(IssuedInitialToken { model, token })
+}
+
+pub async fn revoke_initial_token(
+    Require(principal, _): Require<ManageApps, App>,
+    State(app): State<AppContext<App>>,
+    client_info: ClientInfo,
+    Path((uuid,)): Path<(Uuid,)>,
+) -> Resp<()> {
+    app.service::<Registration>()
+        .revoke_initial_token(principal.domain_uuid, uuid)
+        .await?;
+
+    app.service::<Audit>()
+        .record(AuditEvent {
+            domain_uuid: Some(principal.domain_uuid),
+            actor_uuid: Some(principal.user_uuid),
+            client: client_info,
+            detail: json!({ "operation": "revoke_initial_access_token", "token": uuid }),
+            ..AuditEvent::new(AuditAction::AppChange, AuditOutcome::Success)
+        })
+        .await?;
+
+    ok(())
+}
+
+pub fn routes() -> Router<App> {
+    Router::new()
+        .route(
+            "/initial-access-tokens",
+            get(list_initial_tokens).post(issue_initial_token),
+        )
+        .route(
+            "/initial-access-tokens/:token",
+            delete(revoke_initial_token),
+        )
+}
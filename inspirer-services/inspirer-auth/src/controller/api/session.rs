@@ -0,0 +1,81 @@
+use inspirer_framework::{
+    authorization::Require,
+    extract::{Path, State},
+    preludes::*,
+    routing::{delete, get},
+};
+use uuid::Uuid;
+
+use crate::{
+    app::App,
+    auth::{
+        audit::ClientInfo,
+        rbac::Principal,
+        session::{ManageSessions, SessionInfo},
+    },
+    service::{session::Session, user::User, ServiceInterface},
+};
+
+/// Users of other domains are invisible to the principal
+async fn check_user(app: &AppContext<App>, principal: &Principal, user_uuid: Uuid) -> Result<()> {
+    let user = app.service::<User>().find_user_by_uuid(user_uuid).await?;
+    if user.domain_uuid != principal.domain_uuid {
+        return Err(Error::NotFound);
+    }
+
+    Ok(())
+}
+
+pub async fn list_sessions(
+    Require(principal, _): Require<ManageSessions, App>,
+    State(app): State<AppContext<App>>,
+    Path((user_uuid,)): Path<(Uuid,)>,
+) -> Resp<Vec<SessionInfo>> {
+    check_user(&app, &principal, user_uuid).await?;
+
+    ok(app.service::<Session>().sessions(user_uuid, None).await?)
+}
+
+/// Revoke all sessions of the user, returns the number of revoked sessions
+pub async fn revoke_sessions(
+    Require(principal, _): Require<ManageSessions, App>,
+    State(app): State<AppContext<App>>,
+    client_info: ClientInfo,
+    Path((user_uuid,)): Path<(Uuid,)>,
+) -> Resp<u64> {
+    check_user(&app, &principal, user_uuid).await?;
+
+    ok(app
+        .service::<Session>()
+        .revoke(user_uuid, None, Some(principal.user_uuid), client_info)
+        .await?)
+}
+
+pub async fn revoke_session(
+    Require(principal, _): Require<ManageSessions, App>,
+    State(app): State<AppContext<App>>,
+    client_info: ClientInfo,
+    Path((user_uuid, session_uuid)): Path<(Uuid, Uuid)>,
+) -> Resp<()> {
+    check_user(&app, &principal, user_uuid).await?;
+
+    app.service::<Session>()
+        .revoke(
+            user_uuid,
+            Some(session_uuid),
+            Some(principal.user_uuid),
+            client_info,
+        )
+        .await?;
+
+    ok(())
+}
+
+pub fn routes() -> Router<App> {
+    Router::new()
+        .route(
+            "/users/:user/sessions",
+            get(list_sessions).delete(revoke_sessions),
+        )
+        .route("/users/:user/sessions/:session", delete(revoke_session))
+}
@@ -1,23 +1,35 @@
-use std::env::current_dir;
-
 use axum_login::tower_sessions::Session;
 use inspirer_framework::{
+    axum::{
+        http::{
+            header::{CACHE_CONTROL, CONTENT_TYPE},
+            HeaderValue, Uri,
+        },
+        middleware::Next,
+        response::{Html, Response},
+        Extension,
+    },
     extract::{Json, Query, Request, State},
     preludes::*,
     routing::get,
-    tower::ServiceExt,
-    tower_http::services::{ServeDir, ServeFile},
 };
-use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
 use serde::Deserialize;
 use uuid::Uuid;
 
 use crate::{
     app::App,
-    auth::user::UserCredential,
+    auth::{audit::ClientInfo, session::SESSION_UUID_KEY, user::UserCredential},
+    auth_page::{AuthPageBundle, Branding},
     config::AppConfig,
-    entity::users,
-    service::{user::User, ServiceInterface},
+    controller::authorize::{next_after_login, NextStep},
+    entity::apps,
+    service::{
+        app::App as AppService,
+        authentication::{Authentication, PasswordAuthenticator},
+        domain::Domain as DomainService,
+        session::Session as SessionService,
+        ServiceInterface,
+    },
 };
 
 #[derive(Debug, Deserialize)]
@@ -25,16 +37,34 @@ pub struct LoginParams {
     app_id: Uuid,
 }
 
+/// `index.html` of the auth page with the branding of the app, the default domain is used
+/// if the page is not for an app
+async fn serve_index(
+    app: &AppContext<App>,
+    bundle: &AuthPageBundle,
+    client: Option<&apps::Model>,
+) -> Result<Response> {
+    let domains = app.service::<DomainService>();
+    let domain = match client {
+        Some(client) => domains.find_domain_by_uuid(client.domain_uuid).await?,
+        None => domains.default_domain().await?,
+    };
+
+    let index = bundle.index(&Branding::new(Some(&domain), client)).await?;
+
+    Ok((
+        [(CACHE_CONTROL, HeaderValue::from_static("no-cache"))],
+        Html(index),
+    )
+        .into_response())
+}
+
 pub async fn auth_page(
     State(app): State<AppContext<App>>,
+    Extension(bundle): Extension<AuthPageBundle>,
     Query(params): Query<LoginParams>,
     session: Session,
-    req: Request,
-) -> Result<impl IntoResponse> {
-    let path = current_dir()
-        .unwrap()
-        .join("inspirer-services/inspirer-auth/auth-page/dist");
-
+) -> Result<Response> {
     let app_id: Option<Uuid> = session.get::<Uuid>("app_id").await.map_err(Error::wrap)?;
 
     if let Some(app_id) = app_id {
@@ -48,8 +78,39 @@ pub async fn auth_page(
             .map_err(Error::wrap)?;
     }
 
-    let service = ServeFile::new(path.join("index.html"));
-    service.oneshot(req).await.map_err(Error::wrap)
+    let client = app
+        .service::<AppService>()
+        .find_app_by_uuid(params.app_id)
+        .await?;
+
+    serve_index(&app, &bundle, Some(&client)).await
+}
+
+/// Page where the user enters the user code of device authorization
+pub async fn device_page(
+    State(app): State<AppContext<App>>,
+    Extension(bundle): Extension<AuthPageBundle>,
+) -> Result<Response> {
+    serve_index(&app, &bundle, None).await
+}
+
+/// Static files of the auth page, the built assets have hashed names and are cached forever
+pub async fn asset(Extension(bundle): Extension<AuthPageBundle>, uri: Uri) -> Result<Response> {
+    let asset = bundle.asset(uri.path()).await?.ok_or(Error::NotFound)?;
+
+    let cache_control = match uri.path().starts_with("/assets/") {
+        true => "public, max-age=31536000, immutable",
+        false => "public, max-age=3600",
+    };
+
+    Ok((
+        [
+            (CONTENT_TYPE, HeaderValue::try_from(asset.content_type)?),
+            (CACHE_CONTROL, HeaderValue::from_static(cache_control)),
+        ],
+        asset.content.into_owned(),
+    )
+        .into_response())
 }
 
 #[derive(Deserialize)]
@@ -60,9 +121,10 @@ pub struct LoginRequest {
 
 pub async fn login(
     State(app): State<AppContext<App>>,
-    Json(payload): Json<LoginRequest>,
+    client_info: ClientInfo,
     session: Session,
-) -> Resp<()> {
+    Json(payload): Json<LoginRequest>,
+) -> Resp<NextStep> {
     let app_id = session
         .get::<Uuid>("app_id")
         .await
@@ -71,29 +133,76 @@ pub async fn login(
 
     tracing::trace!("Received login request, app id = {app_id}");
 
-    let user = app
-        .service::<User>()
-        .find_user_by_credential(payload.credential)
+    let client = app.service::<AppService>().find_app_by_uuid(app_id).await?;
+
+    let principal = app
+        .service::<Authentication>()
+        .authenticate(
+            &client,
+            PasswordAuthenticator(payload.credential),
+            &client_info,
+        )
         .await?;
 
-    // generate id token and profile data
+    session.cycle_id().await.map_err(Error::wrap)?;
+    session
+        .insert("user_uuid", principal.user.uuid)
+        .await
+        .map_err(Error::wrap)?;
+    session
+        .insert(SESSION_UUID_KEY, principal.session.uuid)
+        .await
+        .map_err(Error::wrap)?;
 
-    ok(())
+    // 有待处理的授权时回到授权流程继续处理，否则回到应用首页
+    let redirect = next_after_login(&app, &session)
+        .await?
+        .unwrap_or(client.setting.base_setting.endpoint);
+
+    ok(NextStep { redirect })
 }
 
-pub fn routes(app: &AppContext<App>) -> Router<App> {
-    let config = app
-        .config
-        .get::<AppConfig>("app")
-        .expect("Missing app config");
+/// Static files of the auth page, requests of them do not keep the session alive
+fn is_asset(path: &str) -> bool {
+    path == "/vite.svg" || path.starts_with("/assets/")
+}
+
+/// Keep the server-side session alive, the browser session is flushed once the session is revoked
+pub async fn track_session(
+    State(app): State<AppContext<App>>,
+    session: Session,
+    req: Request,
+    next: Next,
+) -> Result<Response> {
+    if is_asset(req.uri().path()) {
+        return Ok(next.run(req).await);
+    }
+
+    let session_uuid = session
+        .get::<Uuid>(SESSION_UUID_KEY)
+        .await
+        .map_err(Error::wrap)?;
 
-    let path = current_dir().unwrap().join(config.default_auth_page);
+    if let Some(session_uuid) = session_uuid {
+        if !app.service::<SessionService>().touch(session_uuid).await? {
+            tracing::debug!(session = %session_uuid, "session revoked, flush browser session");
+            session.flush().await.map_err(Error::wrap)?;
+        }
+    }
 
-    assert!(path.exists());
-    assert!(path.join("index.html").exists());
+    Ok(next.run(req).await)
+}
 
-    Router::new()
-        .route_service("/vite.svg", ServeFile::new(path.join("vite.svg")))
-        .route("/login", get(auth_page))
-        .nest_service("/assets", ServeDir::new(path.join("assets")))
+/// Pages of the auth page, returns an error if the bundle of the page is missing
+pub fn routes(app: &AppContext<App>) -> Result<Router<App>> {
+    let config = app.config.get::<AppConfig>("app")?;
+    let bundle = AuthPageBundle::from_config(&config)?;
+
+    Ok(Router::new()
+        .route("/vite.svg", get(asset))
+        .route("/login", get(auth_page).post(login))
+        .route("/consent", get(auth_page))
+        .route("/device", get(device_page))
+        .route("/assets/*path", get(asset))
+        .layer(Extension(bundle)))
 }
@@ -0,0 +1,385 @@
+use std::collections::HashMap;
+
+use axum_login::tower_sessions::Session;
+use inspirer_framework::{
+    axum::response::Response,
+    extract::{Form, State},
+    http::{header::LOCATION, HeaderValue},
+    preludes::*,
+    routing::{get, on, post, MethodFilter},
+};
+use openidconnect::core::{CoreAuthPrompt, CoreResponseType};
+use serde::Serialize;
+use url::Url;
+use uuid::Uuid;
+
+use crate::{
+    app::App,
+    auth::{
+        consent::{scope_set, ConsentDecision, ConsentDetails, AUTHORIZATION_REQUEST_KEY},
+        device::DEVICE_USER_CODE_KEY,
+        domain::RequestDomain,
+        ocid::AuthenticationRequest,
+        session::SESSION_UUID_KEY,
+    },
+    config::AppConfig,
+    entity::{apps, user_sessions},
+    service::{
+        app::App as AppService, authorization::Authorization, consent::Consent,
+        session::Session as SessionService, ServiceInterface,
+    },
+};
+
+/// Where the user agent should go next, returned to the auth page
+#[derive(Debug, Serialize)]
+pub struct NextStep {
+    pub redirect: Url,
+}
+
+fn found(location: &Url) -> Result<Response> {
+    Ok((
+        StatusCode::FOUND,
+        [(LOCATION, HeaderValue::try_from(location.to_string())?)],
+    )
+        .into_response())
+}
+
+/// Find the app by the client id of the request
+async fn lookup_client(app: &AppContext<App>, client_id: &str) -> Result<apps::Model> {
+    let uuid = client_id
+        .parse::<Uuid>()
+        .map_err(|_| Error::BadRequest("Invalid client id".into()))?;
+
+    app.service::<AppService>()
+        .find_app_by_uuid(uuid)
+        .await
+        .map_err(|_| Error::BadRequest("Invalid client id".into()))
+}
+
+/// The redirect uri must be registered by the app
+pub fn check_redirect_uri(client: &apps::Model, request: &AuthenticationRequest) -> Result<()> {
+    if !client
+        .setting
+        .oidc_setting
+        .redirect_uris
+        .contains(&request.redirect_uri)
+    {
+        return Err(Error::BadRequest("Unregistered redirect uri".into()));
+    }
+
+    Ok(())
+}
+
+/// Find the app of the request, the redirect uri must be registered by the app.
+///
+/// Errors here are shown to the user directly instead of being redirected to the client.
+async fn find_client(
+    app: &AppContext<App>,
+    request: &AuthenticationRequest,
+) -> Result<apps::Model> {
+    let client = lookup_client(app, &request.client_id).await?;
+    check_redirect_uri(&client, request)?;
+
+    Ok(client)
+}
+
+/// Resolve the request from the pushed request of `request_uri`, the request object or the
+/// plain parameters
+async fn resolve_request(
+    app: &AppContext<App>,
+    mut parameters: HashMap<String, String>,
+) -> Result<(apps::Model, AuthenticationRequest)> {
+    let client_id = parameters
+        .get("client_id")
+        .ok_or(Error::BadRequest("Missing client id".into()))?;
+    let client = lookup_client(app, client_id).await?;
+    let service = app.service::<Authorization>();
+
+    let request = match parameters.remove("request_uri") {
+        Some(request_uri) => service.take_pushed_request(&client, &request_uri).await?,
+        None if client
+            .setting
+            .oidc_setting
+            .require_pushed_authorization_requests =>
+        {
+            return Err(Error::BadRequest(
+                "Pushed authorization request is required".into(),
+            ));
+        }
+        None => service.resolve_request(&client, parameters).await?,
+    };
+    check_redirect_uri(&client, &request)?;
+
+    Ok((client, request))
+}
+
+/// Redirect uri with the authentication error response, the pending request is dropped
+async fn error_redirect(
+    session: &Session,
+    request: &AuthenticationRequest,
+    error: &str,
+) -> Result<Url> {
+    session
+        .remove::<AuthenticationRequest>(AUTHORIZATION_REQUEST_KEY)
+        .await
+        .map_err(Error::wrap)?;
+
+    let mut location = request.redirect_uri.clone();
+    location.query_pairs_mut().append_pair("error", error);
+    if let Some(state) = &request.state {
+        location.query_pairs_mut().append_pair("state", state);
+    }
+
+    Ok(location)
+}
+
+/// Active session of the user signed in the domain of the client, users of other domains
+/// have to sign in again
+pub async fn current_session(
+    app: &AppContext<App>,
+    session: &Session,
+    client: &apps::Model,
+) -> Result<Option<user_sessions::Model>> {
+    let Some(session_uuid) = session
+        .get::<Uuid>(SESSION_UUID_KEY)
+        .await
+        .map_err(Error::wrap)?
+    else {
+        return Ok(None);
+    };
+
+    Ok(app
+        .service::<SessionService>()
+        .find_active_session(session_uuid)
+        .await
+        .ok()
+        .filter(|user_session| user_session.domain_uuid == client.domain_uuid))
+}
+
+/// Grant the app in the session and redirect back to the client with the authorization code
+async fn complete(
+    app: &AppContext<App>,
+    session: &Session,
+    client: &apps::Model,
+    user_session: &user_sessions::Model,
+    request: AuthenticationRequest,
+) -> Result<Url> {
+    session
+        .remove::<AuthenticationRequest>(AUTHORIZATION_REQUEST_KEY)
+        .await
+        .map_err(Error::wrap)?;
+
+    let grant = app
+        .service::<SessionService>()
+        .grant(
+            user_session,
+            client.uuid,
+            request.scope.clone(),
+            request.claims.clone(),
+        )
+        .await?;
+    let code = app
+        .service::<Authorization>()
+        .issue_code(client, &grant, &request.redirect_uri, request.nonce)
+        .await?;
+
+    let mut location = request.redirect_uri;
+    location.query_pairs_mut().append_pair("code", &code);
+    if let Some(state) = &request.state {
+        location.query_pairs_mut().append_pair("state", state);
+    }
+
+    Ok(location)
+}
+
+/// Decide the next step of the pending request: login, consent or back to the client
+async fn proceed(
+    app: &AppContext<App>,
+    session: &Session,
+    client: &apps::Model,
+    request: AuthenticationRequest,
+) -> Result<Url> {
+    let config = app.config.get::<AppConfig>("app")?;
+    let prompt_none = request.prompt == Some(CoreAuthPrompt::None);
+
+    let Some(user_session) = current_session(app, session, client).await? else {
+        if prompt_none {
+            return error_redirect(session, &request, "login_required").await;
+        }
+
+        let mut location = config.app_endpoint.join("login")?;
+        location
+            .query_pairs_mut()
+            .append_pair("app_id", &client.uuid.to_string());
+        return Ok(location);
+    };
+
+    let consent_required = !client.setting.oidc_setting.first_party
+        && (request.prompt == Some(CoreAuthPrompt::Consent)
+            || !app
+                .service::<Consent>()
+                .is_covered(user_session.user_uuid, client.uuid, &request.scope)
+                .await?);
+
+    if consent_required {
+        if prompt_none {
+            return error_redirect(session, &request, "consent_required").await;
+        }
+
+        let mut location = config.app_endpoint.join("consent")?;
+        location
+            .query_pairs_mut()
+            .append_pair("app_id", &client.uuid.to_string());
+        return Ok(location);
+    }
+
+    complete(app, session, client, &user_session, request).await
+}
+
+async fn pending_request(
+    app: &AppContext<App>,
+    session: &Session,
+) -> Result<(apps::Model, AuthenticationRequest)> {
+    let request = session
+        .get::<AuthenticationRequest>(AUTHORIZATION_REQUEST_KEY)
+        .await
+        .map_err(Error::wrap)?
+        .ok_or(Error::BadRequest("No pending authorization request".into()))?;
+    let client = find_client(app, &request).await?;
+
+    Ok((client, request))
+}
+
+/// Where to continue after the user signed in: the pending authorization request
+/// or the device verification, `None` if nothing is pending
+pub async fn next_after_login(app: &AppContext<App>, session: &Session) -> Result<Option<Url>> {
+    let config = app.config.get::<AppConfig>("app")?;
+
+    if session
+        .get::<AuthenticationRequest>(AUTHORIZATION_REQUEST_KEY)
+        .await
+        .map_err(Error::wrap)?
+        .is_some()
+    {
+        return Ok(Some(config.app_endpoint.join("oidc/auth/resume")?));
+    }
+
+    if let Some(user_code) = session
+        .get::<String>(DEVICE_USER_CODE_KEY)
+        .await
+        .map_err(Error::wrap)?
+    {
+        let mut location = config.app_endpoint.join("device")?;
+        location
+            .query_pairs_mut()
+            .append_pair("user_code", &user_code);
+        return Ok(Some(location));
+    }
+
+    Ok(None)
+}
+
+/// Authorization endpoint
+///
+/// See [OpenID Connect Core 3.1.2](https://openid.net/specs/openid-connect-core-1_0.html#AuthorizationEndpoint),
+/// the request may also be passed by `request_uri` of a pushed request or a `request` object.
+pub async fn authorize(
+    State(app): State<AppContext<App>>,
+    domain: RequestDomain,
+    session: Session,
+    Form(parameters): Form<HashMap<String, String>>,
+) -> Result<Response> {
+    let (client, request) = resolve_request(&app, parameters).await?;
+
+    if !domain.allows(client.domain_uuid) {
+        return Err(Error::BadRequest("Invalid client id".into()));
+    }
+
+    if request.response_type != CoreResponseType::Code {
+        return found(&error_redirect(&session, &request, "unsupported_response_type").await?);
+    }
+
+    if !scope_set(&request.scope).contains("openid") {
+        return found(&error_redirect(&session, &request, "invalid_scope").await?);
+    }
+
+    session
+        .insert("app_id", client.uuid)
+        .await
+        .map_err(Error::wrap)?;
+    session
+        .insert(AUTHORIZATION_REQUEST_KEY, &request)
+        .await
+        .map_err(Error::wrap)?;
+
+    found(&proceed(&app, &session, &client, request).await?)
+}
+
+/// Continue the pending request after the user signed in
+pub async fn resume(State(app): State<AppContext<App>>, session: Session) -> Result<Response> {
+    let (client, request) = pending_request(&app, &session).await?;
+
+    found(&proceed(&app, &session, &client, request).await?)
+}
+
+/// Scopes of the pending request to be shown on the consent screen
+pub async fn consent_details(
+    State(app): State<AppContext<App>>,
+    session: Session,
+) -> Resp<ConsentDetails> {
+    let (client, request) = pending_request(&app, &session).await?;
+    let user_session = current_session(&app, &session, &client)
+        .await?
+        .ok_or(Error::Unauthorized("Login required".into()))?;
+
+    let granted = app
+        .service::<Consent>()
+        .find_consent(user_session.user_uuid, client.uuid)
+        .await?
+        .map(|consent| scope_set(&consent.scope))
+        .unwrap_or_default();
+
+    ok(ConsentDetails {
+        app_uuid: client.uuid,
+        app_name: client.display_name,
+        scopes: scope_set(&request.scope),
+        granted,
+    })
+}
+
+/// Approve or deny the scopes of the pending request
+pub async fn consent(
+    State(app): State<AppContext<App>>,
+    session: Session,
+    Json(decision): Json<ConsentDecision>,
+) -> Resp<NextStep> {
+    let (client, request) = pending_request(&app, &session).await?;
+    let user_session = current_session(&app, &session, &client)
+        .await?
+        .ok_or(Error::Unauthorized("Login required".into()))?;
+
+    if !decision.approve {
+        return ok(NextStep {
+            redirect: error_redirect(&session, &request, "access_denied").await?,
+        });
+    }
+
+    app.service::<Consent>()
+        .approve(user_session.user_uuid, client.uuid, &request.scope)
+        .await?;
+
+    ok(NextStep {
+        redirect: complete(&app, &session, &client, &user_session, request).await?,
+    })
+}
+
+pub fn routes() -> Router<App> {
+    Router::new()
+        .route(
+            "/oidc/auth",
+            on(MethodFilter::GET.or(MethodFilter::POST), authorize),
+        )
+        .route("/oidc/auth/resume", get(resume))
+        .route("/consent", post(consent))
+        .route("/consent/details", get(consent_details))
+}
@@ -0,0 +1,125 @@
+use axum_login::tower_sessions::Session;
+use inspirer_framework::{
+    axum::response::Response,
+    extract::{Query, State},
+    http::{header::LOCATION, HeaderValue},
+    preludes::*,
+    routing::{get, post},
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    app::App,
+    auth::{
+        device::{normalize_user_code, DeviceDecision, DeviceDetails, DEVICE_USER_CODE_KEY},
+        session::SESSION_UUID_KEY,
+    },
+    config::AppConfig,
+    entity::user_sessions,
+    service::{device::Device, session::Session as SessionService, ServiceInterface},
+};
+
+#[derive(Debug, Deserialize)]
+pub struct UserCodeParams {
+    user_code: String,
+}
+
+async fn current_session(app: &AppContext<App>, session: &Session) -> Result<user_sessions::Model> {
+    let session_uuid = session
+        .get::<Uuid>(SESSION_UUID_KEY)
+        .await
+        .map_err(Error::wrap)?
+        .ok_or(Error::Unauthorized("Login required".into()))?;
+
+    app.service::<SessionService>()
+        .find_active_session(session_uuid)
+        .await
+        .map_err(|_| Error::Unauthorized("Login required".into()))
+}
+
+/// Check the user code entered by the user, the user signs in first if not signed in
+pub async fn verify(
+    State(app): State<AppContext<App>>,
+    session: Session,
+    Query(params): Query<UserCodeParams>,
+) -> Result<Response> {
+    let config = app.config.get::<AppConfig>("app")?;
+    let authorization = app
+        .service::<Device>()
+        .find_pending(&params.user_code)
+        .await
+        .map_err(|_| Error::BadRequest("Invalid or expired user code".into()))?;
+
+    session
+        .insert(DEVICE_USER_CODE_KEY, &authorization.user_code)
+        .await
+        .map_err(Error::wrap)?;
+
+    let location = if current_session(&app, &session).await.is_ok() {
+        let mut location = config.app_endpoint.join("device")?;
+        location
+            .query_pairs_mut()
+            .append_pair("user_code", &authorization.user_code);
+        location
+    } else {
+        session
+            .insert("app_id", authorization.app_uuid)
+            .await
+            .map_err(Error::wrap)?;
+
+        let mut location = config.app_endpoint.join("login")?;
+        location
+            .query_pairs_mut()
+            .append_pair("app_id", &authorization.app_uuid.to_string());
+        location
+    };
+
+    Ok((
+        StatusCode::FOUND,
+        [(LOCATION, HeaderValue::try_from(location.to_string())?)],
+    )
+        .into_response())
+}
+
+/// The app and scopes of the user code to be shown on the device verification page
+pub async fn details(
+    State(app): State<AppContext<App>>,
+    session: Session,
+    Query(params): Query<UserCodeParams>,
+) -> Resp<DeviceDetails> {
+    current_session(&app, &session).await?;
+
+    ok(app.service::<Device>().details(&params.user_code).await?)
+}
+
+/// Approve or deny the device authorization
+pub async fn decide(
+    State(app): State<AppContext<App>>,
+    session: Session,
+    Json(decision): Json<DeviceDecision>,
+) -> Resp<()> {
+    let user_session = current_session(&app, &session).await?;
+
+    // 只允许确认当前浏览器会话中校验过的 user code
+    let verified = session
+        .remove::<String>(DEVICE_USER_CODE_KEY)
+        .await
+        .map_err(Error::wrap)?;
+    if verified.as_deref() != Some(normalize_user_code(&decision.user_code).as_str()) {
+        return Err(Error::BadRequest("Invalid or expired user code".into()));
+    }
+
+    app.service::<Device>()
+        .decide(&user_session, &decision.user_code, decision.approve)
+        .await?;
+
+    ok(())
+}
+
+pub fn routes() -> Router<App> {
+    Router::new()
+        .route("/device/verify", get(verify))
+        .route("/device/details", get(details))
+        .route("/device/decision", post(decide))
+}
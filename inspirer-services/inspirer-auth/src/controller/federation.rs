@@ -0,0 +1,181 @@
+use axum_login::tower_sessions::Session;
+use inspirer_framework::{
+    extract::{Path, Query, State},
+    http::{header::LOCATION, HeaderValue},
+    preludes::*,
+    routing::get,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    app::App,
+    auth::{audit::ClientInfo, federation::FederationState, session::SESSION_UUID_KEY},
+    config::AppConfig,
+    controller::authorize::{current_session, next_after_login},
+    entity::apps,
+    service::{
+        app::App as AppService,
+        authentication::{Authentication, FederatedAuthenticator},
+        federation::Federation,
+        ServiceInterface,
+    },
+};
+
+const FEDERATION_STATE_KEY: &str = "federation_state";
+
+#[derive(Debug, Serialize)]
+pub struct ProviderItem {
+    uuid: Uuid,
+    name: String,
+    display_name: String,
+}
+
+/// The app the user is signing in to, federated logins are scoped to the domain of the app
+async fn session_client(app: &AppContext<App>, session: &Session) -> Result<apps::Model> {
+    let app_id = session
+        .get::<Uuid>("app_id")
+        .await
+        .map_err(Error::wrap)?
+        .ok_or(Error::string("Invalid request"))?;
+
+    app.service::<AppService>().find_app_by_uuid(app_id).await
+}
+
+/// List upstream providers available for current login session
+pub async fn providers(
+    State(app): State<AppContext<App>>,
+    session: Session,
+) -> Resp<Vec<ProviderItem>> {
+    let domain_uuid = session_client(&app, &session).await?.domain_uuid;
+
+    ok(app
+        .service::<Federation>()
+        .providers(domain_uuid)
+        .await?
+        .into_iter()
+        .map(|provider| ProviderItem {
+            uuid: provider.uuid,
+            name: provider.name,
+            display_name: provider.display_name,
+        })
+        .collect())
+}
+
+/// Redirect user agent to the upstream provider
+pub async fn redirect(
+    Path((provider_id,)): Path<(Uuid,)>,
+    State(app): State<AppContext<App>>,
+    session: Session,
+) -> Result<impl IntoResponse> {
+    let domain_uuid = session_client(&app, &session).await?.domain_uuid;
+    let service = app.service::<Federation>();
+    let provider = service.find_provider(domain_uuid, provider_id).await?;
+
+    let (location, state) = service.authorize_url(&provider).await?;
+
+    session
+        .insert(FEDERATION_STATE_KEY, state)
+        .await
+        .map_err(Error::wrap)?;
+
+    Ok((
+        StatusCode::FOUND,
+        [(LOCATION, HeaderValue::try_from(location.to_string())?)],
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CallbackParams {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+}
+
+/// Handle the authorization response of the upstream provider
+pub async fn callback(
+    Path((provider_id,)): Path<(Uuid,)>,
+    State(app): State<AppContext<App>>,
+    Query(params): Query<CallbackParams>,
+    client_info: ClientInfo,
+    session: Session,
+) -> Result<impl IntoResponse> {
+    let state = session
+        .remove::<FederationState>(FEDERATION_STATE_KEY)
+        .await
+        .map_err(Error::wrap)?
+        .ok_or(Error::string("Invalid request"))?;
+
+    if let Some(error) = params.error {
+        return Err(Error::Unauthorized(format!(
+            "Upstream provider rejected the request: {error}"
+        )));
+    }
+
+    if state.provider != provider_id || params.state.as_ref() != Some(&state.csrf_token) {
+        return Err(Error::Unauthorized("Federation state mismatch".into()));
+    }
+
+    let code = params
+        .code
+        .ok_or(Error::BadRequest("Missing authorization code".into()))?;
+
+    let client = session_client(&app, &session).await?;
+    let provider = app
+        .service::<Federation>()
+        .find_provider(client.domain_uuid, provider_id)
+        .await?;
+
+    let current_user = current_session(&app, &session, &client)
+        .await?
+        .map(|user_session| user_session.user_uuid);
+
+    let principal = app
+        .service::<Authentication>()
+        .authenticate(
+            &client,
+            FederatedAuthenticator {
+                provider,
+                state,
+                code,
+                current_user,
+            },
+            &client_info,
+        )
+        .await?;
+
+    session.cycle_id().await.map_err(Error::wrap)?;
+    session
+        .insert("user_uuid", principal.user.uuid)
+        .await
+        .map_err(Error::wrap)?;
+    session
+        .insert(SESSION_UUID_KEY, principal.session.uuid)
+        .await
+        .map_err(Error::wrap)?;
+
+    // 有待处理的授权时回到授权流程继续处理
+    let location = match next_after_login(&app, &session).await? {
+        Some(location) => location,
+        None => {
+            let config = app.config.get::<AppConfig>("app")?;
+            let mut location = config.app_endpoint.join("login")?;
+            location
+                .query_pairs_mut()
+                .append_pair("app_id", &client.uuid.to_string());
+            location
+        }
+    };
+
+    Ok((
+        StatusCode::FOUND,
+        [(LOCATION, HeaderValue::try_from(location.to_string())?)],
+    ))
+}
+
+pub fn routes() -> Router<App> {
+    Router::new()
+        .route("/login/providers", get(providers))
+        .route("/login/federation/:provider", get(redirect))
+        .route("/login/federation/:provider/callback", get(callback))
+}
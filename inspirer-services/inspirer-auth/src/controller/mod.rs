@@ -1,3 +1,8 @@
 pub mod api;
 pub mod auth;
+pub mod authorize;
+pub mod device;
+pub mod federation;
 pub mod oidc;
+pub mod registration;
+pub mod scim;
@@ -1,116 +1,630 @@
-use std::str::FromStr;
+use std::collections::HashMap;
 
+use axum_extra::{
+    headers::{authorization::Basic, Authorization},
+    TypedHeader,
+};
 use inspirer_framework::{
     axum::response::IntoResponse,
-    extract::{Path, Query, State},
-    http::{header::LOCATION, HeaderValue},
+    dpop::{DPoP, DPoPRejection, DPOP_NONCE_HEADER, DPOP_SIGNING_ALGS},
+    extract::{Form, Path, State},
+    http::{header::CACHE_CONTROL, HeaderMap, HeaderValue},
     preludes::*,
-    routing::{get, on, MethodFilter},
+    routing::{get, post},
 };
+use jsonwebtoken::jwk::JwkSet;
 use openidconnect::{
     core::{
         CoreClaimName, CoreJwsSigningAlgorithm, CoreProviderMetadata, CoreResponseType,
         CoreSubjectIdentifierType,
     },
-    AuthUrl, EmptyAdditionalProviderMetadata, IssuerUrl, JsonWebKeySetUrl, ResponseTypes, Scope,
-    TokenUrl, UserInfoUrl,
+    AuthUrl, EmptyAdditionalProviderMetadata, IssuerUrl, JsonWebKeySetUrl, RegistrationUrl,
+    ResponseTypes, Scope, TokenUrl, UserInfoUrl,
 };
 use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
 use serde::{Deserialize, Serialize};
-use url::Url;
+use serde_json::{json, Value};
 use uuid::Uuid;
 
-use crate::{app::App, auth::ocid::AuthenticationRequest, config::AppConfig, entity::apps};
+use crate::{
+    app::App,
+    auth::{
+        application::AppSetting,
+        claims::ClaimMapping,
+        client::{ClientCredentials, TokenEndpointAuthMethod, CLIENT_ASSERTION_TYPE},
+        consent::scope_set,
+        device::DEVICE_CODE_GRANT_TYPE,
+        domain::RequestDomain,
+        exchange::{ACCESS_TOKEN_TYPE, TOKEN_EXCHANGE_GRANT_TYPE},
+        oauth::{OAuthError, OAuthResult},
+        par::REQUEST_OBJECT_SIGNING_ALGS,
+        registration::SUPPORTED_GRANT_TYPES,
+    },
+    config::AppConfig,
+    controller::authorize::check_redirect_uri,
+    entity::{apps, domains},
+    service::{
+        app::App as AppService,
+        authorization::Authorization as AuthorizationService,
+        device::Device,
+        domain::Domain as DomainService,
+        dpop::DPoP as DPoPService,
+        token::{ExchangedToken, IssuedToken, Token},
+        ServiceInterface,
+    },
+    token::AccessToken,
+};
+
+/// Discovery document of the domain of the request, see
+/// [OpenID Connect Discovery 4](https://openid.net/specs/openid-connect-discovery-1_0.html#ProviderConfig)
+pub async fn discovery(
+    State(context): State<AppContext<App>>,
+    RequestDomain(domain): RequestDomain,
+) -> Result<Json<Value>> {
+    let domain = match domain {
+        Some(domain) => domain,
+        None => context.service::<DomainService>().default_domain().await?,
+    };
+
+    // 域的发现文档包含默认映射和域内所有应用映射的 claims
+    let mappings = apps::Entity::find()
+        .filter(apps::Column::DomainUuid.eq(domain.uuid))
+        .all(&context.database)
+        .await?
+        .into_iter()
+        .flat_map(|app| app.setting.claim_mappings())
+        .chain(AppSetting::default().claim_mappings())
+        .collect::<Vec<_>>();
+
+    Ok(Json(provider_metadata(&context, &domain, &mappings)?))
+}
+
+/// Public keys verifying the ID tokens and access tokens, the same key signs the tokens of
+/// every domain, see [RFC 7517 5](https://www.rfc-editor.org/rfc/rfc7517#section-5)
+pub async fn jwks(State(context): State<AppContext<App>>) -> Json<JwkSet> {
+    Json(context.app.signing_key.jwks())
+}
 
+/// Discovery document of the domain of the app, with the metadata specified by the app
 pub async fn openid_configuration(
     Path((app_id,)): Path<(Uuid,)>,
     State(context): State<AppContext<App>>,
-) -> Result<Json<impl Serialize>> {
+) -> Result<Json<Value>> {
     let app = apps::Entity::find()
         .filter(apps::Column::Uuid.eq(app_id))
         .one(&context.database)
         .await?
         .ok_or(Error::NotFound)?;
+    let domain = context
+        .service::<DomainService>()
+        .find_domain_by_uuid(app.domain_uuid)
+        .await?;
+
+    let mut meta = provider_metadata(&context, &domain, &app.setting.claim_mappings())?;
+    meta["require_pushed_authorization_requests"] = app
+        .setting
+        .oidc_setting
+        .require_pushed_authorization_requests
+        .into();
+
+    Ok(Json(meta))
+}
+
+/// Claims set by the issuer in every ID token
+const ISSUER_CLAIMS: &[&str] = &["sub", "iss", "aud", "exp", "iat", "amr"];
+
+/// Scopes and claims of the claim mappings, in the order they first appear
+fn supported_claims(mappings: &[ClaimMapping]) -> (Vec<Scope>, Vec<CoreClaimName>) {
+    let mut scopes = vec!["openid".to_string()];
+    let mut claims = ISSUER_CLAIMS
+        .iter()
+        .map(|claim| claim.to_string())
+        .collect::<Vec<_>>();
+
+    for mapping in mappings {
+        if let Some(scope) = &mapping.scope {
+            if !scopes.contains(scope) {
+                scopes.push(scope.clone());
+            }
+        }
+        if !claims.contains(&mapping.name) {
+            claims.push(mapping.name.clone());
+        }
+    }
+
+    (
+        scopes.into_iter().map(Scope::new).collect(),
+        claims.into_iter().map(CoreClaimName::new).collect(),
+    )
+}
+
+fn provider_metadata(
+    context: &AppContext<App>,
+    domain: &domains::Model,
+    mappings: &[ClaimMapping],
+) -> Result<Value> {
+    let service = context.service::<DomainService>();
+    let endpoint = service.endpoint(domain)?;
+    let config = context.config.get::<AppConfig>("app")?;
+    let (scopes, claims) = supported_claims(mappings);
 
     let meta = CoreProviderMetadata::new(
-        IssuerUrl::from_url(app.setting.base_setting.endpoint.join("/oidc").unwrap()),
-        AuthUrl::from_url(app.setting.base_setting.endpoint.join("/oidc/auth")?),
-        JsonWebKeySetUrl::from_url(
-            app.setting
-                .base_setting
-                .endpoint
-                .join("/oidc/.well-known/jwks.json")?,
-        ),
+        IssuerUrl::from_url(service.issuer(domain)?),
+        AuthUrl::from_url(endpoint.join("oidc/auth")?),
+        JsonWebKeySetUrl::from_url(endpoint.join("oidc/.well-known/jwks.json")?),
         vec![ResponseTypes::new(vec![CoreResponseType::Code])],
         vec![CoreSubjectIdentifierType::Public],
         vec![CoreJwsSigningAlgorithm::EcdsaP256Sha256],
         EmptyAdditionalProviderMetadata {},
     )
-    .set_token_endpoint(Some(TokenUrl::from_url(
-        app.setting.base_setting.endpoint.join("/oidc/token")?,
-    )))
-    .set_userinfo_endpoint(Some(UserInfoUrl::from_url(
-        app.setting.base_setting.endpoint.join("/oidc/userinfo")?,
+    .set_token_endpoint(Some(TokenUrl::from_url(endpoint.join("oidc/token")?)))
+    .set_userinfo_endpoint(Some(UserInfoUrl::from_url(endpoint.join("oidc/userinfo")?)))
+    // 客户端注册由 initial access token 决定所属的域，不区分域的入口
+    .set_registration_endpoint(Some(RegistrationUrl::from_url(
+        config.app_endpoint.join("oidc/register")?,
     )))
-    .set_scopes_supported(Some(vec![
-        Scope::new("openid".to_string()),
-        Scope::new("email".to_string()),
-        Scope::new("profile".to_string()),
-    ]))
-    .set_claims_supported(Some(vec![
-        CoreClaimName::new("sub".to_string()),
-        CoreClaimName::new("aud".to_string()),
-        CoreClaimName::new("email".to_string()),
-        CoreClaimName::new("email_verified".to_string()),
-        CoreClaimName::new("exp".to_string()),
-        CoreClaimName::new("iat".to_string()),
-        CoreClaimName::new("iss".to_string()),
-        CoreClaimName::new("name".to_string()),
-        CoreClaimName::new("given_name".to_string()),
-        CoreClaimName::new("family_name".to_string()),
-        CoreClaimName::new("picture".to_string()),
-        CoreClaimName::new("locale".to_string()),
-    ]))
-    .set_request_parameter_supported(Some(false))
-    .set_claims_parameter_supported(Some(false));
+    .set_scopes_supported(Some(scopes))
+    .set_claims_supported(Some(claims))
+    .set_request_parameter_supported(Some(true))
+    .set_claims_parameter_supported(Some(true));
 
-    Ok(Json(meta))
+    // 标准元数据之外的端点（RFC 8414 注册的扩展元数据）
+    let mut meta = serde_json::to_value(meta)?;
+    for (key, path) in [
+        ("device_authorization_endpoint", "oidc/device_authorization"),
+        ("pushed_authorization_request_endpoint", "oidc/par"),
+        ("introspection_endpoint", "oidc/introspect"),
+        ("revocation_endpoint", "oidc/revoke"),
+    ] {
+        meta[key] = endpoint.join(path)?.to_string().into();
+    }
+    let auth_methods = TokenEndpointAuthMethod::ALL
+        .iter()
+        .map(TokenEndpointAuthMethod::as_str)
+        .collect::<Vec<_>>();
+    let auth_signing_algs = [
+        "HS256", "HS384", "HS512", "RS256", "PS256", "ES256", "EdDSA",
+    ];
+    for endpoint in ["token", "introspection", "revocation"] {
+        meta[format!("{endpoint}_endpoint_auth_methods_supported")] = json!(auth_methods);
+        meta[format!("{endpoint}_endpoint_auth_signing_alg_values_supported")] =
+            json!(auth_signing_algs);
+    }
+    meta["require_pushed_authorization_requests"] = false.into();
+    meta["request_object_signing_alg_values_supported"] = json!(REQUEST_OBJECT_SIGNING_ALGS);
+    meta["dpop_signing_alg_values_supported"] = json!(DPOP_SIGNING_ALGS);
+    meta["grant_types_supported"] = json!(SUPPORTED_GRANT_TYPES);
+
+    Ok(meta)
+}
+
+/// Token request, see [RFC 6749 4.1.3](https://www.rfc-editor.org/rfc/rfc6749#section-4.1.3),
+/// [RFC 6749 6](https://www.rfc-editor.org/rfc/rfc6749#section-6)
+/// and [RFC 8693 2.1](https://www.rfc-editor.org/rfc/rfc8693#section-2.1)
+#[derive(Debug, Deserialize)]
+pub struct TokenRequest {
+    grant_type: String,
+    code: Option<String>,
+    redirect_uri: Option<String>,
+    refresh_token: Option<String>,
+    device_code: Option<String>,
+    /// Parameters of token exchange
+    subject_token: Option<String>,
+    subject_token_type: Option<String>,
+    audience: Option<String>,
+    scope: Option<String>,
+    #[serde(flatten)]
+    client: ClientCredentials,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TokenResponse {
+    access_token: String,
+    /// Type of the issued token of token exchange
+    #[serde(skip_serializing_if = "Option::is_none")]
+    issued_token_type: Option<&'static str>,
+    token_type: &'static str,
+    expires_in: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    refresh_token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id_token: Option<String>,
+    scope: String,
 }
 
-pub async fn auth(
+impl From<IssuedToken> for TokenResponse {
+    fn from(issued: IssuedToken) -> Self {
+        TokenResponse {
+            access_token: issued.access_token,
+            issued_token_type: None,
+            token_type: issued.token_type,
+            expires_in: issued.expires_in,
+            refresh_token: Some(issued.refresh_token),
+            id_token: Some(issued.id_token),
+            scope: issued.scope,
+        }
+    }
+}
+
+impl From<ExchangedToken> for TokenResponse {
+    fn from(exchanged: ExchangedToken) -> Self {
+        TokenResponse {
+            access_token: exchanged.access_token,
+            issued_token_type: Some(ACCESS_TOKEN_TYPE),
+            token_type: exchanged.token_type,
+            expires_in: exchanged.expires_in,
+            refresh_token: None,
+            id_token: None,
+            scope: exchanged.scope,
+        }
+    }
+}
+
+/// Authenticate the client with the method of the app, see
+/// [OpenID Connect Core 9](https://openid.net/specs/openid-connect-core-1_0.html#ClientAuthentication)
+async fn authenticate_client(
+    app: &AppContext<App>,
+    domain: &RequestDomain,
+    basic: Option<TypedHeader<Authorization<Basic>>>,
+    credentials: &ClientCredentials,
+) -> OAuthResult<apps::Model> {
+    let service = app.service::<AppService>();
+    let result = match (&basic, credentials) {
+        (
+            None,
+            ClientCredentials {
+                client_id,
+                client_secret: None,
+                client_assertion_type,
+                client_assertion: Some(assertion),
+            },
+        ) => {
+            if client_assertion_type.as_deref() != Some(CLIENT_ASSERTION_TYPE) {
+                return Err(OAuthError::invalid_request(
+                    "Unsupported client assertion type",
+                ));
+            }
+
+            service
+                .authenticate_client_assertion(client_id.as_deref(), assertion)
+                .await
+        }
+        (
+            Some(TypedHeader(Authorization(basic))),
+            ClientCredentials {
+                client_id: None,
+                client_secret: None,
+                client_assertion: None,
+                ..
+            },
+        ) => {
+            service
+                .authenticate_client(basic.username(), basic.password())
+                .await
+        }
+        (
+            None,
+            ClientCredentials {
+                client_id: Some(client_id),
+                client_secret: Some(client_secret),
+                client_assertion: None,
+                ..
+            },
+        ) => service.authenticate_client(client_id, client_secret).await,
+        (
+            None,
+            ClientCredentials {
+                client_secret: None,
+                client_assertion: None,
+                ..
+            },
+        ) => return Err(OAuthError::invalid_client("Missing client credentials")),
+        _ => {
+            return Err(OAuthError::invalid_request(
+                "Multiple client authentication methods",
+            ))
+        }
+    };
+
+    let client = result.map_err(|err| match err {
+        Error::Unauthorized(reason) => OAuthError::invalid_client(reason),
+        err => err.into(),
+    })?;
+
+    if !domain.allows(client.domain_uuid) {
+        return Err(OAuthError::invalid_client(
+            "The client does not belong to the domain",
+        ));
+    }
+
+    Ok(client)
+}
+
+/// Token endpoint, tokens are bound to the key of the DPoP proof if presented, see
+/// [RFC 9449 5](https://www.rfc-editor.org/rfc/rfc9449#section-5)
+pub async fn token(
     State(app): State<AppContext<App>>,
-    Query(params): Query<AuthenticationRequest>,
-) -> Result<impl IntoResponse> {
-    let config = app.config.get::<AppConfig>("app")?;
+    domain: RequestDomain,
+    basic: Option<TypedHeader<Authorization<Basic>>>,
+    dpop: std::result::Result<DPoP<App>, DPoPRejection>,
+    Form(req): Form<TokenRequest>,
+) -> OAuthResult<impl IntoResponse> {
+    let client = authenticate_client(&app, &domain, basic, &req.client).await?;
+
+    if SUPPORTED_GRANT_TYPES.contains(&req.grant_type.as_str())
+        && !client
+            .setting
+            .oidc_setting
+            .allows_grant_type(&req.grant_type)
+    {
+        return Err(OAuthError::unauthorized_client(format!(
+            "The client is not allowed to use the grant type {}",
+            req.grant_type
+        )));
+    }
 
-    let mut location = config.app_endpoint.join("login")?;
+    let DPoP(proof, _) = dpop?;
+    let jkt = proof.as_ref().map(|proof| proof.jkt.as_str());
+    if client.setting.oidc_setting.dpop_bound_access_tokens && jkt.is_none() {
+        return Err(OAuthError::new(
+            "invalid_dpop_proof",
+            "DPoP proof is required by the client",
+        ));
+    }
 
-    // TODO not finished
+    let response: TokenResponse = match req.grant_type.as_str() {
+        "authorization_code" => {
+            let (Some(code), Some(redirect_uri)) = (req.code, req.redirect_uri) else {
+                return Err(OAuthError::invalid_request(
+                    "Missing authorization code or redirect uri",
+                ));
+            };
 
-    let uuid = Uuid::from_str(&params.client_id)
-        .map_err(|_| Error::BadRequest("Invalid client id".to_string()))?;
+            app.service::<AuthorizationService>()
+                .exchange_code(&client, &code, &redirect_uri, jkt)
+                .await
+                .map_err(|err| match err {
+                    Error::Unauthorized(reason) => OAuthError::invalid_grant(reason),
+                    err => err.into(),
+                })?
+                .into()
+        }
+        "refresh_token" => {
+            let refresh_token = req
+                .refresh_token
+                .ok_or_else(|| OAuthError::invalid_request("Missing refresh token"))?;
 
-    location
-        .query_pairs_mut()
-        .append_pair("app_id", &uuid.to_string());
+            app.service::<Token>()
+                .refresh(&client, &refresh_token, jkt)
+                .await
+                .map_err(|err| match err {
+                    Error::Unauthorized(reason) => OAuthError::invalid_grant(reason),
+                    err => err.into(),
+                })?
+                .into()
+        }
+        DEVICE_CODE_GRANT_TYPE => {
+            let device_code = req
+                .device_code
+                .ok_or_else(|| OAuthError::invalid_request("Missing device code"))?;
+
+            app.service::<Device>()
+                .poll(&client, &device_code, jkt)
+                .await?
+                .into()
+        }
+        TOKEN_EXCHANGE_GRANT_TYPE => {
+            let subject_token = req
+                .subject_token
+                .ok_or_else(|| OAuthError::invalid_request("Missing subject token"))?;
+            if req.subject_token_type.as_deref() != Some(ACCESS_TOKEN_TYPE) {
+                return Err(OAuthError::invalid_request(
+                    "Only access token is supported as subject token",
+                ));
+            }
+            let audience = req
+                .audience
+                .as_deref()
+                .and_then(|audience| Uuid::parse_str(audience).ok())
+                .ok_or_else(|| {
+                    OAuthError::new("invalid_target", "The audience must be a client id")
+                })?;
+
+            app.service::<Token>()
+                .exchange(&client, &subject_token, audience, req.scope.as_deref(), jkt)
+                .await?
+                .into()
+        }
+        grant_type => return Err(OAuthError::unsupported_grant_type(grant_type)),
+    };
+
+    let mut headers = HeaderMap::new();
+    headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    // 返回下一个 nonce，客户端访问资源时使用
+    if let (Some(_), Some(nonce)) = (&proof, app.service::<DPoPService>().nonce()?) {
+        headers.insert(
+            DPOP_NONCE_HEADER,
+            HeaderValue::try_from(nonce).map_err(Error::from)?,
+        );
+    }
+
+    Ok((headers, Json(response)))
+}
+
+/// Device authorization request, see [RFC 8628 3.1](https://www.rfc-editor.org/rfc/rfc8628#section-3.1)
+#[derive(Debug, Deserialize)]
+pub struct DeviceAuthorizationRequest {
+    scope: String,
+    #[serde(flatten)]
+    client: ClientCredentials,
+}
+
+pub async fn device_authorization(
+    State(app): State<AppContext<App>>,
+    domain: RequestDomain,
+    basic: Option<TypedHeader<Authorization<Basic>>>,
+    Form(req): Form<DeviceAuthorizationRequest>,
+) -> OAuthResult<impl IntoResponse> {
+    let client = authenticate_client(&app, &domain, basic, &req.client).await?;
+
+    if !client
+        .setting
+        .oidc_setting
+        .allows_grant_type(DEVICE_CODE_GRANT_TYPE)
+    {
+        return Err(OAuthError::unauthorized_client(
+            "The client is not allowed to use the device authorization grant",
+        ));
+    }
+
+    if !scope_set(&req.scope).contains("openid") {
+        return Err(OAuthError::new(
+            "invalid_scope",
+            "The openid scope is required",
+        ));
+    }
+
+    let response = app
+        .service::<Device>()
+        .authorize(&client, req.scope)
+        .await?;
+
+    Ok((
+        [(CACHE_CONTROL, HeaderValue::from_static("no-store"))],
+        Json(response),
+    ))
+}
+
+/// Pushed authorization request, see [RFC 9126 2.1](https://www.rfc-editor.org/rfc/rfc9126#section-2.1)
+#[derive(Debug, Deserialize)]
+pub struct PushedAuthorizationRequest {
+    #[serde(flatten)]
+    client: ClientCredentials,
+    /// Authorization request parameters, or the `request` object
+    #[serde(flatten)]
+    parameters: HashMap<String, String>,
+}
+
+pub async fn pushed_authorization(
+    State(app): State<AppContext<App>>,
+    domain: RequestDomain,
+    basic: Option<TypedHeader<Authorization<Basic>>>,
+    Form(mut req): Form<PushedAuthorizationRequest>,
+) -> OAuthResult<impl IntoResponse> {
+    let client = authenticate_client(&app, &domain, basic, &req.client).await?;
+
+    if req.parameters.contains_key("request_uri") {
+        return Err(OAuthError::invalid_request(
+            "The request_uri parameter must not be pushed",
+        ));
+    }
+
+    let bad_request = |err| match err {
+        Error::BadRequest(reason) => OAuthError::invalid_request(reason),
+        err => err.into(),
+    };
+
+    // 客户端凭据中的 client_id 已被单独解析，推送的参数需要重新携带
+    req.parameters
+        .insert("client_id".into(), client.uuid.to_string());
+
+    let service = app.service::<AuthorizationService>();
+    let request = service
+        .resolve_request(&client, req.parameters)
+        .await
+        .map_err(bad_request)?;
+    check_redirect_uri(&client, &request).map_err(bad_request)?;
+
+    let response = service.push_request(&client, &request).await?;
+
+    Ok((
+        StatusCode::CREATED,
+        [(CACHE_CONTROL, HeaderValue::from_static("no-store"))],
+        Json(response),
+    ))
+}
+
+/// Introspection request, see [RFC 7662 2.1](https://www.rfc-editor.org/rfc/rfc7662#section-2.1)
+#[derive(Debug, Deserialize)]
+pub struct IntrospectionRequest {
+    token: String,
+    token_type_hint: Option<String>,
+    #[serde(flatten)]
+    client: ClientCredentials,
+}
+
+pub async fn introspect(
+    State(app): State<AppContext<App>>,
+    domain: RequestDomain,
+    basic: Option<TypedHeader<Authorization<Basic>>>,
+    Form(req): Form<IntrospectionRequest>,
+) -> OAuthResult<impl IntoResponse> {
+    let client = authenticate_client(&app, &domain, basic, &req.client).await?;
+
+    let introspection = app
+        .service::<Token>()
+        .introspect(&client, &req.token, req.token_type_hint.as_deref())
+        .await?;
 
     Ok((
-        StatusCode::FOUND,
-        [(
-            LOCATION,
-            HeaderValue::try_from(location.to_string()).expect("URI isn't a valid header value"),
-        )],
+        [(CACHE_CONTROL, HeaderValue::from_static("no-store"))],
+        Json(introspection),
+    ))
+}
+
+/// Revocation request, see [RFC 7009 2.1](https://www.rfc-editor.org/rfc/rfc7009#section-2.1)
+///
+/// `token_type_hint` is ignored, only refresh tokens can be revoked.
+#[derive(Debug, Deserialize)]
+pub struct RevocationRequest {
+    token: String,
+    #[serde(flatten)]
+    client: ClientCredentials,
+}
+
+pub async fn revoke(
+    State(app): State<AppContext<App>>,
+    domain: RequestDomain,
+    basic: Option<TypedHeader<Authorization<Basic>>>,
+    Form(req): Form<RevocationRequest>,
+) -> OAuthResult<StatusCode> {
+    let client = authenticate_client(&app, &domain, basic, &req.client).await?;
+
+    app.service::<Token>().revoke(&client, &req.token).await?;
+
+    Ok(StatusCode::OK)
+}
+
+/// Claims of the user the access token is issued to, see
+/// [OpenID Connect Core 5.3](https://openid.net/specs/openid-connect-core-1_0.html#UserInfo)
+pub async fn userinfo(
+    State(app): State<AppContext<App>>,
+    token: AccessToken,
+) -> Result<impl IntoResponse> {
+    let claims = app.service::<Token>().userinfo(&token).await?;
+
+    Ok((
+        [(CACHE_CONTROL, HeaderValue::from_static("no-store"))],
+        Json(claims),
     ))
 }
 
 pub fn routes() -> Router<App> {
     Router::new()
-        .route(
-            "/app/:appid/oidc/.well-known/openid-configuration",
-            get(openid_configuration),
-        )
-        .route(
-            "/app/login",
-            on(MethodFilter::GET.or(MethodFilter::POST), auth),
-        )
+        .route("/oidc/token", post(token))
+        .route("/oidc/par", post(pushed_authorization))
+        .route("/oidc/introspect", post(introspect))
+        .route("/oidc/revoke", post(revoke))
+        .route("/oidc/userinfo", get(userinfo).post(userinfo))
+        .route("/oidc/device_authorization", post(device_authorization))
+        .route("/oidc/.well-known/openid-configuration", get(discovery))
+        .route("/oidc/.well-known/jwks.json", get(jwks))
+}
+
+/// Discovery of the app is not scoped by the domain of the request
+pub fn app_routes() -> Router<App> {
+    Router::new().route(
+        "/app/:appid/oidc/.well-known/openid-configuration",
+        get(openid_configuration),
+    )
 }
@@ -0,0 +1,139 @@
+use axum_extra::{
+    headers::{authorization::Bearer, Authorization},
+    TypedHeader,
+};
+use inspirer_framework::{
+    axum::response::IntoResponse,
+    extract::{Path, State},
+    http::{header::CACHE_CONTROL, HeaderValue},
+    preludes::*,
+    routing::{get, post},
+};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::{
+    app::App,
+    auth::{
+        oauth::{OAuthError, OAuthResult},
+        registration::{ClientInformation, ClientMetadata},
+    },
+    entity::{apps, client_registrations},
+    service::{registration::Registration, ServiceInterface},
+};
+
+fn bearer(header: Option<TypedHeader<Authorization<Bearer>>>) -> OAuthResult<String> {
+    header
+        .map(|TypedHeader(Authorization(bearer))| bearer.token().to_string())
+        .ok_or_else(|| OAuthError::invalid_token("Missing bearer token"))
+}
+
+fn unauthorized(err: Error) -> OAuthError {
+    match err {
+        Error::Unauthorized(reason) => OAuthError::invalid_token(reason),
+        err => err.into(),
+    }
+}
+
+fn metadata(body: Value) -> OAuthResult<ClientMetadata> {
+    let metadata = serde_json::from_value::<ClientMetadata>(body)
+        .map_err(|err| OAuthError::new("invalid_client_metadata", err.to_string()))?;
+    metadata.validate()?;
+
+    Ok(metadata)
+}
+
+fn respond(status: StatusCode, information: ClientInformation) -> impl IntoResponse {
+    (
+        status,
+        [(CACHE_CONTROL, HeaderValue::from_static("no-store"))],
+        Json(information),
+    )
+}
+
+async fn find_registration(
+    app: &AppContext<App>,
+    client_id: Uuid,
+    header: Option<TypedHeader<Authorization<Bearer>>>,
+) -> OAuthResult<(apps::Model, client_registrations::Model)> {
+    app.service::<Registration>()
+        .find_registration(client_id, &bearer(header)?)
+        .await
+        .map_err(unauthorized)
+}
+
+/// Client registration endpoint, see [RFC 7591 3](https://www.rfc-editor.org/rfc/rfc7591#section-3)
+pub async fn register(
+    State(app): State<AppContext<App>>,
+    header: Option<TypedHeader<Authorization<Bearer>>>,
+    Json(body): Json<Value>,
+) -> OAuthResult<impl IntoResponse> {
+    let service = app.service::<Registration>();
+    let initial_token = service
+        .verify_initial_token(&bearer(header)?)
+        .await
+        .map_err(unauthorized)?;
+
+    let information = service
+        .register(initial_token.domain_uuid, metadata(body)?)
+        .await?;
+
+    Ok(respond(StatusCode::CREATED, information))
+}
+
+/// Client read request, see [RFC 7592 2.1](https://www.rfc-editor.org/rfc/rfc7592#section-2.1)
+pub async fn read(
+    State(app): State<AppContext<App>>,
+    Path((client_id,)): Path<(Uuid,)>,
+    header: Option<TypedHeader<Authorization<Bearer>>>,
+) -> OAuthResult<impl IntoResponse> {
+    let (client, registration) = find_registration(&app, client_id, header).await?;
+    let information = app
+        .service::<Registration>()
+        .information(&client, registration, None)?;
+
+    Ok(respond(StatusCode::OK, information))
+}
+
+/// Client update request, see [RFC 7592 2.2](https://www.rfc-editor.org/rfc/rfc7592#section-2.2)
+pub async fn update(
+    State(app): State<AppContext<App>>,
+    Path((client_id,)): Path<(Uuid,)>,
+    header: Option<TypedHeader<Authorization<Bearer>>>,
+    Json(body): Json<Value>,
+) -> OAuthResult<impl IntoResponse> {
+    let (client, registration) = find_registration(&app, client_id, header).await?;
+
+    if body
+        .get("client_id")
+        .is_some_and(|id| id.as_str() != Some(&client.uuid.to_string()))
+    {
+        return Err(OAuthError::invalid_request("Client id mismatch"));
+    }
+
+    let information = app
+        .service::<Registration>()
+        .update(client, registration, metadata(body)?)
+        .await?;
+
+    Ok(respond(StatusCode::OK, information))
+}
+
+/// Client delete request, see [RFC 7592 2.3](https://www.rfc-editor.org/rfc/rfc7592#section-2.3)
+pub async fn delete(
+    State(app): State<AppContext<App>>,
+    Path((client_id,)): Path<(Uuid,)>,
+    header: Option<TypedHeader<Authorization<Bearer>>>,
+) -> OAuthResult<StatusCode> {
+    let (client, _) = find_registration(&app, client_id, header).await?;
+    app.service::<Registration>().delete(client).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub fn routes() -> Router<App> {
+    Router::new().route("/oidc/register", post(register)).route(
+        "/oidc/register/:client_id",
+        get(read).put(update).delete(delete),
+    )
+}
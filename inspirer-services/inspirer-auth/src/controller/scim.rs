@@ -0,0 +1,390 @@
+use inspirer_framework::{
+    extract::{Path, Query, State},
+    preludes::*,
+    routing::get,
+};
+use serde_json::Value;
+use url::Url;
+use uuid::Uuid;
+
+use crate::{
+    app::App,
+    auth::{
+        audit::ClientInfo,
+        domain::RequestDomain,
+        scim::{
+            patch::PatchRequest, resource_types, schemas, service_provider_config, GroupAttributes,
+            ListQuery, ListResponse, ScimClient, ScimError, ScimJson, ScimResult, UserAttributes,
+        },
+    },
+    config::AppConfig,
+    service::{domain::Domain as DomainService, scim::Scim, ServiceInterface},
+};
+
+/// Base url of the SCIM endpoints of the domain, under the endpoint of the domain
+async fn base(app: &AppContext<App>, domain_uuid: Uuid) -> ScimResult<Url> {
+    let service = app.service::<DomainService>();
+    let domain = service.find_domain_by_uuid(domain_uuid).await?;
+
+    service
+        .endpoint(&domain)?
+        .join("scim/v2/")
+        .map_err(|err| Error::wrap(err).into())
+}
+
+/// Base url of the unauthenticated discovery endpoints, the app endpoint if the request is not
+/// routed to a domain
+fn discovery_base(app: &AppContext<App>, domain: &RequestDomain) -> ScimResult<Url> {
+    let endpoint = match &domain.0 {
+        Some(domain) => app.service::<DomainService>().endpoint(domain)?,
+        None => app.config.get::<AppConfig>("app")?.app_endpoint,
+    };
+
+    endpoint
+        .join("scim/v2/")
+        .map_err(|err| Error::wrap(err).into())
+}
+
+/// Ids which are not valid uuids never match any resource
+fn resource_id(id: &str) -> ScimResult<Uuid> {
+    Uuid::parse_str(id).map_err(|_| ScimError::not_found(format!("Resource {id} not found")))
+}
+
+pub async fn list_users(
+    scim: ScimClient,
+    State(app): State<AppContext<App>>,
+    Query(query): Query<ListQuery>,
+) -> ScimResult<ScimJson<ListResponse>> {
+    let (start_index, count) = query.page();
+    let (total, users) = app
+        .service::<Scim>()
+        .users(
+            scim.domain_uuid,
+            query.filter()?.as_ref(),
+            start_index,
+            count,
+            &base(&app, scim.domain_uuid).await?,
+        )
+        .await?;
+
+    Ok(ScimJson(ListResponse::new(
+        total,
+        start_index,
+        users.into_iter().map(|user| query.project(user)).collect(),
+    )))
+}
+
+pub async fn get_user(
+    scim: ScimClient,
+    State(app): State<AppContext<App>>,
+    Path(id): Path<String>,
+    Query(query): Query<ListQuery>,
+) -> ScimResult<ScimJson<Value>> {
+    let user = app
+        .service::<Scim>()
+        .user(
+            scim.domain_uuid,
+            resource_id(&id)?,
+            &base(&app, scim.domain_uuid).await?,
+        )
+        .await?;
+
+    Ok(ScimJson(query.project(user)))
+}
+
+pub async fn create_user(
+    scim: ScimClient,
+    State(app): State<AppContext<App>>,
+    client_info: ClientInfo,
+    ScimJson(resource): ScimJson<Value>,
+) -> ScimResult<(StatusCode, ScimJson<Value>)> {
+    let user = app
+        .service::<Scim>()
+        .create_user(
+            scim,
+            UserAttributes::from_resource(&resource)?,
+            client_info,
+            &base(&app, scim.domain_uuid).await?,
+        )
+        .await?;
+
+    Ok((StatusCode::CREATED, ScimJson(user)))
+}
+
+pub async fn replace_user(
+    scim: ScimClient,
+    State(app): State<AppContext<App>>,
+    client_info: ClientInfo,
+    Path(id): Path<String>,
+    ScimJson(resource): ScimJson<Value>,
+) -> ScimResult<ScimJson<Value>> {
+    let user = app
+        .service::<Scim>()
+        .replace_user(
+            scim,
+            resource_id(&id)?,
+            UserAttributes::from_resource(&resource)?,
+            client_info,
+            &base(&app, scim.domain_uuid).await?,
+        )
+        .await?;
+
+    Ok(ScimJson(user))
+}
+
+/// Apply the operations to the current representation and replace the user with the result
+pub async fn patch_user(
+    scim: ScimClient,
+    State(app): State<AppContext<App>>,
+    client_info: ClientInfo,
+    Path(id): Path<String>,
+    ScimJson(patch): ScimJson<PatchRequest>,
+) -> ScimResult<ScimJson<Value>> {
+    let uuid = resource_id(&id)?;
+    let base = base(&app, scim.domain_uuid).await?;
+    let service = app.service::<Scim>();
+
+    let mut resource = service.user(scim.domain_uuid, uuid, &base).await?;
+    patch.apply(&mut resource)?;
+
+    let user = service
+        .replace_user(
+            scim,
+            uuid,
+            UserAttributes::from_resource(&resource)?,
+            client_info,
+            &base,
+        )
+        .await?;
+
+    Ok(ScimJson(user))
+}
+
+pub async fn delete_user(
+    scim: ScimClient,
+    State(app): State<AppContext<App>>,
+    client_info: ClientInfo,
+    Path(id): Path<String>,
+) -> ScimResult<StatusCode> {
+    app.service::<Scim>()
+        .delete_user(scim, resource_id(&id)?, client_info)
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn list_groups(
+    scim: ScimClient,
+    State(app): State<AppContext<App>>,
+    Query(query): Query<ListQuery>,
+) -> ScimResult<ScimJson<ListResponse>> {
+    let (start_index, count) = query.page();
+    let (total, groups) = app
+        .service::<Scim>()
+        .groups(
+            scim.domain_uuid,
+            query.filter()?.as_ref(),
+            start_index,
+            count,
+            !query.excludes("members"),
+            &base(&app, scim.domain_uuid).await?,
+        )
+        .await?;
+
+    Ok(ScimJson(ListResponse::new(
+        total,
+        start_index,
+        groups
+            .into_iter()
+            .map(|group| query.project(group))
+            .collect(),
+    )))
+}
+
+pub async fn get_group(
+    scim: ScimClient,
+    State(app): State<AppContext<App>>,
+    Path(id): Path<String>,
+    Query(query): Query<ListQuery>,
+) -> ScimResult<ScimJson<Value>> {
+    let group = app
+        .service::<Scim>()
+        .group(
+            scim.domain_uuid,
+            resource_id(&id)?,
+            !query.excludes("members"),
+            &base(&app, scim.domain_uuid).await?,
+        )
+        .await?;
+
+    Ok(ScimJson(query.project(group)))
+}
+
+pub async fn create_group(
+    scim: ScimClient,
+    State(app): State<AppContext<App>>,
+    client_info: ClientInfo,
+    ScimJson(resource): ScimJson<Value>,
+) -> ScimResult<(StatusCode, ScimJson<Value>)> {
+    let group = app
+        .service::<Scim>()
+        .create_group(
+            scim,
+            GroupAttributes::from_resource(&resource)?,
+            client_info,
+            &base(&app, scim.domain_uuid).await?,
+        )
+        .await?;
+
+    Ok((StatusCode::CREATED, ScimJson(group)))
+}
+
+pub async fn replace_group(
+    scim: ScimClient,
+    State(app): State<AppContext<App>>,
+    client_info: ClientInfo,
+    Path(id): Path<String>,
+    ScimJson(resource): ScimJson<Value>,
+) -> ScimResult<ScimJson<Value>> {
+    let group = app
+        .service::<Scim>()
+        .replace_group(
+            scim,
+            resource_id(&id)?,
+            GroupAttributes::from_resource(&resource)?,
+            client_info,
+            &base(&app, scim.domain_uuid).await?,
+        )
+        .await?;
+
+    Ok(ScimJson(group))
+}
+
+/// Apply the operations to the current representation and replace the group with the result
+pub async fn patch_group(
+    scim: ScimClient,
+    State(app): State<AppContext<App>>,
+    client_info: ClientInfo,
+    Path(id): Path<String>,
+    ScimJson(patch): ScimJson<PatchRequest>,
+) -> ScimResult<ScimJson<Value>> {
+    let uuid = resource_id(&id)?;
+    let base = base(&app, scim.domain_uuid).await?;
+    let service = app.service::<Scim>();
+
+    let mut resource = service.group(scim.domain_uuid, uuid, true, &base).await?;
+    patch.apply(&mut resource)?;
+
+    let group = service
+        .replace_group(
+            scim,
+            uuid,
+            GroupAttributes::from_resource(&resource)?,
+            client_info,
+            &base,
+        )
+        .await?;
+
+    Ok(ScimJson(group))
+}
+
+pub async fn delete_group(
+    scim: ScimClient,
+    State(app): State<AppContext<App>>,
+    client_info: ClientInfo,
+    Path(id): Path<String>,
+) -> ScimResult<StatusCode> {
+    app.service::<Scim>()
+        .delete_group(scim, resource_id(&id)?, client_info)
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn get_service_provider_config(
+    State(app): State<AppContext<App>>,
+    domain: RequestDomain,
+) -> ScimResult<ScimJson<Value>> {
+    Ok(ScimJson(service_provider_config(&discovery_base(
+        &app, &domain,
+    )?)))
+}
+
+pub async fn list_resource_types(
+    State(app): State<AppContext<App>>,
+    domain: RequestDomain,
+) -> ScimResult<ScimJson<ListResponse>> {
+    let resource_types = resource_types(&discovery_base(&app, &domain)?);
+
+    Ok(ScimJson(ListResponse::new(
+        resource_types.len() as u64,
+        1,
+        resource_types,
+    )))
+}
+
+pub async fn get_resource_type(
+    State(app): State<AppContext<App>>,
+    domain: RequestDomain,
+    Path(id): Path<String>,
+) -> ScimResult<ScimJson<Value>> {
+    resource_types(&discovery_base(&app, &domain)?)
+        .into_iter()
+        .find(|resource_type| resource_type["id"] == id.as_str())
+        .map(ScimJson)
+        .ok_or_else(|| ScimError::not_found(format!("Resource type {id} not found")))
+}
+
+pub async fn list_schemas(
+    State(app): State<AppContext<App>>,
+    domain: RequestDomain,
+) -> ScimResult<ScimJson<ListResponse>> {
+    let schemas = schemas(&discovery_base(&app, &domain)?);
+
+    Ok(ScimJson(ListResponse::new(
+        schemas.len() as u64,
+        1,
+        schemas,
+    )))
+}
+
+pub async fn get_schema(
+    State(app): State<AppContext<App>>,
+    domain: RequestDomain,
+    Path(id): Path<String>,
+) -> ScimResult<ScimJson<Value>> {
+    schemas(&discovery_base(&app, &domain)?)
+        .into_iter()
+        .find(|schema| schema["id"] == id.as_str())
+        .map(ScimJson)
+        .ok_or_else(|| ScimError::not_found(format!("Schema {id} not found")))
+}
+
+/// SCIM 2.0 endpoints, the discovery endpoints do not require authentication
+pub fn routes() -> Router<App> {
+    Router::new()
+        .route("/scim/v2/Users", get(list_users).post(create_user))
+        .route(
+            "/scim/v2/Users/:id",
+            get(get_user)
+                .put(replace_user)
+                .patch(patch_user)
+                .delete(delete_user),
+        )
+        .route("/scim/v2/Groups", get(list_groups).post(create_group))
+        .route(
+            "/scim/v2/Groups/:id",
+            get(get_group)
+                .put(replace_group)
+                .patch(patch_group)
+                .delete(delete_group),
+        )
+        .route(
+            "/scim/v2/ServiceProviderConfig",
+            get(get_service_provider_config),
+        )
+        .route("/scim/v2/ResourceTypes", get(list_resource_types))
+        .route("/scim/v2/ResourceTypes/:id", get(get_resource_type))
+        .route("/scim/v2/Schemas", get(list_schemas))
+        .route("/scim/v2/Schemas/:id", get(get_schema))
+}
@@ -13,7 +13,6 @@ pub struct Model {
     #[sea_orm(unique)]
     pub uuid: Uuid,
     pub domain_uuid: Uuid,
-    #[sea_orm(unique)]
     pub name: String,
     pub display_name: String,
     #[tabled(display_with = "crate::helper::base64_encode")]
@@ -3,6 +3,8 @@
 use sea_orm::entity::prelude::*;
 use tabled::Tabled;
 
+use crate::auth::domain::DomainSetting;
+
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Tabled)]
 #[sea_orm(table_name = "domains")]
 pub struct Model {
@@ -14,6 +16,8 @@ pub struct Model {
     pub name: String,
     pub display_name: String,
     pub profile: Json,
+    #[tabled(skip)]
+    pub setting: DomainSetting,
     pub created_at: DateTimeUtc,
     pub updated_at: DateTimeUtc,
 }
@@ -2,6 +2,33 @@
 
 pub mod prelude;
 
+pub mod account_deletions;
+pub mod api_tokens;
 pub mod apps;
+pub mod audit_events;
+pub mod authorization_codes;
+pub mod client_assertions;
+pub mod client_registrations;
+pub mod consents;
+pub mod device_authorizations;
 pub mod domains;
+pub mod dpop_proofs;
+pub mod grants;
+pub mod group_members;
+pub mod group_roles;
+pub mod groups;
+pub mod identity_providers;
+pub mod initial_access_tokens;
+pub mod linked_identities;
+pub mod password_histories;
+pub mod permissions;
+pub mod pushed_authorization_requests;
+pub mod refresh_tokens;
+pub mod role_permissions;
+pub mod roles;
+pub mod user_roles;
+pub mod user_sessions;
 pub mod users;
+pub mod verifications;
+pub mod webhook_dead_letters;
+pub mod webhook_deliveries;
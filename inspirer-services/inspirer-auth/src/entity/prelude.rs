@@ -1,5 +1,32 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.15
 
+pub use super::account_deletions::Entity as AccountDeletions;
+pub use super::api_tokens::Entity as ApiTokens;
 pub use super::apps::Entity as Apps;
+pub use super::audit_events::Entity as AuditEvents;
+pub use super::authorization_codes::Entity as AuthorizationCodes;
+pub use super::client_assertions::Entity as ClientAssertions;
+pub use super::client_registrations::Entity as ClientRegistrations;
+pub use super::consents::Entity as Consents;
+pub use super::device_authorizations::Entity as DeviceAuthorizations;
 pub use super::domains::Entity as Domains;
+pub use super::dpop_proofs::Entity as DpopProofs;
+pub use super::grants::Entity as Grants;
+pub use super::group_members::Entity as GroupMembers;
+pub use super::group_roles::Entity as GroupRoles;
+pub use super::groups::Entity as Groups;
+pub use super::identity_providers::Entity as IdentityProviders;
+pub use super::initial_access_tokens::Entity as InitialAccessTokens;
+pub use super::linked_identities::Entity as LinkedIdentities;
+pub use super::password_histories::Entity as PasswordHistories;
+pub use super::permissions::Entity as Permissions;
+pub use super::pushed_authorization_requests::Entity as PushedAuthorizationRequests;
+pub use super::refresh_tokens::Entity as RefreshTokens;
+pub use super::role_permissions::Entity as RolePermissions;
+pub use super::roles::Entity as Roles;
+pub use super::user_roles::Entity as UserRoles;
+pub use super::user_sessions::Entity as UserSessions;
 pub use super::users::Entity as Users;
+pub use super::verifications::Entity as Verifications;
+pub use super::webhook_dead_letters::Entity as WebhookDeadLetters;
+pub use super::webhook_deliveries::Entity as WebhookDeliveries;
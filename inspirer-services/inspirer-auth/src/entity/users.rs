@@ -3,6 +3,8 @@
 use sea_orm::entity::prelude::*;
 use tabled::Tabled;
 
+use crate::auth::{account_status::AccountStatus, user::UserProfile};
+
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Tabled)]
 #[sea_orm(table_name = "users")]
 pub struct Model {
@@ -11,16 +13,22 @@ pub struct Model {
     #[sea_orm(unique)]
     pub uuid: Uuid,
     pub domain_uuid: Uuid,
-    #[sea_orm(unique)]
     #[tabled(display_with = "crate::helper::display_option")]
     pub email: Option<String>,
-    #[sea_orm(unique)]
     #[tabled(display_with = "crate::helper::display_option")]
     pub username: Option<String>,
     #[tabled(display_with = "crate::helper::display_option")]
     pub phone_number: Option<String>,
+    #[tabled(display_with = "crate::helper::display_option")]
+    pub external_id: Option<String>,
+    pub status: AccountStatus,
+    #[tabled(display_with = "crate::helper::display_option")]
+    pub status_reason: Option<String>,
+    #[tabled(skip)]
+    pub status_changed_at: Option<DateTimeUtc>,
     pub password: String,
-    pub profile: Json,
+    #[tabled(skip)]
+    pub profile: UserProfile,
     pub created_at: DateTimeUtc,
     pub updated_at: DateTimeUtc,
 }
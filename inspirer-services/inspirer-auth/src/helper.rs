@@ -1,7 +1,9 @@
 use std::fmt;
 
 use base64::prelude::*;
+use rand::{rngs::OsRng, RngCore};
 use serde::Serialize;
+use sha2::{Digest, Sha256};
 
 /// Base64 standard encoding
 ///
@@ -28,3 +30,21 @@ pub fn display_option<T: fmt::Display>(o: &Option<T>) -> String {
         None => "None".to_string(),
     }
 }
+
+/// Random opaque token encoded in URL-safe base64 without padding
+pub fn random_token(bytes: usize) -> String {
+    let mut buf = vec![0u8; bytes];
+    OsRng.fill_bytes(&mut buf);
+
+    BASE64_URL_SAFE_NO_PAD.encode(buf)
+}
+
+/// SHA-256 digest in hex, used to store opaque tokens
+pub fn sha256_hex(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+/// Compare two byte slices in constant time
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
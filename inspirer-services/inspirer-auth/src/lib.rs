@@ -1,5 +1,6 @@
 pub mod app;
 pub mod auth;
+pub mod auth_page;
 pub mod command;
 pub mod config;
 pub mod controller;
@@ -2,18 +2,118 @@ use argon2::{
     password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
     Algorithm, Argon2, Params, Version,
 };
+use scrypt::Scrypt;
+use serde::{Deserialize, Serialize};
+
+/// Argon2 parameters used to hash new passwords
+///
+/// Hashes made with other parameters are still verifiable, and will be rehashed
+/// with the current parameters on next successful login.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PasswordHashConfig {
+    /// Memory size in 1 KiB blocks
+    pub m_cost: u32,
+    /// Number of iterations
+    pub t_cost: u32,
+    /// Degree of parallelism
+    pub p_cost: u32,
+}
+
+impl Default for PasswordHashConfig {
+    fn default() -> Self {
+        PasswordHashConfig {
+            m_cost: Params::DEFAULT_M_COST,
+            t_cost: Params::DEFAULT_T_COST,
+            p_cost: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+impl PasswordHashConfig {
+    fn params(&self) -> eyre::Result<Params> {
+        Ok(Params::new(self.m_cost, self.t_cost, self.p_cost, None)?)
+    }
+}
 
 pub fn password_hash<P: AsRef<[u8]>>(password: P) -> eyre::Result<String> {
+    password_hash_with(&PasswordHashConfig::default(), password)
+}
+
+pub fn password_hash_with<P: AsRef<[u8]>>(
+    config: &PasswordHashConfig,
+    password: P,
+) -> eyre::Result<String> {
     let salt = SaltString::generate(&mut OsRng);
-    let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, Params::DEFAULT);
+    let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, config.params()?);
 
     Ok(argon2.hash_password(password.as_ref(), &salt)?.to_string())
 }
 
+/// Verify the password against the hash, argon2 and scrypt hashes in PHC string format and
+/// bcrypt hashes (e.g. imported from legacy systems) are supported
 pub fn password_verify<P: AsRef<[u8]>, H: AsRef<str>>(password: P, hashed: H) -> eyre::Result<()> {
-    let parsed = PasswordHash::new(hashed.as_ref())?;
+    let hashed = hashed.as_ref();
+    if is_bcrypt(hashed) {
+        return match bcrypt::verify(password, hashed)? {
+            true => Ok(()),
+            false => Err(eyre::eyre!("password mismatch")),
+        };
+    }
+
+    let parsed = PasswordHash::new(hashed)?;
+    if parsed.algorithm == scrypt::ALG_ID {
+        return Ok(Scrypt.verify_password(password.as_ref(), &parsed)?);
+    }
+
     let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, Params::DEFAULT);
 
     Ok(argon2.verify_password(password.as_ref(), &parsed)?)
 }
 
+/// Check the hash is one of the formats [password_verify] supports, used to validate
+/// pre-hashed passwords before they are stored
+pub fn password_hash_supported<H: AsRef<str>>(hashed: H) -> bool {
+    let hashed = hashed.as_ref();
+    if is_bcrypt(hashed) {
+        return hashed.len() == 60;
+    }
+
+    let Ok(parsed) = PasswordHash::new(hashed) else {
+        return false;
+    };
+
+    parsed.algorithm == scrypt::ALG_ID
+        || parsed.algorithm == Algorithm::Argon2id.ident()
+        || parsed.algorithm == Algorithm::Argon2i.ident()
+        || parsed.algorithm == Algorithm::Argon2d.ident()
+}
+
+/// bcrypt hashes use the modular crypt format `$2b$<cost>$<salt and hash>` rather than PHC
+fn is_bcrypt(hashed: &str) -> bool {
+    ["$2a$", "$2b$", "$2x$", "$2y$"]
+        .iter()
+        .any(|prefix| hashed.starts_with(prefix))
+}
+
+/// Check whether the hash was made with outdated algorithm or parameters
+pub fn password_needs_rehash<H: AsRef<str>>(config: &PasswordHashConfig, hashed: H) -> bool {
+    let Ok(parsed) = PasswordHash::new(hashed.as_ref()) else {
+        return true;
+    };
+
+    if parsed.algorithm != Algorithm::Argon2id.ident()
+        || parsed.version != Some(Version::V0x13.into())
+    {
+        return true;
+    }
+
+    match (Params::try_from(&parsed), config.params()) {
+        (Ok(current), Ok(expected)) => {
+            current.m_cost() != expected.m_cost()
+                || current.t_cost() != expected.t_cost()
+                || current.p_cost() != expected.p_cost()
+        }
+        _ => true,
+    }
+}
@@ -0,0 +1,204 @@
+use chrono::{TimeDelta, Utc};
+use inspirer_framework::preludes::*;
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, QueryOrder, Set};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    auth::{
+        api_token::{ApiTokenKind, TOKEN_PREFIX_LENGTH},
+        audit::{AuditAction, AuditEvent, AuditOutcome, ClientInfo},
+        consent::{join_scopes, scope_set},
+    },
+    config::AppConfig,
+    entity::api_tokens,
+    helper::{random_token, sha256_hex},
+};
+
+use super::{audit::Audit, Service, ServiceInterface};
+
+pub struct ApiToken;
+
+/// Personal access token or API key to be issued
+#[derive(Debug)]
+pub struct NewApiToken {
+    pub name: String,
+    /// Space-delimited scopes
+    pub scope: String,
+    /// Lifetime in seconds, the token never expires if not set
+    pub expires_in: Option<u64>,
+}
+
+impl Service<ApiToken> {
+    /// Issue a token owned by the user or the app, returns the model and the plaintext token
+    /// which is only available now
+    pub async fn issue(
+        &self,
+        kind: ApiTokenKind,
+        domain_uuid: Uuid,
+        owner_uuid: Uuid,
+        new: NewApiToken,
+        actor_uuid: Option<Uuid>,
+        client: ClientInfo,
+    ) -> Result<(api_tokens::Model, String)> {
+        let name = new.name.trim();
+        if name.is_empty() || name.chars().count() > 100 {
+            return Err(Error::BadRequest(
+                "The name must be 1 to 100 characters".into(),
+            ));
+        }
+
+        let config = self.config.get::<AppConfig>("app")?.api_token;
+        let scopes = scope_set(&new.scope);
+        if let Some(scope) = scopes.iter().find(|scope| !config.scopes.contains(scope)) {
+            return Err(Error::BadRequest(format!("Unknown scope {scope}")));
+        }
+        let scope = join_scopes(&scopes);
+        if scope.len() > 255 {
+            return Err(Error::BadRequest(
+                "The scope must not exceed 255 characters".into(),
+            ));
+        }
+
+        let now = Utc::now();
+        let expires_at = match new.expires_in {
+            Some(expires_in) if expires_in > config.max_lifetime => {
+                return Err(Error::BadRequest(format!(
+                    "The lifetime must not exceed {} seconds",
+                    config.max_lifetime
+                )))
+            }
+            Some(expires_in) => Some(
+                i64::try_from(expires_in)
+                    .ok()
+                    .and_then(|expires_in| now.checked_add_signed(TimeDelta::seconds(expires_in)))
+                    .ok_or_else(|| Error::BadRequest("Invalid lifetime".into()))?,
+            ),
+            None => None,
+        };
+
+        let token = format!("{}{}", kind.marker(), random_token(32));
+
+        let model = api_tokens::ActiveModel {
+            uuid: Set(Uuid::new_v4()),
+            domain_uuid: Set(domain_uuid),
+            kind: Set(kind),
+            owner_uuid: Set(owner_uuid),
+            name: Set(name.to_string()),
+            prefix: Set(token[..TOKEN_PREFIX_LENGTH].to_string()),
+            token: Set(sha256_hex(token.as_bytes())),
+            scope: Set(scope),
+            expires_at: Set(expires_at),
+            last_used_at: Set(None),
+            created_at: Set(now),
+            revoked_at: Set(None),
+            ..Default::default()
+        }
+        .insert(&self.database)
+        .await?;
+
+        self.context
+            .service::<Audit>()
+            .record(AuditEvent {
+                domain_uuid: Some(domain_uuid),
+                actor_uuid,
+                subject_uuid: (kind == ApiTokenKind::PersonalAccessToken).then_some(owner_uuid),
+                client,
+                detail: json!({
+                    "operation": "issue",
+                    "kind": kind,
+                    "owner": owner_uuid,
+                    "token": model.uuid,
+                    "prefix": model.prefix,
+                }),
+                ..AuditEvent::new(AuditAction::ApiTokenChange, AuditOutcome::Success)
+            })
+            .await?;
+
+        Ok((model, token))
+    }
+
+    /// Tokens of the owner which are not revoked, including expired ones
+    pub async fn tokens(
+        &self,
+        kind: ApiTokenKind,
+        owner_uuid: Uuid,
+    ) -> Result<Vec<api_tokens::Model>> {
+        Ok(api_tokens::Entity::find()
+            .filter(api_tokens::Column::Kind.eq(kind))
+            .filter(api_tokens::Column::OwnerUuid.eq(owner_uuid))
+            .filter(api_tokens::Column::RevokedAt.is_null())
+            .order_by_desc(api_tokens::Column::Id)
+            .all(&self.database)
+            .await?)
+    }
+
+    pub async fn revoke(
+        &self,
+        kind: ApiTokenKind,
+        owner_uuid: Uuid,
+        uuid: Uuid,
+        actor_uuid: Option<Uuid>,
+        client: ClientInfo,
+    ) -> Result<()> {
+        let token = api_tokens::Entity::find()
+            .filter(api_tokens::Column::Kind.eq(kind))
+            .filter(api_tokens::Column::OwnerUuid.eq(owner_uuid))
+            .filter(api_tokens::Column::Uuid.eq(uuid))
+            .filter(api_tokens::Column::RevokedAt.is_null())
+            .one(&self.database)
+            .await?
+            .ok_or(Error::NotFound)?;
+
+        api_tokens::Entity::update_many()
+            .col_expr(api_tokens::Column::RevokedAt, Some(Utc::now()).into())
+            .filter(api_tokens::Column::Id.eq(token.id))
+            .exec(&self.database)
+            .await?;
+
+        self.context
+            .service::<Audit>()
+            .record(AuditEvent {
+                domain_uuid: Some(token.domain_uuid),
+                actor_uuid,
+                subject_uuid: (kind == ApiTokenKind::PersonalAccessToken).then_some(owner_uuid),
+                client,
+                detail: json!({
+                    "operation": "revoke",
+                    "kind": kind,
+                    "owner": owner_uuid,
+                    "token": token.uuid,
+                    "prefix": token.prefix,
+                }),
+                ..AuditEvent::new(AuditAction::ApiTokenChange, AuditOutcome::Success)
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Find the token which is neither revoked nor expired, the last used time is updated
+    pub async fn verify(&self, token: &str) -> Result<Option<api_tokens::Model>> {
+        let now = Utc::now();
+        let Some(model) = api_tokens::Entity::find()
+            .filter(api_tokens::Column::Token.eq(sha256_hex(token.as_bytes())))
+            .filter(api_tokens::Column::RevokedAt.is_null())
+            .one(&self.database)
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        if model.expires_at.is_some_and(|expires_at| expires_at <= now) {
+            return Ok(None);
+        }
+
+        api_tokens::Entity::update_many()
+            .col_expr(api_tokens::Column::LastUsedAt, Some(now).into())
+            .filter(api_tokens::Column::Id.eq(model.id))
+            .exec(&self.database)
+            .await?;
+
+        Ok(Some(model))
+    }
+}
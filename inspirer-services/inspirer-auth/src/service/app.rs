@@ -1,5 +1,329 @@
-use super::Service;
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::RwLock,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use inspirer_framework::preludes::*;
+use jsonwebtoken::{decode, decode_header, jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use once_cell::sync::Lazy;
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, Set, SqlErr};
+use url::{Host, Url};
+use uuid::Uuid;
+
+use crate::{
+    auth::client::{ClientAssertion, TokenEndpointAuthMethod},
+    entity::{apps, client_assertions},
+    helper::{base64_encode, constant_time_eq, sha256_hex},
+};
+
+use super::{domain::Domain, Service, ServiceInterface};
+
+const JWKS_FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+const JWKS_CACHE_TTL: Duration = Duration::from_secs(300);
+
+/// JWK Set fetched from `uri` at `fetched_at`
+struct CachedJwks {
+    uri: Url,
+    fetched_at: Instant,
+    jwks: JwkSet,
+}
+
+/// JWK Sets fetched from the JWKS URI of the apps, cached by app
+static JWKS_CACHE: Lazy<RwLock<HashMap<Uuid, CachedJwks>>> = Lazy::new(Default::default);
 
 pub struct App;
 
-impl Service<App> {}
+impl Service<App> {
+    pub async fn find_app_by_uuid(&self, uuid: Uuid) -> Result<apps::Model> {
+        apps::Entity::find()
+            .filter(apps::Column::Uuid.eq(uuid))
+            .one(&self.database)
+            .await?
+            .ok_or(Error::NotFound)
+    }
+
+    /// Authenticate the app as an OAuth client, the client secret is the base64 encoded app secret
+    pub async fn authenticate_client(&self, client_id: &str, secret: &str) -> Result<apps::Model> {
+        let invalid = || Error::Unauthorized("Client authentication failed".into());
+
+        let uuid = client_id.parse::<Uuid>().map_err(|_| invalid())?;
+        let app = self.find_app_by_uuid(uuid).await.map_err(|_| invalid())?;
+
+        if !app
+            .setting
+            .oidc_setting
+            .token_endpoint_auth_method
+            .accepts_secret()
+            || !constant_time_eq(base64_encode(&app.secret).as_bytes(), secret.as_bytes())
+        {
+            return Err(invalid());
+        }
+
+        Ok(app)
+    }
+
+    /// Public key of the app to verify its signed JWTs, the key is selected by `kid` from the
+    /// registered JWK Set or the one fetched from the JWKS URI
+    pub async fn client_public_key(
+        &self,
+        app: &apps::Model,
+        kid: Option<&str>,
+    ) -> Result<DecodingKey> {
+        let invalid = || Error::Unauthorized("No key of the client to verify the signature".into());
+        let setting = &app.setting.oidc_setting;
+
+        let jwks = match (&setting.jwks, &setting.jwks_uri) {
+            (Some(jwks), _) => jwks.clone(),
+            (None, Some(jwks_uri)) => cached_jwks(app.uuid, jwks_uri).await?,
+            (None, None) => return Err(invalid()),
+        };
+        let jwk = match kid {
+            Some(kid) => jwks.find(kid),
+            None if jwks.keys.len() == 1 => jwks.keys.first(),
+            None => None,
+        }
+        .ok_or_else(invalid)?;
+
+        DecodingKey::from_jwk(jwk).map_err(|_| invalid())
+    }
+
+    /// Authenticate the app with the JWT client assertion of `client_secret_jwt` or
+    /// `private_key_jwt`, see [RFC 7523 3](https://www.rfc-editor.org/rfc/rfc7523#section-3)
+    ///
+    /// The audience must be the issuer or the token endpoint, and the `jti` of the assertion is
+    /// recorded until it expires to reject replays.
+    pub async fn authenticate_client_assertion(
+        &self,
+        client_id: Option<&str>,
+        assertion: &str,
+    ) -> Result<apps::Model> {
+        let invalid = || Error::Unauthorized("Client authentication failed".into());
+
+        let header = decode_header(assertion).map_err(|_| invalid())?;
+
+        // 验证签名前需要先从断言中取得客户端，才能确定验证使用的密钥
+        let mut insecure = Validation::new(header.alg);
+        insecure.insecure_disable_signature_validation();
+        insecure.validate_aud = false;
+        let subject =
+            decode::<ClientAssertion>(assertion, &DecodingKey::from_secret(&[]), &insecure)
+                .map_err(|_| invalid())?
+                .claims
+                .sub;
+
+        if client_id.is_some_and(|client_id| client_id != subject) {
+            return Err(invalid());
+        }
+
+        let uuid = subject.parse::<Uuid>().map_err(|_| invalid())?;
+        let app = self.find_app_by_uuid(uuid).await.map_err(|_| invalid())?;
+        let hmac = matches!(
+            header.alg,
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+        );
+        let key = match app.setting.oidc_setting.token_endpoint_auth_method {
+            TokenEndpointAuthMethod::ClientSecretJwt if hmac => {
+                DecodingKey::from_secret(base64_encode(&app.secret).as_bytes())
+            }
+            TokenEndpointAuthMethod::PrivateKeyJwt if !hmac => self
+                .client_public_key(&app, header.kid.as_deref())
+                .await
+                .map_err(|_| invalid())?,
+            _ => return Err(invalid()),
+        };
+
+        let issuer = self
+            .context
+            .service::<Domain>()
+            .issuer_of(app.domain_uuid)
+            .await?;
+        let audience = [issuer.to_string(), format!("{issuer}/token")];
+        let mut validation = Validation::new(header.alg);
+        validation.set_audience(&audience);
+        validation.set_issuer(&[&subject]);
+        validation.sub = Some(subject.clone());
+        validation.set_required_spec_claims(&["exp", "aud", "iss", "sub"]);
+
+        let assertion = decode::<ClientAssertion>(assertion, &key, &validation)
+            .map_err(|_| invalid())?
+            .claims;
+
+        client_assertions::Entity::delete_many()
+            .filter(client_assertions::Column::ExpiresAt.lt(Utc::now()))
+            .exec(&self.database)
+            .await?;
+
+        let expires_at = DateTime::from_timestamp(assertion.exp, 0).ok_or_else(invalid)?;
+        let recorded = client_assertions::ActiveModel {
+            app_uuid: Set(app.uuid),
+            jti: Set(sha256_hex(assertion.jti.as_bytes())),
+            expires_at: Set(expires_at),
+            created_at: Set(Utc::now()),
+            ..Default::default()
+        }
+        .insert(&self.database)
+        .await;
+
+        match recorded {
+            Ok(_) => Ok(app),
+            Err(err) if matches!(err.sql_err(), Some(SqlErr::UniqueConstraintViolation(_))) => {
+                tracing::warn!(client = %app.uuid, "client assertion replayed");
+                Err(Error::Unauthorized("Client assertion has been used".into()))
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+/// The JWK Set of the app from cache, fetched again when it is older than [`JWKS_CACHE_TTL`] or
+/// the JWKS URI has changed
+async fn cached_jwks(app_uuid: Uuid, jwks_uri: &Url) -> Result<JwkSet> {
+    let cached = JWKS_CACHE
+        .read()
+        .unwrap()
+        .get(&app_uuid)
+        .filter(|cached| &cached.uri == jwks_uri && cached.fetched_at.elapsed() < JWKS_CACHE_TTL)
+        .map(|cached| cached.jwks.clone());
+    if let Some(jwks) = cached {
+        return Ok(jwks);
+    }
+
+    let jwks = fetch_jwks(jwks_uri).await?;
+    JWKS_CACHE.write().unwrap().insert(
+        app_uuid,
+        CachedJwks {
+            uri: jwks_uri.clone(),
+            fetched_at: Instant::now(),
+            jwks: jwks.clone(),
+        },
+    );
+
+    Ok(jwks)
+}
+
+/// Fetch the JWK Set over https, the host must resolve to public addresses only and the
+/// connection is pinned to the resolved addresses, redirects are not followed
+async fn fetch_jwks(jwks_uri: &Url) -> Result<JwkSet> {
+    let refused = || Error::Unauthorized("JWKS URI of the client is not allowed".into());
+
+    if jwks_uri.scheme() != "https" {
+        return Err(refused());
+    }
+    let port = jwks_uri.port_or_known_default().ok_or_else(refused)?;
+    let (domain, addrs): (_, Vec<SocketAddr>) = match jwks_uri.host().ok_or_else(refused)? {
+        Host::Domain(domain) => (
+            Some(domain),
+            tokio::net::lookup_host((domain, port))
+                .await
+                .map_err(Error::wrap)?
+                .collect(),
+        ),
+        Host::Ipv4(ip) => (None, vec![SocketAddr::new(ip.into(), port)]),
+        Host::Ipv6(ip) => (None, vec![SocketAddr::new(ip.into(), port)]),
+    };
+    if addrs.is_empty() || !addrs.iter().all(|addr| is_public_ip(addr.ip())) {
+        tracing::warn!(%jwks_uri, "JWKS URI resolves to a non-public address");
+        return Err(refused());
+    }
+
+    let mut client = reqwest::Client::builder()
+        .timeout(JWKS_FETCH_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::none());
+    if let Some(domain) = domain {
+        client = client.resolve_to_addrs(domain, &addrs);
+    }
+
+    client
+        .build()
+        .map_err(Error::wrap)?
+        .get(jwks_uri.clone())
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(Error::wrap)?
+        .json::<JwkSet>()
+        .await
+        .map_err(Error::wrap)
+}
+
+/// Whether the address is reachable on the public internet, loopback, private, link-local and
+/// other special-purpose addresses are not
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                // 0.0.0.0/8, shared address space 100.64.0.0/10, benchmarking 198.18.0.0/15
+                || a == 0
+                || (a == 100 && (b & 0xc0) == 64)
+                || (a == 198 && (b & 0xfe) == 18)
+                || a >= 240)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_ip(ip.into()),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    // unique local fc00::/7, link-local fe80::/10
+                    || (first & 0xfe00) == 0xfc00
+                    || (first & 0xffc0) == 0xfe80)
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn internal_addresses_are_not_public() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "0.0.0.0",
+            "100.64.0.1",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "::ffff:169.254.169.254",
+        ] {
+            assert!(!is_public_ip(ip.parse().unwrap()), "{ip}");
+        }
+
+        for ip in ["93.184.216.34", "8.8.8.8", "2606:4700::1111"] {
+            assert!(is_public_ip(ip.parse().unwrap()), "{ip}");
+        }
+    }
+
+    #[tokio::test]
+    async fn jwks_uri_must_use_https() {
+        let uri = Url::parse("http://93.184.216.34/jwks.json").unwrap();
+        assert!(matches!(
+            fetch_jwks(&uri).await,
+            Err(Error::Unauthorized(_))
+        ));
+
+        let uri = Url::parse("https://127.0.0.1/jwks.json").unwrap();
+        assert!(matches!(
+            fetch_jwks(&uri).await,
+            Err(Error::Unauthorized(_))
+        ));
+    }
+}
@@ -0,0 +1,157 @@
+use chrono::{DateTime, Utc};
+use inspirer_framework::preludes::*;
+use sea_orm::{
+    sea_query::Expr, ColumnTrait, Condition, ConnectionTrait, EntityTrait, QueryFilter, QueryOrder,
+    QuerySelect, Set,
+};
+use uuid::Uuid;
+
+use crate::{
+    auth::audit::{AuditAction, AuditEvent},
+    entity::audit_events,
+};
+
+use super::Service;
+
+pub struct Audit;
+
+/// Filter of audit events, conditions not set are ignored
+#[derive(Debug, Default)]
+pub struct AuditFilter {
+    pub domain_uuid: Option<Uuid>,
+    pub action: Option<AuditAction>,
+    pub actor_uuid: Option<Uuid>,
+    pub subject_uuid: Option<Uuid>,
+    /// Only return events with id less than the cursor, used for pagination
+    pub before: Option<u64>,
+    /// Only return events with id greater than the cursor, used for tailing
+    pub after: Option<u64>,
+    pub limit: u64,
+}
+
+impl Service<Audit> {
+    pub async fn record(&self, event: AuditEvent) -> Result<()> {
+        tracing::debug!(
+            action = %event.action,
+            outcome = %event.outcome,
+            actor = ?event.actor_uuid,
+            subject = ?event.subject_uuid,
+            "record audit event"
+        );
+
+        audit_events::Entity::insert(audit_events::ActiveModel {
+            uuid: Set(Uuid::new_v4()),
+            domain_uuid: Set(event.domain_uuid),
+            action: Set(event.action),
+            outcome: Set(event.outcome),
+            actor_uuid: Set(event.actor_uuid),
+            subject_uuid: Set(event.subject_uuid),
+            ip: Set(event.client.ip),
+            user_agent: Set(event
+                .client
+                .user_agent
+                .map(|agent| agent.chars().take(255).collect())),
+            detail: Set(event.detail),
+            created_at: Set(Utc::now()),
+            ..Default::default()
+        })
+        .exec(&self.database)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Query audit events, newest first
+    pub async fn events(&self, filter: AuditFilter) -> Result<Vec<audit_events::Model>> {
+        let mut query = audit_events::Entity::find();
+
+        if let Some(domain_uuid) = filter.domain_uuid {
+            query = query.filter(audit_events::Column::DomainUuid.eq(domain_uuid));
+        }
+        if let Some(action) = filter.action {
+            query = query.filter(audit_events::Column::Action.eq(action));
+        }
+        if let Some(actor_uuid) = filter.actor_uuid {
+            query = query.filter(audit_events::Column::ActorUuid.eq(actor_uuid));
+        }
+        if let Some(subject_uuid) = filter.subject_uuid {
+            query = query.filter(audit_events::Column::SubjectUuid.eq(subject_uuid));
+        }
+        if let Some(before) = filter.before {
+            query = query.filter(audit_events::Column::Id.lt(before));
+        }
+        if let Some(after) = filter.after {
+            query = query.filter(audit_events::Column::Id.gt(after));
+        }
+
+        Ok(query
+            .order_by_desc(audit_events::Column::Id)
+            .limit(filter.limit)
+            .all(&self.database)
+            .await?)
+    }
+
+    /// Delete events created before the time, returns the number of deleted events
+    pub async fn prune(&self, before: DateTime<Utc>) -> Result<u64> {
+        Ok(audit_events::Entity::delete_many()
+            .filter(audit_events::Column::CreatedAt.lt(before))
+            .exec(&self.database)
+            .await?
+            .rows_affected)
+    }
+
+    /// Anonymise events of the deleted user, returns the number of updated events.
+    ///
+    /// References to the user and client information are removed, so are the login
+    /// identifiers of the user recorded in failed attempts. Runs on the connection of the
+    /// caller, so that the events are anonymised in the same transaction as the deletion.
+    pub async fn anonymise<C: ConnectionTrait>(
+        &self,
+        db: &C,
+        user_uuid: Uuid,
+        identifiers: Vec<String>,
+    ) -> Result<u64> {
+        let mut condition = Condition::any()
+            .add(audit_events::Column::ActorUuid.eq(user_uuid))
+            .add(audit_events::Column::SubjectUuid.eq(user_uuid));
+        if !identifiers.is_empty() {
+            condition = condition.add(
+                Expr::expr(Expr::cust(
+                    "json_unquote(json_extract(`detail`, '$.identifier'))",
+                ))
+                .is_in(identifiers),
+            );
+        }
+
+        let updated = audit_events::Entity::update_many()
+            .col_expr(
+                audit_events::Column::Ip,
+                Expr::value(Option::<String>::None),
+            )
+            .col_expr(
+                audit_events::Column::UserAgent,
+                Expr::value(Option::<String>::None),
+            )
+            .col_expr(
+                audit_events::Column::Detail,
+                Expr::cust("json_remove(`detail`, '$.identifier')"),
+            )
+            .filter(condition)
+            .exec(db)
+            .await?
+            .rows_affected;
+
+        for column in [
+            audit_events::Column::ActorUuid,
+            audit_events::Column::SubjectUuid,
+        ] {
+            audit_events::Entity::update_many()
+                .col_expr(column, Expr::value(Option::<Uuid>::None))
+                .filter(column.eq(user_uuid))
+                .exec(db)
+                .await?;
+        }
+
+        Ok(updated)
+    }
+}
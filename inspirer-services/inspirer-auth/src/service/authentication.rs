@@ -0,0 +1,196 @@
+use inspirer_framework::{http::StatusCode, preludes::*, response::ErrorDetail};
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use serde_json::{json, Map, Value};
+use uuid::Uuid;
+
+use crate::{
+    app::App,
+    auth::{
+        audit::{AuditAction, AuditEvent, AuditOutcome, ClientInfo},
+        authentication::{AuthenticatedPrincipal, AuthenticationMethod},
+        federation::FederationState,
+        user::UserCredential,
+    },
+    entity::{apps, identity_providers, users},
+    password::password_verify,
+};
+
+use super::{
+    audit::Audit, federation::Federation, session::Session, user::User, Service, ServiceInterface,
+};
+
+pub struct Authentication;
+
+/// Authenticator verifies one kind of credential and finds the user of the domain
+#[async_trait::async_trait]
+pub trait Authenticator: Send {
+    /// Method reference recorded in `amr`
+    fn method(&self) -> AuthenticationMethod;
+
+    fn audit_action(&self) -> AuditAction {
+        AuditAction::Login
+    }
+
+    /// Audit detail of the attempt, secrets of the credential must not be included
+    fn audit_detail(&self) -> Map<String, Value> {
+        Map::new()
+    }
+
+    async fn authenticate(
+        self,
+        context: &AppContext<App>,
+        domain_uuid: Uuid,
+    ) -> Result<users::Model>;
+}
+
+/// Username or email with password
+pub struct PasswordAuthenticator(pub UserCredential);
+
+#[async_trait::async_trait]
+impl Authenticator for PasswordAuthenticator {
+    fn method(&self) -> AuthenticationMethod {
+        AuthenticationMethod::Password
+    }
+
+    fn audit_detail(&self) -> Map<String, Value> {
+        Map::from_iter([("identifier".into(), self.0.identifier().into())])
+    }
+
+    async fn authenticate(
+        self,
+        context: &AppContext<App>,
+        domain_uuid: Uuid,
+    ) -> Result<users::Model> {
+        let query = users::Entity::find().filter(users::Column::DomainUuid.eq(domain_uuid));
+        let query = match &self.0 {
+            UserCredential::Username { username, .. } => {
+                query.filter(users::Column::Username.eq(username))
+            }
+            UserCredential::Email { email, .. } => query.filter(users::Column::Email.eq(email)),
+        };
+
+        let user = query
+            .one(&context.database)
+            .await?
+            .ok_or(Error::CustomError(
+                StatusCode::NOT_FOUND,
+                ErrorDetail::with_reason("User not exists"),
+            ))?;
+
+        if password_verify(self.0.password(), &user.password).is_err() {
+            return Err(Error::Unauthorized(
+                "User not exists or password error".into(),
+            ));
+        }
+
+        context
+            .service::<User>()
+            .upgrade_password_hash(user, self.0.password())
+            .await
+    }
+}
+
+/// Authorization response of an upstream identity provider
+pub struct FederatedAuthenticator {
+    pub provider: identity_providers::Model,
+    pub state: FederationState,
+    pub code: String,
+    /// User signed in the domain, the upstream identity is linked to the user if not linked
+    pub current_user: Option<Uuid>,
+}
+
+#[async_trait::async_trait]
+impl Authenticator for FederatedAuthenticator {
+    fn method(&self) -> AuthenticationMethod {
+        AuthenticationMethod::Federated
+    }
+
+    fn audit_action(&self) -> AuditAction {
+        AuditAction::FederatedLogin
+    }
+
+    fn audit_detail(&self) -> Map<String, Value> {
+        Map::from_iter([("provider".into(), json!(self.provider.uuid))])
+    }
+
+    async fn authenticate(
+        self,
+        context: &AppContext<App>,
+        domain_uuid: Uuid,
+    ) -> Result<users::Model> {
+        if self.provider.domain_uuid != domain_uuid {
+            return Err(Error::NotFound);
+        }
+
+        let service = context.service::<Federation>();
+        let identity = service
+            .exchange(&self.provider, self.state, self.code)
+            .await?;
+
+        service
+            .link_or_create_user(&self.provider, identity, self.current_user)
+            .await
+    }
+}
+
+impl Service<Authentication> {
+    /// Authenticate the user of the app's domain with the authenticator, each attempt is
+    /// audited and a session is created once the user is authenticated
+    pub async fn authenticate<A: Authenticator>(
+        &self,
+        app: &apps::Model,
+        authenticator: A,
+        client_info: &ClientInfo,
+    ) -> Result<AuthenticatedPrincipal> {
+        let amr = vec![authenticator.method()];
+        let action = authenticator.audit_action();
+        let mut detail = authenticator.audit_detail();
+        detail.insert("app".into(), json!(app.uuid));
+        detail.insert("amr".into(), json!(amr));
+
+        // 凭证正确但账号状态不可用时同样视为登录失败
+        let user = match authenticator
+            .authenticate(&self.context, app.domain_uuid)
+            .await
+            .and_then(|user| user.status.check().map(|_| user))
+        {
+            Ok(user) => user,
+            Err(err) => {
+                detail.insert("reason".into(), err.to_string().into());
+                self.context
+                    .service::<Audit>()
+                    .record(AuditEvent {
+                        domain_uuid: Some(app.domain_uuid),
+                        client: client_info.clone(),
+                        detail: Value::Object(detail),
+                        ..AuditEvent::new(action, AuditOutcome::Failure)
+                    })
+                    .await?;
+
+                return Err(err);
+            }
+        };
+
+        self.context
+            .service::<Audit>()
+            .record(AuditEvent {
+                domain_uuid: Some(user.domain_uuid),
+                actor_uuid: Some(user.uuid),
+                subject_uuid: Some(user.uuid),
+                client: client_info.clone(),
+                detail: Value::Object(detail),
+                ..AuditEvent::new(action, AuditOutcome::Success)
+            })
+            .await?;
+
+        tracing::debug!(user = %user.uuid, amr = ?amr, "user authenticated");
+
+        let session = self
+            .context
+            .service::<Session>()
+            .create(&user, client_info, &amr)
+            .await?;
+
+        Ok(AuthenticatedPrincipal { user, session, amr })
+    }
+}
@@ -0,0 +1,343 @@
+use std::{collections::HashMap, time::Duration};
+
+use chrono::Utc;
+use inspirer_framework::preludes::*;
+use jsonwebtoken::{decode, decode_header, Algorithm, Validation};
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, Set};
+use serde_json::{Map, Value};
+use url::Url;
+
+use crate::{
+    auth::{
+        ocid::AuthenticationRequest,
+        par::{
+            PushedAuthorizationResponse, REQUEST_OBJECT_MAX_LIFETIME, REQUEST_OBJECT_SIGNING_ALGS,
+            REQUEST_URI_PREFIX,
+        },
+    },
+    entity::{apps, authorization_codes, grants, pushed_authorization_requests},
+    helper::{random_token, sha256_hex},
+};
+
+use super::{
+    app::App,
+    domain::Domain,
+    session::Session,
+    token::{IssuedToken, Token},
+    user::User,
+    Service, ServiceInterface,
+};
+
+pub struct Authorization;
+
+impl Service<Authorization> {
+    /// Issue an authorization code of the grant, returns the code in plain text
+    pub async fn issue_code(
+        &self,
+        app: &apps::Model,
+        grant: &grants::Model,
+        redirect_uri: &Url,
+        nonce: Option<String>,
+    ) -> Result<String> {
+        let code = random_token(32);
+        let now = Utc::now();
+
+        authorization_codes::ActiveModel {
+            code: Set(sha256_hex(code.as_bytes())),
+            grant_uuid: Set(grant.uuid),
+            app_uuid: Set(app.uuid),
+            redirect_uri: Set(redirect_uri.to_string()),
+            nonce: Set(nonce),
+            expires_at: Set(
+                now + Duration::from_secs(app.setting.oidc_setting.authorize_code_expire_in)
+            ),
+            created_at: Set(now),
+            consumed_at: Set(None),
+            ..Default::default()
+        }
+        .insert(&self.database)
+        .await?;
+
+        Ok(code)
+    }
+
+    /// Exchange the authorization code for tokens, the code can be used only once.
+    ///
+    /// Returns `Error::Unauthorized` if the code is invalid, expired, used or not issued to the
+    /// app with the redirect uri. Once a used code is presented again, the grant is revoked.
+    pub async fn exchange_code(
+        &self,
+        app: &apps::Model,
+        code: &str,
+        redirect_uri: &str,
+        jkt: Option<&str>,
+    ) -> Result<IssuedToken> {
+        let invalid = || Error::Unauthorized("Invalid authorization code".into());
+
+        let stored = authorization_codes::Entity::find()
+            .filter(authorization_codes::Column::Code.eq(sha256_hex(code.as_bytes())))
+            .one(&self.database)
+            .await?
+            .ok_or_else(invalid)?;
+
+        if stored.app_uuid != app.uuid
+            || stored.redirect_uri != redirect_uri
+            || stored.expires_at < Utc::now()
+        {
+            return Err(invalid());
+        }
+
+        let consumed = authorization_codes::Entity::update_many()
+            .col_expr(authorization_codes::Column::ConsumedAt, Utc::now().into())
+            .filter(authorization_codes::Column::Id.eq(stored.id))
+            .filter(authorization_codes::Column::ConsumedAt.is_null())
+            .exec(&self.database)
+            .await?
+            .rows_affected;
+
+        let sessions = self.context.service::<Session>();
+        let grant = sessions
+            .find_active_grant(stored.grant_uuid)
+            .await
+            .map_err(|_| invalid())?;
+
+        if consumed == 0 {
+            // 授权码被重复使用，可能已泄露，撤销由它签发的所有 token
+            tracing::warn!(grant = %grant.uuid, "authorization code reused, revoke the grant");
+            sessions.revoke_grant(grant.user_uuid, grant.uuid).await?;
+
+            return Err(invalid());
+        }
+
+        let user = self
+            .context
+            .service::<User>()
+            .find_user_by_uuid(grant.user_uuid)
+            .await?;
+
+        self.context
+            .service::<Token>()
+            .issue(app, &user, &grant, stored.nonce, jkt)
+            .await
+    }
+
+    /// Resolve the authentication request of the client from the request parameters,
+    /// the parameters of the signed request object are used if `request` is present, see
+    /// [RFC 9101 4](https://www.rfc-editor.org/rfc/rfc9101#section-4)
+    ///
+    /// Returns `Error::BadRequest` if the parameters or the request object are invalid.
+    pub async fn resolve_request(
+        &self,
+        client: &apps::Model,
+        mut parameters: HashMap<String, String>,
+    ) -> Result<AuthenticationRequest> {
+        let parameters = match parameters.remove("request") {
+            Some(request_object) => self.decode_request_object(client, &request_object).await?,
+            None => parameters
+                .into_iter()
+                .map(|(key, value)| (key, Value::String(value)))
+                .collect(),
+        };
+
+        let request = serde_json::from_value::<AuthenticationRequest>(Value::Object(parameters))
+            .map_err(|err| Error::BadRequest(format!("Invalid authorization request: {err}")))?;
+
+        if request.client_id != client.uuid.to_string() {
+            return Err(Error::BadRequest("Client id mismatch".into()));
+        }
+
+        Ok(request)
+    }
+
+    /// Verify the request object with the public key of the client, returns its claims
+    async fn decode_request_object(
+        &self,
+        client: &apps::Model,
+        request_object: &str,
+    ) -> Result<Map<String, Value>> {
+        let invalid = || Error::BadRequest("Invalid request object".into());
+
+        let header = decode_header(request_object).map_err(|_| invalid())?;
+        if !REQUEST_OBJECT_SIGNING_ALGS
+            .iter()
+            .any(|alg| alg.parse::<Algorithm>().ok() == Some(header.alg))
+        {
+            return Err(invalid());
+        }
+
+        let key = self
+            .context
+            .service::<App>()
+            .client_public_key(client, header.kid.as_deref())
+            .await
+            .map_err(|_| invalid())?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_audience(&[self
+            .context
+            .service::<Domain>()
+            .issuer_of(client.domain_uuid)
+            .await?]);
+        validation.set_issuer(&[client.uuid]);
+        validation.set_required_spec_claims(&["exp", "aud", "iss"]);
+
+        let claims = decode::<Map<String, Value>>(request_object, &key, &validation)
+            .map_err(|_| invalid())?
+            .claims;
+
+        // 限制请求对象的有效期，避免长期有效的请求对象被重放
+        let exp = claims
+            .get("exp")
+            .and_then(Value::as_i64)
+            .ok_or_else(invalid)?;
+        let issued_at = claims
+            .get("iat")
+            .and_then(Value::as_i64)
+            .unwrap_or_else(|| Utc::now().timestamp());
+        if exp - issued_at.min(Utc::now().timestamp()) > REQUEST_OBJECT_MAX_LIFETIME {
+            return Err(Error::BadRequest(
+                "The lifetime of the request object is too long".into(),
+            ));
+        }
+
+        Ok(claims)
+    }
+
+    /// Store the authentication request pushed by the client, see
+    /// [RFC 9126 2](https://www.rfc-editor.org/rfc/rfc9126#section-2)
+    pub async fn push_request(
+        &self,
+        client: &apps::Model,
+        request: &AuthenticationRequest,
+    ) -> Result<PushedAuthorizationResponse> {
+        let expires_in = client.setting.oidc_setting.pushed_request_expire_in;
+        let reference = random_token(32);
+
+        pushed_authorization_requests::Entity::delete_many()
+            .filter(pushed_authorization_requests::Column::ExpiresAt.lt(Utc::now()))
+            .exec(&self.database)
+            .await?;
+
+        pushed_authorization_requests::ActiveModel {
+            request_uri: Set(sha256_hex(reference.as_bytes())),
+            app_uuid: Set(client.uuid),
+            parameters: Set(serde_json::to_value(request)?),
+            expires_at: Set(Utc::now() + Duration::from_secs(expires_in)),
+            created_at: Set(Utc::now()),
+            ..Default::default()
+        }
+        .insert(&self.database)
+        .await?;
+
+        Ok(PushedAuthorizationResponse {
+            request_uri: format!("{REQUEST_URI_PREFIX}{reference}"),
+            expires_in,
+        })
+    }
+
+    /// Take the pushed request of the client by the request uri, the request uri can be used
+    /// only once
+    pub async fn take_pushed_request(
+        &self,
+        client: &apps::Model,
+        request_uri: &str,
+    ) -> Result<AuthenticationRequest> {
+        let invalid = || Error::BadRequest("Invalid or expired request uri".into());
+
+        let reference = request_uri
+            .strip_prefix(REQUEST_URI_PREFIX)
+            .ok_or_else(invalid)?;
+        let stored = pushed_authorization_requests::Entity::find()
+            .filter(
+                pushed_authorization_requests::Column::RequestUri
+                    .eq(sha256_hex(reference.as_bytes())),
+            )
+            .filter(pushed_authorization_requests::Column::AppUuid.eq(client.uuid))
+            .filter(pushed_authorization_requests::Column::ExpiresAt.gt(Utc::now()))
+            .one(&self.database)
+            .await?
+            .ok_or_else(invalid)?;
+
+        let deleted = pushed_authorization_requests::Entity::delete_by_id(stored.id)
+            .exec(&self.database)
+            .await?
+            .rows_affected;
+        if deleted == 0 {
+            return Err(invalid());
+        }
+
+        Ok(serde_json::from_value(stored.parameters)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::prelude::*;
+    use chrono::Utc;
+    use crypto_utils::{p256::P256, KeyPair, KeyPairTrait};
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use serde_json::json;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::{
+        auth::application::AppSetting,
+        service::testing::{context, ScriptedDatabase},
+    };
+
+    /// Client with a registered P-256 key
+    fn client() -> apps::Model {
+        let key_pair = KeyPair::<P256>::generate().unwrap();
+        let mut setting = AppSetting::default();
+        setting.oidc_setting.jwks =
+            Some(serde_json::from_value(json!({ "keys": [key_pair.get_jwks()] })).unwrap());
+
+        apps::Model {
+            id: 1,
+            uuid: Uuid::new_v4(),
+            domain_uuid: Uuid::new_v4(),
+            name: "wiki".into(),
+            display_name: "Wiki".into(),
+            secret: b"client-secret".to_vec(),
+            profile: json!({}),
+            setting,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn request_objects_of_disallowed_algs_are_refused() {
+        let client = client();
+        let claims = json!({
+            "iss": client.uuid,
+            "client_id": client.uuid,
+            "exp": Utc::now().timestamp() + 60,
+        });
+        let hs256 = encode(
+            &Header::new(Algorithm::HS256),
+            &claims,
+            &EncodingKey::from_secret(&client.secret),
+        )
+        .unwrap();
+        // 只检查头部的算法，签名不会被验证
+        let es384 = format!(
+            "{}.{}.c2lnbmF0dXJl",
+            BASE64_URL_SAFE_NO_PAD.encode(r#"{"alg":"ES384","typ":"JWT"}"#),
+            BASE64_URL_SAFE_NO_PAD.encode(claims.to_string()),
+        );
+
+        let (context, database) = context(ScriptedDatabase::default(), "").await;
+        let service = context.service::<Authorization>();
+        for request_object in [hs256, es384] {
+            assert!(matches!(
+                service
+                    .decode_request_object(&client, &request_object)
+                    .await,
+                Err(Error::BadRequest(_))
+            ));
+        }
+        // 在查询签发者之前拒绝
+        assert!(database.statements().is_empty());
+    }
+}
@@ -0,0 +1,95 @@
+use chrono::Utc;
+use inspirer_framework::preludes::*;
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, IntoActiveModel, QueryFilter, Set};
+use uuid::Uuid;
+
+use crate::{
+    auth::consent::{join_scopes, scope_set},
+    entity::consents,
+};
+
+use super::{session::Session, Service, ServiceInterface};
+
+pub struct Consent;
+
+impl Service<Consent> {
+    pub async fn find_consent(
+        &self,
+        user_uuid: Uuid,
+        app_uuid: Uuid,
+    ) -> Result<Option<consents::Model>> {
+        Ok(consents::Entity::find()
+            .filter(consents::Column::UserUuid.eq(user_uuid))
+            .filter(consents::Column::AppUuid.eq(app_uuid))
+            .one(&self.database)
+            .await?)
+    }
+
+    /// Whether the user has approved all the scopes for the app
+    pub async fn is_covered(&self, user_uuid: Uuid, app_uuid: Uuid, scope: &str) -> Result<bool> {
+        let Some(consent) = self.find_consent(user_uuid, app_uuid).await? else {
+            return Ok(false);
+        };
+
+        Ok(scope_set(scope).is_subset(&scope_set(&consent.scope)))
+    }
+
+    /// Record the approved scopes, which are merged into the scopes approved before
+    pub async fn approve(
+        &self,
+        user_uuid: Uuid,
+        app_uuid: Uuid,
+        scope: &str,
+    ) -> Result<consents::Model> {
+        let consent = match self.find_consent(user_uuid, app_uuid).await? {
+            Some(consent) => {
+                let mut scopes = scope_set(&consent.scope);
+                scopes.extend(scope_set(scope));
+
+                let mut consent = consent.into_active_model();
+                consent.scope = Set(join_scopes(&scopes));
+                consent.updated_at = Set(Utc::now());
+                consent.update(&self.database).await?
+            }
+            None => {
+                consents::ActiveModel {
+                    uuid: Set(Uuid::new_v4()),
+                    user_uuid: Set(user_uuid),
+                    app_uuid: Set(app_uuid),
+                    scope: Set(join_scopes(&scope_set(scope))),
+                    created_at: Set(Utc::now()),
+                    updated_at: Set(Utc::now()),
+                    ..Default::default()
+                }
+                .insert(&self.database)
+                .await?
+            }
+        };
+
+        Ok(consent)
+    }
+
+    pub async fn consents(&self, user_uuid: Uuid) -> Result<Vec<consents::Model>> {
+        Ok(consents::Entity::find()
+            .filter(consents::Column::UserUuid.eq(user_uuid))
+            .all(&self.database)
+            .await?)
+    }
+
+    /// Withdraw the consent of the app, the app's grants of the user are revoked as well
+    pub async fn withdraw(&self, user_uuid: Uuid, app_uuid: Uuid) -> Result<()> {
+        let consent = self
+            .find_consent(user_uuid, app_uuid)
+            .await?
+            .ok_or(Error::NotFound)?;
+
+        consents::Entity::delete_by_id(consent.id)
+            .exec(&self.database)
+            .await?;
+
+        self.context
+            .service::<Session>()
+            .revoke_app_grants(app_uuid, Some(user_uuid))
+            .await
+    }
+}
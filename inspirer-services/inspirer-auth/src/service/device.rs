@@ -0,0 +1,410 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use inspirer_framework::preludes::*;
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, IntoActiveModel, QueryFilter, Set};
+
+use crate::{
+    auth::{
+        consent::scope_set,
+        device::{
+            display_user_code, generate_user_code, normalize_user_code,
+            DeviceAuthorizationResponse, DeviceAuthorizationStatus, DeviceDetails,
+            POLLING_INTERVAL,
+        },
+        oauth::{OAuthError, OAuthResult},
+    },
+    config::AppConfig,
+    entity::{apps, device_authorizations, user_sessions},
+    helper::{random_token, sha256_hex},
+};
+
+use super::{
+    app::App,
+    consent::Consent,
+    session::Session,
+    token::{IssuedToken, Token},
+    user::User,
+    Service, ServiceInterface,
+};
+
+pub struct Device;
+
+impl Service<Device> {
+    /// Start a device authorization of the app, expired authorizations are cleaned up
+    pub async fn authorize(
+        &self,
+        app: &apps::Model,
+        scope: String,
+    ) -> Result<DeviceAuthorizationResponse> {
+        let config = self.config.get::<AppConfig>("app")?;
+        let expires_in = app.setting.oidc_setting.device_code_expire_in;
+
+        device_authorizations::Entity::delete_many()
+            .filter(device_authorizations::Column::ExpiresAt.lt(Utc::now()))
+            .exec(&self.database)
+            .await?;
+
+        let device_code = random_token(32);
+        let user_code = generate_user_code();
+
+        device_authorizations::ActiveModel {
+            device_code: Set(sha256_hex(device_code.as_bytes())),
+            user_code: Set(user_code.clone()),
+            app_uuid: Set(app.uuid),
+            scope: Set(scope),
+            status: Set(DeviceAuthorizationStatus::Pending),
+            grant_uuid: Set(None),
+            interval: Set(POLLING_INTERVAL),
+            last_polled_at: Set(None),
+            expires_at: Set(Utc::now() + Duration::from_secs(expires_in)),
+            created_at: Set(Utc::now()),
+            ..Default::default()
+        }
+        .insert(&self.database)
+        .await?;
+
+        let verification_uri = config.app_endpoint.join("device")?;
+        let mut verification_uri_complete = verification_uri.clone();
+        verification_uri_complete
+            .query_pairs_mut()
+            .append_pair("user_code", &display_user_code(&user_code));
+
+        Ok(DeviceAuthorizationResponse {
+            device_code,
+            user_code: display_user_code(&user_code),
+            verification_uri,
+            verification_uri_complete,
+            expires_in,
+            interval: POLLING_INTERVAL,
+        })
+    }
+
+    /// Find the pending authorization of the user code which is not expired
+    pub async fn find_pending(&self, user_code: &str) -> Result<device_authorizations::Model> {
+        device_authorizations::Entity::find()
+            .filter(device_authorizations::Column::UserCode.eq(normalize_user_code(user_code)))
+            .filter(device_authorizations::Column::Status.eq(DeviceAuthorizationStatus::Pending))
+            .filter(device_authorizations::Column::ExpiresAt.gt(Utc::now()))
+            .one(&self.database)
+            .await?
+            .ok_or(Error::NotFound)
+    }
+
+    pub async fn details(&self, user_code: &str) -> Result<DeviceDetails> {
+        let authorization = self.find_pending(user_code).await?;
+        let app = self
+            .context
+            .service::<App>()
+            .find_app_by_uuid(authorization.app_uuid)
+            .await?;
+
+        Ok(DeviceDetails {
+            user_code: display_user_code(&authorization.user_code),
+            app_uuid: app.uuid,
+            app_name: app.display_name,
+            scopes: scope_set(&authorization.scope).into_iter().collect(),
+        })
+    }
+
+    /// Approve or deny the device authorization in the session of the user,
+    /// the approved scopes are recorded as consent of the user
+    pub async fn decide(
+        &self,
+        user_session: &user_sessions::Model,
+        user_code: &str,
+        approve: bool,
+    ) -> Result<()> {
+        let authorization = self.find_pending(user_code).await?;
+        let app = self
+            .context
+            .service::<App>()
+            .find_app_by_uuid(authorization.app_uuid)
+            .await?;
+        if app.domain_uuid != user_session.domain_uuid {
+            return Err(Error::Unauthorized(
+                "The user does not belong to the domain of the app".into(),
+            ));
+        }
+
+        let (status, grant_uuid) = if approve {
+            let grant = self
+                .context
+                .service::<Session>()
+                .grant(
+                    user_session,
+                    authorization.app_uuid,
+                    authorization.scope.clone(),
+                    None,
+                )
+                .await?;
+            self.context
+                .service::<Consent>()
+                .approve(
+                    user_session.user_uuid,
+                    authorization.app_uuid,
+                    &authorization.scope,
+                )
+                .await?;
+
+            (DeviceAuthorizationStatus::Approved, Some(grant.uuid))
+        } else {
+            (DeviceAuthorizationStatus::Denied, None)
+        };
+
+        let mut authorization = authorization.into_active_model();
+        authorization.status = Set(status);
+        authorization.grant_uuid = Set(grant_uuid);
+        authorization.update(&self.database).await?;
+
+        Ok(())
+    }
+
+    /// Poll the device authorization, tokens are issued once the user approved,
+    /// see [RFC 8628 3.5](https://www.rfc-editor.org/rfc/rfc8628#section-3.5)
+    pub async fn poll(
+        &self,
+        app: &apps::Model,
+        device_code: &str,
+        jkt: Option<&str>,
+    ) -> OAuthResult<IssuedToken> {
+        let authorization = device_authorizations::Entity::find()
+            .filter(
+                device_authorizations::Column::DeviceCode.eq(sha256_hex(device_code.as_bytes())),
+            )
+            .filter(device_authorizations::Column::AppUuid.eq(app.uuid))
+            .one(&self.database)
+            .await
+            .map_err(Error::from)?
+            .ok_or_else(|| OAuthError::invalid_grant("Invalid device code"))?;
+
+        if authorization.expires_at < Utc::now() {
+            return Err(OAuthError::new(
+                "expired_token",
+                "The device code has expired",
+            ));
+        }
+
+        match authorization.status {
+            DeviceAuthorizationStatus::Pending => {
+                let now = Utc::now();
+                let too_fast = authorization.last_polled_at.is_some_and(|polled_at| {
+                    now < polled_at + chrono::Duration::seconds(authorization.interval as i64)
+                });
+                let interval = authorization.interval;
+
+                let mut authorization = authorization.into_active_model();
+                authorization.last_polled_at = Set(Some(now));
+                if too_fast {
+                    authorization.interval = Set(interval + 5);
+                }
+                authorization
+                    .update(&self.database)
+                    .await
+                    .map_err(Error::from)?;
+
+                Err(if too_fast {
+                    OAuthError::new("slow_down", "Polling too frequently")
+                } else {
+                    OAuthError::new(
+                        "authorization_pending",
+                        "The user has not yet completed the authorization",
+                    )
+                })
+            }
+            DeviceAuthorizationStatus::Denied => Err(OAuthError::new(
+                "access_denied",
+                "The user denied the authorization",
+            )),
+            DeviceAuthorizationStatus::Consumed => {
+                Err(OAuthError::invalid_grant("The device code has been used"))
+            }
+            DeviceAuthorizationStatus::Approved => {
+                // 只有成功将状态改为已使用的请求才能签发 token，避免并发轮询重复签发
+                let consumed = device_authorizations::Entity::update_many()
+                    .col_expr(
+                        device_authorizations::Column::Status,
+                        DeviceAuthorizationStatus::Consumed.into(),
+                    )
+                    .filter(device_authorizations::Column::Id.eq(authorization.id))
+                    .filter(
+                        device_authorizations::Column::Status
+                            .eq(DeviceAuthorizationStatus::Approved),
+                    )
+                    .exec(&self.database)
+                    .await
+                    .map_err(Error::from)?
+                    .rows_affected;
+
+                if consumed == 0 {
+                    return Err(OAuthError::invalid_grant("The device code has been used"));
+                }
+
+                let grant = self
+                    .context
+                    .service::<Session>()
+                    .find_active_grant(authorization.grant_uuid.ok_or(Error::NotFound)?)
+                    .await
+                    .map_err(|_| OAuthError::invalid_grant("The grant has been revoked"))?;
+                let user = self
+                    .context
+                    .service::<User>()
+                    .find_user_by_uuid(grant.user_uuid)
+                    .await?;
+
+                Ok(self
+                    .context
+                    .service::<Token>()
+                    .issue(app, &user, &grant, None, jkt)
+                    .await?)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::service::testing::{context, ScriptedDatabase};
+
+    const DEVICE_CODE: &str = "device-code";
+
+    fn app() -> apps::Model {
+        apps::Model {
+            id: 1,
+            uuid: Uuid::new_v4(),
+            domain_uuid: Uuid::new_v4(),
+            name: "tv".into(),
+            display_name: "TV".into(),
+            secret: vec![],
+            profile: serde_json::json!({}),
+            setting: Default::default(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn authorization(
+        app: &apps::Model,
+        status: DeviceAuthorizationStatus,
+    ) -> device_authorizations::Model {
+        device_authorizations::Model {
+            id: 1,
+            device_code: sha256_hex(DEVICE_CODE.as_bytes()),
+            user_code: "BCDFGHJK".into(),
+            app_uuid: app.uuid,
+            scope: "openid".into(),
+            status,
+            grant_uuid: Some(Uuid::new_v4()),
+            interval: POLLING_INTERVAL,
+            last_polled_at: None,
+            expires_at: Utc::now() + chrono::Duration::minutes(10),
+            created_at: Utc::now(),
+        }
+    }
+
+    async fn poll(database: ScriptedDatabase, app: &apps::Model) -> (OAuthError, Vec<String>) {
+        let (context, database) = context(database, "").await;
+        let error = context
+            .service::<Device>()
+            .poll(app, DEVICE_CODE, None)
+            .await
+            .unwrap_err();
+        assert!(database.exhausted());
+
+        (error, database.statements())
+    }
+
+    #[tokio::test]
+    async fn pending_authorization_is_reported() {
+        let app = app();
+        let pending = authorization(&app, DeviceAuthorizationStatus::Pending);
+        let polled = device_authorizations::Model {
+            last_polled_at: Some(Utc::now()),
+            ..pending.clone()
+        };
+
+        let (error, statements) = poll(
+            ScriptedDatabase::default()
+                .rows([pending])
+                // 记录轮询时间，MySQL 更新后重新读取
+                .affected(1)
+                .rows([polled]),
+            &app,
+        )
+        .await;
+
+        assert_eq!(error.error, "authorization_pending");
+        assert!(statements[1].starts_with("UPDATE `device_authorizations` SET"));
+        assert!(statements[1].contains("`last_polled_at` = "));
+        assert!(!statements[1].contains("`interval` = "));
+    }
+
+    #[tokio::test]
+    async fn polling_faster_than_the_interval_slows_down() {
+        let app = app();
+        let pending = device_authorizations::Model {
+            last_polled_at: Some(Utc::now() - chrono::Duration::seconds(1)),
+            ..authorization(&app, DeviceAuthorizationStatus::Pending)
+        };
+        let slowed = device_authorizations::Model {
+            interval: POLLING_INTERVAL + 5,
+            ..pending.clone()
+        };
+
+        let (error, statements) = poll(
+            ScriptedDatabase::default()
+                .rows([pending])
+                .affected(1)
+                .rows([slowed]),
+            &app,
+        )
+        .await;
+
+        assert_eq!(error.error, "slow_down");
+        assert!(statements[1].contains(&format!("`interval` = {}", POLLING_INTERVAL + 5)));
+    }
+
+    #[tokio::test]
+    async fn expired_device_code_is_refused() {
+        let app = app();
+        let expired = device_authorizations::Model {
+            expires_at: Utc::now() - chrono::Duration::seconds(1),
+            ..authorization(&app, DeviceAuthorizationStatus::Approved)
+        };
+
+        let (error, statements) = poll(ScriptedDatabase::default().rows([expired]), &app).await;
+
+        assert_eq!(error.error, "expired_token");
+        assert_eq!(statements.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn device_code_is_redeemed_once() {
+        let app = app();
+
+        // 并发轮询时另一个请求已将状态改为已使用
+        let (error, statements) = poll(
+            ScriptedDatabase::default()
+                .rows([authorization(&app, DeviceAuthorizationStatus::Approved)])
+                .affected(0),
+            &app,
+        )
+        .await;
+        assert_eq!(error.error, "invalid_grant");
+        assert!(statements[1].contains("`status` = 'consumed'"));
+        assert!(statements[1].contains("`status` = 'approved'"));
+        assert_eq!(statements.len(), 2);
+
+        let (error, statements) = poll(
+            ScriptedDatabase::default()
+                .rows([authorization(&app, DeviceAuthorizationStatus::Consumed)]),
+            &app,
+        )
+        .await;
+        assert_eq!(error.error, "invalid_grant");
+        assert_eq!(statements.len(), 1);
+    }
+}
@@ -0,0 +1,168 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+
+use inspirer_framework::{http::uri::Authority, preludes::*};
+use once_cell::sync::Lazy;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use url::{Host, Url};
+use uuid::Uuid;
+
+use crate::{config::AppConfig, entity::domains};
+
+use super::Service;
+
+/// How long the hosts of the domain endpoints are cached, a changed endpoint takes effect once
+/// the cache expires
+const ENDPOINT_HOSTS_TTL: Duration = Duration::from_secs(60);
+
+/// Domain of the host of each domain endpoint
+struct EndpointHosts {
+    loaded_at: Instant,
+    domains: Arc<HashMap<Host, Uuid>>,
+}
+
+static ENDPOINT_HOSTS: Lazy<RwLock<Option<EndpointHosts>>> = Lazy::new(Default::default);
+
+pub struct Domain;
+
+impl Service<Domain> {
+    pub async fn find_domain_by_uuid(&self, uuid: Uuid) -> Result<domains::Model> {
+        domains::Entity::find()
+            .filter(domains::Column::Uuid.eq(uuid))
+            .one(&self.database)
+            .await?
+            .ok_or(Error::NotFound)
+    }
+
+    pub async fn find_domain_by_name(&self, name: &str) -> Result<domains::Model> {
+        domains::Entity::find()
+            .filter(domains::Column::Name.eq(name))
+            .one(&self.database)
+            .await?
+            .ok_or(Error::NotFound)
+    }
+
+    /// The default domain is the one named after the app name, created by `app:init`
+    pub async fn default_domain(&self) -> Result<domains::Model> {
+        let config = self.config.get::<AppConfig>("app")?;
+
+        self.find_domain_by_name(&config.app_name).await
+    }
+
+    /// Find the domain whose endpoint is served on the host, the port of the host is ignored
+    pub async fn find_domain_by_host(&self, host: &str) -> Result<Option<domains::Model>> {
+        let Some(host) = parse_host(host) else {
+            return Ok(None);
+        };
+
+        let domain_uuid = match self.endpoint_hosts().await?.get(&host) {
+            Some(domain_uuid) => *domain_uuid,
+            None => return Ok(None),
+        };
+        match self.find_domain_by_uuid(domain_uuid).await {
+            Ok(domain) => Ok(Some(domain)),
+            Err(Error::NotFound) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Hosts of the domain endpoints, loaded again after [`ENDPOINT_HOSTS_TTL`]
+    async fn endpoint_hosts(&self) -> Result<Arc<HashMap<Host, Uuid>>> {
+        if let Some(hosts) = ENDPOINT_HOSTS
+            .read()
+            .unwrap()
+            .as_ref()
+            .filter(|hosts| hosts.loaded_at.elapsed() < ENDPOINT_HOSTS_TTL)
+        {
+            return Ok(hosts.domains.clone());
+        }
+
+        // 域的数量通常很少，定期整体加载比在 json 字段上建立索引更简单
+        let domains = Arc::new(
+            domains::Entity::find()
+                .all(&self.database)
+                .await?
+                .into_iter()
+                .filter_map(|domain| {
+                    let host = domain.setting.endpoint.as_ref()?.host()?.to_owned();
+                    Some((host, domain.uuid))
+                })
+                .collect::<HashMap<_, _>>(),
+        );
+        *ENDPOINT_HOSTS.write().unwrap() = Some(EndpointHosts {
+            loaded_at: Instant::now(),
+            domains: domains.clone(),
+        });
+
+        Ok(domains)
+    }
+
+    /// Base url of the domain: the endpoint of the domain if set, otherwise the app endpoint
+    /// for the default domain or `/d/{domain}/` under the app endpoint for others
+    pub fn endpoint(&self, domain: &domains::Model) -> Result<Url> {
+        if let Some(endpoint) = &domain.setting.endpoint {
+            return Ok(endpoint.clone());
+        }
+
+        let config = self.config.get::<AppConfig>("app")?;
+        if domain.name == config.app_name {
+            return Ok(config.app_endpoint);
+        }
+
+        Ok(config.app_endpoint.join(&format!("d/{}/", domain.name))?)
+    }
+
+    /// Issuer of tokens of the domain
+    pub fn issuer(&self, domain: &domains::Model) -> Result<Url> {
+        Ok(self.endpoint(domain)?.join("oidc")?)
+    }
+
+    pub async fn issuer_of(&self, domain_uuid: Uuid) -> Result<Url> {
+        self.issuer(&self.find_domain_by_uuid(domain_uuid).await?)
+    }
+}
+
+/// Host of the `Host` header without the port, `None` if it is not a valid host
+fn parse_host(host: &str) -> Option<Host> {
+    let authority = host.parse::<Authority>().ok()?;
+
+    Host::parse(authority.host()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use super::*;
+
+    #[test]
+    fn hosts_are_parsed_without_port() {
+        assert_eq!(
+            parse_host("Auth.Example.com:8443"),
+            Some(Host::Domain("auth.example.com".into()))
+        );
+        assert_eq!(
+            parse_host("auth.example.com"),
+            Some(Host::Domain("auth.example.com".into()))
+        );
+        assert_eq!(
+            parse_host("127.0.0.1:3000"),
+            Some(Host::Ipv4(Ipv4Addr::LOCALHOST))
+        );
+        assert_eq!(parse_host("[::1]"), Some(Host::Ipv6(Ipv6Addr::LOCALHOST)));
+        assert_eq!(
+            parse_host("[::1]:3000"),
+            Some(Host::Ipv6(Ipv6Addr::LOCALHOST))
+        );
+        assert_eq!(parse_host("exa mple.com"), None);
+
+        let endpoint = Url::parse("https://[::1]:3000/").unwrap();
+        assert_eq!(
+            parse_host("[::1]:8080"),
+            endpoint.host().map(|host| host.to_owned())
+        );
+    }
+}
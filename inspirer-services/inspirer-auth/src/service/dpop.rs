@@ -0,0 +1,77 @@
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use inspirer_framework::preludes::*;
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, Set, SqlErr};
+use sha2::Sha256;
+
+use crate::{
+    config::AppConfig,
+    entity::dpop_proofs,
+    helper::{constant_time_eq, sha256_hex},
+};
+
+use super::Service;
+
+pub struct DPoP;
+
+impl Service<DPoP> {
+    /// Record the `jti` of the proof key until it expires, returns `false` if the proof is
+    /// replayed. Expired records are cleaned up.
+    pub async fn record_proof(&self, jkt: &str, jti: &str, expires_at: i64) -> Result<bool> {
+        dpop_proofs::Entity::delete_many()
+            .filter(dpop_proofs::Column::ExpiresAt.lt(Utc::now()))
+            .exec(&self.database)
+            .await?;
+
+        let recorded = dpop_proofs::ActiveModel {
+            jti: Set(sha256_hex(format!("{jkt}:{jti}").as_bytes())),
+            expires_at: Set(DateTime::from_timestamp(expires_at, 0).unwrap_or_else(Utc::now)),
+            created_at: Set(Utc::now()),
+            ..Default::default()
+        }
+        .insert(&self.database)
+        .await;
+
+        match recorded {
+            Ok(_) => Ok(true),
+            Err(err) if matches!(err.sql_err(), Some(SqlErr::UniqueConstraintViolation(_))) => {
+                Ok(false)
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Nonce of the current period, `None` if nonces are not configured
+    pub fn nonce(&self) -> Result<Option<String>> {
+        let config = self.config.get::<AppConfig>("app")?.dpop;
+
+        Ok(config
+            .nonce_secret
+            .map(|secret| derive_nonce(&secret, current_period(config.nonce_lifetime))))
+    }
+
+    /// Nonces of the current and the previous period are accepted
+    pub fn verify_nonce(&self, nonce: &str) -> Result<bool> {
+        let config = self.config.get::<AppConfig>("app")?.dpop;
+        let Some(secret) = config.nonce_secret else {
+            return Ok(true);
+        };
+
+        let period = current_period(config.nonce_lifetime);
+        Ok([period, period - 1].into_iter().any(|period| {
+            constant_time_eq(derive_nonce(&secret, period).as_bytes(), nonce.as_bytes())
+        }))
+    }
+}
+
+fn current_period(lifetime: u64) -> i64 {
+    Utc::now().timestamp() / lifetime.max(1) as i64
+}
+
+fn derive_nonce(secret: &str, period: i64) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC can take key of any size");
+    mac.update(period.to_string().as_bytes());
+
+    hex::encode(mac.finalize().into_bytes())
+}
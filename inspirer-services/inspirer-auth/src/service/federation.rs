@@ -0,0 +1,537 @@
+use chrono::Utc;
+use inspirer_framework::preludes::*;
+use openidconnect::{
+    core::{CoreAuthenticationFlow, CoreClient, CoreProviderMetadata, CoreUserInfoClaims},
+    reqwest::async_http_client,
+    AuthorizationCode, ClientId, ClientSecret, CsrfToken, IssuerUrl, Nonce, OAuth2TokenResponse,
+    PkceCodeChallenge, PkceCodeVerifier, RedirectUrl, Scope, TokenResponse,
+};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, EntityTrait, IntoActiveModel, QueryFilter, Set, TransactionTrait,
+};
+use serde_json::json;
+use url::Url;
+use uuid::Uuid;
+
+use crate::{
+    auth::{
+        federation::{link_target, ExternalIdentity, FederationState, LinkTarget},
+        user::UserProfile,
+        webhook::UserEvent,
+    },
+    config::AppConfig,
+    entity::{identity_providers, linked_identities, users},
+};
+
+use super::{webhook::Webhook, Service, ServiceInterface};
+
+pub struct Federation;
+
+impl Service<Federation> {
+    pub async fn providers(&self, domain_uuid: Uuid) -> Result<Vec<identity_providers::Model>> {
+        Ok(identity_providers::Entity::find()
+            .filter(identity_providers::Column::DomainUuid.eq(domain_uuid))
+            .all(&self.database)
+            .await?)
+    }
+
+    pub async fn find_provider(
+        &self,
+        domain_uuid: Uuid,
+        provider_uuid: Uuid,
+    ) -> Result<identity_providers::Model> {
+        identity_providers::Entity::find()
+            .filter(identity_providers::Column::DomainUuid.eq(domain_uuid))
+            .filter(identity_providers::Column::Uuid.eq(provider_uuid))
+            .one(&self.database)
+            .await?
+            .ok_or(Error::NotFound)
+    }
+
+    /// Build the authorization url of the upstream provider
+    pub async fn authorize_url(
+        &self,
+        provider: &identity_providers::Model,
+    ) -> Result<(Url, FederationState)> {
+        let client = self.client(provider).await?;
+        let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+
+        let (url, csrf_token, nonce) = provider
+            .scopes
+            .split_whitespace()
+            .fold(
+                client.authorize_url(
+                    CoreAuthenticationFlow::AuthorizationCode,
+                    CsrfToken::new_random,
+                    Nonce::new_random,
+                ),
+                |request, scope| request.add_scope(Scope::new(scope.to_string())),
+            )
+            .set_pkce_challenge(pkce_challenge)
+            .url();
+
+        Ok((
+            url,
+            FederationState {
+                provider: provider.uuid,
+                csrf_token: csrf_token.secret().clone(),
+                nonce: nonce.secret().clone(),
+                pkce_verifier: pkce_verifier.secret().clone(),
+            },
+        ))
+    }
+
+    /// Exchange the authorization code and resolve the upstream identity
+    pub async fn exchange(
+        &self,
+        provider: &identity_providers::Model,
+        state: FederationState,
+        code: String,
+    ) -> Result<ExternalIdentity> {
+        exchange_code(&self.client(provider).await?, provider, state, code).await
+    }
+
+    /// Find the local user linked to the external identity, link or create one if not exists.
+    ///
+    /// `current_user` is the user signed in the domain, who links the identity to themselves.
+    pub async fn link_or_create_user(
+        &self,
+        provider: &identity_providers::Model,
+        identity: ExternalIdentity,
+        current_user: Option<Uuid>,
+    ) -> Result<users::Model> {
+        let linked = linked_identities::Entity::find()
+            .filter(linked_identities::Column::ProviderUuid.eq(provider.uuid))
+            .filter(linked_identities::Column::Subject.eq(&identity.subject))
+            .one(&self.database)
+            .await?;
+
+        if let Some(linked) = linked {
+            let user = users::Entity::find()
+                .filter(users::Column::Uuid.eq(linked.user_uuid))
+                .one(&self.database)
+                .await?
+                .ok_or(Error::NotFound)?;
+
+            let mut linked = linked.into_active_model();
+            linked.claims = Set(identity.claims);
+            linked.updated_at = Set(Utc::now());
+            linked.update(&self.database).await?;
+
+            return Ok(user);
+        }
+
+        let txn = self.database.begin().await?;
+
+        let current = match current_user {
+            Some(user_uuid) => {
+                users::Entity::find()
+                    .filter(users::Column::DomainUuid.eq(provider.domain_uuid))
+                    .filter(users::Column::Uuid.eq(user_uuid))
+                    .one(&txn)
+                    .await?
+            }
+            None => None,
+        };
+        let existing = match &identity.email {
+            Some(email) => {
+                users::Entity::find()
+                    .filter(users::Column::DomainUuid.eq(provider.domain_uuid))
+                    .filter(users::Column::Email.eq(email))
+                    .one(&txn)
+                    .await?
+            }
+            None => None,
+        };
+
+        let mut created = false;
+        let user = match link_target(provider, &identity, current, existing)? {
+            LinkTarget::User(user) => *user,
+            LinkTarget::Create => {
+                let username = match identity.username.clone() {
+                    Some(username) => users::Entity::find()
+                        .filter(users::Column::DomainUuid.eq(provider.domain_uuid))
+                        .filter(users::Column::Username.eq(&username))
+                        .one(&txn)
+                        .await?
+                        .map_or(Some(username), |_| None),
+                    None => None,
+                };
+
+                let user_uuid = Uuid::new_v4();
+                let mut profile = UserProfile::new(
+                    user_uuid.to_string(),
+                    identity
+                        .name
+                        .clone()
+                        .or(username.clone())
+                        .unwrap_or_default(),
+                );
+                profile.preferred_username = username.clone();
+                profile.email = identity.email.clone();
+                profile.email_verified = Some(identity.email_verified);
+                profile.picture = identity
+                    .picture
+                    .as_deref()
+                    .and_then(|picture| Url::parse(picture).ok());
+
+                let user = users::ActiveModel {
+                    uuid: Set(user_uuid),
+                    domain_uuid: Set(provider.domain_uuid),
+                    email: Set(identity.email.clone()),
+                    username: Set(username),
+                    phone_number: Set(identity.phone_number.clone()),
+                    password: Set(String::new()),
+                    profile: Set(profile),
+                    created_at: Set(Utc::now()),
+                    updated_at: Set(Utc::now()),
+                    ..Default::default()
+                }
+                .insert(&txn)
+                .await?;
+                created = true;
+
+                user
+            }
+        };
+
+        linked_identities::ActiveModel {
+            user_uuid: Set(user.uuid),
+            provider_uuid: Set(provider.uuid),
+            subject: Set(identity.subject),
+            claims: Set(identity.claims),
+            created_at: Set(Utc::now()),
+            updated_at: Set(Utc::now()),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await?;
+
+        txn.commit().await?;
+
+        if created {
+            self.context
+                .service::<Webhook>()
+                .dispatch(
+                    user.domain_uuid,
+                    UserEvent::Created,
+                    user.uuid,
+                    json!({ "source": "federation", "provider": provider.uuid }),
+                )
+                .await?;
+        }
+
+        Ok(user)
+    }
+
+    async fn client(&self, provider: &identity_providers::Model) -> Result<CoreClient> {
+        let config = self.config.get::<AppConfig>("app")?;
+        let redirect_url = config
+            .app_endpoint
+            .join(&format!("login/federation/{}/callback", provider.uuid))?;
+
+        discover_client(provider, redirect_url).await
+    }
+}
+
+/// Client of the upstream provider configured by its discovery document
+async fn discover_client(
+    provider: &identity_providers::Model,
+    redirect_url: Url,
+) -> Result<CoreClient> {
+    let metadata = CoreProviderMetadata::discover_async(
+        IssuerUrl::new(provider.issuer_url.clone())?,
+        async_http_client,
+    )
+    .await
+    .map_err(Error::wrap)?;
+
+    Ok(CoreClient::from_provider_metadata(
+        metadata,
+        ClientId::new(provider.client_id.clone()),
+        provider.client_secret.clone().map(ClientSecret::new),
+    )
+    .set_redirect_uri(RedirectUrl::from_url(redirect_url)))
+}
+
+/// Exchange the authorization code at the upstream provider, the ID token is verified and the
+/// claims of it are merged with the userinfo response
+async fn exchange_code(
+    client: &CoreClient,
+    provider: &identity_providers::Model,
+    state: FederationState,
+    code: String,
+) -> Result<ExternalIdentity> {
+    let token_response = client
+        .exchange_code(AuthorizationCode::new(code))
+        .set_pkce_verifier(PkceCodeVerifier::new(state.pkce_verifier))
+        .request_async(async_http_client)
+        .await
+        .map_err(Error::wrap)?;
+
+    let id_token = token_response.id_token().ok_or(Error::Unauthorized(
+        "Upstream provider returned no id token".into(),
+    ))?;
+    let id_token_claims = id_token
+        .claims(&client.id_token_verifier(), &Nonce::new(state.nonce))
+        .map_err(|err| Error::Unauthorized(err.to_string()))?;
+
+    let subject = id_token_claims.subject().to_string();
+    let mut claims = serde_json::to_value(id_token_claims)?;
+
+    // Userinfo is optional, only merge it when the upstream provider supports it
+    if let Ok(request) = client.user_info(
+        token_response.access_token().clone(),
+        Some(id_token_claims.subject().clone()),
+    ) {
+        let userinfo: CoreUserInfoClaims = request
+            .request_async(async_http_client)
+            .await
+            .map_err(Error::wrap)?;
+
+        if let (Some(claims), serde_json::Value::Object(userinfo)) =
+            (claims.as_object_mut(), serde_json::to_value(userinfo)?)
+        {
+            claims.extend(userinfo);
+        }
+    }
+
+    Ok(provider.claim_mapping.map(subject, claims))
+}
+
+#[cfg(test)]
+mod tests {
+    use crypto_utils::{p256::P256, KeyPair, KeyPairTrait};
+    use inspirer_framework::{
+        axum::{
+            routing::{get, post},
+            Router,
+        },
+        http::StatusCode,
+        Error,
+    };
+    use serde_json::json;
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::{
+        auth::{account_status::AccountStatus, federation::ClaimMapping},
+        token::SigningKey,
+    };
+
+    const CLIENT_ID: &str = "inspirer";
+    const NONCE: &str = "nonce-of-the-login";
+    const SUBJECT: &str = "upstream-subject";
+
+    /// Serve discovery, JWKS, token and userinfo endpoints of an upstream provider whose ID
+    /// token carries the email claims, returns the issuer url
+    async fn mock_issuer(email_verified: bool) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let issuer = format!("http://{}", listener.local_addr().unwrap());
+        let key = SigningKey::from_pem(
+            &KeyPair::<P256>::generate()
+                .unwrap()
+                .get_private_key_pem()
+                .unwrap(),
+        )
+        .unwrap();
+
+        let metadata = json!({
+            "issuer": issuer,
+            "authorization_endpoint": format!("{issuer}/auth"),
+            "token_endpoint": format!("{issuer}/token"),
+            "userinfo_endpoint": format!("{issuer}/userinfo"),
+            "jwks_uri": format!("{issuer}/jwks"),
+            "response_types_supported": ["code"],
+            "subject_types_supported": ["public"],
+            "id_token_signing_alg_values_supported": ["ES256"],
+        });
+        let now = chrono::Utc::now().timestamp();
+        let id_token = key.sign(&json!({
+            "iss": issuer,
+            "sub": SUBJECT,
+            "aud": CLIENT_ID,
+            "iat": now,
+            "exp": now + 300,
+            "nonce": NONCE,
+            "email": "alice@example.com",
+            "email_verified": email_verified,
+        }));
+        let jwks = serde_json::to_value(key.jwks()).unwrap();
+
+        let router = Router::new()
+            .route(
+                "/.well-known/openid-configuration",
+                get(move || async move { Json(metadata) }),
+            )
+            .route("/jwks", get(move || async move { Json(jwks) }))
+            .route(
+                "/token",
+                post(move || async move {
+                    Json(json!({
+                        "access_token": "upstream-access-token",
+                        "token_type": "Bearer",
+                        "expires_in": 300,
+                        "id_token": id_token,
+                    }))
+                }),
+            )
+            .route(
+                "/userinfo",
+                get(|| async {
+                    Json(json!({ "sub": SUBJECT, "name": "Alice", "preferred_username": "alice" }))
+                }),
+            );
+        tokio::spawn(async move { inspirer_framework::axum::serve(listener, router).await });
+
+        issuer
+    }
+
+    fn provider(issuer: String, auto_link_verified_email: bool) -> identity_providers::Model {
+        identity_providers::Model {
+            id: 1,
+            uuid: Uuid::new_v4(),
+            domain_uuid: Uuid::new_v4(),
+            name: "upstream".into(),
+            display_name: "Upstream".into(),
+            issuer_url: issuer,
+            client_id: CLIENT_ID.into(),
+            client_secret: None,
+            scopes: "openid email profile".into(),
+            claim_mapping: ClaimMapping::default(),
+            auto_link_verified_email,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn user(email: &str) -> users::Model {
+        let uuid = Uuid::new_v4();
+
+        users::Model {
+            id: 1,
+            uuid,
+            domain_uuid: Uuid::new_v4(),
+            email: Some(email.into()),
+            username: None,
+            phone_number: None,
+            external_id: None,
+            status: AccountStatus::Active,
+            status_reason: None,
+            status_changed_at: None,
+            password: String::new(),
+            profile: UserProfile::new(uuid.to_string(), ""),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    async fn login(provider: &identity_providers::Model) -> ExternalIdentity {
+        let client = discover_client(
+            provider,
+            Url::parse("https://auth.example.com/callback").unwrap(),
+        )
+        .await
+        .unwrap();
+        let state = FederationState {
+            provider: provider.uuid,
+            csrf_token: "state".into(),
+            nonce: NONCE.into(),
+            pkce_verifier: "verifier-of-the-login-which-is-long-enough-for-pkce".into(),
+        };
+
+        exchange_code(&client, provider, state, "code".into())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn resolves_the_identity_of_the_mock_issuer() {
+        let provider = provider(mock_issuer(true).await, false);
+        let identity = login(&provider).await;
+
+        assert_eq!(identity.subject, SUBJECT);
+        assert_eq!(identity.email.as_deref(), Some("alice@example.com"));
+        assert!(identity.email_verified);
+        // userinfo 的 claims 合并到 ID token 的 claims 中
+        assert_eq!(identity.name.as_deref(), Some("Alice"));
+        assert_eq!(identity.username.as_deref(), Some("alice"));
+    }
+
+    #[tokio::test]
+    async fn rejects_the_id_token_of_another_nonce() {
+        let provider = provider(mock_issuer(true).await, false);
+        let client = discover_client(
+            &provider,
+            Url::parse("https://auth.example.com/callback").unwrap(),
+        )
+        .await
+        .unwrap();
+        let state = FederationState {
+            provider: provider.uuid,
+            csrf_token: "state".into(),
+            nonce: "another-nonce".into(),
+            pkce_verifier: "verifier-of-the-login-which-is-long-enough-for-pkce".into(),
+        };
+
+        assert!(matches!(
+            exchange_code(&client, &provider, state, "code".into()).await,
+            Err(Error::Unauthorized(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn does_not_link_by_verified_email_by_default() {
+        let provider = provider(mock_issuer(true).await, false);
+        let identity = login(&provider).await;
+
+        let result = link_target(&provider, &identity, None, Some(user("alice@example.com")));
+        assert!(matches!(
+            result,
+            Err(Error::CustomError(StatusCode::CONFLICT, _))
+        ));
+    }
+
+    #[tokio::test]
+    async fn links_by_verified_email_if_enabled() {
+        let provider = provider(mock_issuer(true).await, true);
+        let identity = login(&provider).await;
+        let existing = user("alice@example.com");
+
+        assert_eq!(
+            link_target(&provider, &identity, None, Some(existing.clone())).unwrap(),
+            LinkTarget::User(Box::new(existing))
+        );
+    }
+
+    #[tokio::test]
+    async fn does_not_link_by_unverified_email() {
+        let provider = provider(mock_issuer(false).await, true);
+        let identity = login(&provider).await;
+        assert!(!identity.email_verified);
+
+        assert!(link_target(&provider, &identity, None, Some(user("alice@example.com"))).is_err());
+    }
+
+    #[tokio::test]
+    async fn links_to_the_signed_in_user() {
+        let provider = provider(mock_issuer(true).await, false);
+        let identity = login(&provider).await;
+        let current = user("bob@example.com");
+
+        assert_eq!(
+            link_target(
+                &provider,
+                &identity,
+                Some(current.clone()),
+                Some(user("alice@example.com"))
+            )
+            .unwrap(),
+            LinkTarget::User(Box::new(current))
+        );
+        assert_eq!(
+            link_target(&provider, &identity, None, None).unwrap(),
+            LinkTarget::Create
+        );
+    }
+}
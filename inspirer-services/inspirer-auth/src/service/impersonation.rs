@@ -0,0 +1,233 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use inspirer_framework::{http::StatusCode, preludes::*, response::ErrorDetail};
+use serde_json::{json, Map, Value};
+use uuid::Uuid;
+
+use crate::{
+    auth::{
+        audit::{AuditAction, AuditEvent, AuditOutcome, ClientInfo},
+        claims::ClaimTarget,
+        consent::{join_scopes, scope_set},
+        rbac::Principal,
+    },
+    config::AppConfig,
+    entity::audit_events,
+    token::{AccessToken, Actor},
+};
+
+use super::{
+    app::App,
+    audit::{Audit, AuditFilter},
+    domain::Domain,
+    rbac::Rbac,
+    token::Token,
+    user::User,
+    Service, ServiceInterface,
+};
+
+pub struct Impersonation;
+
+const DEFAULT_SCOPE: &str = "openid profile email phone";
+
+/// Impersonation request of the admin
+#[derive(Debug)]
+pub struct ImpersonationRequest {
+    pub user_uuid: Uuid,
+    /// The app the token is issued to
+    pub app_uuid: Uuid,
+    /// Why the admin impersonates the user, shown to the user
+    pub reason: String,
+    /// Space-delimited scopes, defaults to `openid profile email phone`
+    pub scope: Option<String>,
+    /// Lifetime in seconds, limited by the configured max lifetime
+    pub expires_in: Option<u64>,
+}
+
+/// Access token issued to the admin on behalf of the user
+#[derive(Debug)]
+pub struct ImpersonationToken {
+    pub access_token: String,
+    pub token_type: &'static str,
+    pub expires_in: u64,
+    pub scope: String,
+}
+
+impl Service<Impersonation> {
+    /// Issue an access token of the user for the admin, the admin is recorded in the `act`
+    /// claim. Both issued and rejected requests are audited.
+    pub async fn impersonate(
+        &self,
+        actor: &Principal,
+        req: ImpersonationRequest,
+        client: ClientInfo,
+    ) -> Result<ImpersonationToken> {
+        let mut detail = Map::from_iter([
+            ("app".into(), json!(req.app_uuid)),
+            ("reason".into(), json!(req.reason)),
+        ]);
+        let user_uuid = req.user_uuid;
+
+        let (token, expires_at) = match self.issue(actor, req).await {
+            Ok(issued) => issued,
+            Err(err) => {
+                detail.insert("error".into(), err.to_string().into());
+                self.context
+                    .service::<Audit>()
+                    .record(AuditEvent {
+                        domain_uuid: Some(actor.domain_uuid),
+                        actor_uuid: Some(actor.user_uuid),
+                        subject_uuid: Some(user_uuid),
+                        client,
+                        detail: Value::Object(detail),
+                        ..AuditEvent::new(AuditAction::Impersonation, AuditOutcome::Failure)
+                    })
+                    .await?;
+
+                return Err(err);
+            }
+        };
+
+        detail.insert("scope".into(), json!(token.scope));
+        detail.insert("expires_at".into(), json!(expires_at));
+        self.context
+            .service::<Audit>()
+            .record(AuditEvent {
+                domain_uuid: Some(actor.domain_uuid),
+                actor_uuid: Some(actor.user_uuid),
+                subject_uuid: Some(user_uuid),
+                client,
+                detail: Value::Object(detail),
+                ..AuditEvent::new(AuditAction::Impersonation, AuditOutcome::Success)
+            })
+            .await?;
+
+        Ok(token)
+    }
+
+    /// Impersonation events on the user's account, newest first
+    pub async fn events(
+        &self,
+        user_uuid: Uuid,
+        before: Option<u64>,
+        limit: u64,
+    ) -> Result<Vec<audit_events::Model>> {
+        self.context
+            .service::<Audit>()
+            .events(AuditFilter {
+                action: Some(AuditAction::Impersonation),
+                subject_uuid: Some(user_uuid),
+                before,
+                limit,
+                ..Default::default()
+            })
+            .await
+    }
+
+    async fn issue(
+        &self,
+        actor: &Principal,
+        req: ImpersonationRequest,
+    ) -> Result<(ImpersonationToken, i64)> {
+        let forbidden = |reason: &str| {
+            Error::CustomError(
+                StatusCode::FORBIDDEN,
+                ErrorDetail::new("forbidden".to_string(), reason.to_string()),
+            )
+        };
+
+        if actor.actor_uuid.is_some() {
+            return Err(forbidden("Delegated tokens cannot impersonate users"));
+        }
+        if actor.user_uuid == req.user_uuid {
+            return Err(Error::BadRequest("Cannot impersonate yourself".into()));
+        }
+
+        let reason = req.reason.trim();
+        if reason.is_empty() || reason.chars().count() > 255 {
+            return Err(Error::BadRequest(
+                "The reason must be 1 to 255 characters".into(),
+            ));
+        }
+
+        let user = self
+            .context
+            .service::<User>()
+            .find_user_by_uuid(req.user_uuid)
+            .await?;
+        let app = self
+            .context
+            .service::<App>()
+            .find_app_by_uuid(req.app_uuid)
+            .await?;
+        if user.domain_uuid != actor.domain_uuid || app.domain_uuid != actor.domain_uuid {
+            return Err(Error::NotFound);
+        }
+        user.status.check()?;
+
+        // 只能模拟权限不超过自己的用户，避免通过模拟提升权限
+        let target = self.context.service::<Rbac>().principal(&user).await?;
+        if !target.permissions.is_subset(&actor.permissions) {
+            return Err(forbidden(
+                "The user has permissions the admin does not have",
+            ));
+        }
+
+        let scopes = scope_set(req.scope.as_deref().unwrap_or(DEFAULT_SCOPE));
+        if scopes.is_empty() {
+            return Err(Error::BadRequest("The scope must not be empty".into()));
+        }
+        let scope = join_scopes(&scopes);
+
+        let max_lifetime = self
+            .config
+            .get::<AppConfig>("app")?
+            .impersonation
+            .max_lifetime;
+        let expires_in = req
+            .expires_in
+            .unwrap_or(max_lifetime)
+            .min(max_lifetime)
+            .min(app.setting.oidc_setting.access_token_expire_in);
+
+        let claims = self
+            .context
+            .service::<Token>()
+            .claims(&app, &user, &scope, None, ClaimTarget::AccessToken)
+            .await?;
+
+        let now = Utc::now();
+        let expires_at = (now + Duration::from_secs(expires_in)).timestamp();
+        let access_token = AccessToken {
+            iss: self
+                .context
+                .service::<Domain>()
+                .issuer_of(app.domain_uuid)
+                .await?
+                .to_string(),
+            aud: app.uuid,
+            sub: user.uuid,
+            scope: scope.clone(),
+            iat: now.timestamp() as usize,
+            exp: expires_at as usize,
+            sid: None,
+            act: Some(Actor {
+                sub: actor.user_uuid,
+                act: None,
+            }),
+            cnf: None,
+            claims,
+        };
+
+        Ok((
+            ImpersonationToken {
+                access_token: access_token.token(&self.app.signing_key),
+                token_type: "Bearer",
+                expires_in,
+                scope,
+            },
+            expires_at,
+        ))
+    }
+}
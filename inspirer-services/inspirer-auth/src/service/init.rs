@@ -1,15 +1,28 @@
 use crate::{
-    auth::{application::AppSetting, user::Gender},
+    auth::{
+        account_status::ManageAccountStatus,
+        application::AppSetting,
+        audit::{AuditAction, AuditEvent, AuditOutcome, ReadAudit},
+        domain::DomainSetting,
+        impersonation::Impersonate,
+        rbac::ManageRbac,
+        registration::ManageApps,
+        session::ManageSessions,
+        user::{Gender, UserProfile},
+    },
     config::AppConfig,
     entity::{apps, domains, users},
-    password::password_hash,
+    password::password_hash_with,
 };
 
-use super::Service;
+use super::{audit::Audit, rbac::Rbac, Service, ServiceInterface};
 use chrono::Utc;
-use inspirer_framework::preludes::*;
-use openidconnect::{StandardClaims, SubjectIdentifier};
-use rand::{rngs::OsRng, RngCore};
+use inspirer_framework::{authorization::Permission, preludes::*};
+use rand::{
+    distributions::{Alphanumeric, DistString},
+    rngs::OsRng,
+    RngCore,
+};
 use sea_orm::{EntityTrait, Set};
 use serde_json::json;
 use uuid::Uuid;
@@ -27,6 +40,7 @@ impl Service<Init> {
             name: Set(config.app_name.clone()),
             display_name: Set(config.app_name.clone()),
             profile: Set(json!("{}")),
+            setting: Set(DomainSetting::default()),
             created_at: Set(Utc::now()),
             updated_at: Set(Utc::now()),
             ..Default::default()
@@ -60,23 +74,36 @@ impl Service<Init> {
         .exec(&self.database)
         .await?;
 
+        self.context
+            .service::<Audit>()
+            .record(AuditEvent {
+                domain_uuid: Some(domain_uuid),
+                detail: json!({ "operation": "create_app", "app": app_uuid, "source": "cli" }),
+                ..AuditEvent::new(AuditAction::AppChange, AuditOutcome::Success)
+            })
+            .await?;
+
         Ok(app_uuid)
     }
 
-    pub async fn init_user(&self, domain_uuid: Uuid) -> Result<Uuid> {
+    /// Create the default user with a random password, returns user uuid and the password
+    pub async fn init_user(&self, domain_uuid: Uuid) -> Result<(Uuid, String)> {
         let config = self.config.get::<AppConfig>("app")?;
 
         println!("Initialize user data.");
         let user_uuid = Uuid::new_v4();
+        let password = Alphanumeric.sample_string(&mut OsRng, 16);
         users::Entity::insert(users::ActiveModel {
             uuid: Set(user_uuid),
             domain_uuid: Set(domain_uuid),
             username: Set(Some(config.app_name.clone())),
-            password: Set(password_hash(config.app_name.clone())?),
-            profile: Set(serde_json::to_value(
-                StandardClaims::new(SubjectIdentifier::new(user_uuid.to_string()))
-                    .set_gender(Some(Gender::Other("unknown".into()))),
-            )?),
+            password: Set(password_hash_with(&config.password_hash, &password)?),
+            profile: Set(UserProfile {
+                preferred_username: Some(config.app_name.clone()),
+                gender: Some(Gender::Other("unknown".into())),
+                updated_at: Some(Utc::now()),
+                ..UserProfile::new(user_uuid.to_string(), config.app_name.clone())
+            }),
             created_at: Set(Utc::now()),
             updated_at: Set(Utc::now()),
             ..Default::default()
@@ -84,6 +111,44 @@ impl Service<Init> {
         .exec(&self.database)
         .await?;
 
-        Ok(user_uuid)
+        Ok((user_uuid, password))
+    }
+
+    /// Create the admin role which is allowed to manage roles, permissions and groups,
+    /// and assign it to the user
+    pub async fn init_rbac(&self, domain_uuid: Uuid, user_uuid: Uuid) -> Result<Uuid> {
+        println!("Initialize rbac data.");
+        let rbac = self.context.service::<Rbac>();
+
+        let role = rbac
+            .create_role(domain_uuid, "admin".into(), "Administrator".into())
+            .await?;
+        let permissions = [
+            (ManageRbac::NAME, "Manage roles, permissions and groups"),
+            (ReadAudit::NAME, "Read audit events"),
+            (ManageSessions::NAME, "Read and revoke sessions of users"),
+            (
+                ManageApps::NAME,
+                "Manage apps and issue initial access tokens",
+            ),
+            (Impersonate::NAME, "Impersonate users of the domain"),
+            (
+                ManageAccountStatus::NAME,
+                "Change the status of users of the domain",
+            ),
+        ];
+
+        for (name, description) in permissions {
+            let permission = rbac
+                .create_permission(domain_uuid, name.into(), description.into())
+                .await?;
+            rbac.grant_permission(domain_uuid, role.uuid, permission.uuid)
+                .await?;
+        }
+
+        rbac.assign_user_role(domain_uuid, user_uuid, role.uuid)
+            .await?;
+
+        Ok(role.uuid)
     }
 }
@@ -4,9 +4,29 @@ use inspirer_framework::app::AppContext;
 
 use crate::app::App;
 
+pub mod api_token;
 pub mod app;
+pub mod audit;
+pub mod authentication;
+pub mod authorization;
+pub mod consent;
+pub mod device;
+pub mod domain;
+pub mod dpop;
+pub mod federation;
+pub mod impersonation;
 pub mod init;
+pub mod privacy;
+pub mod rbac;
+pub mod registration;
+pub mod scim;
+pub mod session;
+#[cfg(test)]
+pub mod testing;
+pub mod token;
 pub mod user;
+pub mod verification;
+pub mod webhook;
 
 pub struct Service<T> {
     pub(crate) context: AppContext<App>,
@@ -0,0 +1,357 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use inspirer_framework::preludes::*;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, Condition, EntityTrait, IntoActiveModel, QueryFilter,
+    QueryOrder, QuerySelect, Set,
+};
+use serde::Serialize;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::{
+    auth::{
+        api_token::ApiTokenKind,
+        audit::{AuditAction, AuditEvent, AuditOutcome, ClientInfo},
+        user::UserProfile,
+    },
+    config::AppConfig,
+    entity::{
+        account_deletions, api_tokens, audit_events, consents, grants, linked_identities,
+        user_sessions, users,
+    },
+};
+
+use super::{audit::Audit, user::User, Service, ServiceInterface};
+
+/// Max number of deletions handled in one poll
+const DELETION_BATCH_SIZE: u64 = 100;
+
+pub struct Privacy;
+
+/// All data held about the user, the password hash and token hashes are not included
+#[derive(Debug, Serialize)]
+pub struct PersonalData {
+    pub exported_at: DateTime<Utc>,
+    pub user: Value,
+    pub profile: UserProfile,
+    pub sessions: Vec<user_sessions::Model>,
+    pub grants: Vec<grants::Model>,
+    pub consents: Vec<consents::Model>,
+    pub linked_identities: Vec<Value>,
+    pub personal_access_tokens: Vec<api_tokens::Model>,
+    pub account_deletions: Vec<account_deletions::Model>,
+    /// Events the user performed or was the subject of
+    pub audit_events: Vec<audit_events::Model>,
+}
+
+impl Service<Privacy> {
+    pub async fn export(&self, user_uuid: Uuid) -> Result<PersonalData> {
+        let user = self
+            .context
+            .service::<User>()
+            .find_user_by_uuid(user_uuid)
+            .await?;
+
+        let sessions = user_sessions::Entity::find()
+            .filter(user_sessions::Column::UserUuid.eq(user.uuid))
+            .order_by_asc(user_sessions::Column::Id)
+            .all(&self.database)
+            .await?;
+        let grants = grants::Entity::find()
+            .filter(grants::Column::UserUuid.eq(user.uuid))
+            .order_by_asc(grants::Column::Id)
+            .all(&self.database)
+            .await?;
+        let consents = consents::Entity::find()
+            .filter(consents::Column::UserUuid.eq(user.uuid))
+            .order_by_asc(consents::Column::Id)
+            .all(&self.database)
+            .await?;
+        let linked_identities = linked_identities::Entity::find()
+            .filter(linked_identities::Column::UserUuid.eq(user.uuid))
+            .order_by_asc(linked_identities::Column::Id)
+            .all(&self.database)
+            .await?
+            .into_iter()
+            .map(|identity| {
+                json!({
+                    "provider": identity.provider_uuid,
+                    "subject": identity.subject,
+                    "claims": identity.claims,
+                    "created_at": identity.created_at,
+                    "updated_at": identity.updated_at,
+                })
+            })
+            .collect();
+        let personal_access_tokens = api_tokens::Entity::find()
+            .filter(api_tokens::Column::Kind.eq(ApiTokenKind::PersonalAccessToken))
+            .filter(api_tokens::Column::OwnerUuid.eq(user.uuid))
+            .order_by_asc(api_tokens::Column::Id)
+            .all(&self.database)
+            .await?;
+        let account_deletions = account_deletions::Entity::find()
+            .filter(account_deletions::Column::UserUuid.eq(user.uuid))
+            .order_by_asc(account_deletions::Column::Id)
+            .all(&self.database)
+            .await?;
+        let audit_events = audit_events::Entity::find()
+            .filter(
+                Condition::any()
+                    .add(audit_events::Column::ActorUuid.eq(user.uuid))
+                    .add(audit_events::Column::SubjectUuid.eq(user.uuid)),
+            )
+            .order_by_asc(audit_events::Column::Id)
+            .all(&self.database)
+            .await?;
+
+        Ok(PersonalData {
+            exported_at: Utc::now(),
+            user: json!({
+                "uuid": user.uuid,
+                "domain_uuid": user.domain_uuid,
+                "username": user.username,
+                "email": user.email,
+                "phone_number": user.phone_number,
+                "external_id": user.external_id,
+                "has_password": !user.password.is_empty(),
+                "created_at": user.created_at,
+                "updated_at": user.updated_at,
+            }),
+            profile: user.profile,
+            sessions,
+            grants,
+            consents,
+            linked_identities,
+            personal_access_tokens,
+            account_deletions,
+            audit_events,
+        })
+    }
+
+    /// The deletion request of the user which is neither cancelled nor completed
+    pub async fn pending_deletion(
+        &self,
+        user_uuid: Uuid,
+    ) -> Result<Option<account_deletions::Model>> {
+        Ok(account_deletions::Entity::find()
+            .filter(account_deletions::Column::UserUuid.eq(user_uuid))
+            .filter(account_deletions::Column::CancelledAt.is_null())
+            .filter(account_deletions::Column::CompletedAt.is_null())
+            .one(&self.database)
+            .await?)
+    }
+
+    /// Schedule the deletion of the account after the grace period, the pending request is
+    /// returned if the deletion has been requested
+    pub async fn request_deletion(
+        &self,
+        user_uuid: Uuid,
+        reason: Option<String>,
+        client: ClientInfo,
+    ) -> Result<account_deletions::Model> {
+        let config = self.config.get::<AppConfig>("app")?.account_deletion;
+        let user = self
+            .context
+            .service::<User>()
+            .find_user_by_uuid(user_uuid)
+            .await?;
+
+        if let Some(deletion) = self.pending_deletion(user.uuid).await? {
+            return Ok(deletion);
+        }
+
+        let reason = reason
+            .map(|reason| reason.trim().to_string())
+            .filter(|reason| !reason.is_empty());
+        if reason
+            .as_ref()
+            .is_some_and(|reason| reason.chars().count() > 255)
+        {
+            return Err(Error::BadRequest(
+                "The reason must be at most 255 characters".into(),
+            ));
+        }
+
+        let now = Utc::now();
+        let deletion = account_deletions::ActiveModel {
+            uuid: Set(Uuid::new_v4()),
+            domain_uuid: Set(user.domain_uuid),
+            user_uuid: Set(user.uuid),
+            reason: Set(reason),
+            requested_at: Set(now),
+            scheduled_at: Set(now + Duration::from_secs(config.grace_period)),
+            cancelled_at: Set(None),
+            completed_at: Set(None),
+            attempts: Set(0),
+            last_error: Set(None),
+            ..Default::default()
+        }
+        .insert(&self.database)
+        .await?;
+
+        self.context
+            .service::<Audit>()
+            .record(AuditEvent {
+                domain_uuid: Some(user.domain_uuid),
+                actor_uuid: Some(user.uuid),
+                subject_uuid: Some(user.uuid),
+                client,
+                detail: json!({
+                    "operation": "request",
+                    "deletion": deletion.uuid,
+                    "scheduled_at": deletion.scheduled_at,
+                }),
+                ..AuditEvent::new(AuditAction::AccountDeletion, AuditOutcome::Success)
+            })
+            .await?;
+
+        Ok(deletion)
+    }
+
+    pub async fn cancel_deletion(&self, user_uuid: Uuid, client: ClientInfo) -> Result<()> {
+        let deletion = self
+            .pending_deletion(user_uuid)
+            .await?
+            .ok_or(Error::NotFound)?;
+
+        account_deletions::Entity::update_many()
+            .col_expr(
+                account_deletions::Column::CancelledAt,
+                Some(Utc::now()).into(),
+            )
+            .filter(account_deletions::Column::Id.eq(deletion.id))
+            .exec(&self.database)
+            .await?;
+
+        self.context
+            .service::<Audit>()
+            .record(AuditEvent {
+                domain_uuid: Some(deletion.domain_uuid),
+                actor_uuid: Some(user_uuid),
+                subject_uuid: Some(user_uuid),
+                client,
+                detail: json!({ "operation": "cancel", "deletion": deletion.uuid }),
+                ..AuditEvent::new(AuditAction::AccountDeletion, AuditOutcome::Success)
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Delete accounts whose grace period has ended, returns the number of deleted accounts.
+    ///
+    /// A failed deletion is recorded on the request and does not stop the others, deletions
+    /// failed more often are tried last, so that they can not block the batch.
+    pub async fn delete_due(&self) -> Result<usize> {
+        let deletions = account_deletions::Entity::find()
+            .filter(account_deletions::Column::ScheduledAt.lte(Utc::now()))
+            .filter(account_deletions::Column::CancelledAt.is_null())
+            .filter(account_deletions::Column::CompletedAt.is_null())
+            .order_by_asc(account_deletions::Column::Attempts)
+            .order_by_asc(account_deletions::Column::Id)
+            .limit(DELETION_BATCH_SIZE)
+            .all(&self.database)
+            .await?;
+
+        let mut deleted = 0;
+        for deletion in deletions {
+            match self.complete_deletion(&deletion).await {
+                Ok(true) => deleted += 1,
+                Ok(false) => {}
+                Err(err) => {
+                    tracing::error!(deletion = %deletion.uuid, error = %err, "delete account failed");
+                    if let Err(err) = self.record_deletion_failure(deletion, &err).await {
+                        tracing::error!(error = %err, "record account deletion failure failed");
+                    }
+                }
+            }
+        }
+
+        Ok(deleted)
+    }
+
+    /// Returns whether the user is deleted, `false` if the user was already deleted
+    async fn complete_deletion(&self, deletion: &account_deletions::Model) -> Result<bool> {
+        let user = users::Entity::find()
+            .filter(users::Column::Uuid.eq(deletion.user_uuid))
+            .one(&self.database)
+            .await?;
+
+        let deleted = match user {
+            Some(user) => {
+                self.context
+                    .service::<User>()
+                    .delete_user(user, None, ClientInfo::default(), "account_deletion")
+                    .await?;
+                true
+            }
+            // 用户已被其他方式删除
+            None => {
+                account_deletions::Entity::update_many()
+                    .col_expr(
+                        account_deletions::Column::CompletedAt,
+                        Some(Utc::now()).into(),
+                    )
+                    .filter(account_deletions::Column::Id.eq(deletion.id))
+                    .exec(&self.database)
+                    .await?;
+                false
+            }
+        };
+
+        self.context
+            .service::<Audit>()
+            .record(AuditEvent {
+                domain_uuid: Some(deletion.domain_uuid),
+                detail: json!({ "operation": "complete", "deletion": deletion.uuid }),
+                ..AuditEvent::new(AuditAction::AccountDeletion, AuditOutcome::Success)
+            })
+            .await?;
+
+        Ok(deleted)
+    }
+
+    async fn record_deletion_failure(
+        &self,
+        deletion: account_deletions::Model,
+        err: &Error,
+    ) -> Result<()> {
+        let last_error = err.to_string().chars().take(1024).collect::<String>();
+        let (uuid, domain_uuid, attempts) =
+            (deletion.uuid, deletion.domain_uuid, deletion.attempts + 1);
+
+        let mut deletion = deletion.into_active_model();
+        deletion.attempts = Set(attempts);
+        deletion.last_error = Set(Some(last_error.clone()));
+        deletion.update(&self.database).await?;
+
+        self.context
+            .service::<Audit>()
+            .record(AuditEvent {
+                domain_uuid: Some(domain_uuid),
+                detail: json!({
+                    "operation": "complete",
+                    "deletion": uuid,
+                    "attempts": attempts,
+                    "error": last_error,
+                }),
+                ..AuditEvent::new(AuditAction::AccountDeletion, AuditOutcome::Failure)
+            })
+            .await
+    }
+
+    /// Delete due accounts forever, used as the background worker of server
+    pub async fn work(&self) -> Result<()> {
+        let config = self.config.get::<AppConfig>("app")?.account_deletion;
+
+        loop {
+            if let Err(err) = self.delete_due().await {
+                tracing::error!(error = %err, "delete accounts failed");
+            }
+
+            tokio::time::sleep(Duration::from_secs(config.poll_interval)).await;
+        }
+    }
+}
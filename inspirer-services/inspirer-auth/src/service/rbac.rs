@@ -0,0 +1,458 @@
+use std::collections::HashSet;
+
+use chrono::Utc;
+use inspirer_framework::preludes::*;
+use sea_orm::{
+    sea_query::OnConflict, ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, Set,
+    TransactionTrait,
+};
+use uuid::Uuid;
+
+use crate::{
+    auth::rbac::Principal,
+    entity::{
+        group_members, group_roles, groups, permissions, role_permissions, roles, user_roles, users,
+    },
+};
+
+use super::Service;
+
+pub struct Rbac;
+
+impl Service<Rbac> {
+    pub async fn roles(&self, domain_uuid: Uuid) -> Result<Vec<roles::Model>> {
+        Ok(roles::Entity::find()
+            .filter(roles::Column::DomainUuid.eq(domain_uuid))
+            .all(&self.database)
+            .await?)
+    }
+
+    pub async fn find_role(&self, domain_uuid: Uuid, role_uuid: Uuid) -> Result<roles::Model> {
+        roles::Entity::find()
+            .filter(roles::Column::DomainUuid.eq(domain_uuid))
+            .filter(roles::Column::Uuid.eq(role_uuid))
+            .one(&self.database)
+            .await?
+            .ok_or(Error::NotFound)
+    }
+
+    pub async fn create_role(
+        &self,
+        domain_uuid: Uuid,
+        name: String,
+        display_name: String,
+    ) -> Result<roles::Model> {
+        Ok(roles::ActiveModel {
+            uuid: Set(Uuid::new_v4()),
+            domain_uuid: Set(domain_uuid),
+            name: Set(name),
+            display_name: Set(display_name),
+            created_at: Set(Utc::now()),
+            updated_at: Set(Utc::now()),
+            ..Default::default()
+        }
+        .insert(&self.database)
+        .await?)
+    }
+
+    pub async fn delete_role(&self, domain_uuid: Uuid, role_uuid: Uuid) -> Result<()> {
+        let role = self.find_role(domain_uuid, role_uuid).await?;
+        let txn = self.database.begin().await?;
+
+        role_permissions::Entity::delete_many()
+            .filter(role_permissions::Column::RoleUuid.eq(role.uuid))
+            .exec(&txn)
+            .await?;
+        user_roles::Entity::delete_many()
+            .filter(user_roles::Column::RoleUuid.eq(role.uuid))
+            .exec(&txn)
+            .await?;
+        group_roles::Entity::delete_many()
+            .filter(group_roles::Column::RoleUuid.eq(role.uuid))
+            .exec(&txn)
+            .await?;
+        roles::Entity::delete_by_id(role.id).exec(&txn).await?;
+
+        txn.commit().await?;
+
+        Ok(())
+    }
+
+    pub async fn permissions(&self, domain_uuid: Uuid) -> Result<Vec<permissions::Model>> {
+        Ok(permissions::Entity::find()
+            .filter(permissions::Column::DomainUuid.eq(domain_uuid))
+            .all(&self.database)
+            .await?)
+    }
+
+    pub async fn find_permission(
+        &self,
+        domain_uuid: Uuid,
+        permission_uuid: Uuid,
+    ) -> Result<permissions::Model> {
+        permissions::Entity::find()
+            .filter(permissions::Column::DomainUuid.eq(domain_uuid))
+            .filter(permissions::Column::Uuid.eq(permission_uuid))
+            .one(&self.database)
+            .await?
+            .ok_or(Error::NotFound)
+    }
+
+    pub async fn create_permission(
+        &self,
+        domain_uuid: Uuid,
+        name: String,
+        description: String,
+    ) -> Result<permissions::Model> {
+        Ok(permissions::ActiveModel {
+            uuid: Set(Uuid::new_v4()),
+            domain_uuid: Set(domain_uuid),
+            name: Set(name),
+            description: Set(description),
+            created_at: Set(Utc::now()),
+            updated_at: Set(Utc::now()),
+            ..Default::default()
+        }
+        .insert(&self.database)
+        .await?)
+    }
+
+    pub async fn delete_permission(&self, domain_uuid: Uuid, permission_uuid: Uuid) -> Result<()> {
+        let permission = self.find_permission(domain_uuid, permission_uuid).await?;
+        let txn = self.database.begin().await?;
+
+        role_permissions::Entity::delete_many()
+            .filter(role_permissions::Column::PermissionUuid.eq(permission.uuid))
+            .exec(&txn)
+            .await?;
+        permissions::Entity::delete_by_id(permission.id)
+            .exec(&txn)
+            .await?;
+
+        txn.commit().await?;
+
+        Ok(())
+    }
+
+    pub async fn groups(&self, domain_uuid: Uuid) -> Result<Vec<groups::Model>> {
+        Ok(groups::Entity::find()
+            .filter(groups::Column::DomainUuid.eq(domain_uuid))
+            .all(&self.database)
+            .await?)
+    }
+
+    pub async fn find_group(&self, domain_uuid: Uuid, group_uuid: Uuid) -> Result<groups::Model> {
+        groups::Entity::find()
+            .filter(groups::Column::DomainUuid.eq(domain_uuid))
+            .filter(groups::Column::Uuid.eq(group_uuid))
+            .one(&self.database)
+            .await?
+            .ok_or(Error::NotFound)
+    }
+
+    pub async fn create_group(
+        &self,
+        domain_uuid: Uuid,
+        name: String,
+        display_name: String,
+    ) -> Result<groups::Model> {
+        Ok(groups::ActiveModel {
+            uuid: Set(Uuid::new_v4()),
+            domain_uuid: Set(domain_uuid),
+            name: Set(name),
+            display_name: Set(display_name),
+            created_at: Set(Utc::now()),
+            updated_at: Set(Utc::now()),
+            ..Default::default()
+        }
+        .insert(&self.database)
+        .await?)
+    }
+
+    pub async fn delete_group(&self, domain_uuid: Uuid, group_uuid: Uuid) -> Result<()> {
+        let group = self.find_group(domain_uuid, group_uuid).await?;
+        let txn = self.database.begin().await?;
+
+        group_members::Entity::delete_many()
+            .filter(group_members::Column::GroupUuid.eq(group.uuid))
+            .exec(&txn)
+            .await?;
+        group_roles::Entity::delete_many()
+            .filter(group_roles::Column::GroupUuid.eq(group.uuid))
+            .exec(&txn)
+            .await?;
+        groups::Entity::delete_by_id(group.id).exec(&txn).await?;
+
+        txn.commit().await?;
+
+        Ok(())
+    }
+
+    pub async fn grant_permission(
+        &self,
+        domain_uuid: Uuid,
+        role_uuid: Uuid,
+        permission_uuid: Uuid,
+    ) -> Result<()> {
+        let role = self.find_role(domain_uuid, role_uuid).await?;
+        let permission = self.find_permission(domain_uuid, permission_uuid).await?;
+
+        role_permissions::Entity::insert(role_permissions::ActiveModel {
+            role_uuid: Set(role.uuid),
+            permission_uuid: Set(permission.uuid),
+            created_at: Set(Utc::now()),
+        })
+        .on_conflict(
+            OnConflict::columns([
+                role_permissions::Column::RoleUuid,
+                role_permissions::Column::PermissionUuid,
+            ])
+            .do_nothing()
+            .to_owned(),
+        )
+        .exec_without_returning(&self.database)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn revoke_permission(
+        &self,
+        domain_uuid: Uuid,
+        role_uuid: Uuid,
+        permission_uuid: Uuid,
+    ) -> Result<()> {
+        let role = self.find_role(domain_uuid, role_uuid).await?;
+
+        role_permissions::Entity::delete_many()
+            .filter(role_permissions::Column::RoleUuid.eq(role.uuid))
+            .filter(role_permissions::Column::PermissionUuid.eq(permission_uuid))
+            .exec(&self.database)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn assign_user_role(
+        &self,
+        domain_uuid: Uuid,
+        user_uuid: Uuid,
+        role_uuid: Uuid,
+    ) -> Result<()> {
+        let user = self.find_user(domain_uuid, user_uuid).await?;
+        let role = self.find_role(domain_uuid, role_uuid).await?;
+
+        user_roles::Entity::insert(user_roles::ActiveModel {
+            user_uuid: Set(user.uuid),
+            role_uuid: Set(role.uuid),
+            created_at: Set(Utc::now()),
+        })
+        .on_conflict(
+            OnConflict::columns([user_roles::Column::UserUuid, user_roles::Column::RoleUuid])
+                .do_nothing()
+                .to_owned(),
+        )
+        .exec_without_returning(&self.database)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn unassign_user_role(
+        &self,
+        domain_uuid: Uuid,
+        user_uuid: Uuid,
+        role_uuid: Uuid,
+    ) -> Result<()> {
+        let user = self.find_user(domain_uuid, user_uuid).await?;
+
+        user_roles::Entity::delete_many()
+            .filter(user_roles::Column::UserUuid.eq(user.uuid))
+            .filter(user_roles::Column::RoleUuid.eq(role_uuid))
+            .exec(&self.database)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn add_group_member(
+        &self,
+        domain_uuid: Uuid,
+        group_uuid: Uuid,
+        user_uuid: Uuid,
+    ) -> Result<()> {
+        let group = self.find_group(domain_uuid, group_uuid).await?;
+        let user = self.find_user(domain_uuid, user_uuid).await?;
+
+        group_members::Entity::insert(group_members::ActiveModel {
+            group_uuid: Set(group.uuid),
+            user_uuid: Set(user.uuid),
+            created_at: Set(Utc::now()),
+        })
+        .on_conflict(
+            OnConflict::columns([
+                group_members::Column::GroupUuid,
+                group_members::Column::UserUuid,
+            ])
+            .do_nothing()
+            .to_owned(),
+        )
+        .exec_without_returning(&self.database)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn remove_group_member(
+        &self,
+        domain_uuid: Uuid,
+        group_uuid: Uuid,
+        user_uuid: Uuid,
+    ) -> Result<()> {
+        let group = self.find_group(domain_uuid, group_uuid).await?;
+
+        group_members::Entity::delete_many()
+            .filter(group_members::Column::GroupUuid.eq(group.uuid))
+            .filter(group_members::Column::UserUuid.eq(user_uuid))
+            .exec(&self.database)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn assign_group_role(
+        &self,
+        domain_uuid: Uuid,
+        group_uuid: Uuid,
+        role_uuid: Uuid,
+    ) -> Result<()> {
+        let group = self.find_group(domain_uuid, group_uuid).await?;
+        let role = self.find_role(domain_uuid, role_uuid).await?;
+
+        group_roles::Entity::insert(group_roles::ActiveModel {
+            group_uuid: Set(group.uuid),
+            role_uuid: Set(role.uuid),
+            created_at: Set(Utc::now()),
+        })
+        .on_conflict(
+            OnConflict::columns([
+                group_roles::Column::GroupUuid,
+                group_roles::Column::RoleUuid,
+            ])
+            .do_nothing()
+            .to_owned(),
+        )
+        .exec_without_returning(&self.database)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn unassign_group_role(
+        &self,
+        domain_uuid: Uuid,
+        group_uuid: Uuid,
+        role_uuid: Uuid,
+    ) -> Result<()> {
+        let group = self.find_group(domain_uuid, group_uuid).await?;
+
+        group_roles::Entity::delete_many()
+            .filter(group_roles::Column::GroupUuid.eq(group.uuid))
+            .filter(group_roles::Column::RoleUuid.eq(role_uuid))
+            .exec(&self.database)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Effective roles of the user, include roles assigned to user's groups
+    pub async fn user_roles(&self, user_uuid: Uuid) -> Result<Vec<roles::Model>> {
+        let mut role_uuids = user_roles::Entity::find()
+            .filter(user_roles::Column::UserUuid.eq(user_uuid))
+            .all(&self.database)
+            .await?
+            .into_iter()
+            .map(|assignment| assignment.role_uuid)
+            .collect::<HashSet<_>>();
+
+        let group_uuids = group_members::Entity::find()
+            .filter(group_members::Column::UserUuid.eq(user_uuid))
+            .all(&self.database)
+            .await?
+            .into_iter()
+            .map(|member| member.group_uuid)
+            .collect::<Vec<_>>();
+
+        if !group_uuids.is_empty() {
+            role_uuids.extend(
+                group_roles::Entity::find()
+                    .filter(group_roles::Column::GroupUuid.is_in(group_uuids))
+                    .all(&self.database)
+                    .await?
+                    .into_iter()
+                    .map(|assignment| assignment.role_uuid),
+            );
+        }
+
+        if role_uuids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        Ok(roles::Entity::find()
+            .filter(roles::Column::Uuid.is_in(role_uuids))
+            .all(&self.database)
+            .await?)
+    }
+
+    /// Effective permission names of the roles
+    pub async fn role_permissions(&self, role_uuids: Vec<Uuid>) -> Result<HashSet<String>> {
+        if role_uuids.is_empty() {
+            return Ok(HashSet::new());
+        }
+
+        let permission_uuids = role_permissions::Entity::find()
+            .filter(role_permissions::Column::RoleUuid.is_in(role_uuids))
+            .all(&self.database)
+            .await?
+            .into_iter()
+            .map(|grant| grant.permission_uuid)
+            .collect::<HashSet<_>>();
+
+        if permission_uuids.is_empty() {
+            return Ok(HashSet::new());
+        }
+
+        Ok(permissions::Entity::find()
+            .filter(permissions::Column::Uuid.is_in(permission_uuids))
+            .all(&self.database)
+            .await?
+            .into_iter()
+            .map(|permission| permission.name)
+            .collect())
+    }
+
+    pub async fn principal(&self, user: &users::Model) -> Result<Principal> {
+        let roles = self.user_roles(user.uuid).await?;
+        let permissions = self
+            .role_permissions(roles.iter().map(|role| role.uuid).collect())
+            .await?;
+
+        Ok(Principal {
+            user_uuid: user.uuid,
+            domain_uuid: user.domain_uuid,
+            session_uuid: None,
+            actor_uuid: None,
+            roles: roles.into_iter().map(|role| role.name).collect(),
+            permissions,
+        })
+    }
+
+    async fn find_user(&self, domain_uuid: Uuid, user_uuid: Uuid) -> Result<users::Model> {
+        users::Entity::find()
+            .filter(users::Column::DomainUuid.eq(domain_uuid))
+            .filter(users::Column::Uuid.eq(user_uuid))
+            .one(&self.database)
+            .await?
+            .ok_or(Error::NotFound)
+    }
+}
@@ -0,0 +1,284 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use inspirer_framework::preludes::*;
+use rand::{rngs::OsRng, RngCore};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, EntityTrait, IntoActiveModel, QueryFilter, QueryOrder, Set,
+    TransactionTrait,
+};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    auth::{
+        api_token::ApiTokenKind,
+        application::AppSetting,
+        audit::{AuditAction, AuditEvent, AuditOutcome},
+        registration::{ClientInformation, ClientMetadata},
+    },
+    config::AppConfig,
+    entity::{api_tokens, apps, client_registrations, initial_access_tokens},
+    helper::{base64_encode, constant_time_eq, random_token, sha256_hex},
+};
+
+use super::{audit::Audit, session::Session, Service, ServiceInterface};
+
+pub struct Registration;
+
+impl Service<Registration> {
+    /// Issue an initial access token of the domain, returns the token in plain text
+    pub async fn issue_initial_token(
+        &self,
+        domain_uuid: Uuid,
+        description: String,
+        expires_in: Option<u64>,
+    ) -> Result<(initial_access_tokens::Model, String)> {
+        let config = self.config.get::<AppConfig>("app")?.registration;
+        let token = random_token(32);
+        let expires_in = expires_in.unwrap_or(config.initial_token_expire_in);
+
+        let model = initial_access_tokens::ActiveModel {
+            uuid: Set(Uuid::new_v4()),
+            domain_uuid: Set(domain_uuid),
+            token: Set(sha256_hex(token.as_bytes())),
+            description: Set(description),
+            expires_at: Set(Utc::now() + Duration::from_secs(expires_in)),
+            created_at: Set(Utc::now()),
+            ..Default::default()
+        }
+        .insert(&self.database)
+        .await?;
+
+        Ok((model, token))
+    }
+
+    pub async fn initial_tokens(
+        &self,
+        domain_uuid: Uuid,
+    ) -> Result<Vec<initial_access_tokens::Model>> {
+        Ok(initial_access_tokens::Entity::find()
+            .filter(initial_access_tokens::Column::DomainUuid.eq(domain_uuid))
+            .order_by_desc(initial_access_tokens::Column::Id)
+            .all(&self.database)
+            .await?)
+    }
+
+    pub async fn revoke_initial_token(&self, domain_uuid: Uuid, uuid: Uuid) -> Result<()> {
+        let result = initial_access_tokens::Entity::delete_many()
+            .filter(initial_access_tokens::Column::DomainUuid.eq(domain_uuid))
+            .filter(initial_access_tokens::Column::Uuid.eq(uuid))
+            .exec(&self.database)
+            .await?;
+
+        if result.rows_affected == 0 {
+            return Err(Error::NotFound);
+        }
+
+        Ok(())
+    }
+
+    /// Find the initial access token which is not expired, returns `Error::Unauthorized` otherwise
+    pub async fn verify_initial_token(&self, token: &str) -> Result<initial_access_tokens::Model> {
+        initial_access_tokens::Entity::find()
+            .filter(initial_access_tokens::Column::Token.eq(sha256_hex(token.as_bytes())))
+            .filter(initial_access_tokens::Column::ExpiresAt.gt(Utc::now()))
+            .one(&self.database)
+            .await?
+            .ok_or(Error::Unauthorized("Invalid initial access token".into()))
+    }
+
+    /// Create an app of the domain with the metadata
+    pub async fn register(
+        &self,
+        domain_uuid: Uuid,
+        metadata: ClientMetadata,
+    ) -> Result<ClientInformation> {
+        let config = self.config.get::<AppConfig>("app")?;
+
+        let mut secret = [0u8; 16];
+        OsRng.fill_bytes(&mut secret);
+
+        let mut setting = AppSetting::default();
+        setting.base_setting.endpoint = config.app_endpoint.clone();
+        apply_metadata(&mut setting, &metadata);
+
+        let app_uuid = Uuid::new_v4();
+        let name = format!("client-{}", app_uuid.simple());
+        let token = random_token(32);
+
+        let txn = self.database.begin().await?;
+
+        let app = apps::ActiveModel {
+            uuid: Set(app_uuid),
+            domain_uuid: Set(domain_uuid),
+            display_name: Set(metadata.client_name.clone().unwrap_or(name.clone())),
+            name: Set(name),
+            secret: Set(secret.to_vec()),
+            profile: Set(json!({})),
+            setting: Set(setting),
+            created_at: Set(Utc::now()),
+            updated_at: Set(Utc::now()),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await?;
+
+        let registration = client_registrations::ActiveModel {
+            app_uuid: Set(app_uuid),
+            token: Set(sha256_hex(token.as_bytes())),
+            metadata: Set(metadata),
+            created_at: Set(Utc::now()),
+            updated_at: Set(Utc::now()),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await?;
+
+        txn.commit().await?;
+
+        self.audit(domain_uuid, "register_client", app_uuid).await?;
+
+        self.information(&app, registration, Some(token))
+    }
+
+    /// Find the registered app by the registration access token, returns `Error::Unauthorized`
+    /// if the client or the token is invalid
+    pub async fn find_registration(
+        &self,
+        client_id: Uuid,
+        token: &str,
+    ) -> Result<(apps::Model, client_registrations::Model)> {
+        let invalid = || Error::Unauthorized("Invalid registration access token".into());
+
+        let registration = client_registrations::Entity::find()
+            .filter(client_registrations::Column::AppUuid.eq(client_id))
+            .one(&self.database)
+            .await?
+            .ok_or_else(invalid)?;
+
+        if !constant_time_eq(
+            registration.token.as_bytes(),
+            sha256_hex(token.as_bytes()).as_bytes(),
+        ) {
+            return Err(invalid());
+        }
+
+        let app = apps::Entity::find()
+            .filter(apps::Column::Uuid.eq(client_id))
+            .one(&self.database)
+            .await?
+            .ok_or_else(invalid)?;
+
+        Ok((app, registration))
+    }
+
+    pub fn information(
+        &self,
+        app: &apps::Model,
+        registration: client_registrations::Model,
+        registration_access_token: Option<String>,
+    ) -> Result<ClientInformation> {
+        let config = self.config.get::<AppConfig>("app")?;
+
+        Ok(ClientInformation {
+            client_id: app.uuid.to_string(),
+            client_secret: base64_encode(&app.secret),
+            client_id_issued_at: app.created_at,
+            client_secret_expires_at: 0,
+            registration_access_token,
+            registration_client_uri: config
+                .app_endpoint
+                .join(&format!("oidc/register/{}", app.uuid))?,
+            metadata: registration.metadata,
+        })
+    }
+
+    /// Replace the metadata of the registered app
+    pub async fn update(
+        &self,
+        app: apps::Model,
+        registration: client_registrations::Model,
+        metadata: ClientMetadata,
+    ) -> Result<ClientInformation> {
+        let txn = self.database.begin().await?;
+
+        let mut setting = app.setting.clone();
+        apply_metadata(&mut setting, &metadata);
+
+        let mut app = app.into_active_model();
+        if let Some(client_name) = &metadata.client_name {
+            app.display_name = Set(client_name.clone());
+        }
+        app.setting = Set(setting);
+        app.updated_at = Set(Utc::now());
+        let app = app.update(&txn).await?;
+
+        let mut registration = registration.into_active_model();
+        registration.metadata = Set(metadata);
+        registration.updated_at = Set(Utc::now());
+        let registration = registration.update(&txn).await?;
+
+        txn.commit().await?;
+
+        self.audit(app.domain_uuid, "update_client", app.uuid)
+            .await?;
+
+        self.information(&app, registration, None)
+    }
+
+    /// Delete the registered app, grants of the app are revoked
+    pub async fn delete(&self, app: apps::Model) -> Result<()> {
+        let txn = self.database.begin().await?;
+
+        client_registrations::Entity::delete_many()
+            .filter(client_registrations::Column::AppUuid.eq(app.uuid))
+            .exec(&txn)
+            .await?;
+        // API key 随应用一起失效
+        api_tokens::Entity::update_many()
+            .col_expr(api_tokens::Column::RevokedAt, Some(Utc::now()).into())
+            .filter(api_tokens::Column::Kind.eq(ApiTokenKind::ApiKey))
+            .filter(api_tokens::Column::OwnerUuid.eq(app.uuid))
+            .filter(api_tokens::Column::RevokedAt.is_null())
+            .exec(&txn)
+            .await?;
+        apps::Entity::delete_by_id(app.id).exec(&txn).await?;
+
+        txn.commit().await?;
+
+        self.context
+            .service::<Session>()
+            .revoke_app_grants(app.uuid, None)
+            .await?;
+
+        self.audit(app.domain_uuid, "delete_client", app.uuid).await
+    }
+
+    async fn audit(&self, domain_uuid: Uuid, operation: &str, app_uuid: Uuid) -> Result<()> {
+        self.context
+            .service::<Audit>()
+            .record(AuditEvent {
+                domain_uuid: Some(domain_uuid),
+                detail: json!({
+                    "operation": operation,
+                    "app": app_uuid,
+                    "source": "registration",
+                }),
+                ..AuditEvent::new(AuditAction::AppChange, AuditOutcome::Success)
+            })
+            .await
+    }
+}
+
+/// Apply the client metadata to the setting of the app
+fn apply_metadata(setting: &mut AppSetting, metadata: &ClientMetadata) {
+    setting.oidc_setting.redirect_uris = metadata.redirect_uris.clone();
+    setting.oidc_setting.token_endpoint_auth_method = metadata.token_endpoint_auth_method;
+    setting.oidc_setting.jwks = metadata.jwks.clone();
+    setting.oidc_setting.jwks_uri = metadata.jwks_uri.clone();
+    setting.oidc_setting.require_pushed_authorization_requests =
+        metadata.require_pushed_authorization_requests;
+    setting.oidc_setting.dpop_bound_access_tokens = metadata.dpop_bound_access_tokens;
+    setting.oidc_setting.grant_types = metadata.grant_types.clone();
+}
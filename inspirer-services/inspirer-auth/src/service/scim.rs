@@ -0,0 +1,870 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::Utc;
+use inspirer_framework::{http::StatusCode, preludes::*, response::ErrorDetail};
+use sea_orm::{
+    sea_query::{Expr, Query},
+    ActiveModelTrait, ColumnTrait, Condition, EntityTrait, IntoActiveModel, PaginatorTrait,
+    QueryFilter, QueryOrder, QuerySelect, Set,
+};
+use serde_json::{json, Map, Value};
+use url::Url;
+use uuid::Uuid;
+
+use crate::{
+    auth::{
+        account_status::AccountStatus,
+        audit::{AuditAction, AuditEvent, AuditOutcome, ClientInfo},
+        scim::{
+            filter::{AttrPath, Comparison, Filter, Operator},
+            group_resource, user_resource, GroupAttributes, ScimClient, UserAttributes,
+            MAX_FILTER_CANDIDATES,
+        },
+        user::UserProfile,
+        webhook::UserEvent,
+    },
+    config::AppConfig,
+    entity::{group_members, groups, users},
+    password::password_hash_with,
+};
+
+use super::{
+    audit::Audit, domain::Domain, rbac::Rbac, user::User, webhook::Webhook, Service,
+    ServiceInterface,
+};
+
+pub struct Scim;
+
+/// Error with the SCIM detail error type carried in the `error` field
+fn scim_error(status: StatusCode, scim_type: &str, detail: String) -> Error {
+    Error::CustomError(status, ErrorDetail::new(scim_type.to_string(), detail))
+}
+
+/// Filters are evaluated on the resources in memory, reject filters matching too many
+fn check_candidates(candidates: u64) -> Result<()> {
+    if candidates > MAX_FILTER_CANDIDATES {
+        return Err(scim_error(
+            StatusCode::BAD_REQUEST,
+            "tooMany",
+            format!("The filter matches more than {MAX_FILTER_CANDIDATES} resources"),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Condition of the column for the comparison of a string attribute, orderings are left to
+/// the evaluation on the resources since the collation of the column may differ
+///
+/// 字符串比较不区分大小写，依赖列的排序规则同样不区分大小写。
+fn string_condition(column: impl ColumnTrait, comparison: Comparison) -> Option<Condition> {
+    let escaped = |value: &str| {
+        value
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_")
+    };
+
+    let expr = match comparison {
+        None | Some((Operator::Ne, Value::Null)) => column.is_not_null(),
+        Some((Operator::Eq, Value::Null)) => column.is_null(),
+        Some((Operator::Eq, Value::String(value))) => column.eq(value),
+        Some((Operator::Co, Value::String(value))) => column.like(format!("%{}%", escaped(value))),
+        Some((Operator::Sw, Value::String(value))) => column.like(format!("{}%", escaped(value))),
+        Some((Operator::Ew, Value::String(value))) => column.like(format!("%{}", escaped(value))),
+        Some(_) => return None,
+    };
+
+    Some(Condition::all().add(expr))
+}
+
+/// Condition of the column for the comparison of an `id` attribute
+fn uuid_condition(column: impl ColumnTrait, comparison: Comparison) -> Option<Condition> {
+    match comparison? {
+        (Operator::Eq, Value::String(value)) => Some(match Uuid::parse_str(value) {
+            Ok(uuid) => Condition::all().add(column.eq(uuid)),
+            Err(_) => Condition::all().add(Expr::value(false)),
+        }),
+        _ => None,
+    }
+}
+
+/// Condition of the user columns for the comparison, see [Filter::narrow]
+fn user_condition(path: &AttrPath, comparison: Comparison) -> Option<Condition> {
+    let attr = path.attr.to_ascii_lowercase();
+    let sub_attr = path.sub_attr.as_deref().map(str::to_ascii_lowercase);
+
+    match (attr.as_str(), sub_attr.as_deref()) {
+        // 没有用户名时以邮箱作为 userName
+        ("username", None) => Some(
+            Condition::any()
+                .add(string_condition(users::Column::Username, comparison)?)
+                .add(
+                    Condition::all()
+                        .add(users::Column::Username.is_null())
+                        .add(string_condition(users::Column::Email, comparison)?),
+                ),
+        ),
+        ("externalid", None) => string_condition(users::Column::ExternalId, comparison),
+        ("emails", None | Some("value")) => string_condition(users::Column::Email, comparison),
+        ("phonenumbers", None | Some("value")) => {
+            string_condition(users::Column::PhoneNumber, comparison)
+        }
+        ("id", None) => uuid_condition(users::Column::Uuid, comparison),
+        ("active", None) => {
+            let active = match comparison? {
+                (Operator::Eq, Value::Bool(active)) => *active,
+                (Operator::Ne, Value::Bool(active)) => !active,
+                _ => return None,
+            };
+            Some(Condition::all().add(match active {
+                true => users::Column::Status.eq(AccountStatus::Active),
+                false => users::Column::Status.ne(AccountStatus::Active),
+            }))
+        }
+        _ => None,
+    }
+}
+
+/// Condition of the group columns for the comparison, see [Filter::narrow]
+fn group_condition(path: &AttrPath, comparison: Comparison) -> Option<Condition> {
+    let attr = path.attr.to_ascii_lowercase();
+    let sub_attr = path.sub_attr.as_deref().map(str::to_ascii_lowercase);
+
+    match (attr.as_str(), sub_attr.as_deref()) {
+        ("displayname", None) => string_condition(groups::Column::DisplayName, comparison),
+        ("externalid", None) => string_condition(groups::Column::ExternalId, comparison),
+        ("id", None) => uuid_condition(groups::Column::Uuid, comparison),
+        ("members", None | Some("value")) => match comparison? {
+            (Operator::Eq, Value::String(value)) => Some(match Uuid::parse_str(value) {
+                Ok(uuid) => Condition::all().add(
+                    groups::Column::Uuid.in_subquery(
+                        Query::select()
+                            .column(group_members::Column::GroupUuid)
+                            .from(group_members::Entity)
+                            .and_where(group_members::Column::UserUuid.eq(uuid))
+                            .to_owned(),
+                    ),
+                ),
+                Err(_) => Condition::all().add(Expr::value(false)),
+            }),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Reasons of status changes made by `active`
+const DEACTIVATED_REASON: &str = "Deactivated by SCIM";
+const ACTIVATED_REASON: &str = "Activated by SCIM";
+
+/// Apply the claims to the profile, claims set to `null` are removed
+fn apply_claims(current: &UserProfile, claims: &Map<String, Value>) -> Result<UserProfile> {
+    let mut profile = serde_json::to_value(current)?;
+    if let Value::Object(profile) = &mut profile {
+        for (claim, value) in claims {
+            match value {
+                Value::Null => profile.remove(claim),
+                value => profile.insert(claim.clone(), value.clone()),
+            };
+        }
+    }
+
+    let mut profile: UserProfile = serde_json::from_value(profile)
+        .map_err(|err| scim_error(StatusCode::BAD_REQUEST, "invalidValue", err.to_string()))?;
+
+    let violations = profile.validate();
+    if !violations.is_empty() {
+        return Err(scim_error(
+            StatusCode::BAD_REQUEST,
+            "invalidValue",
+            violations.join("; "),
+        ));
+    }
+
+    if profile.email != current.email {
+        profile.email_verified = profile.email.as_ref().map(|_| false);
+    }
+    if profile.phone_number != current.phone_number {
+        profile.phone_number_verified = profile.phone_number.as_ref().map(|_| false);
+    }
+    profile.updated_at = Some(Utc::now());
+
+    Ok(profile)
+}
+
+impl Service<Scim> {
+    /// Users of the domain matching the filter, returns the total number and the page
+    pub async fn users(
+        &self,
+        domain_uuid: Uuid,
+        filter: Option<&Filter>,
+        start_index: u64,
+        count: u64,
+        base: &Url,
+    ) -> Result<(u64, Vec<Value>)> {
+        let mut query = users::Entity::find()
+            .filter(users::Column::DomainUuid.eq(domain_uuid))
+            .order_by_asc(users::Column::Id);
+
+        let Some(filter) = filter else {
+            let total = query.clone().count(&self.database).await?;
+            let users = query
+                .offset(start_index - 1)
+                .limit(count)
+                .all(&self.database)
+                .await?;
+
+            return Ok((total, self.user_resources(&users, base).await?));
+        };
+
+        // 过滤条件先尽量在数据库中缩小范围，完整的过滤条件在资源的 JSON 表示上求值
+        if let Some(condition) = filter.narrow(&user_condition) {
+            query = query.filter(condition);
+        }
+        check_candidates(query.clone().count(&self.database).await?)?;
+
+        let users = query.all(&self.database).await?;
+        let resources = self
+            .user_resources(&users, base)
+            .await?
+            .into_iter()
+            .filter(|resource| filter.matches(resource))
+            .collect::<Vec<_>>();
+
+        Ok((
+            resources.len() as u64,
+            resources
+                .into_iter()
+                .skip(start_index as usize - 1)
+                .take(count as usize)
+                .collect(),
+        ))
+    }
+
+    pub async fn user(&self, domain_uuid: Uuid, uuid: Uuid, base: &Url) -> Result<Value> {
+        let user = self.find_user(domain_uuid, uuid).await?;
+
+        self.user_resource(user, base).await
+    }
+
+    pub async fn create_user(
+        &self,
+        scim: ScimClient,
+        attributes: UserAttributes,
+        client: ClientInfo,
+        base: &Url,
+    ) -> Result<Value> {
+        let uuid = Uuid::new_v4();
+        let profile = apply_claims(&UserProfile::new(uuid.to_string(), ""), &attributes.claims)?;
+        let phone_number = profile.phone_number_e164();
+        self.check_user_uniqueness(scim.domain_uuid, None, &attributes, &phone_number)
+            .await?;
+
+        let password = match &attributes.password {
+            Some(password) => self.hash_password(scim.domain_uuid, password).await?,
+            None => String::new(),
+        };
+
+        let now = Utc::now();
+        let (status, status_reason, status_changed_at) = match attributes.active {
+            true => (AccountStatus::Active, None, None),
+            false => (
+                AccountStatus::Disabled,
+                Some(DEACTIVATED_REASON.to_string()),
+                Some(now),
+            ),
+        };
+        let user = users::ActiveModel {
+            uuid: Set(uuid),
+            domain_uuid: Set(scim.domain_uuid),
+            email: Set(profile.email.clone()),
+            username: Set(Some(attributes.user_name)),
+            phone_number: Set(phone_number),
+            external_id: Set(attributes.external_id),
+            status: Set(status),
+            status_reason: Set(status_reason),
+            status_changed_at: Set(status_changed_at),
+            password: Set(password),
+            profile: Set(profile),
+            created_at: Set(now),
+            updated_at: Set(now),
+            ..Default::default()
+        }
+        .insert(&self.database)
+        .await?;
+
+        self.audit(scim, Some(user.uuid), "create_user", client)
+            .await?;
+        self.context
+            .service::<Webhook>()
+            .dispatch(
+                user.domain_uuid,
+                UserEvent::Created,
+                user.uuid,
+                json!({ "source": "scim" }),
+            )
+            .await?;
+
+        Ok(user_resource(&user, &[], base))
+    }
+
+    /// Replace the attributes of the user, the password is changed if given
+    pub async fn replace_user(
+        &self,
+        scim: ScimClient,
+        uuid: Uuid,
+        attributes: UserAttributes,
+        client: ClientInfo,
+        base: &Url,
+    ) -> Result<Value> {
+        let user = self.find_user(scim.domain_uuid, uuid).await?;
+        let profile = apply_claims(&user.profile, &attributes.claims)?;
+        let phone_number = profile.phone_number_e164();
+        self.check_user_uniqueness(scim.domain_uuid, Some(uuid), &attributes, &phone_number)
+            .await?;
+
+        let mut model = user.into_active_model();
+        model.email = Set(profile.email.clone());
+        model.username = Set(Some(attributes.user_name));
+        model.phone_number = Set(phone_number);
+        model.external_id = Set(attributes.external_id);
+        model.profile = Set(profile);
+        model.updated_at = Set(Utc::now());
+        let mut user = model.update(&self.database).await?;
+
+        if let Some(password) = &attributes.password {
+            user = self
+                .context
+                .service::<User>()
+                .change_password(user, password, None)
+                .await?;
+        }
+
+        // 只在 active 与 disabled 之间切换，锁定和待验证的账号不受 active 影响
+        let status = match (attributes.active, user.status) {
+            (true, AccountStatus::Disabled) => Some((AccountStatus::Active, ACTIVATED_REASON)),
+            (false, AccountStatus::Active) => Some((AccountStatus::Disabled, DEACTIVATED_REASON)),
+            _ => None,
+        };
+        if let Some((status, reason)) = status {
+            user = self
+                .context
+                .service::<User>()
+                .change_status(user, status, reason, None, client.clone())
+                .await?;
+        }
+
+        self.audit(scim, Some(user.uuid), "replace_user", client)
+            .await?;
+        self.context
+            .service::<Webhook>()
+            .dispatch(
+                user.domain_uuid,
+                UserEvent::Updated,
+                user.uuid,
+                json!({ "source": "scim" }),
+            )
+            .await?;
+
+        self.user_resource(user, base).await
+    }
+
+    pub async fn delete_user(
+        &self,
+        scim: ScimClient,
+        uuid: Uuid,
+        client: ClientInfo,
+    ) -> Result<()> {
+        let user = self.find_user(scim.domain_uuid, uuid).await?;
+
+        self.context
+            .service::<User>()
+            .delete_user(user, None, client.clone(), "scim")
+            .await?;
+
+        self.audit(scim, Some(uuid), "delete_user", client).await
+    }
+
+    /// Groups of the domain matching the filter, returns the total number and the page
+    pub async fn groups(
+        &self,
+        domain_uuid: Uuid,
+        filter: Option<&Filter>,
+        start_index: u64,
+        count: u64,
+        with_members: bool,
+        base: &Url,
+    ) -> Result<(u64, Vec<Value>)> {
+        let mut query = groups::Entity::find()
+            .filter(groups::Column::DomainUuid.eq(domain_uuid))
+            .order_by_asc(groups::Column::Id);
+
+        let Some(filter) = filter else {
+            let total = query.clone().count(&self.database).await?;
+            let groups = query
+                .offset(start_index - 1)
+                .limit(count)
+                .all(&self.database)
+                .await?;
+
+            return Ok((
+                total,
+                self.group_resources(&groups, with_members, base).await?,
+            ));
+        };
+
+        if let Some(condition) = filter.narrow(&group_condition) {
+            query = query.filter(condition);
+        }
+        check_candidates(query.clone().count(&self.database).await?)?;
+
+        // 过滤条件可能引用成员，求值时总是包含成员
+        let groups = query.all(&self.database).await?;
+        let resources = self
+            .group_resources(&groups, true, base)
+            .await?
+            .into_iter()
+            .filter(|resource| filter.matches(resource))
+            .collect::<Vec<_>>();
+
+        Ok((
+            resources.len() as u64,
+            resources
+                .into_iter()
+                .skip(start_index as usize - 1)
+                .take(count as usize)
+                .collect(),
+        ))
+    }
+
+    pub async fn group(
+        &self,
+        domain_uuid: Uuid,
+        uuid: Uuid,
+        with_members: bool,
+        base: &Url,
+    ) -> Result<Value> {
+        let group = self
+            .context
+            .service::<Rbac>()
+            .find_group(domain_uuid, uuid)
+            .await?;
+
+        Ok(self
+            .group_resources(&[group], with_members, base)
+            .await?
+            .remove(0))
+    }
+
+    pub async fn create_group(
+        &self,
+        scim: ScimClient,
+        attributes: GroupAttributes,
+        client: ClientInfo,
+        base: &Url,
+    ) -> Result<Value> {
+        self.check_group_uniqueness(scim.domain_uuid, None, &attributes)
+            .await?;
+        self.check_members(scim.domain_uuid, &attributes.members)
+            .await?;
+
+        let now = Utc::now();
+        let group = groups::ActiveModel {
+            uuid: Set(Uuid::new_v4()),
+            domain_uuid: Set(scim.domain_uuid),
+            name: Set(attributes.display_name.clone()),
+            display_name: Set(attributes.display_name),
+            external_id: Set(attributes.external_id),
+            created_at: Set(now),
+            updated_at: Set(now),
+            ..Default::default()
+        }
+        .insert(&self.database)
+        .await?;
+
+        let rbac = self.context.service::<Rbac>();
+        for member in &attributes.members {
+            rbac.add_group_member(scim.domain_uuid, group.uuid, *member)
+                .await?;
+        }
+
+        self.audit(scim, None, "create_group", client).await?;
+
+        self.group(scim.domain_uuid, group.uuid, true, base).await
+    }
+
+    /// Replace the attributes and the members of the group
+    pub async fn replace_group(
+        &self,
+        scim: ScimClient,
+        uuid: Uuid,
+        attributes: GroupAttributes,
+        client: ClientInfo,
+        base: &Url,
+    ) -> Result<Value> {
+        let rbac = self.context.service::<Rbac>();
+        let group = rbac.find_group(scim.domain_uuid, uuid).await?;
+        self.check_group_uniqueness(scim.domain_uuid, Some(uuid), &attributes)
+            .await?;
+        self.check_members(scim.domain_uuid, &attributes.members)
+            .await?;
+
+        let mut model = group.into_active_model();
+        model.display_name = Set(attributes.display_name);
+        model.external_id = Set(attributes.external_id);
+        model.updated_at = Set(Utc::now());
+        let group = model.update(&self.database).await?;
+
+        let current = group_members::Entity::find()
+            .filter(group_members::Column::GroupUuid.eq(group.uuid))
+            .all(&self.database)
+            .await?
+            .into_iter()
+            .map(|member| member.user_uuid)
+            .collect::<HashSet<_>>();
+        let members = attributes.members.into_iter().collect::<HashSet<_>>();
+
+        for added in members.difference(&current) {
+            rbac.add_group_member(scim.domain_uuid, group.uuid, *added)
+                .await?;
+        }
+        for removed in current.difference(&members) {
+            rbac.remove_group_member(scim.domain_uuid, group.uuid, *removed)
+                .await?;
+        }
+
+        self.audit(scim, None, "replace_group", client).await?;
+
+        self.group(scim.domain_uuid, group.uuid, true, base).await
+    }
+
+    pub async fn delete_group(
+        &self,
+        scim: ScimClient,
+        uuid: Uuid,
+        client: ClientInfo,
+    ) -> Result<()> {
+        self.context
+            .service::<Rbac>()
+            .delete_group(scim.domain_uuid, uuid)
+            .await?;
+
+        self.audit(scim, None, "delete_group", client).await
+    }
+
+    async fn find_user(&self, domain_uuid: Uuid, uuid: Uuid) -> Result<users::Model> {
+        users::Entity::find()
+            .filter(users::Column::DomainUuid.eq(domain_uuid))
+            .filter(users::Column::Uuid.eq(uuid))
+            .one(&self.database)
+            .await?
+            .ok_or(Error::NotFound)
+    }
+
+    async fn user_resource(&self, user: users::Model, base: &Url) -> Result<Value> {
+        Ok(self.user_resources(&[user], base).await?.remove(0))
+    }
+
+    async fn user_resources(&self, users: &[users::Model], base: &Url) -> Result<Vec<Value>> {
+        let members = group_members::Entity::find()
+            .filter(group_members::Column::UserUuid.is_in(users.iter().map(|user| user.uuid)))
+            .all(&self.database)
+            .await?;
+        let groups = groups::Entity::find()
+            .filter(groups::Column::Uuid.is_in(members.iter().map(|member| member.group_uuid)))
+            .all(&self.database)
+            .await?
+            .into_iter()
+            .map(|group| (group.uuid, group))
+            .collect::<HashMap<_, _>>();
+
+        Ok(users
+            .iter()
+            .map(|user| {
+                let groups = members
+                    .iter()
+                    .filter(|member| member.user_uuid == user.uuid)
+                    .filter_map(|member| groups.get(&member.group_uuid).cloned())
+                    .collect::<Vec<_>>();
+
+                user_resource(user, &groups, base)
+            })
+            .collect())
+    }
+
+    async fn group_resources(
+        &self,
+        groups: &[groups::Model],
+        with_members: bool,
+        base: &Url,
+    ) -> Result<Vec<Value>> {
+        if !with_members {
+            return Ok(groups
+                .iter()
+                .map(|group| group_resource(group, None, base))
+                .collect());
+        }
+
+        let members = group_members::Entity::find()
+            .filter(group_members::Column::GroupUuid.is_in(groups.iter().map(|group| group.uuid)))
+            .all(&self.database)
+            .await?;
+        let users = users::Entity::find()
+            .filter(users::Column::Uuid.is_in(members.iter().map(|member| member.user_uuid)))
+            .all(&self.database)
+            .await?
+            .into_iter()
+            .map(|user| (user.uuid, user))
+            .collect::<HashMap<_, _>>();
+
+        Ok(groups
+            .iter()
+            .map(|group| {
+                let users = members
+                    .iter()
+                    .filter(|member| member.group_uuid == group.uuid)
+                    .filter_map(|member| users.get(&member.user_uuid).cloned())
+                    .collect::<Vec<_>>();
+
+                group_resource(group, Some(&users), base)
+            })
+            .collect())
+    }
+
+    /// Check the unique attributes are not used by other users of the domain
+    async fn check_user_uniqueness(
+        &self,
+        domain_uuid: Uuid,
+        uuid: Option<Uuid>,
+        attributes: &UserAttributes,
+        phone_number: &Option<String>,
+    ) -> Result<()> {
+        for (attr, column, value) in [
+            (
+                "userName",
+                users::Column::Username,
+                Some(&attributes.user_name),
+            ),
+            (
+                "externalId",
+                users::Column::ExternalId,
+                attributes.external_id.as_ref(),
+            ),
+            ("emails", users::Column::Email, attributes.email.as_ref()),
+            (
+                "phoneNumbers",
+                users::Column::PhoneNumber,
+                phone_number.as_ref(),
+            ),
+        ] {
+            let Some(value) = value else {
+                continue;
+            };
+
+            let mut query = users::Entity::find()
+                .filter(users::Column::DomainUuid.eq(domain_uuid))
+                .filter(column.eq(value));
+            if let Some(uuid) = uuid {
+                query = query.filter(users::Column::Uuid.ne(uuid));
+            }
+
+            if query.one(&self.database).await?.is_some() {
+                return Err(scim_error(
+                    StatusCode::CONFLICT,
+                    "uniqueness",
+                    format!("{attr} {value} is already in use"),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check the unique attributes are not used by other groups of the domain, the display name
+    /// is also checked against the names of groups
+    async fn check_group_uniqueness(
+        &self,
+        domain_uuid: Uuid,
+        uuid: Option<Uuid>,
+        attributes: &GroupAttributes,
+    ) -> Result<()> {
+        let mut conditions = vec![(
+            "displayName",
+            &attributes.display_name,
+            Condition::any()
+                .add(groups::Column::Name.eq(&attributes.display_name))
+                .add(groups::Column::DisplayName.eq(&attributes.display_name)),
+        )];
+        if let Some(external_id) = &attributes.external_id {
+            conditions.push((
+                "externalId",
+                external_id,
+                Condition::all().add(groups::Column::ExternalId.eq(external_id)),
+            ));
+        }
+
+        for (attr, value, condition) in conditions {
+            let mut query = groups::Entity::find()
+                .filter(groups::Column::DomainUuid.eq(domain_uuid))
+                .filter(condition);
+            if let Some(uuid) = uuid {
+                query = query.filter(groups::Column::Uuid.ne(uuid));
+            }
+
+            if query.one(&self.database).await?.is_some() {
+                return Err(scim_error(
+                    StatusCode::CONFLICT,
+                    "uniqueness",
+                    format!("{attr} {value} is already in use"),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check the members are users of the domain
+    async fn check_members(&self, domain_uuid: Uuid, members: &[Uuid]) -> Result<()> {
+        let found = users::Entity::find()
+            .filter(users::Column::DomainUuid.eq(domain_uuid))
+            .filter(users::Column::Uuid.is_in(members.iter().copied()))
+            .all(&self.database)
+            .await?
+            .into_iter()
+            .map(|user| user.uuid)
+            .collect::<HashSet<_>>();
+
+        match members.iter().find(|member| !found.contains(member)) {
+            Some(member) => Err(scim_error(
+                StatusCode::BAD_REQUEST,
+                "invalidValue",
+                format!("Member {member} is not a user of the domain"),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Hash the password of the new user, the password must satisfy the policy of the domain
+    async fn hash_password(&self, domain_uuid: Uuid, password: &str) -> Result<String> {
+        let config = self.config.get::<AppConfig>("app")?;
+        let domain = self
+            .context
+            .service::<Domain>()
+            .find_domain_by_uuid(domain_uuid)
+            .await?;
+
+        let violations = domain.setting.password_policy.check(password)?;
+        if !violations.is_empty() {
+            let violations = violations
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>();
+
+            return Err(scim_error(
+                StatusCode::BAD_REQUEST,
+                "invalidValue",
+                violations.join("; "),
+            ));
+        }
+
+        Ok(password_hash_with(&config.password_hash, password)?)
+    }
+
+    async fn audit(
+        &self,
+        scim: ScimClient,
+        subject_uuid: Option<Uuid>,
+        operation: &str,
+        client: ClientInfo,
+    ) -> Result<()> {
+        self.context
+            .service::<Audit>()
+            .record(AuditEvent {
+                domain_uuid: Some(scim.domain_uuid),
+                subject_uuid,
+                client,
+                detail: json!({
+                    "operation": operation,
+                    "token": scim.token_uuid,
+                }),
+                ..AuditEvent::new(AuditAction::ScimChange, AuditOutcome::Success)
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::{DbBackend, QueryTrait};
+
+    use super::*;
+
+    fn users_where(filter: &str) -> Option<String> {
+        let condition = Filter::parse(filter).unwrap().narrow(&user_condition)?;
+        let sql = users::Entity::find()
+            .filter(condition)
+            .build(DbBackend::MySql)
+            .to_string();
+
+        Some(sql.split_once(" WHERE ").unwrap().1.to_string())
+    }
+
+    fn groups_where(filter: &str) -> Option<String> {
+        let condition = Filter::parse(filter).unwrap().narrow(&group_condition)?;
+        let sql = groups::Entity::find()
+            .filter(condition)
+            .build(DbBackend::MySql)
+            .to_string();
+
+        Some(sql.split_once(" WHERE ").unwrap().1.to_string())
+    }
+
+    #[test]
+    fn translates_user_filters() {
+        assert_eq!(
+            users_where(r#"userName eq "bjensen""#).unwrap(),
+            "`users`.`username` = 'bjensen' OR (`users`.`username` IS NULL AND `users`.`email` = 'bjensen')"
+        );
+        assert_eq!(
+            users_where(r#"emails[type eq "work" and value sw "b%_"]"#).unwrap(),
+            r"`users`.`email` LIKE 'b\\%\\_%'"
+        );
+        assert_eq!(
+            users_where(r#"externalId co "7" or phoneNumbers pr"#).unwrap(),
+            "`users`.`external_id` LIKE '%7%' OR `users`.`phone_number` IS NOT NULL"
+        );
+        assert_eq!(
+            users_where(r#"active eq false and title eq "Intern""#).unwrap(),
+            "`users`.`status` <> 'active'"
+        );
+        assert_eq!(users_where(r#"id eq "not-a-uuid""#).unwrap(), "FALSE");
+
+        assert_eq!(users_where(r#"userName gt "b""#), None);
+        assert_eq!(users_where(r#"userName ne "b""#), None);
+        assert_eq!(users_where(r#"active eq true or title pr"#), None);
+    }
+
+    #[test]
+    fn translates_group_filters() {
+        assert_eq!(
+            groups_where(r#"displayName ew "ops" and externalId eq null"#).unwrap(),
+            "`groups`.`display_name` LIKE '%ops' AND `groups`.`external_id` IS NULL"
+        );
+        assert_eq!(
+            groups_where(r#"members[value eq "2819c223-7f76-453a-919d-413861904646"]"#).unwrap(),
+            "`groups`.`uuid` IN (SELECT `group_uuid` FROM `group_members` \
+             WHERE `group_members`.`user_uuid` = '2819c223-7f76-453a-919d-413861904646')"
+        );
+        assert_eq!(groups_where(r#"members.display co "a""#), None);
+    }
+
+    #[test]
+    fn rejects_too_many_candidates() {
+        assert!(check_candidates(MAX_FILTER_CANDIDATES).is_ok());
+        assert!(matches!(
+            check_candidates(MAX_FILTER_CANDIDATES + 1),
+            Err(Error::CustomError(StatusCode::BAD_REQUEST, detail)) if detail.error.as_deref() == Some("tooMany")
+        ));
+    }
+}
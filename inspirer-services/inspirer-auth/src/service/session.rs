@@ -0,0 +1,319 @@
+use chrono::Utc;
+use inspirer_framework::preludes::*;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, EntityTrait, QueryFilter, QueryOrder, Set,
+    TransactionTrait,
+};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    auth::{
+        audit::{AuditAction, AuditEvent, AuditOutcome, ClientInfo},
+        authentication::{join_methods, AuthenticationMethod},
+        claims::ClaimsRequest,
+        session::{SessionInfo, SESSION_TOUCH_INTERVAL},
+    },
+    entity::{grants, refresh_tokens, user_sessions, users},
+};
+
+use super::{audit::Audit, Service, ServiceInterface};
+
+pub struct Session;
+
+impl Service<Session> {
+    /// Create a session after the user is authenticated with the methods
+    pub async fn create(
+        &self,
+        user: &users::Model,
+        client: &ClientInfo,
+        amr: &[AuthenticationMethod],
+    ) -> Result<user_sessions::Model> {
+        Ok(user_sessions::ActiveModel {
+            uuid: Set(Uuid::new_v4()),
+            user_uuid: Set(user.uuid),
+            domain_uuid: Set(user.domain_uuid),
+            ip: Set(client.ip.clone()),
+            user_agent: Set(client
+                .user_agent
+                .as_ref()
+                .map(|agent| agent.chars().take(255).collect())),
+            amr: Set(join_methods(amr)),
+            created_at: Set(Utc::now()),
+            last_seen_at: Set(Utc::now()),
+            revoked_at: Set(None),
+            ..Default::default()
+        }
+        .insert(&self.database)
+        .await?)
+    }
+
+    /// Find the session which is not revoked
+    pub async fn find_active_session(&self, uuid: Uuid) -> Result<user_sessions::Model> {
+        user_sessions::Entity::find()
+            .filter(user_sessions::Column::Uuid.eq(uuid))
+            .filter(user_sessions::Column::RevokedAt.is_null())
+            .one(&self.database)
+            .await?
+            .ok_or(Error::NotFound)
+    }
+
+    /// Update last seen time of the session, returns `false` if the session was revoked.
+    ///
+    /// 最近 [SESSION_TOUCH_INTERVAL] 秒内已更新过时不再写入，避免每个请求都更新会话。
+    pub async fn touch(&self, uuid: Uuid) -> Result<bool> {
+        let Some(session) = user_sessions::Entity::find()
+            .filter(user_sessions::Column::Uuid.eq(uuid))
+            .filter(user_sessions::Column::RevokedAt.is_null())
+            .one(&self.database)
+            .await?
+        else {
+            return Ok(false);
+        };
+
+        let now = Utc::now();
+        if (now - session.last_seen_at).num_seconds() < SESSION_TOUCH_INTERVAL {
+            return Ok(true);
+        }
+
+        Ok(user_sessions::Entity::update_many()
+            .col_expr(user_sessions::Column::LastSeenAt, now.into())
+            .filter(user_sessions::Column::Uuid.eq(uuid))
+            .filter(user_sessions::Column::RevokedAt.is_null())
+            .exec(&self.database)
+            .await?
+            .rows_affected
+            > 0)
+    }
+
+    /// Grant the app access in the session, the existing grant is reused and its scope and
+    /// requested claims are replaced
+    pub async fn grant(
+        &self,
+        session: &user_sessions::Model,
+        app_uuid: Uuid,
+        scope: String,
+        claims: Option<ClaimsRequest>,
+    ) -> Result<grants::Model> {
+        let existing = grants::Entity::find()
+            .filter(grants::Column::SessionUuid.eq(session.uuid))
+            .filter(grants::Column::AppUuid.eq(app_uuid))
+            .filter(grants::Column::RevokedAt.is_null())
+            .one(&self.database)
+            .await?;
+
+        let grant = match existing {
+            Some(grant) => {
+                let mut grant: grants::ActiveModel = grant.into();
+                grant.scope = Set(scope);
+                grant.claims = Set(claims);
+                grant.last_used_at = Set(Utc::now());
+                grant.update(&self.database).await?
+            }
+            None => {
+                grants::ActiveModel {
+                    uuid: Set(Uuid::new_v4()),
+                    session_uuid: Set(session.uuid),
+                    user_uuid: Set(session.user_uuid),
+                    app_uuid: Set(app_uuid),
+                    scope: Set(scope),
+                    claims: Set(claims),
+                    created_at: Set(Utc::now()),
+                    last_used_at: Set(Utc::now()),
+                    revoked_at: Set(None),
+                    ..Default::default()
+                }
+                .insert(&self.database)
+                .await?
+            }
+        };
+
+        Ok(grant)
+    }
+
+    /// Find the grant which is not revoked, and the session of it is not revoked either
+    pub async fn find_active_grant(&self, uuid: Uuid) -> Result<grants::Model> {
+        let grant = grants::Entity::find()
+            .filter(grants::Column::Uuid.eq(uuid))
+            .filter(grants::Column::RevokedAt.is_null())
+            .one(&self.database)
+            .await?
+            .ok_or(Error::NotFound)?;
+
+        self.find_active_session(grant.session_uuid).await?;
+
+        Ok(grant)
+    }
+
+    /// Active sessions of the user with their active grants, latest seen first
+    pub async fn sessions(
+        &self,
+        user_uuid: Uuid,
+        current: Option<Uuid>,
+    ) -> Result<Vec<SessionInfo>> {
+        let sessions = user_sessions::Entity::find()
+            .filter(user_sessions::Column::UserUuid.eq(user_uuid))
+            .filter(user_sessions::Column::RevokedAt.is_null())
+            .order_by_desc(user_sessions::Column::LastSeenAt)
+            .all(&self.database)
+            .await?;
+
+        let grants = grants::Entity::find()
+            .filter(grants::Column::UserUuid.eq(user_uuid))
+            .filter(grants::Column::RevokedAt.is_null())
+            .all(&self.database)
+            .await?;
+
+        Ok(sessions
+            .into_iter()
+            .map(|session| SessionInfo {
+                current: current == Some(session.uuid),
+                grants: grants
+                    .iter()
+                    .filter(|grant| grant.session_uuid == session.uuid)
+                    .cloned()
+                    .collect(),
+                session,
+            })
+            .collect())
+    }
+
+    /// Revoke sessions of the user with the grants and refresh tokens, all sessions are
+    /// revoked if `session_uuid` is `None`. Returns the number of revoked sessions.
+    pub async fn revoke(
+        &self,
+        user_uuid: Uuid,
+        session_uuid: Option<Uuid>,
+        actor_uuid: Option<Uuid>,
+        client: ClientInfo,
+    ) -> Result<u64> {
+        let mut query = user_sessions::Entity::find()
+            .filter(user_sessions::Column::UserUuid.eq(user_uuid))
+            .filter(user_sessions::Column::RevokedAt.is_null());
+        if let Some(session_uuid) = session_uuid {
+            query = query.filter(user_sessions::Column::Uuid.eq(session_uuid));
+        }
+        let sessions = query.all(&self.database).await?;
+
+        if session_uuid.is_some() && sessions.is_empty() {
+            return Err(Error::NotFound);
+        }
+
+        let session_uuids = sessions
+            .iter()
+            .map(|session| session.uuid)
+            .collect::<Vec<_>>();
+        let Some(domain_uuid) = sessions.first().map(|session| session.domain_uuid) else {
+            return Ok(0);
+        };
+
+        let txn = self.database.begin().await?;
+
+        user_sessions::Entity::update_many()
+            .col_expr(user_sessions::Column::RevokedAt, Utc::now().into())
+            .filter(user_sessions::Column::Uuid.is_in(session_uuids.clone()))
+            .exec(&txn)
+            .await?;
+
+        let grant_uuids = grants::Entity::find()
+            .filter(grants::Column::SessionUuid.is_in(session_uuids.clone()))
+            .filter(grants::Column::RevokedAt.is_null())
+            .all(&txn)
+            .await?
+            .into_iter()
+            .map(|grant| grant.uuid)
+            .collect();
+        revoke_grants(&txn, grant_uuids).await?;
+
+        txn.commit().await?;
+
+        self.context
+            .service::<Audit>()
+            .record(AuditEvent {
+                domain_uuid: Some(domain_uuid),
+                actor_uuid,
+                subject_uuid: Some(user_uuid),
+                client,
+                detail: json!({ "sessions": session_uuids }),
+                ..AuditEvent::new(AuditAction::SessionRevoke, AuditOutcome::Success)
+            })
+            .await?;
+
+        Ok(session_uuids.len() as u64)
+    }
+
+    /// Revoke the grant of the user with its refresh tokens
+    pub async fn revoke_grant(&self, user_uuid: Uuid, grant_uuid: Uuid) -> Result<()> {
+        grants::Entity::find()
+            .filter(grants::Column::Uuid.eq(grant_uuid))
+            .filter(grants::Column::UserUuid.eq(user_uuid))
+            .filter(grants::Column::RevokedAt.is_null())
+            .one(&self.database)
+            .await?
+            .ok_or(Error::NotFound)?;
+
+        let txn = self.database.begin().await?;
+        revoke_grants(&txn, vec![grant_uuid]).await?;
+        txn.commit().await?;
+
+        Ok(())
+    }
+
+    /// Revoke all grants of the user, including grants not bound to a session
+    pub async fn revoke_user_grants(&self, user_uuid: Uuid) -> Result<()> {
+        let grant_uuids = grants::Entity::find()
+            .filter(grants::Column::UserUuid.eq(user_uuid))
+            .filter(grants::Column::RevokedAt.is_null())
+            .all(&self.database)
+            .await?
+            .into_iter()
+            .map(|grant| grant.uuid)
+            .collect();
+
+        let txn = self.database.begin().await?;
+        revoke_grants(&txn, grant_uuids).await?;
+        txn.commit().await?;
+
+        Ok(())
+    }
+
+    /// Revoke grants of the app, only grants of the user are revoked if `user_uuid` is given
+    pub async fn revoke_app_grants(&self, app_uuid: Uuid, user_uuid: Option<Uuid>) -> Result<()> {
+        let mut query = grants::Entity::find()
+            .filter(grants::Column::AppUuid.eq(app_uuid))
+            .filter(grants::Column::RevokedAt.is_null());
+        if let Some(user_uuid) = user_uuid {
+            query = query.filter(grants::Column::UserUuid.eq(user_uuid));
+        }
+
+        let grant_uuids = query
+            .all(&self.database)
+            .await?
+            .into_iter()
+            .map(|grant| grant.uuid)
+            .collect();
+
+        let txn = self.database.begin().await?;
+        revoke_grants(&txn, grant_uuids).await?;
+        txn.commit().await?;
+
+        Ok(())
+    }
+}
+
+async fn revoke_grants<C: ConnectionTrait>(db: &C, grant_uuids: Vec<Uuid>) -> Result<()> {
+    grants::Entity::update_many()
+        .col_expr(grants::Column::RevokedAt, Utc::now().into())
+        .filter(grants::Column::Uuid.is_in(grant_uuids.clone()))
+        .exec(db)
+        .await?;
+
+    refresh_tokens::Entity::update_many()
+        .col_expr(refresh_tokens::Column::RevokedAt, Utc::now().into())
+        .filter(refresh_tokens::Column::GrantUuid.is_in(grant_uuids))
+        .filter(refresh_tokens::Column::RevokedAt.is_null())
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
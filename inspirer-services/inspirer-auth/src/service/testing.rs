@@ -0,0 +1,166 @@
+//! Context of service tests backed by a scripted database
+//!
+//! 测试按顺序准备查询和执行的结果，执行后再检查数据库收到的语句。
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use crypto_utils::{p256::P256, KeyPair, KeyPairTrait};
+use inspirer_framework::{
+    app::AppContext,
+    config::{ConfigLoader, Environment},
+};
+use sea_orm::{
+    Database, DatabaseBackend, DbErr, EntityTrait, IdenStatic, Iterable, ModelTrait,
+    ProxyDatabaseTrait, ProxyExecResult, ProxyRow, Statement,
+};
+use uuid::Uuid;
+
+use crate::{app::App, token::SigningKey};
+
+/// The `app` section shared by the tests
+const CONFIG: &str = r#"
+[app]
+app_name = "inspirer"
+app_endpoint = "https://auth.example.com/"
+signing_key = { private_key = "signing-key.pem" }
+session = { driver = "memory" }
+"#;
+
+/// Database answering the statements with the prepared results in order, queries and
+/// executions have their own queues
+#[derive(Debug, Default)]
+pub struct ScriptedDatabase {
+    queries: Mutex<VecDeque<Vec<ProxyRow>>>,
+    executions: Mutex<VecDeque<u64>>,
+    statements: Mutex<Vec<Statement>>,
+}
+
+impl ScriptedDatabase {
+    /// Result of the next query
+    pub fn rows<M: ModelTrait>(self, models: impl IntoIterator<Item = M>) -> Self {
+        let rows = models
+            .into_iter()
+            .map(|model| {
+                <M::Entity as EntityTrait>::Column::iter()
+                    .map(|column| (column.as_str().to_string(), model.get(column)))
+                    .collect::<std::collections::BTreeMap<_, _>>()
+                    .into()
+            })
+            .collect();
+        self.queries.lock().unwrap().push_back(rows);
+        self
+    }
+
+    /// Result of the next query of a single value, e.g. the number of `count`
+    pub fn value(self, name: &str, value: impl Into<sea_orm::Value>) -> Self {
+        self.queries.lock().unwrap().push_back(vec![ProxyRow::new(
+            [(name.to_string(), value.into())].into(),
+        )]);
+        self
+    }
+
+    /// Number of rows affected by the next execution
+    pub fn affected(self, rows_affected: u64) -> Self {
+        self.executions.lock().unwrap().push_back(rows_affected);
+        self
+    }
+
+    /// Statements received so far
+    pub fn statements(&self) -> Vec<String> {
+        self.statements
+            .lock()
+            .unwrap()
+            .iter()
+            .map(Statement::to_string)
+            .collect()
+    }
+
+    /// Whether all the prepared results are used
+    pub fn exhausted(&self) -> bool {
+        self.queries.lock().unwrap().is_empty() && self.executions.lock().unwrap().is_empty()
+    }
+}
+
+#[async_trait::async_trait]
+impl ProxyDatabaseTrait for ScriptedDatabase {
+    async fn query(&self, statement: Statement) -> Result<Vec<ProxyRow>, DbErr> {
+        let sql = statement.to_string();
+        self.statements.lock().unwrap().push(statement);
+        self.queries
+            .lock()
+            .unwrap()
+            .pop_front()
+            .ok_or_else(|| DbErr::Custom(format!("unexpected query: {sql}")))
+    }
+
+    async fn execute(&self, statement: Statement) -> Result<ProxyExecResult, DbErr> {
+        let sql = statement.to_string();
+        self.statements.lock().unwrap().push(statement);
+        let rows_affected = self
+            .executions
+            .lock()
+            .unwrap()
+            .pop_front()
+            .ok_or_else(|| DbErr::Custom(format!("unexpected execution: {sql}")))?;
+
+        Ok(ProxyExecResult::new(1, rows_affected))
+    }
+}
+
+/// Context of the app on the database, `extra` is appended to the `app` section of the config,
+/// e.g. `webhook = { max_attempts = 3 }`
+pub async fn context(
+    database: ScriptedDatabase,
+    extra: &str,
+) -> (AppContext<App>, Arc<ScriptedDatabase>) {
+    let database = Arc::new(database);
+    let proxy: Arc<Box<dyn ProxyDatabaseTrait>> = Arc::new(Box::new(Shared(database.clone())));
+    let connection = Database::connect_proxy(DatabaseBackend::MySql, proxy)
+        .await
+        .unwrap();
+
+    let folder = std::env::temp_dir().join(format!("inspirer-auth-tests-{}", Uuid::new_v4()));
+    std::fs::create_dir_all(&folder).unwrap();
+    std::fs::write(folder.join("test.toml"), format!("{CONFIG}{extra}\n")).unwrap();
+    let config = ConfigLoader::default()
+        .load_folder(&Environment::Test, &folder)
+        .unwrap();
+    std::fs::remove_dir_all(&folder).unwrap();
+
+    let signing_key = SigningKey::from_pem(
+        &KeyPair::<P256>::generate()
+            .unwrap()
+            .get_private_key_pem()
+            .unwrap(),
+    )
+    .unwrap();
+
+    let context = AppContext::new(
+        App {
+            database: connection,
+            signing_key,
+        },
+        config,
+        Environment::Test,
+    );
+
+    (context, database)
+}
+
+/// The database shared by the connection and the test
+#[derive(Debug)]
+struct Shared(Arc<ScriptedDatabase>);
+
+#[async_trait::async_trait]
+impl ProxyDatabaseTrait for Shared {
+    async fn query(&self, statement: Statement) -> Result<Vec<ProxyRow>, DbErr> {
+        self.0.query(statement).await
+    }
+
+    async fn execute(&self, statement: Statement) -> Result<ProxyExecResult, DbErr> {
+        self.0.execute(statement).await
+    }
+}
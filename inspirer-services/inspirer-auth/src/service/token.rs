@@ -0,0 +1,735 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use inspirer_framework::{http::StatusCode, preludes::*, response::ErrorDetail};
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, Set, TransactionTrait};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+use crate::{
+    auth::{
+        api_token::ApiTokenKind,
+        claims::{ClaimSource, ClaimSubject, ClaimTarget, ClaimsRequest},
+        consent::{join_scopes, scope_set},
+        oauth::{OAuthError, OAuthResult},
+    },
+    entity::{apps, grants, refresh_tokens, users},
+    helper::{random_token, sha256_hex},
+    token::{AccessToken, Actor, Confirmation, GetToken, IdToken},
+};
+
+use super::{
+    api_token::ApiToken, app::App, domain::Domain, rbac::Rbac, session::Session, user::User,
+    Service, ServiceInterface,
+};
+
+pub struct Token;
+
+/// Tokens issued to the app for the user
+#[derive(Debug)]
+pub struct IssuedToken {
+    pub access_token: String,
+    /// `DPoP` if the tokens are bound to the proof key, `Bearer` otherwise
+    pub token_type: &'static str,
+    pub id_token: String,
+    /// Lifetime in seconds of the access token
+    pub expires_in: u64,
+    pub refresh_token: String,
+    pub scope: String,
+}
+
+/// Access token exchanged for another audience
+#[derive(Debug)]
+pub struct ExchangedToken {
+    pub access_token: String,
+    pub token_type: &'static str,
+    pub expires_in: u64,
+    pub scope: String,
+}
+
+/// Introspection response, see [RFC 7662 2.2](https://www.rfc-editor.org/rfc/rfc7662#section-2.2)
+///
+/// Only `active` is returned for inactive tokens.
+#[derive(Debug, Default, Serialize)]
+pub struct TokenIntrospection {
+    pub active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aud: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iss: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_type: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exp: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iat: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub act: Option<Actor>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cnf: Option<Confirmation>,
+}
+
+impl Service<Token> {
+    /// Issue tokens of the grant, a new refresh token is created for each issuing.
+    /// The access token and the refresh token are bound to the DPoP proof key if `jkt` is given.
+    pub async fn issue(
+        &self,
+        app: &apps::Model,
+        user: &users::Model,
+        grant: &grants::Model,
+        nonce: Option<String>,
+        jkt: Option<&str>,
+    ) -> Result<IssuedToken> {
+        // 用户只能获得所在域内应用的 token
+        if user.domain_uuid != app.domain_uuid {
+            return Err(Error::Unauthorized(
+                "The user does not belong to the domain of the app".into(),
+            ));
+        }
+        // 签发前检查账户状态，授权码、设备码和 refresh token 都经由这里签发 token
+        user.status.check()?;
+
+        let setting = &app.setting.oidc_setting;
+        let now = Utc::now();
+
+        let requested = grant.claims.as_ref();
+        let issuer = self
+            .context
+            .service::<Domain>()
+            .issuer_of(app.domain_uuid)
+            .await?
+            .to_string();
+
+        let access_token = AccessToken {
+            iss: issuer.clone(),
+            aud: app.uuid,
+            sub: user.uuid,
+            scope: grant.scope.clone(),
+            iat: now.timestamp() as usize,
+            exp: (now + Duration::from_secs(setting.access_token_expire_in)).timestamp() as usize,
+            sid: Some(grant.session_uuid),
+            act: None,
+            cnf: jkt.map(confirmation),
+            claims: self
+                .claims(app, user, &grant.scope, requested, ClaimTarget::AccessToken)
+                .await?,
+        };
+
+        let mut id_token_claims = self
+            .claims(app, user, &grant.scope, requested, ClaimTarget::IdToken)
+            .await?;
+        if let Ok(session) = self
+            .context
+            .service::<Session>()
+            .find_active_session(grant.session_uuid)
+            .await
+        {
+            id_token_claims.insert(
+                "amr".into(),
+                session.amr.split_whitespace().collect::<Vec<_>>().into(),
+            );
+        }
+
+        let id_token = IdToken {
+            iss: issuer,
+            sub: user.uuid,
+            aud: app.uuid,
+            iat: now.timestamp() as usize,
+            exp: (now + Duration::from_secs(setting.id_token_expire_in)).timestamp() as usize,
+            nonce,
+            claims: id_token_claims,
+        };
+
+        let refresh_token = random_token(32);
+        refresh_tokens::ActiveModel {
+            uuid: Set(Uuid::new_v4()),
+            grant_uuid: Set(grant.uuid),
+            token: Set(sha256_hex(refresh_token.as_bytes())),
+            jkt: Set(jkt.map(str::to_string)),
+            expires_at: Set(now + Duration::from_secs(setting.refresh_token_expire_in)),
+            created_at: Set(now),
+            revoked_at: Set(None),
+            ..Default::default()
+        }
+        .insert(&self.database)
+        .await?;
+
+        Ok(IssuedToken {
+            access_token: access_token.token(&self.app.signing_key),
+            token_type: token_type(jkt),
+            id_token: id_token.get_token(&self.app.signing_key),
+            expires_in: setting.access_token_expire_in,
+            refresh_token,
+            scope: grant.scope.clone(),
+        })
+    }
+
+    /// Claims of the user returned by the userinfo endpoint, see
+    /// [OpenID Connect Core 5.3](https://openid.net/specs/openid-connect-core-1_0.html#UserInfo)
+    ///
+    /// The token must be issued with the `openid` scope and the account must be active.
+    pub async fn userinfo(&self, token: &AccessToken) -> Result<Map<String, Value>> {
+        if !scope_set(&token.scope).contains("openid") {
+            return Err(Error::CustomError(
+                StatusCode::FORBIDDEN,
+                ErrorDetail::new(
+                    "insufficient_scope",
+                    "The access token does not have the openid scope",
+                ),
+            ));
+        }
+
+        let user = self
+            .context
+            .service::<User>()
+            .find_user_by_uuid(token.sub)
+            .await
+            .map_err(|_| Error::Unauthorized("User not exists".into()))?;
+        user.status.check()?;
+
+        let app = self
+            .context
+            .service::<App>()
+            .find_app_by_uuid(token.aud)
+            .await
+            .map_err(|_| Error::Unauthorized("App not exists".into()))?;
+
+        // 请求的 claims 记录在签发 token 的授权上
+        let grant = match token.sid {
+            Some(sid) => {
+                grants::Entity::find()
+                    .filter(grants::Column::SessionUuid.eq(sid))
+                    .filter(grants::Column::AppUuid.eq(app.uuid))
+                    .filter(grants::Column::RevokedAt.is_null())
+                    .one(&self.database)
+                    .await?
+            }
+            None => None,
+        };
+
+        let mut claims = Map::new();
+        claims.insert("sub".into(), user.uuid.to_string().into());
+        claims.extend(
+            self.claims(
+                &app,
+                &user,
+                &token.scope,
+                grant.as_ref().and_then(|grant| grant.claims.as_ref()),
+                ClaimTarget::Userinfo,
+            )
+            .await?,
+        );
+
+        Ok(claims)
+    }
+
+    /// Claims of the user emitted into the target by the claim mappings of the app.
+    ///
+    /// Claims whose template fails to render are skipped, so that a broken mapping does not
+    /// stop the user from signing in.
+    pub async fn claims(
+        &self,
+        app: &apps::Model,
+        user: &users::Model,
+        scope: &str,
+        requested: Option<&ClaimsRequest>,
+        target: ClaimTarget,
+    ) -> Result<Map<String, Value>> {
+        let scopes = scope_set(scope);
+        let mappings = app
+            .setting
+            .claim_mappings()
+            .into_iter()
+            .filter(|mapping| mapping.emitted(target, &scopes, requested))
+            .collect::<Vec<_>>();
+        if mappings.is_empty() {
+            return Ok(Map::new());
+        }
+
+        let roles = if mappings.iter().any(|mapping| {
+            matches!(
+                mapping.source,
+                ClaimSource::Roles | ClaimSource::Template { .. }
+            )
+        }) {
+            self.context
+                .service::<Rbac>()
+                .user_roles(user.uuid)
+                .await?
+                .into_iter()
+                .map(|role| role.name)
+                .collect()
+        } else {
+            vec![]
+        };
+
+        let subject = ClaimSubject::new(user, roles, app)?;
+        let mut claims = Map::new();
+        for mapping in mappings {
+            match subject.resolve(&mapping) {
+                Ok(Some(value)) => {
+                    claims.insert(mapping.name, value);
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    tracing::warn!(app = %app.uuid, claim = %mapping.name, error = %err, "render claim failed");
+                }
+            }
+        }
+
+        Ok(claims)
+    }
+
+    /// Exchange the refresh token for new tokens, the refresh token is rotated.
+    ///
+    /// Returns `Error::Unauthorized` if the refresh token is invalid, expired, revoked,
+    /// not issued to the app or bound to another DPoP key, or the grant of it has been revoked.
+    pub async fn refresh(
+        &self,
+        app: &apps::Model,
+        refresh_token: &str,
+        jkt: Option<&str>,
+    ) -> Result<IssuedToken> {
+        let invalid = || Error::Unauthorized("Invalid refresh token".into());
+
+        let stored = refresh_tokens::Entity::find()
+            .filter(refresh_tokens::Column::Token.eq(sha256_hex(refresh_token.as_bytes())))
+            .one(&self.database)
+            .await?
+            .ok_or_else(invalid)?;
+
+        if stored.revoked_at.is_some()
+            || stored.expires_at < Utc::now()
+            || stored
+                .jkt
+                .as_deref()
+                .is_some_and(|bound| Some(bound) != jkt)
+        {
+            return Err(invalid());
+        }
+
+        let grant = self
+            .context
+            .service::<Session>()
+            .find_active_grant(stored.grant_uuid)
+            .await
+            .map_err(|_| invalid())?;
+
+        if grant.app_uuid != app.uuid {
+            return Err(invalid());
+        }
+
+        let user = self
+            .context
+            .service::<User>()
+            .find_user_by_uuid(grant.user_uuid)
+            .await?;
+
+        user.status.check()?;
+
+        // 只有成功撤销旧 token 的请求才能继续，避免并发请求重复使用同一个 refresh token
+        let txn = self.database.begin().await?;
+        let revoked = refresh_tokens::Entity::update_many()
+            .col_expr(refresh_tokens::Column::RevokedAt, Utc::now().into())
+            .filter(refresh_tokens::Column::Id.eq(stored.id))
+            .filter(refresh_tokens::Column::RevokedAt.is_null())
+            .exec(&txn)
+            .await?
+            .rows_affected;
+
+        if revoked == 0 {
+            return Err(invalid());
+        }
+
+        grants::Entity::update_many()
+            .col_expr(grants::Column::LastUsedAt, Utc::now().into())
+            .filter(grants::Column::Id.eq(grant.id))
+            .exec(&txn)
+            .await?;
+        txn.commit().await?;
+
+        self.issue(app, &user, &grant, None, jkt).await
+    }
+
+    /// Exchange the subject token issued to the client for a token of the audience,
+    /// see [RFC 8693 2](https://www.rfc-editor.org/rfc/rfc8693#section-2)
+    ///
+    /// The scope of the new token is limited by the subject token and the exchange policy of
+    /// the client, and the client is recorded in the `act` claim.
+    pub async fn exchange(
+        &self,
+        client: &apps::Model,
+        subject_token: &str,
+        audience: Uuid,
+        scope: Option<&str>,
+        jkt: Option<&str>,
+    ) -> OAuthResult<ExchangedToken> {
+        let issuer = self
+            .context
+            .service::<Domain>()
+            .issuer_of(client.domain_uuid)
+            .await?
+            .to_string();
+        let subject =
+            AccessToken::verify(subject_token, &self.app.signing_key, Some(&issuer), None)
+                .map_err(|_| OAuthError::invalid_request("Invalid subject token"))?;
+
+        if subject.aud != client.uuid {
+            return Err(OAuthError::invalid_request(
+                "The subject token is not issued to the client",
+            ));
+        }
+
+        if let Some(sid) = subject.sid {
+            self.context
+                .service::<Session>()
+                .find_active_session(sid)
+                .await
+                .map_err(|_| OAuthError::invalid_request("The subject token has been revoked"))?;
+        }
+
+        // 代理登录等没有会话的 token 不会随会话撤销失效，需检查账户状态
+        let subject_user = self
+            .context
+            .service::<User>()
+            .find_user_by_uuid(subject.sub)
+            .await
+            .map_err(|_| OAuthError::invalid_request("Invalid subject token"))?;
+        subject_user
+            .status
+            .check()
+            .map_err(|_| OAuthError::invalid_request("The subject is not active"))?;
+
+        let invalid_target = || OAuthError::new("invalid_target", "The audience is not allowed");
+        let policy = client
+            .setting
+            .token_exchange
+            .iter()
+            .find(|policy| policy.audience == audience)
+            .ok_or_else(invalid_target)?;
+        let target = self
+            .context
+            .service::<App>()
+            .find_app_by_uuid(audience)
+            .await
+            .map_err(|_| invalid_target())?;
+        if target.domain_uuid != client.domain_uuid {
+            return Err(invalid_target());
+        }
+
+        let allowed = policy.allowed_scopes(&scope_set(&subject.scope));
+        let scopes = match scope {
+            Some(scope) => scope_set(scope),
+            None => allowed.clone(),
+        };
+        if scopes.is_empty() || !scopes.is_subset(&allowed) {
+            return Err(OAuthError::new(
+                "invalid_scope",
+                "The requested scope exceeds the subject token or the exchange policy",
+            ));
+        }
+
+        let now = Utc::now();
+        let expires_at = (now
+            + Duration::from_secs(target.setting.oidc_setting.access_token_expire_in))
+        .timestamp()
+        .min(subject.exp as i64);
+        let scope = join_scopes(&scopes);
+
+        let access_token = AccessToken {
+            iss: subject.iss,
+            aud: target.uuid,
+            sub: subject.sub,
+            scope: scope.clone(),
+            iat: now.timestamp() as usize,
+            exp: expires_at as usize,
+            sid: subject.sid,
+            act: Some(Actor {
+                sub: client.uuid,
+                act: subject.act.map(Box::new),
+            }),
+            cnf: jkt.map(confirmation),
+            claims: subject.claims,
+        };
+
+        Ok(ExchangedToken {
+            access_token: access_token.token(&self.app.signing_key),
+            token_type: token_type(jkt),
+            expires_in: (expires_at - now.timestamp()).max(0) as u64,
+            scope,
+        })
+    }
+
+    /// Introspect the access token or refresh token for the client, the hint decides which
+    /// type of token is looked up first
+    ///
+    /// Access tokens issued to any app of the client's domain are introspectable, so that
+    /// resource servers can validate tokens, while refresh tokens are only introspectable by
+    /// the app they are issued to. Personal access tokens and API keys are recognized by
+    /// their marker and introspectable in the same domain.
+    pub async fn introspect(
+        &self,
+        client: &apps::Model,
+        token: &str,
+        token_type_hint: Option<&str>,
+    ) -> Result<TokenIntrospection> {
+        let introspection = if ApiTokenKind::of_token(token).is_some() {
+            self.introspect_api_token(client, token).await?
+        } else if token_type_hint == Some("refresh_token") {
+            match self.introspect_refresh_token(client, token).await? {
+                Some(introspection) => Some(introspection),
+                None => self.introspect_access_token(client, token).await?,
+            }
+        } else {
+            match self.introspect_access_token(client, token).await? {
+                Some(introspection) => Some(introspection),
+                None => self.introspect_refresh_token(client, token).await?,
+            }
+        };
+
+        Ok(introspection.unwrap_or_default())
+    }
+
+    async fn introspect_api_token(
+        &self,
+        client: &apps::Model,
+        token: &str,
+    ) -> Result<Option<TokenIntrospection>> {
+        let Some(token) = self.context.service::<ApiToken>().verify(token).await? else {
+            return Ok(None);
+        };
+
+        // SCIM tokens are only used by the provisioning endpoints
+        if token.domain_uuid != client.domain_uuid || token.kind == ApiTokenKind::ScimToken {
+            return Ok(None);
+        }
+
+        // 个人访问令牌随所有者的账户状态失效，账户恢复后令牌重新生效
+        if token.kind == ApiTokenKind::PersonalAccessToken
+            && !self.user_active(token.owner_uuid).await?
+        {
+            return Ok(None);
+        }
+        // 应用删除后其 API key 不再有效
+        if token.kind == ApiTokenKind::ApiKey && !self.app_exists(token.owner_uuid).await? {
+            return Ok(None);
+        }
+
+        Ok(Some(TokenIntrospection {
+            active: true,
+            scope: Some(token.scope),
+            client_id: (token.kind == ApiTokenKind::ApiKey).then_some(token.owner_uuid),
+            sub: Some(token.owner_uuid),
+            iss: Some(
+                self.context
+                    .service::<Domain>()
+                    .issuer_of(token.domain_uuid)
+                    .await?
+                    .to_string(),
+            ),
+            token_type: Some(token.kind.as_str()),
+            exp: token.expires_at.map(|expires_at| expires_at.timestamp()),
+            iat: Some(token.created_at.timestamp()),
+            ..Default::default()
+        }))
+    }
+
+    async fn introspect_access_token(
+        &self,
+        client: &apps::Model,
+        token: &str,
+    ) -> Result<Option<TokenIntrospection>> {
+        let issuer = self
+            .context
+            .service::<Domain>()
+            .issuer_of(client.domain_uuid)
+            .await?
+            .to_string();
+        let Ok(token) = AccessToken::verify(token, &self.app.signing_key, Some(&issuer), None)
+        else {
+            return Ok(None);
+        };
+
+        if let Some(sid) = token.sid {
+            if self
+                .context
+                .service::<Session>()
+                .find_active_session(sid)
+                .await
+                .is_err()
+            {
+                return Ok(None);
+            }
+        }
+
+        if !self.user_active(token.sub).await? {
+            return Ok(None);
+        }
+
+        let app = match self
+            .context
+            .service::<App>()
+            .find_app_by_uuid(token.aud)
+            .await
+        {
+            Ok(app) if app.domain_uuid == client.domain_uuid => app,
+            Ok(_) | Err(Error::NotFound) => return Ok(None),
+            Err(err) => return Err(err),
+        };
+
+        Ok(Some(TokenIntrospection {
+            active: true,
+            scope: Some(token.scope),
+            client_id: Some(app.uuid),
+            sub: Some(token.sub),
+            aud: Some(app.uuid),
+            iss: Some(token.iss),
+            token_type: Some(token_type(token.cnf.as_ref().map(|cnf| cnf.jkt.as_str()))),
+            exp: Some(token.exp as i64),
+            iat: Some(token.iat as i64),
+            act: token.act,
+            cnf: token.cnf,
+        }))
+    }
+
+    /// Whether the user exists and the account is active
+    async fn user_active(&self, user_uuid: Uuid) -> Result<bool> {
+        match self
+            .context
+            .service::<User>()
+            .find_user_by_uuid(user_uuid)
+            .await
+        {
+            Ok(user) => Ok(user.status.check().is_ok()),
+            Err(Error::NotFound) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    async fn app_exists(&self, app_uuid: Uuid) -> Result<bool> {
+        match self
+            .context
+            .service::<App>()
+            .find_app_by_uuid(app_uuid)
+            .await
+        {
+            Ok(_) => Ok(true),
+            Err(Error::NotFound) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    async fn introspect_refresh_token(
+        &self,
+        client: &apps::Model,
+        token: &str,
+    ) -> Result<Option<TokenIntrospection>> {
+        let Some(stored) = refresh_tokens::Entity::find()
+            .filter(refresh_tokens::Column::Token.eq(sha256_hex(token.as_bytes())))
+            .filter(refresh_tokens::Column::RevokedAt.is_null())
+            .filter(refresh_tokens::Column::ExpiresAt.gt(Utc::now()))
+            .one(&self.database)
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        let grant = match self
+            .context
+            .service::<Session>()
+            .find_active_grant(stored.grant_uuid)
+            .await
+        {
+            Ok(grant) if grant.app_uuid == client.uuid => grant,
+            Ok(_) | Err(Error::NotFound) => return Ok(None),
+            Err(err) => return Err(err),
+        };
+
+        Ok(Some(TokenIntrospection {
+            active: true,
+            scope: Some(grant.scope),
+            client_id: Some(client.uuid),
+            sub: Some(grant.user_uuid),
+            aud: Some(client.uuid),
+            iss: Some(
+                self.context
+                    .service::<Domain>()
+                    .issuer_of(client.domain_uuid)
+                    .await?
+                    .to_string(),
+            ),
+            exp: Some(stored.expires_at.timestamp()),
+            iat: Some(stored.created_at.timestamp()),
+            ..Default::default()
+        }))
+    }
+
+    /// Revoke the refresh token with its grant, see
+    /// [RFC 7009 2.1](https://www.rfc-editor.org/rfc/rfc7009#section-2.1)
+    ///
+    /// Access tokens are self-contained and can not be revoked individually, invalid tokens
+    /// are ignored.
+    pub async fn revoke(&self, client: &apps::Model, token: &str) -> OAuthResult<()> {
+        let stored = refresh_tokens::Entity::find()
+            .filter(refresh_tokens::Column::Token.eq(sha256_hex(token.as_bytes())))
+            .one(&self.database)
+            .await
+            .map_err(Error::from)?;
+
+        let Some(stored) = stored else {
+            if AccessToken::verify(token, &self.app.signing_key, None, None).is_ok() {
+                return Err(OAuthError::new(
+                    "unsupported_token_type",
+                    "Revocation of access tokens is not supported",
+                ));
+            }
+
+            return Ok(());
+        };
+
+        let Some(grant) = grants::Entity::find()
+            .filter(grants::Column::Uuid.eq(stored.grant_uuid))
+            .filter(grants::Column::RevokedAt.is_null())
+            .one(&self.database)
+            .await
+            .map_err(Error::from)?
+        else {
+            return Ok(());
+        };
+
+        if grant.app_uuid != client.uuid {
+            return Err(OAuthError::new(
+                "unauthorized_client",
+                "The token is not issued to the client",
+            ));
+        }
+
+        self.context
+            .service::<Session>()
+            .revoke_grant(grant.user_uuid, grant.uuid)
+            .await?;
+
+        Ok(())
+    }
+}
+
+fn confirmation(jkt: &str) -> Confirmation {
+    Confirmation {
+        jkt: jkt.to_string(),
+    }
+}
+
+fn token_type(jkt: Option<&str>) -> &'static str {
+    if jkt.is_some() {
+        "DPoP"
+    } else {
+        "Bearer"
+    }
+}
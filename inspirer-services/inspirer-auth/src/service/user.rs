@@ -1,46 +1,623 @@
+use chrono::Utc;
 use inspirer_framework::{http::StatusCode, preludes::*, response::ErrorDetail};
-use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, EntityTrait, IntoActiveModel, PaginatorTrait,
+    QueryFilter, QueryOrder, QuerySelect, Set, TransactionTrait,
+};
+use serde_json::{json, Map, Value};
+use uuid::Uuid;
 
-use crate::{auth::user::UserCredential, entity::users, password::password_verify};
+use crate::{
+    auth::{
+        account_status::AccountStatus,
+        api_token::ApiTokenKind,
+        audit::{AuditAction, AuditEvent, AuditOutcome, ClientInfo},
+        domain::domain_setting::PolicyViolation,
+        user::UserProfile,
+        user_transfer::ImportedUser,
+        verification::VerificationChannel,
+        webhook::UserEvent,
+    },
+    config::AppConfig,
+    entity::{
+        account_deletions, api_tokens, consents, domains, group_members, linked_identities,
+        password_histories, user_roles, user_sessions, users, verifications,
+    },
+    password::{password_hash_with, password_needs_rehash, password_verify},
+};
 
-use super::Service;
+use super::{
+    audit::Audit, session::Session, verification::Verification, webhook::Webhook, Service,
+    ServiceInterface,
+};
 
 pub struct User;
 
 impl Service<User> {
-    pub async fn find_user_by_credential(
+    pub async fn find_user_by_uuid(&self, uuid: Uuid) -> Result<users::Model> {
+        users::Entity::find()
+            .filter(users::Column::Uuid.eq(uuid))
+            .one(&self.database)
+            .await?
+            .ok_or(Error::NotFound)
+    }
+
+    /// Rehash the password if it was hashed with outdated parameters,
+    /// the password must have been verified before.
+    pub async fn upgrade_password_hash(
+        &self,
+        user: users::Model,
+        password: &str,
+    ) -> Result<users::Model> {
+        let config = self.config.get::<AppConfig>("app")?;
+
+        if !password_needs_rehash(&config.password_hash, &user.password) {
+            return Ok(user);
+        }
+
+        tracing::debug!(user = %user.uuid, "rehash password with current parameters");
+
+        let mut user = user.into_active_model();
+        user.password = Set(password_hash_with(&config.password_hash, password)?);
+
+        Ok(user.update(&self.database).await?)
+    }
+
+    /// Change user password, the new password must satisfy the password policy of user's domain
+    pub async fn change_password(
         &self,
-        credential: UserCredential,
+        user: users::Model,
+        new_password: &str,
+        actor_uuid: Option<Uuid>,
     ) -> Result<users::Model> {
-        let mut query = users::Entity::find();
-
-        let password = match credential {
-            UserCredential::Username { username, password } => {
-                tracing::debug!(
-                    username = username,
-                    password = password,
-                    "login use username credential"
-                );
-                query = query.filter(users::Column::Username.eq(username));
-                password
+        let config = self.config.get::<AppConfig>("app")?;
+
+        let domain = domains::Entity::find()
+            .filter(domains::Column::Uuid.eq(user.domain_uuid))
+            .one(&self.database)
+            .await?
+            .ok_or(Error::NotFound)?;
+        let policy = &domain.setting.password_policy;
+
+        let mut violations = policy.check(new_password)?;
+
+        if policy.history_size > 0 {
+            let histories = password_histories::Entity::find()
+                .filter(password_histories::Column::UserUuid.eq(user.uuid))
+                .order_by_desc(password_histories::Column::Id)
+                .limit(policy.history_size - 1)
+                .all(&self.database)
+                .await?;
+
+            let reused = std::iter::once(&user.password)
+                .chain(histories.iter().map(|history| &history.password))
+                .any(|hashed| password_verify(new_password, hashed).is_ok());
+
+            if reused {
+                violations.push(PolicyViolation::RecentlyUsed(policy.history_size));
             }
-            UserCredential::Email { email, password } => {
-                query = query.filter(users::Column::Email.eq(email));
-                password
+        }
+
+        let event = AuditEvent {
+            domain_uuid: Some(user.domain_uuid),
+            actor_uuid,
+            subject_uuid: Some(user.uuid),
+            ..AuditEvent::new(AuditAction::PasswordChange, AuditOutcome::Success)
+        };
+
+        if !violations.is_empty() {
+            let violations = violations
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>();
+
+            self.context
+                .service::<Audit>()
+                .record(AuditEvent {
+                    outcome: AuditOutcome::Failure,
+                    detail: json!({ "violations": violations }),
+                    ..event
+                })
+                .await?;
+
+            return Err(Error::CustomError(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                ErrorDetail::new(
+                    "password_policy_violation".to_string(),
+                    violations.join("; "),
+                ),
+            ));
+        }
+
+        let txn = self.database.begin().await?;
+
+        if policy.history_size > 0 && !user.password.is_empty() {
+            password_histories::ActiveModel {
+                user_uuid: Set(user.uuid),
+                password: Set(user.password.clone()),
+                created_at: Set(Utc::now()),
+                ..Default::default()
             }
+            .insert(&txn)
+            .await?;
+        }
+
+        let mut user = user.into_active_model();
+        user.password = Set(password_hash_with(&config.password_hash, new_password)?);
+        user.updated_at = Set(Utc::now());
+        let user = user.update(&txn).await?;
+
+        txn.commit().await?;
+
+        self.context.service::<Audit>().record(event).await?;
+
+        Ok(user)
+    }
+
+    /// Update claims of user profile, claims set to `null` are removed.
+    ///
+    /// Changing email or phone number resets the verified status and sends a new verification code.
+    pub async fn update_profile(
+        &self,
+        user: users::Model,
+        changes: Map<String, Value>,
+        client: ClientInfo,
+    ) -> Result<users::Model> {
+        let invalid = |reason: String| {
+            Error::CustomError(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                ErrorDetail::new("invalid_profile".to_string(), reason),
+            )
         };
 
-        let user = query.one(&self.database).await?.ok_or(Error::CustomError(
-            StatusCode::NOT_FOUND,
-            ErrorDetail::with_reason("User not exists"),
-        ))?;
+        // 本地化的 claim（如 `name#ja-Kana-JP`）按其基础 claim 判断是否可编辑
+        if let Some(claim) = changes.keys().find(|claim| {
+            let base = claim.split('#').next().unwrap_or_default();
+            !UserProfile::EDITABLE_CLAIMS.contains(&base)
+        }) {
+            return Err(invalid(format!("{claim} is not editable")));
+        }
+
+        let current = user.profile.clone();
+        let mut profile = serde_json::to_value(&current)?;
+        for (claim, value) in &changes {
+            match value {
+                Value::Null => profile.as_object_mut().map(|profile| profile.remove(claim)),
+                value => profile
+                    .as_object_mut()
+                    .map(|profile| profile.insert(claim.clone(), value.clone())),
+            };
+        }
+        let mut profile: UserProfile =
+            serde_json::from_value(profile).map_err(|err| invalid(err.to_string()))?;
+
+        let violations = profile.validate();
+        if !violations.is_empty() {
+            return Err(invalid(violations.join("; ")));
+        }
+
+        let email_changed = profile.email != current.email;
+        let phone_changed = profile.phone_number != current.phone_number;
+
+        if email_changed {
+            profile.email_verified = profile.email.as_ref().map(|_| false);
+
+            if let Some(email) = &profile.email {
+                let exists = users::Entity::find()
+                    .filter(users::Column::DomainUuid.eq(user.domain_uuid))
+                    .filter(users::Column::Email.eq(email))
+                    .filter(users::Column::Uuid.ne(user.uuid))
+                    .one(&self.database)
+                    .await?
+                    .is_some();
+
+                if exists {
+                    return Err(Error::CustomError(
+                        StatusCode::CONFLICT,
+                        ErrorDetail::with_reason("Email already registered"),
+                    ));
+                }
+            }
+        }
+        if phone_changed {
+            profile.phone_number_verified = profile.phone_number.as_ref().map(|_| false);
+        }
+        profile.updated_at = Some(Utc::now());
+
+        let mut model = user.into_active_model();
+        model.email = Set(profile.email.clone());
+        model.phone_number = Set(profile.phone_number_e164());
+        model.profile = Set(profile);
+        model.updated_at = Set(Utc::now());
+        let user = model.update(&self.database).await?;
+
+        let claims = changes.keys().collect::<Vec<_>>();
+
+        self.context
+            .service::<Audit>()
+            .record(AuditEvent {
+                domain_uuid: Some(user.domain_uuid),
+                actor_uuid: Some(user.uuid),
+                subject_uuid: Some(user.uuid),
+                client,
+                detail: json!({ "claims": claims }),
+                ..AuditEvent::new(AuditAction::ProfileUpdate, AuditOutcome::Success)
+            })
+            .await?;
+
+        self.context
+            .service::<Webhook>()
+            .dispatch(
+                user.domain_uuid,
+                UserEvent::Updated,
+                user.uuid,
+                json!({ "claims": claims }),
+            )
+            .await?;
+
+        let verification = self.context.service::<Verification>();
+        if email_changed && user.email.is_some() {
+            verification
+                .start(&user, VerificationChannel::Email)
+                .await?;
+        }
+        if phone_changed && user.phone_number.is_some() {
+            verification
+                .start(&user, VerificationChannel::Phone)
+                .await?;
+        }
+
+        Ok(user)
+    }
+
+    /// Import the user into the domain, returns the conflicts with existing users.
+    /// Nothing is written if `dry_run` is set or there are conflicts.
+    pub async fn import_user(
+        &self,
+        domain_uuid: Uuid,
+        user: ImportedUser,
+        dry_run: bool,
+    ) -> Result<Vec<String>> {
+        let phone_number = user.profile.phone_number_e164();
+        let mut conflicts = vec![];
+
+        let uuid_exists = users::Entity::find()
+            .filter(users::Column::Uuid.eq(user.uuid))
+            .one(&self.database)
+            .await?
+            .is_some();
+        if uuid_exists {
+            conflicts.push(format!("uuid {} already exists", user.uuid));
+        }
+
+        for (field, column, value) in [
+            ("username", users::Column::Username, &user.username),
+            ("email", users::Column::Email, &user.email),
+            ("phone_number", users::Column::PhoneNumber, &phone_number),
+        ] {
+            let Some(value) = value else {
+                continue;
+            };
+
+            let exists = users::Entity::find()
+                .filter(users::Column::DomainUuid.eq(domain_uuid))
+                .filter(column.eq(value))
+                .one(&self.database)
+                .await?
+                .is_some();
+            if exists {
+                conflicts.push(format!("{field} {value} already registered"));
+            }
+        }
+
+        if dry_run || !conflicts.is_empty() {
+            return Ok(conflicts);
+        }
+
+        let now = Utc::now();
+        let user = users::ActiveModel {
+            uuid: Set(user.uuid),
+            domain_uuid: Set(domain_uuid),
+            email: Set(user.email),
+            username: Set(user.username),
+            phone_number: Set(phone_number),
+            password: Set(user.password),
+            profile: Set(user.profile),
+            created_at: Set(now),
+            updated_at: Set(now),
+            ..Default::default()
+        }
+        .insert(&self.database)
+        .await?;
+
+        self.context
+            .service::<Webhook>()
+            .dispatch(
+                user.domain_uuid,
+                UserEvent::Created,
+                user.uuid,
+                json!({ "source": "import" }),
+            )
+            .await?;
+
+        Ok(conflicts)
+    }
+
+    /// Users of the domain with id greater than the cursor, ordered by id
+    pub async fn users(
+        &self,
+        domain_uuid: Uuid,
+        after: u64,
+        limit: u64,
+    ) -> Result<Vec<users::Model>> {
+        Ok(users::Entity::find()
+            .filter(users::Column::DomainUuid.eq(domain_uuid))
+            .filter(users::Column::Id.gt(after))
+            .order_by_asc(users::Column::Id)
+            .limit(limit)
+            .all(&self.database)
+            .await?)
+    }
 
-        if password_verify(password, &user.password).is_err() {
-            return Err(Error::Unauthorized(
-                "User not exists or password error".into(),
+    pub async fn count_users(&self, domain_uuid: Uuid) -> Result<u64> {
+        Ok(users::Entity::find()
+            .filter(users::Column::DomainUuid.eq(domain_uuid))
+            .count(&self.database)
+            .await?)
+    }
+
+    /// Change the status of the user with the reason, sessions and grants of the user are
+    /// revoked if the account is disabled or locked
+    pub async fn change_status(
+        &self,
+        user: users::Model,
+        status: AccountStatus,
+        reason: &str,
+        actor_uuid: Option<Uuid>,
+        client: ClientInfo,
+    ) -> Result<users::Model> {
+        let reason = reason.trim();
+        if reason.is_empty() || reason.chars().count() > 255 {
+            return Err(Error::BadRequest(
+                "The reason must be 1 to 255 characters".into(),
             ));
         }
+        if actor_uuid == Some(user.uuid) {
+            return Err(Error::BadRequest(
+                "Cannot change the status of yourself".into(),
+            ));
+        }
+
+        let previous = user.status;
+        let mut model = user.into_active_model();
+        model.status = Set(status);
+        model.status_reason = Set(Some(reason.to_string()));
+        model.status_changed_at = Set(Some(Utc::now()));
+        model.updated_at = Set(Utc::now());
+        let user = model.update(&self.database).await?;
+
+        if status.revokes_sessions() {
+            let session = self.context.service::<Session>();
+            session
+                .revoke(user.uuid, None, actor_uuid, client.clone())
+                .await?;
+            session.revoke_user_grants(user.uuid).await?;
+        }
+
+        self.context
+            .service::<Audit>()
+            .record(AuditEvent {
+                domain_uuid: Some(user.domain_uuid),
+                actor_uuid,
+                subject_uuid: Some(user.uuid),
+                client,
+                detail: json!({
+                    "from": previous,
+                    "to": status,
+                    "reason": reason,
+                }),
+                ..AuditEvent::new(AuditAction::AccountStatusChange, AuditOutcome::Success)
+            })
+            .await?;
+
+        let event = match status {
+            AccountStatus::Disabled => UserEvent::Disabled,
+            _ => UserEvent::Updated,
+        };
+        self.context
+            .service::<Webhook>()
+            .dispatch(
+                user.domain_uuid,
+                event,
+                user.uuid,
+                json!({ "status": status, "reason": reason }),
+            )
+            .await?;
 
         Ok(user)
     }
+
+    /// Delete the user, sessions and grants of the user are revoked and the memberships,
+    /// credentials and consents are deleted. Audit events and sessions of the user are
+    /// anonymised, `source` is sent in the `user.deleted` event.
+    pub async fn delete_user(
+        &self,
+        user: users::Model,
+        actor_uuid: Option<Uuid>,
+        client: ClientInfo,
+        source: &str,
+    ) -> Result<()> {
+        let session = self.context.service::<Session>();
+        session.revoke(user.uuid, None, actor_uuid, client).await?;
+        session.revoke_user_grants(user.uuid).await?;
+
+        let txn = self.database.begin().await?;
+
+        group_members::Entity::delete_many()
+            .filter(group_members::Column::UserUuid.eq(user.uuid))
+            .exec(&txn)
+            .await?;
+        user_roles::Entity::delete_many()
+            .filter(user_roles::Column::UserUuid.eq(user.uuid))
+            .exec(&txn)
+            .await?;
+        linked_identities::Entity::delete_many()
+            .filter(linked_identities::Column::UserUuid.eq(user.uuid))
+            .exec(&txn)
+            .await?;
+        consents::Entity::delete_many()
+            .filter(consents::Column::UserUuid.eq(user.uuid))
+            .exec(&txn)
+            .await?;
+        api_tokens::Entity::delete_many()
+            .filter(api_tokens::Column::Kind.eq(ApiTokenKind::PersonalAccessToken))
+            .filter(api_tokens::Column::OwnerUuid.eq(user.uuid))
+            .exec(&txn)
+            .await?;
+        password_histories::Entity::delete_many()
+            .filter(password_histories::Column::UserUuid.eq(user.uuid))
+            .exec(&txn)
+            .await?;
+        verifications::Entity::delete_many()
+            .filter(verifications::Column::UserUuid.eq(user.uuid))
+            .exec(&txn)
+            .await?;
+        user_sessions::Entity::update_many()
+            .col_expr(
+                user_sessions::Column::Ip,
+                Expr::value(Option::<String>::None),
+            )
+            .col_expr(
+                user_sessions::Column::UserAgent,
+                Expr::value(Option::<String>::None),
+            )
+            .filter(user_sessions::Column::UserUuid.eq(user.uuid))
+            .exec(&txn)
+            .await?;
+        account_deletions::Entity::update_many()
+            .col_expr(
+                account_deletions::Column::CompletedAt,
+                Some(Utc::now()).into(),
+            )
+            .filter(account_deletions::Column::UserUuid.eq(user.uuid))
+            .filter(account_deletions::Column::CancelledAt.is_null())
+            .filter(account_deletions::Column::CompletedAt.is_null())
+            .exec(&txn)
+            .await?;
+        users::Entity::delete_by_id(user.id).exec(&txn).await?;
+
+        // 审计日志与用户数据一同清理，匿名化失败时删除回滚，下次重试
+        let identifiers = [&user.username, &user.email, &user.phone_number]
+            .into_iter()
+            .flatten()
+            .cloned()
+            .collect();
+        self.context
+            .service::<Audit>()
+            .anonymise(&txn, user.uuid, identifiers)
+            .await?;
+
+        txn.commit().await?;
+
+        self.context
+            .service::<Webhook>()
+            .dispatch(
+                user.domain_uuid,
+                UserEvent::Deleted,
+                user.uuid,
+                json!({ "source": source }),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        entity::apps,
+        service::testing::{context, ScriptedDatabase},
+    };
+
+    fn pending_user() -> users::Model {
+        let uuid = Uuid::new_v4();
+
+        users::Model {
+            id: 1,
+            uuid,
+            domain_uuid: Uuid::new_v4(),
+            email: Some("alice@example.com".into()),
+            username: None,
+            phone_number: None,
+            external_id: None,
+            status: AccountStatus::PendingVerification,
+            status_reason: Some("Onboarding".into()),
+            status_changed_at: None,
+            password: String::new(),
+            profile: UserProfile::new(uuid.to_string(), ""),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn admin_activates_pending_account() {
+        let user = pending_user();
+        let admin = Uuid::new_v4();
+        let activated = users::Model {
+            status: AccountStatus::Active,
+            status_reason: Some("Identity checked".into()),
+            ..user.clone()
+        };
+        let (context, database) = context(
+            ScriptedDatabase::default()
+                // 更新状态，MySQL 更新后重新读取
+                .affected(1)
+                .rows([activated])
+                // 审计日志
+                .affected(1)
+                // 没有订阅 webhook 的应用
+                .rows(Vec::<apps::Model>::new()),
+            "",
+        )
+        .await;
+
+        let user = context
+            .service::<User>()
+            .change_status(
+                user,
+                AccountStatus::Active,
+                "Identity checked",
+                Some(admin),
+                ClientInfo::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(user.status, AccountStatus::Active);
+        assert!(user.status.check().is_ok());
+
+        let statements = database.statements();
+        assert!(database.exhausted());
+        assert!(statements[0].starts_with("UPDATE `users` SET"));
+        assert!(statements[0].contains("`status` = 'active'"));
+        assert!(statements[0].contains("`status_reason` = 'Identity checked'"));
+        // 激活不撤销会话，变更原因和操作者写入审计日志
+        assert!(statements[2].starts_with("INSERT INTO `audit_events`"));
+        assert!(statements[2].contains(&admin.to_string()));
+        assert!(statements[2].contains("pending_verification"));
+        assert_eq!(statements.len(), 4);
+    }
+
+    #[test]
+    fn pending_accounts_are_refused() {
+        let error = AccountStatus::PendingVerification.check().unwrap_err();
+        assert!(matches!(
+            error,
+            Error::CustomError(StatusCode::FORBIDDEN, detail)
+                if detail.error.as_deref() == Some("account_pending_verification")
+        ));
+        assert!(!AccountStatus::PendingVerification.revokes_sessions());
+    }
 }
@@ -0,0 +1,162 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use inspirer_framework::{http::StatusCode, preludes::*, response::ErrorDetail};
+use rand::{rngs::OsRng, Rng};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, EntityTrait, IntoActiveModel, QueryFilter, QueryOrder, Set,
+};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    auth::{
+        verification::{code_digest, VerificationChannel},
+        webhook::UserEvent,
+    },
+    config::AppConfig,
+    entity::{users, verifications},
+};
+
+use super::{webhook::Webhook, Service, ServiceInterface};
+
+pub struct Verification;
+
+impl Service<Verification> {
+    /// Send a new verification code to the current email or phone number of user,
+    /// codes sent before are invalidated.
+    pub async fn start(&self, user: &users::Model, channel: VerificationChannel) -> Result<()> {
+        let config = self.config.get::<AppConfig>("app")?.verification;
+
+        let target = match channel {
+            VerificationChannel::Email => user.email.clone(),
+            VerificationChannel::Phone => user.phone_number.clone(),
+        }
+        .ok_or(Error::BadRequest(format!("No {channel:?} to verify")))?;
+
+        verifications::Entity::delete_many()
+            .filter(verifications::Column::UserUuid.eq(user.uuid))
+            .filter(verifications::Column::Channel.eq(channel))
+            .filter(verifications::Column::VerifiedAt.is_null())
+            .exec(&self.database)
+            .await?;
+
+        let code = format!("{:06}", OsRng.gen_range(0..1_000_000));
+        let expires_at = Utc::now() + Duration::from_secs(config.expires_in);
+
+        verifications::ActiveModel {
+            uuid: Set(Uuid::new_v4()),
+            user_uuid: Set(user.uuid),
+            channel: Set(channel),
+            target: Set(target.clone()),
+            code: Set(code_digest(&code)),
+            attempts: Set(0),
+            expires_at: Set(expires_at),
+            verified_at: Set(None),
+            created_at: Set(Utc::now()),
+            ..Default::default()
+        }
+        .insert(&self.database)
+        .await?;
+
+        let Some(sender) = config.sender else {
+            tracing::warn!(
+                user = %user.uuid,
+                ?channel,
+                "verification sender not configured, code is not delivered"
+            );
+            return Ok(());
+        };
+
+        reqwest::Client::new()
+            .post(sender)
+            .json(&json!({
+                "channel": channel,
+                "target": target,
+                "code": code,
+                "user": user.uuid,
+                "expires_at": expires_at,
+            }))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(Error::wrap)?;
+
+        Ok(())
+    }
+
+    /// Confirm the verification code, marks the email or phone number of user as verified.
+    /// The account status is left unchanged.
+    pub async fn confirm(
+        &self,
+        user: users::Model,
+        channel: VerificationChannel,
+        code: &str,
+    ) -> Result<users::Model> {
+        let config = self.config.get::<AppConfig>("app")?.verification;
+        let invalid = || {
+            Error::CustomError(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                ErrorDetail::with_reason("Invalid or expired verification code"),
+            )
+        };
+
+        let verification = verifications::Entity::find()
+            .filter(verifications::Column::UserUuid.eq(user.uuid))
+            .filter(verifications::Column::Channel.eq(channel))
+            .filter(verifications::Column::VerifiedAt.is_null())
+            .order_by_desc(verifications::Column::Id)
+            .one(&self.database)
+            .await?
+            .ok_or_else(invalid)?;
+
+        let target = match channel {
+            VerificationChannel::Email => user.email.as_ref(),
+            VerificationChannel::Phone => user.phone_number.as_ref(),
+        };
+
+        if verification.expires_at < Utc::now()
+            || verification.attempts >= config.max_attempts
+            || target != Some(&verification.target)
+        {
+            return Err(invalid());
+        }
+
+        if verification.code != code_digest(code) {
+            let attempts = verification.attempts + 1;
+            let mut verification = verification.into_active_model();
+            verification.attempts = Set(attempts);
+            verification.update(&self.database).await?;
+
+            return Err(invalid());
+        }
+
+        let mut verification = verification.into_active_model();
+        verification.verified_at = Set(Some(Utc::now()));
+        verification.update(&self.database).await?;
+
+        let mut profile = user.profile.clone();
+        match channel {
+            VerificationChannel::Email => profile.email_verified = Some(true),
+            VerificationChannel::Phone => profile.phone_number_verified = Some(true),
+        }
+        profile.updated_at = Some(Utc::now());
+
+        let mut user = user.into_active_model();
+        user.profile = Set(profile);
+        user.updated_at = Set(Utc::now());
+        let user = user.update(&self.database).await?;
+
+        self.context
+            .service::<Webhook>()
+            .dispatch(
+                user.domain_uuid,
+                UserEvent::Verified,
+                user.uuid,
+                json!({ "channel": channel }),
+            )
+            .await?;
+
+        Ok(user)
+    }
+}
@@ -0,0 +1,402 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use inspirer_framework::preludes::*;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, EntityTrait, IntoActiveModel, QueryFilter, QueryOrder,
+    QuerySelect, Set, TransactionTrait,
+};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::{
+    auth::webhook::{sign, UserEvent, DELIVERY_HEADER, EVENT_HEADER, SIGNATURE_HEADER},
+    config::AppConfig,
+    entity::{apps, webhook_dead_letters, webhook_deliveries},
+};
+
+use super::{app::App, Service, ServiceInterface};
+
+pub struct Webhook;
+
+impl Service<Webhook> {
+    /// Queue the event to all subscriptions of apps in the domain
+    pub async fn dispatch(
+        &self,
+        domain_uuid: Uuid,
+        event: UserEvent,
+        user_uuid: Uuid,
+        data: Value,
+    ) -> Result<()> {
+        let apps = apps::Entity::find()
+            .filter(apps::Column::DomainUuid.eq(domain_uuid))
+            .all(&self.database)
+            .await?;
+
+        let now = Utc::now();
+        let deliveries = apps
+            .iter()
+            .flat_map(|app| {
+                app.setting
+                    .webhooks
+                    .iter()
+                    .filter(|subscription| subscription.subscribed(event))
+                    .map(|subscription| {
+                        let uuid = Uuid::new_v4();
+
+                        webhook_deliveries::ActiveModel {
+                            uuid: Set(uuid),
+                            app_uuid: Set(app.uuid),
+                            event: Set(event),
+                            url: Set(subscription.url.to_string()),
+                            payload: Set(json!({
+                                "id": uuid,
+                                "event": event,
+                                "domain": domain_uuid,
+                                "user": user_uuid,
+                                "data": data,
+                                "created_at": now,
+                            })),
+                            attempts: Set(0),
+                            next_attempt_at: Set(now),
+                            last_error: Set(None),
+                            created_at: Set(now),
+                            updated_at: Set(now),
+                            ..Default::default()
+                        }
+                    })
+            })
+            .collect::<Vec<_>>();
+
+        if deliveries.is_empty() {
+            return Ok(());
+        }
+
+        tracing::debug!(
+            event = %event,
+            user = %user_uuid,
+            count = deliveries.len(),
+            "queue webhook deliveries"
+        );
+
+        webhook_deliveries::Entity::insert_many(deliveries)
+            .exec_without_returning(&self.database)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Send deliveries that are due, returns the number of deliveries sent
+    pub async fn deliver_due(&self) -> Result<usize> {
+        let config = self.config.get::<AppConfig>("app")?.webhook;
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout))
+            .build()
+            .map_err(Error::wrap)?;
+
+        let deliveries = webhook_deliveries::Entity::find()
+            .filter(webhook_deliveries::Column::NextAttemptAt.lte(Utc::now()))
+            .order_by_asc(webhook_deliveries::Column::NextAttemptAt)
+            .limit(config.batch_size)
+            .all(&self.database)
+            .await?;
+
+        let mut sent = 0;
+        for delivery in deliveries {
+            // 先把下次投递时间往后推来占有该投递，避免多个实例重复发送
+            let lease = Utc::now() + chrono::Duration::seconds(config.timeout as i64 * 2);
+            let claimed = webhook_deliveries::Entity::update_many()
+                .col_expr(webhook_deliveries::Column::NextAttemptAt, lease.into())
+                .filter(webhook_deliveries::Column::Id.eq(delivery.id))
+                .filter(webhook_deliveries::Column::NextAttemptAt.eq(delivery.next_attempt_at))
+                .exec(&self.database)
+                .await?
+                .rows_affected;
+
+            if claimed == 0 {
+                continue;
+            }
+
+            let result = self.send(&client, &delivery).await;
+            sent += 1;
+
+            match result {
+                Ok(()) => {
+                    webhook_deliveries::Entity::delete_by_id(delivery.id)
+                        .exec(&self.database)
+                        .await?;
+                }
+                Err(err) => {
+                    tracing::warn!(
+                        delivery = %delivery.uuid,
+                        error = %err,
+                        "webhook delivery failed"
+                    );
+                    self.retry_or_kill(delivery, err, config.max_attempts)
+                        .await?;
+                }
+            }
+        }
+
+        Ok(sent)
+    }
+
+    /// Deliver due webhooks forever, used as the background worker of server
+    pub async fn work(&self) -> Result<()> {
+        let config = self.config.get::<AppConfig>("app")?.webhook;
+
+        loop {
+            if let Err(err) = self.deliver_due().await {
+                tracing::error!(error = %err, "deliver webhooks failed");
+            }
+
+            tokio::time::sleep(Duration::from_secs(config.poll_interval)).await;
+        }
+    }
+
+    pub async fn dead_letters(&self, limit: u64) -> Result<Vec<webhook_dead_letters::Model>> {
+        Ok(webhook_dead_letters::Entity::find()
+            .order_by_desc(webhook_dead_letters::Column::Id)
+            .limit(limit)
+            .all(&self.database)
+            .await?)
+    }
+
+    /// Move dead letters back to the delivery queue, all dead letters are replayed if `uuid` is `None`.
+    /// Returns the number of replayed deliveries.
+    pub async fn replay(&self, uuid: Option<Uuid>) -> Result<usize> {
+        let txn = self.database.begin().await?;
+
+        let mut query = webhook_dead_letters::Entity::find();
+        if let Some(uuid) = uuid {
+            query = query.filter(webhook_dead_letters::Column::Uuid.eq(uuid));
+        }
+        let letters = query.all(&txn).await?;
+
+        if letters.is_empty() {
+            return Ok(0);
+        }
+
+        let now = Utc::now();
+        let ids = letters.iter().map(|letter| letter.id).collect::<Vec<_>>();
+        let count = letters.len();
+
+        webhook_deliveries::Entity::insert_many(letters.into_iter().map(|letter| {
+            webhook_deliveries::ActiveModel {
+                uuid: Set(letter.uuid),
+                app_uuid: Set(letter.app_uuid),
+                event: Set(letter.event),
+                url: Set(letter.url),
+                payload: Set(letter.payload),
+                attempts: Set(0),
+                next_attempt_at: Set(now),
+                last_error: Set(letter.last_error),
+                created_at: Set(letter.created_at),
+                updated_at: Set(now),
+                ..Default::default()
+            }
+        }))
+        .exec_without_returning(&txn)
+        .await?;
+
+        webhook_dead_letters::Entity::delete_many()
+            .filter(webhook_dead_letters::Column::Id.is_in(ids))
+            .exec(&txn)
+            .await?;
+
+        txn.commit().await?;
+
+        Ok(count)
+    }
+
+    async fn send(
+        &self,
+        client: &reqwest::Client,
+        delivery: &webhook_deliveries::Model,
+    ) -> Result<()> {
+        // 每次投递时使用应用当前的密钥签名，密钥轮换后重试的投递也能通过校验
+        let app = self
+            .context
+            .service::<App>()
+            .find_app_by_uuid(delivery.app_uuid)
+            .await?;
+        let body = serde_json::to_vec(&delivery.payload)?;
+        let signature = sign(&app.secret, Utc::now().timestamp(), &body);
+
+        let response = client
+            .post(&delivery.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, delivery.event.to_string())
+            .header(DELIVERY_HEADER, delivery.uuid.to_string())
+            .header(SIGNATURE_HEADER, signature)
+            .body(body)
+            .send()
+            .await
+            .map_err(Error::wrap)?;
+
+        if !response.status().is_success() {
+            return Err(Error::string(&format!(
+                "Unexpected response status {}",
+                response.status()
+            )));
+        }
+
+        Ok(())
+    }
+
+    async fn retry_or_kill(
+        &self,
+        delivery: webhook_deliveries::Model,
+        err: Error,
+        max_attempts: u32,
+    ) -> Result<()> {
+        let attempts = delivery.attempts + 1;
+        let last_error = err.to_string().chars().take(1024).collect::<String>();
+
+        if attempts < max_attempts {
+            let mut delivery = delivery.into_active_model();
+            delivery.attempts = Set(attempts);
+            delivery.last_error = Set(Some(last_error));
+            delivery.next_attempt_at =
+                Set(Utc::now() + chrono::Duration::seconds(backoff(attempts)));
+            delivery.updated_at = Set(Utc::now());
+            delivery.update(&self.database).await?;
+
+            return Ok(());
+        }
+
+        tracing::warn!(delivery = %delivery.uuid, "move webhook delivery to dead letters");
+
+        let txn = self.database.begin().await?;
+
+        webhook_dead_letters::ActiveModel {
+            uuid: Set(delivery.uuid),
+            app_uuid: Set(delivery.app_uuid),
+            event: Set(delivery.event),
+            url: Set(delivery.url),
+            payload: Set(delivery.payload),
+            attempts: Set(attempts),
+            last_error: Set(Some(last_error)),
+            created_at: Set(delivery.created_at),
+            failed_at: Set(Utc::now()),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await?;
+
+        webhook_deliveries::Entity::delete_by_id(delivery.id)
+            .exec(&txn)
+            .await?;
+
+        txn.commit().await?;
+
+        Ok(())
+    }
+}
+
+/// 第 `attempts` 次失败后的重试间隔（秒），指数退避：30s, 60s, 120s ... 最长 6 小时
+fn backoff(attempts: u32) -> i64 {
+    (30i64 << attempts.saturating_sub(1).min(10)).min(6 * 3600)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::testing::{context, ScriptedDatabase};
+
+    fn delivery(attempts: u32) -> webhook_deliveries::Model {
+        webhook_deliveries::Model {
+            id: 1,
+            uuid: Uuid::new_v4(),
+            app_uuid: Uuid::new_v4(),
+            event: UserEvent::Created,
+            url: "https://hooks.example.com/inspirer".into(),
+            payload: json!({ "event": "user.created" }),
+            attempts,
+            next_attempt_at: Utc::now(),
+            last_error: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_six_hours() {
+        let schedule = (1..=12).map(backoff).collect::<Vec<_>>();
+        assert_eq!(
+            schedule,
+            [30, 60, 120, 240, 480, 960, 1920, 3840, 7680, 15360, 21600, 21600]
+        );
+        assert_eq!(backoff(u32::MAX), 6 * 3600);
+    }
+
+    #[tokio::test]
+    async fn failed_delivery_is_retried() {
+        let delivery = delivery(0);
+        let retried = webhook_deliveries::Model {
+            attempts: 1,
+            ..delivery.clone()
+        };
+        let (context, database) = context(
+            ScriptedDatabase::default()
+                // MySQL 更新后重新读取
+                .affected(1)
+                .rows([retried]),
+            "",
+        )
+        .await;
+
+        context
+            .service::<Webhook>()
+            .retry_or_kill(delivery, Error::string("connection refused"), 3)
+            .await
+            .unwrap();
+
+        let statements = database.statements();
+        assert!(database.exhausted());
+        assert!(statements[0].starts_with("UPDATE `webhook_deliveries` SET"));
+        assert!(statements[0].contains("`attempts` = 1"));
+        assert!(statements[0].contains("connection refused"));
+        assert_eq!(statements.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn delivery_moves_to_dead_letters_at_max_attempts() {
+        let delivery = delivery(2);
+        let dead_letter = webhook_dead_letters::Model {
+            id: 1,
+            uuid: delivery.uuid,
+            app_uuid: delivery.app_uuid,
+            event: delivery.event,
+            url: delivery.url.clone(),
+            payload: delivery.payload.clone(),
+            attempts: 3,
+            last_error: Some("connection refused".into()),
+            created_at: delivery.created_at,
+            failed_at: Utc::now(),
+        };
+        let (context, database) = context(
+            ScriptedDatabase::default()
+                // 写入死信，MySQL 插入后重新读取
+                .affected(1)
+                .rows([dead_letter])
+                // 删除投递
+                .affected(1),
+            "",
+        )
+        .await;
+
+        context
+            .service::<Webhook>()
+            .retry_or_kill(delivery.clone(), Error::string("connection refused"), 3)
+            .await
+            .unwrap();
+
+        let statements = database.statements();
+        assert!(database.exhausted());
+        assert!(statements[0].starts_with("INSERT INTO `webhook_dead_letters`"));
+        assert!(statements[0].contains(&delivery.uuid.to_string()));
+        assert!(statements[2].starts_with("DELETE FROM `webhook_deliveries`"));
+        assert_eq!(statements.len(), 3);
+    }
+}
@@ -1,36 +1,235 @@
-use jsonwebtoken::{encode, EncodingKey, Header};
+use std::{fs, path::Path, sync::Arc};
+
+use crypto_utils::{p256::P256, KeyPair, KeyPairTrait};
+use inspirer_framework::{dpop::jwk_thumbprint, preludes::*};
+use jsonwebtoken::{
+    decode, encode,
+    jwk::{Jwk, JwkSet},
+    Algorithm, DecodingKey, EncodingKey, Header, Validation,
+};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use uuid::Uuid;
 
+/// Key signing the access tokens and ID tokens, published in the JWKS of every domain
+#[derive(Clone)]
+pub struct SigningKey(Arc<SigningKeyInner>);
+
+struct SigningKeyInner {
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+    jwk: Jwk,
+}
+
+impl SigningKey {
+    pub const ALGORITHM: Algorithm = Algorithm::ES256;
+
+    /// Load the P-256 private key from the PKCS#8 PEM file
+    pub fn load(path: &Path) -> Result<Self> {
+        let pem = fs::read_to_string(path).map_err(|err| {
+            Error::string(&format!(
+                "Failed to read signing key {}: {err}",
+                path.display()
+            ))
+        })?;
+
+        Self::from_pem(&pem)
+    }
+
+    pub fn from_pem(pem: &str) -> Result<Self> {
+        let key_pair = KeyPair::<P256>::from_private_key_pem(pem)?;
+
+        let mut jwk = key_pair.get_jwks();
+        jwk["use"] = "sig".into();
+        jwk["alg"] = "ES256".into();
+        let mut jwk: Jwk = serde_json::from_value(jwk)?;
+        // kid 取公钥的 thumbprint，更换密钥后 kid 随之变化
+        jwk.common.key_id = jwk_thumbprint(&jwk);
+
+        Ok(SigningKey(Arc::new(SigningKeyInner {
+            encoding: EncodingKey::from_ec_pem(pem.as_bytes()).map_err(Error::wrap)?,
+            decoding: DecodingKey::from_jwk(&jwk).map_err(Error::wrap)?,
+            jwk,
+        })))
+    }
+
+    pub fn kid(&self) -> Option<&str> {
+        self.0.jwk.common.key_id.as_deref()
+    }
+
+    /// Public keys verifying the tokens
+    pub fn jwks(&self) -> JwkSet {
+        JwkSet {
+            keys: vec![self.0.jwk.clone()],
+        }
+    }
+
+    pub fn sign<T: Serialize>(&self, claims: &T) -> String {
+        let mut header = Header::new(Self::ALGORITHM);
+        header.kid = self.kid().map(str::to_string);
+
+        // 密钥在加载时已校验，签名不会失败
+        encode(&header, claims, &self.0.encoding).expect("Failed to sign the token")
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AccessToken {
+    pub iss: String,
     pub sub: Uuid,
     pub aud: Uuid,
     pub scope: String,
     pub iat: usize,
     pub exp: usize,
+    /// Session the token is issued in, the token is rejected once the session is revoked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<Uuid>,
+    /// The party acting on behalf of the subject, see
+    /// [RFC 8693 4.1](https://www.rfc-editor.org/rfc/rfc8693#section-4.1)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub act: Option<Actor>,
+    /// Key the token is bound to, see [RFC 9449 6.1](https://www.rfc-editor.org/rfc/rfc9449#section-6.1)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cnf: Option<Confirmation>,
+    /// Additional claims, e.g. the roles claim
+    #[serde(flatten)]
+    pub claims: Map<String, Value>,
+}
+
+/// Actor of the `act` claim, nested actors are the prior actors in the delegation chain
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Actor {
+    pub sub: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub act: Option<Box<Actor>>,
+}
+
+/// Confirmation of the DPoP proof key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Confirmation {
+    /// JWK SHA-256 thumbprint of the proof key
+    pub jkt: String,
 }
 
 impl AccessToken {
-    pub fn token(&self) -> String {
-        encode(
-            &Header::default(),
-            &self,
-            &EncodingKey::from_secret(b"secret"),
-        )
-        .unwrap()
+    pub fn token(&self, key: &SigningKey) -> String {
+        key.sign(self)
+    }
+
+    /// Decode and verify the access token signed by the key, the issuer and the audience are
+    /// checked if given
+    pub fn verify(
+        token: &str,
+        key: &SigningKey,
+        issuer: Option<&str>,
+        audience: Option<Uuid>,
+    ) -> jsonwebtoken::errors::Result<Self> {
+        let mut validation = Validation::new(SigningKey::ALGORITHM);
+        validation.set_required_spec_claims(&["exp", "iss", "sub", "aud"]);
+        if let Some(issuer) = issuer {
+            validation.set_issuer(&[issuer]);
+        }
+        match audience {
+            Some(audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+
+        Ok(decode::<Self>(token, &key.0.decoding, &validation)?.claims)
+    }
+}
+
+/// ID Token
+///
+/// 相关结构标准的定义可查阅
+/// [OpenId Connect Core 2. ID Token](https://openid.net/specs/openid-connect-core-1_0.html#IDToken)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IdToken {
+    pub iss: String,
+    pub sub: Uuid,
+    pub aud: Uuid,
+    pub iat: usize,
+    pub exp: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    /// Profile claims and additional claims, e.g. the roles claim
+    #[serde(flatten)]
+    pub claims: Map<String, Value>,
+}
+
+impl GetToken for IdToken {}
+
+pub trait GetToken: Serialize + Sized {
+    fn get_token(&self, key: &SigningKey) -> String {
+        key.sign(self)
     }
 }
 
-pub trait GetToken: Serialize {
-    fn get_token(&self) -> String {
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key() -> SigningKey {
+        let pem = KeyPair::<P256>::generate()
+            .unwrap()
+            .get_private_key_pem()
+            .unwrap();
 
-        // EncodingKey::from_ec_pem(key)
-        encode(
-            &Header::default(),
-            &self,
-            &EncodingKey::from_secret(b"secret"),
+        SigningKey::from_pem(&pem).unwrap()
+    }
+
+    fn access_token(iss: &str, aud: Uuid) -> AccessToken {
+        let now = chrono::Utc::now().timestamp() as usize;
+
+        AccessToken {
+            iss: iss.to_string(),
+            sub: Uuid::new_v4(),
+            aud,
+            scope: "openid".into(),
+            iat: now,
+            exp: now + 60,
+            sid: None,
+            act: None,
+            cnf: None,
+            claims: Map::new(),
+        }
+    }
+
+    #[test]
+    fn signs_with_es256_and_publishes_the_key() {
+        let key = key();
+        let token = access_token("https://auth.example.com/oidc", Uuid::new_v4()).token(&key);
+
+        let header = jsonwebtoken::decode_header(&token).unwrap();
+        assert_eq!(header.alg, Algorithm::ES256);
+        assert_eq!(header.kid.as_deref(), key.kid());
+        assert!(key.jwks().find(key.kid().unwrap()).is_some());
+    }
+
+    #[test]
+    fn verifies_issuer_and_audience() {
+        let key = key();
+        let aud = Uuid::new_v4();
+        let token = access_token("https://auth.example.com/oidc", aud).token(&key);
+
+        assert!(AccessToken::verify(&token, &key, None, None).is_ok());
+        assert!(AccessToken::verify(
+            &token,
+            &key,
+            Some("https://auth.example.com/oidc"),
+            Some(aud)
         )
-        .unwrap()
+        .is_ok());
+        assert!(
+            AccessToken::verify(&token, &key, Some("https://other.example.com/oidc"), None)
+                .is_err()
+        );
+        assert!(AccessToken::verify(&token, &key, None, Some(Uuid::new_v4())).is_err());
+    }
+
+    #[test]
+    fn rejects_tokens_of_other_keys() {
+        let token = access_token("https://auth.example.com/oidc", Uuid::new_v4()).token(&key());
+
+        assert!(AccessToken::verify(&token, &key(), None, None).is_err());
     }
-}
\ No newline at end of file
+}